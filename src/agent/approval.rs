@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::agent::session::message_key;
+use crate::bus::{InboundMessage, OutboundMessage};
+
+const MAX_PROMPT_ARGS_CHARS: usize = 500;
+
+/// Decides whether a tool listed in `tools.require_approval` may run.
+#[async_trait]
+pub trait Approver: Send + Sync {
+    async fn approve(&self, origin: &InboundMessage, tool: &str, arguments: &Value) -> bool;
+}
+
+pub fn approval_prompt(tool: &str, arguments: &Value) -> String {
+    let mut args = arguments.to_string();
+    if args.chars().count() > MAX_PROMPT_ARGS_CHARS {
+        args = format!("{}...", args.chars().take(MAX_PROMPT_ARGS_CHARS).collect::<String>());
+    }
+    format!("Allow {} to run with {}? Reply yes or no.", tool, args)
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes" | "ya" | "ok" | "approve" | "allow")
+}
+
+/// Asks on the terminal. There is no timeout: the user is right there, and an
+/// abandoned read would swallow their next line.
+#[derive(Default)]
+pub struct CliApprover {
+    /// Concurrent tool calls ask one at a time
+    prompt_lock: Mutex<()>,
+}
+
+impl CliApprover {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Approver for CliApprover {
+    async fn approve(&self, _origin: &InboundMessage, tool: &str, arguments: &Value) -> bool {
+        let _guard = self.prompt_lock.lock().await;
+
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\n{} [y/N] ", approval_prompt(tool, arguments));
+        let _ = stderr.flush();
+
+        let answer = tokio::task::spawn_blocking(|| {
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input).map(|_| input)
+        })
+        .await;
+
+        matches!(answer, Ok(Ok(input)) if is_yes(&input))
+    }
+}
+
+/// Asks in the chat the request came from and treats the requester's next
+/// message there as the answer. Inbound traffic must pass through `forward`
+/// so answers are picked off before they reach the agent.
+pub struct ChatApprover {
+    outbound_tx: mpsc::Sender<OutboundMessage>,
+    timeout: Duration,
+    /// Open question per conversation: who may answer, and where the answer goes
+    pending: std::sync::Mutex<HashMap<String, (String, oneshot::Sender<bool>)>>,
+    prompt_lock: Mutex<()>,
+}
+
+impl ChatApprover {
+    pub fn new(outbound_tx: mpsc::Sender<OutboundMessage>, timeout: Duration) -> Self {
+        Self {
+            outbound_tx,
+            timeout,
+            pending: std::sync::Mutex::new(HashMap::new()),
+            prompt_lock: Mutex::new(()),
+        }
+    }
+
+    /// Settle the open question `msg` answers, if any. Returns whether the
+    /// message was consumed as an answer.
+    pub fn resolve(&self, msg: &InboundMessage) -> bool {
+        let key = message_key(msg);
+        let mut pending = self.pending.lock().unwrap();
+        match pending.remove(&key) {
+            Some((sender, answer)) if sender == msg.sender_id => {
+                let _ = answer.send(is_yes(&msg.content));
+                true
+            }
+            Some(other) => {
+                // Not the requester; keep waiting for them
+                pending.insert(key, other);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Pass messages from `raw_rx` on to the agent, keeping back the ones
+    /// that answer an approval question.
+    pub fn forward(
+        self: std::sync::Arc<Self>,
+        mut raw_rx: mpsc::Receiver<InboundMessage>,
+        inbound_tx: mpsc::Sender<InboundMessage>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            while let Some(msg) = raw_rx.recv().await {
+                if self.resolve(&msg) {
+                    continue;
+                }
+                if inbound_tx.send(msg).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl Approver for ChatApprover {
+    async fn approve(&self, origin: &InboundMessage, tool: &str, arguments: &Value) -> bool {
+        let _guard = self.prompt_lock.lock().await;
+
+        let key = message_key(origin);
+        let (answer_tx, answer_rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(key.clone(), (origin.sender_id.clone(), answer_tx));
+
+        if self.outbound_tx.send(origin.reply(approval_prompt(tool, arguments))).await.is_err() {
+            self.pending.lock().unwrap().remove(&key);
+            return false;
+        }
+
+        match tokio::time::timeout(self.timeout, answer_rx).await {
+            Ok(Ok(approved)) => approved,
+            _ => {
+                tracing::info!("Approval for {} in {} timed out", tool, key);
+                self.pending.lock().unwrap().remove(&key);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn message(sender: &str, content: &str) -> InboundMessage {
+        InboundMessage::new("telegram".to_string(), sender.to_string(), "-100".to_string(), content.to_string())
+    }
+
+    #[tokio::test]
+    async fn test_chat_approval_waits_for_the_requesters_answer() {
+        let (outbound_tx, mut outbound_rx) = mpsc::channel(10);
+        let (raw_tx, raw_rx) = mpsc::channel(10);
+        let (inbound_tx, mut inbound_rx) = mpsc::channel(10);
+
+        let approver = Arc::new(ChatApprover::new(outbound_tx, Duration::from_secs(5)));
+        Arc::clone(&approver).forward(raw_rx, inbound_tx);
+
+        let origin = message("42", "clean up the build dir");
+        let pending = {
+            let approver = Arc::clone(&approver);
+            tokio::spawn(async move { approver.approve(&origin, "shell", &json!({"command": "rm -r build"})).await })
+        };
+
+        let question = outbound_rx.recv().await.unwrap();
+        assert!(question.content.contains("Allow shell"));
+        assert_eq!(question.chat_id, "-100");
+
+        // Someone else in the group can't answer for the requester
+        raw_tx.send(message("7", "yes")).await.unwrap();
+        assert_eq!(inbound_rx.recv().await.unwrap().sender_id, "7");
+
+        raw_tx.send(message("42", "Yes")).await.unwrap();
+        assert!(pending.await.unwrap());
+
+        // With nothing pending, messages flow through to the agent again
+        raw_tx.send(message("42", "thanks")).await.unwrap();
+        assert_eq!(inbound_rx.recv().await.unwrap().content, "thanks");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_chat_approval_denies_on_no_or_timeout() {
+        let (outbound_tx, _outbound_rx) = mpsc::channel(10);
+        let approver = Arc::new(ChatApprover::new(outbound_tx, Duration::from_secs(60)));
+        let origin = message("42", "write it");
+
+        let pending = {
+            let approver = Arc::clone(&approver);
+            let origin = origin.clone();
+            tokio::spawn(async move { approver.approve(&origin, "write_file", &json!({})).await })
+        };
+        tokio::task::yield_now().await;
+        assert!(approver.resolve(&message("42", "no")));
+        assert!(!pending.await.unwrap());
+
+        assert!(!approver.approve(&origin, "write_file", &json!({})).await);
+        assert!(approver.pending.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const MANIFEST_FILE: &str = "artifacts.jsonl";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Artifact {
+    pub path: String,
+    /// "created" or "modified"
+    pub action: &'static str,
+    pub at: DateTime<Utc>,
+}
+
+/// Files written by the filesystem tools during each turn, appended as one
+/// JSON line per turn to `artifacts.jsonl` in the workspace.
+pub struct ArtifactTracker {
+    manifest_path: PathBuf,
+    /// Where relative tool paths resolve, when tools are confined to the workspace
+    base_dir: Option<PathBuf>,
+    turns: Mutex<HashMap<String, Vec<Artifact>>>,
+}
+
+impl ArtifactTracker {
+    pub fn new(workspace: &Path, base_dir: Option<PathBuf>) -> Self {
+        Self {
+            manifest_path: workspace.join(MANIFEST_FILE),
+            base_dir,
+            turns: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The file a call would write to, if the tool writes files at all.
+    pub fn written_path(tool: &str, arguments: &Value) -> Option<String> {
+        let writes = match tool {
+            "write_file" | "edit_file" | "apply_patch" => true,
+            "structured_edit" => arguments["action"].as_str() != Some("get"),
+            _ => false,
+        };
+        writes.then(|| arguments["path"].as_str().map(str::to_string)).flatten()
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        let path = Path::new(path);
+        match &self.base_dir {
+            Some(dir) if path.is_relative() => dir.join(path).exists(),
+            _ => path.exists(),
+        }
+    }
+
+    /// Note a successful write. A file written twice in one turn keeps its
+    /// first action, so a new file stays "created".
+    pub fn record(&self, key: &str, path: &str, existed: bool) {
+        let mut turns = self.turns.lock().unwrap();
+        let files = turns.entry(key.to_string()).or_default();
+        let now = Utc::now();
+        match files.iter_mut().find(|a| a.path == path) {
+            Some(artifact) => artifact.at = now,
+            None => files.push(Artifact {
+                path: path.to_string(),
+                action: if existed { "modified" } else { "created" },
+                at: now,
+            }),
+        }
+    }
+
+    pub fn pending(&self, key: &str) -> Vec<Artifact> {
+        self.turns.lock().unwrap().get(key).cloned().unwrap_or_default()
+    }
+
+    /// End the turn for `key`, appending its files to the manifest.
+    pub fn finish(&self, key: &str) -> Result<Vec<Artifact>, String> {
+        let files = self.turns.lock().unwrap().remove(key).unwrap_or_default();
+        if files.is_empty() {
+            return Ok(files);
+        }
+
+        let entry = serde_json::json!({
+            "session": key,
+            "finished_at": Utc::now(),
+            "files": files,
+        });
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.manifest_path)
+            .map_err(|e| format!("Failed to open {}: {}", self.manifest_path.display(), e))?;
+        writeln!(file, "{}", entry).map_err(|e| format!("Failed to write artifact manifest: {}", e))?;
+
+        Ok(files)
+    }
+}
+
+/// "\n\nI created: a.md, chart.png", plus a line for modified files.
+pub fn announcement(files: &[Artifact]) -> String {
+    let names = |action: &str| {
+        files
+            .iter()
+            .filter(|a| a.action == action)
+            .map(|a| Path::new(&a.path).file_name().map_or(a.path.clone(), |n| n.to_string_lossy().to_string()))
+            .collect::<Vec<_>>()
+    };
+
+    let mut lines = Vec::new();
+    let created = names("created");
+    if !created.is_empty() {
+        lines.push(format!("I created: {}", created.join(", ")));
+    }
+    let modified = names("modified");
+    if !modified.is_empty() {
+        lines.push(format!("I updated: {}", modified.join(", ")));
+    }
+
+    if lines.is_empty() {
+        String::new()
+    } else {
+        format!("\n\n{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_only_writing_calls_produce_artifacts() {
+        assert_eq!(ArtifactTracker::written_path("write_file", &json!({"path": "a.md"})).as_deref(), Some("a.md"));
+        assert_eq!(ArtifactTracker::written_path("structured_edit", &json!({"path": "c.toml", "action": "set"})).as_deref(), Some("c.toml"));
+        assert_eq!(ArtifactTracker::written_path("structured_edit", &json!({"path": "c.toml", "action": "get"})), None);
+        assert_eq!(ArtifactTracker::written_path("read_file", &json!({"path": "a.md"})), None);
+    }
+
+    #[test]
+    fn test_announcement_lists_created_and_updated_files() {
+        let at = Utc::now();
+        let files = vec![
+            Artifact { path: "/ws/report.md".to_string(), action: "created", at },
+            Artifact { path: "chart.png".to_string(), action: "created", at },
+            Artifact { path: "notes.txt".to_string(), action: "modified", at },
+        ];
+        assert_eq!(announcement(&files), "\n\nI created: report.md, chart.png\nI updated: notes.txt");
+        assert_eq!(announcement(&[]), "");
+    }
+}
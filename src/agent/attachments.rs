@@ -0,0 +1,89 @@
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::agent::tools::validate_read_path;
+use crate::providers::ChatMessage;
+
+/// Per-file cap so one large attachment can't blow the context window.
+const MAX_ATTACHMENT_CHARS: usize = 20_000;
+
+fn directive_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?:^|\s)@file:(\S+)").unwrap())
+}
+
+/// Paths referenced with `@file:<path>` in a message, in order, without duplicates.
+pub fn file_references(content: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for capture in directive_regex().captures_iter(content) {
+        let path = capture[1].trim_end_matches(['.', ',', ';', ')']).to_string();
+        if !path.is_empty() && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// Read the referenced files (relative to the workspace, never outside it) and
+/// render them as one context message. Unreadable files are reported inline so
+/// the model can tell the user.
+pub fn context_message(workspace: &Path, paths: &[String]) -> Option<ChatMessage> {
+    if paths.is_empty() {
+        return None;
+    }
+
+    let sections: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            let full = workspace.join(path);
+            let content = validate_read_path(Some(workspace), &full.to_string_lossy())
+                .and_then(|p| std::fs::read_to_string(p).map_err(|e| e.to_string()));
+
+            match content {
+                Ok(text) if text.chars().count() > MAX_ATTACHMENT_CHARS => {
+                    let truncated: String = text.chars().take(MAX_ATTACHMENT_CHARS).collect();
+                    format!("## {}\n\n{}\n\n[truncated]", path, truncated)
+                }
+                Ok(text) => format!("## {}\n\n{}", path, text),
+                Err(e) => format!("## {}\n\n[could not attach: {}]", path, e),
+            }
+        })
+        .collect();
+
+    Some(ChatMessage::system(format!(
+        "The user attached these files for this message:\n\n{}",
+        sections.join("\n\n")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_file_references() {
+        assert_eq!(
+            file_references("Summarize @file:notes.md and @file:docs/plan.txt, please. email@file:x"),
+            vec!["notes.md", "docs/plan.txt"]
+        );
+        assert!(file_references("no directives here").is_empty());
+    }
+
+    #[test]
+    fn test_context_message_stays_in_workspace() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("notes.md"), "Meeting at 10").unwrap();
+
+        let message = context_message(
+            workspace.path(),
+            &["notes.md".to_string(), "../../etc/passwd".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(message.role, "system");
+        assert!(message.content.contains("Meeting at 10"));
+        assert!(message.content.contains("## ../../etc/passwd\n\n[could not attach:"));
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::bus::InboundMessage;
+
+/// Append-only JSON Lines record of every tool execution (`[audit] file`).
+pub struct AuditLog {
+    path: PathBuf,
+    /// Argument names whose values are replaced with "[redacted]"
+    redact: Vec<String>,
+    max_result_chars: usize,
+    /// One line is written at a time, so concurrent tool calls never interleave
+    write_lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, redact: Vec<String>, max_result_chars: usize) -> Self {
+        Self {
+            path,
+            redact,
+            max_result_chars,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    fn redacted(&self, arguments: &Value) -> Value {
+        let mut arguments = arguments.clone();
+        if let Some(obj) = arguments.as_object_mut() {
+            for (key, value) in obj.iter_mut() {
+                if self.redact.iter().any(|r| r.eq_ignore_ascii_case(key)) {
+                    *value = json!("[redacted]");
+                }
+            }
+        }
+        arguments
+    }
+
+    fn truncated(&self, text: &str) -> String {
+        if text.chars().count() <= self.max_result_chars {
+            return text.to_string();
+        }
+        format!("{}... [truncated]", text.chars().take(self.max_result_chars).collect::<String>())
+    }
+
+    pub fn record(
+        &self,
+        origin: &InboundMessage,
+        tool: &str,
+        arguments: &Value,
+        result: &Result<String, String>,
+        duration: Duration,
+    ) -> Result<(), String> {
+        let (ok, output) = match result {
+            Ok(output) => (true, output),
+            Err(error) => (false, error),
+        };
+        let entry = json!({
+            "timestamp": Utc::now(),
+            "channel": origin.channel,
+            "chat_id": origin.chat_id,
+            "sender_id": origin.sender_id,
+            "tool": tool,
+            "arguments": self.redacted(arguments),
+            "success": ok,
+            "result": self.truncated(output),
+            "duration_ms": duration.as_millis() as u64,
+        });
+        let line = format!("{}\n", entry);
+
+        let _guard = self.write_lock.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open audit log {}: {}", self.path.display(), e))?;
+        file.write_all(line.as_bytes())
+            .map_err(|e| format!("Failed to write audit log: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_execution_is_recorded_with_redacted_arguments() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("logs").join("audit.jsonl");
+        let audit = AuditLog::new(path.clone(), vec!["password".to_string()], 5);
+        let origin = InboundMessage::new("telegram".to_string(), "7".to_string(), "42".to_string(), "log in".to_string());
+
+        audit.record(
+            &origin,
+            "shell",
+            &json!({"command": "login", "password": "hunter2"}),
+            &Ok("Welcome back!".to_string()),
+            Duration::from_millis(12),
+        ).unwrap();
+        audit.record(&origin, "read_file", &json!({"path": "x"}), &Err("Not found".to_string()), Duration::ZERO).unwrap();
+
+        let lines: Vec<Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+
+        let entry = &lines[0];
+        assert!(entry["timestamp"].is_string());
+        assert_eq!(entry["channel"], "telegram");
+        assert_eq!(entry["chat_id"], "42");
+        assert_eq!(entry["tool"], "shell");
+        assert_eq!(entry["arguments"], json!({"command": "login", "password": "[redacted]"}));
+        assert_eq!(entry["success"], true);
+        assert_eq!(entry["result"], "Welco... [truncated]");
+        assert_eq!(entry["duration_ms"], 12);
+
+        assert_eq!(lines[1]["success"], false);
+        assert_eq!(lines[1]["result"], "Not f... [truncated]");
+    }
+}
@@ -0,0 +1,160 @@
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+
+use crate::bus::InboundMessage;
+use crate::config::ToolAvailabilityRule;
+
+/// A `ToolAvailabilityRule` with its time window and days parsed.
+struct Rule {
+    senders: Vec<String>,
+    channels: Vec<String>,
+    hours: Option<(NaiveTime, NaiveTime)>,
+    days: Vec<Weekday>,
+}
+
+fn parse_hours(hours: &str) -> Result<Option<(NaiveTime, NaiveTime)>, String> {
+    if hours.trim().is_empty() {
+        return Ok(None);
+    }
+    let parse = |t: &str| NaiveTime::parse_from_str(t.trim(), "%H:%M");
+    match hours.split_once('-').map(|(from, to)| (parse(from), parse(to))) {
+        Some((Ok(from), Ok(to))) => Ok(Some((from, to))),
+        _ => Err(format!("invalid hours '{}', expected HH:MM-HH:MM", hours)),
+    }
+}
+
+impl Rule {
+    fn parse(rule: &ToolAvailabilityRule) -> Result<Self, String> {
+        let days = rule
+            .days
+            .iter()
+            .map(|day| day.trim().parse::<Weekday>().map_err(|_| format!("invalid day '{}'", day)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            senders: rule.senders.clone(),
+            channels: rule.channels.clone(),
+            hours: parse_hours(&rule.hours)?,
+            days,
+        })
+    }
+
+    /// Why the tool can't be used here and now, if it can't.
+    fn refusal(&self, origin: &InboundMessage, now: DateTime<Tz>) -> Option<String> {
+        if !self.senders.is_empty() && !self.senders.contains(&origin.sender_id) {
+            return Some("is not available to this sender".to_string());
+        }
+        if !self.channels.is_empty() && !self.channels.contains(&origin.channel) {
+            return Some(format!("is not available on {}", origin.channel));
+        }
+        if !self.days.is_empty() && !self.days.contains(&now.weekday()) {
+            return Some(format!("is not available on {}", now.format("%A")));
+        }
+        if let Some((from, to)) = self.hours {
+            let time = now.time();
+            let open = if from <= to { from <= time && time < to } else { time >= from || time < to };
+            if !open {
+                return Some(format!("is only available {}-{}", from.format("%H:%M"), to.format("%H:%M")));
+            }
+        }
+        None
+    }
+}
+
+/// Which tools a turn may use, from `tools.availability`. Unavailable tools
+/// are not advertised, and calls to them are refused.
+pub struct ToolAvailability {
+    rules: HashMap<String, Rule>,
+    timezone: Tz,
+}
+
+impl ToolAvailability {
+    /// A rule that doesn't parse makes its tool unavailable rather than open.
+    pub fn new(rules: &HashMap<String, ToolAvailabilityRule>, timezone: Tz) -> Self {
+        let rules = rules
+            .iter()
+            .map(|(tool, rule)| {
+                let rule = Rule::parse(rule).unwrap_or_else(|e| {
+                    tracing::error!("tools.availability.{}: {}; the tool is disabled", tool, e);
+                    Rule {
+                        senders: Vec::new(),
+                        channels: vec![String::new()],
+                        hours: None,
+                        days: Vec::new(),
+                    }
+                });
+                (tool.clone(), rule)
+            })
+            .collect();
+        Self { rules, timezone }
+    }
+
+    pub fn check(&self, tool: &str, origin: &InboundMessage, now: DateTime<Utc>) -> Result<(), String> {
+        let Some(rule) = self.rules.get(tool) else {
+            return Ok(());
+        };
+        match rule.refusal(origin, now.with_timezone(&self.timezone)) {
+            Some(reason) => Err(format!("Refused: '{}' {}.", tool, reason)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_available(&self, tool: &str, origin: &InboundMessage, now: DateTime<Utc>) -> bool {
+        self.check(tool, origin, now).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(channel: &str, sender: &str) -> InboundMessage {
+        InboundMessage::new(channel.to_string(), sender.to_string(), "1".to_string(), "hi".to_string())
+    }
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_rules_by_sender_channel_and_business_hours() {
+        let mut rules = HashMap::new();
+        rules.insert("shell".to_string(), ToolAvailabilityRule {
+            senders: vec!["admin".to_string()],
+            ..Default::default()
+        });
+        rules.insert("send_email".to_string(), ToolAvailabilityRule {
+            channels: vec!["telegram".to_string()],
+            hours: "09:00-17:00".to_string(),
+            days: vec!["mon".to_string(), "tue".to_string(), "wed".to_string(), "thu".to_string(), "fri".to_string()],
+            ..Default::default()
+        });
+        let availability = ToolAvailability::new(&rules, "Asia/Jakarta".parse().unwrap());
+
+        // Wednesday 10:00 in Jakarta
+        let office = at("2030-01-02T03:00:00Z");
+        assert!(availability.is_available("shell", &message("telegram", "admin"), office));
+        assert!(availability.check("shell", &message("telegram", "guest"), office).unwrap_err().contains("not available to this sender"));
+        assert!(availability.is_available("read_file", &message("telegram", "guest"), office));
+
+        assert!(availability.is_available("send_email", &message("telegram", "guest"), office));
+        assert!(!availability.is_available("send_email", &message("cli", "guest"), office));
+        // Wednesday 20:00, then Saturday 10:00
+        assert!(availability.check("send_email", &message("telegram", "guest"), at("2030-01-02T13:00:00Z")).unwrap_err().contains("09:00-17:00"));
+        assert!(!availability.is_available("send_email", &message("telegram", "guest"), at("2030-01-05T03:00:00Z")));
+    }
+
+    #[test]
+    fn test_overnight_window_and_invalid_rule() {
+        let mut rules = HashMap::new();
+        rules.insert("backup".to_string(), ToolAvailabilityRule { hours: "22:00-06:00".to_string(), ..Default::default() });
+        rules.insert("broken".to_string(), ToolAvailabilityRule { hours: "9am to 5pm".to_string(), ..Default::default() });
+        let availability = ToolAvailability::new(&rules, Tz::UTC);
+        let origin = message("telegram", "1");
+
+        assert!(availability.is_available("backup", &origin, at("2030-01-02T23:30:00Z")));
+        assert!(availability.is_available("backup", &origin, at("2030-01-02T05:59:00Z")));
+        assert!(!availability.is_available("backup", &origin, at("2030-01-02T12:00:00Z")));
+        assert!(!availability.is_available("broken", &origin, at("2030-01-02T12:00:00Z")));
+    }
+}
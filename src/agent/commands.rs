@@ -0,0 +1,89 @@
+use crate::agent::context::Verbosity;
+
+/// Slash commands handled by the agent itself, without calling the LLM.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Fork,
+    Switch(String),
+    Branches,
+    NoTools,
+    Broadcast(String),
+    Schema(String),
+    Focus(String),
+    /// Reply length for this conversation: `/brief`, `/normal`, `/detailed`
+    Verbosity(Verbosity),
+    /// Wipe the conversation's history; triggered by `agent.clear_commands`
+    Clear,
+}
+
+impl Command {
+    /// Like `parse`, but first checks the configurable clear/reset aliases.
+    pub fn parse_with_clear(input: &str, clear_commands: &[String]) -> Option<Self> {
+        let first = input.split_whitespace().next()?;
+        if clear_commands.iter().any(|c| c.eq_ignore_ascii_case(first)) {
+            return Some(Command::Clear);
+        }
+        Self::parse(input)
+    }
+
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        if !input.starts_with('/') {
+            return None;
+        }
+
+        let mut parts = input.split_whitespace();
+        let name = parts.next()?.to_lowercase();
+        let arg = parts.next().map(|s| s.to_string());
+        let rest = input.split_once(char::is_whitespace).map(|(_, r)| r.trim().to_string());
+
+        match name.as_str() {
+            "/fork" => Some(Command::Fork),
+            "/switch" => Some(Command::Switch(arg.unwrap_or_default())),
+            "/branches" => Some(Command::Branches),
+            "/notools" => Some(Command::NoTools),
+            "/broadcast" => Some(Command::Broadcast(rest.unwrap_or_default())),
+            "/schema" => Some(Command::Schema(rest.unwrap_or_default())),
+            "/focus" => Some(Command::Focus(arg.unwrap_or_default())),
+            "/brief" => Some(Command::Verbosity(Verbosity::Brief)),
+            "/normal" => Some(Command::Verbosity(Verbosity::Normal)),
+            "/detailed" => Some(Command::Verbosity(Verbosity::Detailed)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(Command::parse("/fork"), Some(Command::Fork));
+        assert_eq!(Command::parse("  /switch fork-1 "), Some(Command::Switch("fork-1".to_string())));
+        assert_eq!(Command::parse("/BRANCHES"), Some(Command::Branches));
+        assert_eq!(Command::parse("/notools"), Some(Command::NoTools));
+        assert_eq!(
+            Command::parse("/broadcast post the daily status"),
+            Some(Command::Broadcast("post the daily status".to_string()))
+        );
+        assert_eq!(
+            Command::parse("/schema {\"type\": \"object\"}"),
+            Some(Command::Schema("{\"type\": \"object\"}".to_string()))
+        );
+        assert_eq!(Command::parse("/focus TASK.md"), Some(Command::Focus("TASK.md".to_string())));
+        assert_eq!(Command::parse("/Brief"), Some(Command::Verbosity(Verbosity::Brief)));
+        assert_eq!(Command::parse("/detailed"), Some(Command::Verbosity(Verbosity::Detailed)));
+        assert_eq!(Command::parse("/unknown"), None);
+        assert_eq!(Command::parse("fork"), None);
+    }
+
+    #[test]
+    fn test_parse_clear_aliases() {
+        let aliases = vec!["/clear".to_string(), "/reset".to_string()];
+        assert_eq!(Command::parse_with_clear("/CLEAR", &aliases), Some(Command::Clear));
+        assert_eq!(Command::parse_with_clear(" /reset ", &aliases), Some(Command::Clear));
+        assert_eq!(Command::parse_with_clear("/fork", &aliases), Some(Command::Fork));
+        assert_eq!(Command::parse_with_clear("/clear", &[]), None);
+    }
+}
@@ -1,9 +1,95 @@
 use crate::agent::memory::MemoryStore;
+use crate::providers::ChatMessage;
+use chrono_tz::Tz;
+use serde::Deserialize;
 use std::path::{Path, PathBuf};
 
+/// Rough token count for a message: ~4 characters per token plus a little
+/// per-message overhead for the role and framing.
+pub fn estimate_tokens(message: &ChatMessage) -> usize {
+    message.content.chars().count() / 4 + 4
+}
+
+/// How long replies should be, per `agent.verbosity` or `/brief`, `/detailed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    Brief,
+    #[default]
+    Normal,
+    Detailed,
+}
+
+impl Verbosity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "brief" => Some(Verbosity::Brief),
+            "normal" => Some(Verbosity::Normal),
+            "detailed" => Some(Verbosity::Detailed),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Verbosity::Brief => "brief",
+            Verbosity::Normal => "normal",
+            Verbosity::Detailed => "detailed",
+        }
+    }
+
+    /// The system prompt section steering reply length; none for normal.
+    pub fn section(self) -> Option<&'static str> {
+        match self {
+            Verbosity::Brief => Some(
+                "## Response Length\n\nKeep replies brief: answer in one to three sentences or a short list. \
+                 Skip preambles, recaps and offers of further help.",
+            ),
+            Verbosity::Normal => None,
+            Verbosity::Detailed => Some(
+                "## Response Length\n\nGive detailed replies: explain your reasoning, cover edge cases and \
+                 alternatives, and include examples where they help.",
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExamplePair {
+    user: String,
+    assistant: String,
+}
+
+/// Pairs from `User:` / `Assistant:` blocks. A turn runs until the next
+/// marker line; a user turn with no answer is dropped.
+fn parse_example_markdown(markdown: &str) -> Vec<ExamplePair> {
+    let mut turns: Vec<(bool, Vec<&str>)> = Vec::new();
+    for line in markdown.lines() {
+        if let Some(rest) = line.strip_prefix("User:") {
+            turns.push((true, vec![rest.trim_start()]));
+        } else if let Some(rest) = line.strip_prefix("Assistant:") {
+            turns.push((false, vec![rest.trim_start()]));
+        } else if let Some((_, lines)) = turns.last_mut() {
+            lines.push(line);
+        }
+    }
+
+    let text = |lines: &[&str]| lines.join("\n").trim().to_string();
+    turns
+        .windows(2)
+        .filter(|pair| pair[0].0 && !pair[1].0)
+        .map(|pair| ExamplePair { user: text(&pair[0].1), assistant: text(&pair[1].1) })
+        .collect()
+}
+
 pub struct ContextBuilder {
     workspace: PathBuf,
     memory: MemoryStore,
+    max_context_tokens: usize,
+    timezone: Tz,
+    /// Run-specific instructions placed right after the identity
+    system_instruction: Option<String>,
+    /// Whether to send the workspace's few-shot examples as prior turns
+    use_examples: bool,
 }
 
 impl ContextBuilder {
@@ -11,9 +97,92 @@ impl ContextBuilder {
         Self {
             workspace: workspace.to_path_buf(),
             memory: MemoryStore::new(workspace),
+            max_context_tokens: 0,
+            timezone: Tz::UTC,
+            system_instruction: None,
+            use_examples: false,
         }
     }
 
+    /// Zone the "Current Time" section is shown in.
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Extra instructions for this run, on top of the workspace identity files.
+    pub fn with_system_instruction(mut self, instruction: impl Into<String>) -> Self {
+        let instruction = instruction.into();
+        self.system_instruction = (!instruction.trim().is_empty()).then(|| instruction.trim().to_string());
+        self
+    }
+
+    /// Cap the memory section of the system prompt at `max_chars`; 0 means no limit.
+    pub fn with_max_memory_chars(mut self, max_chars: usize) -> Self {
+        self.memory = MemoryStore::new(&self.workspace).with_max_context_chars(max_chars);
+        self
+    }
+
+    /// Send the user/assistant pairs from `examples.json` or `EXAMPLES.md`
+    /// after the system prompt, ahead of the real history.
+    pub fn with_examples(mut self, enabled: bool) -> Self {
+        self.use_examples = enabled;
+        self
+    }
+
+    /// Cap the estimated size of built messages; 0 means no limit.
+    pub fn with_max_context_tokens(mut self, max_context_tokens: usize) -> Self {
+        self.max_context_tokens = max_context_tokens;
+        self
+    }
+
+    /// Drop the oldest history messages until the estimate fits the budget.
+    /// The first `keep` messages (system prompt and examples) and the current
+    /// message (last) are always kept. Returns the dropped messages, oldest first.
+    fn trim_to_budget(&self, messages: &mut Vec<ChatMessage>, keep: usize) -> Vec<ChatMessage> {
+        if self.max_context_tokens == 0 {
+            return Vec::new();
+        }
+
+        let mut total: usize = messages.iter().map(estimate_tokens).sum();
+        let mut drop_count = 0;
+        let history_len = messages.len().saturating_sub(keep + 1);
+
+        while total > self.max_context_tokens && drop_count < history_len {
+            total -= estimate_tokens(&messages[keep + drop_count]);
+            drop_count += 1;
+        }
+
+        messages.drain(keep..keep + drop_count).collect()
+    }
+
+    /// Few-shot examples as alternating user/assistant messages. `examples.json`
+    /// holds `[{"user": ..., "assistant": ...}]`; `EXAMPLES.md` is used when it
+    /// is absent, with each turn starting on a `User:` or `Assistant:` line.
+    fn example_messages(&self) -> Vec<ChatMessage> {
+        if !self.use_examples {
+            return Vec::new();
+        }
+
+        let pairs = match std::fs::read_to_string(self.workspace.join("examples.json")) {
+            Ok(json) => match serde_json::from_str::<Vec<ExamplePair>>(&json) {
+                Ok(pairs) => pairs,
+                Err(e) => {
+                    tracing::warn!("Ignoring examples.json: {}", e);
+                    Vec::new()
+                }
+            },
+            Err(_) => std::fs::read_to_string(self.workspace.join("EXAMPLES.md"))
+                .map(|markdown| parse_example_markdown(&markdown))
+                .unwrap_or_default(),
+        };
+
+        pairs
+            .into_iter()
+            .flat_map(|pair| [ChatMessage::user(pair.user), ChatMessage::assistant(pair.assistant)])
+            .collect()
+    }
+
     pub fn build_system_prompt(&self) -> String {
         let identity = self.get_identity();
         let bootstrap = self.load_bootstrap_files();
@@ -21,6 +190,10 @@ impl ContextBuilder {
 
         let mut parts = vec![identity];
 
+        if let Some(instruction) = &self.system_instruction {
+            parts.push(format!("## Instructions for This Session\n\n{}", instruction));
+        }
+
         if !bootstrap.is_empty() {
             parts.push(bootstrap);
         }
@@ -61,7 +234,7 @@ If the user's request doesn't require any tools, just respond naturally with tex
     }
 
     fn get_identity(&self) -> String {
-        let now = chrono::Local::now().format("%Y-%m-%d %H:%M (%A)");
+        let now = chrono::Utc::now().with_timezone(&self.timezone).format("%Y-%m-%d %H:%M (%A) %Z");
         let workspace_path = self.workspace.display();
 
         format!(
@@ -83,14 +256,13 @@ You have access to tools that allow you to:
 - Execute shell commands
 - Fetch web pages
 - Send messages to users
-- Spawn subagents for background tasks
 
 IMPORTANT: When responding to direct questions or conversations, reply directly with your text response.
 Only use the 'message' tool when you need to send a message to a specific chat channel.
 
 Always be helpful, accurate, and concise. When using tools, think step by step.
-When remembering something important, write to {}/memory/MEMORY.md"#,
-            now, workspace_path, workspace_path, workspace_path, workspace_path
+When remembering something important, use the remember tool; use recall to look up what you saved"#,
+            now, workspace_path, workspace_path, workspace_path
         )
     }
 
@@ -113,13 +285,15 @@ When remembering something important, write to {}/memory/MEMORY.md"#,
         parts.join("\n\n")
     }
 
+    /// Build the request messages for a turn. Returns the messages plus any
+    /// history that was dropped to stay within `max_context_tokens`.
     pub fn build_messages(
         &self,
         history: &[serde_json::Value],
         current_message: &str,
         channel: Option<&str>,
         chat_id: Option<&str>,
-    ) -> Vec<crate::providers::ChatMessage> {
+    ) -> (Vec<ChatMessage>, Vec<ChatMessage>) {
         let mut messages = Vec::new();
 
         let mut system_prompt = self.build_system_prompt();
@@ -132,6 +306,8 @@ When remembering something important, write to {}/memory/MEMORY.md"#,
         }
 
         messages.push(crate::providers::ChatMessage::system(system_prompt));
+        messages.extend(self.example_messages());
+        let keep = messages.len();
 
         for msg in history {
             let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("user");
@@ -139,13 +315,15 @@ When remembering something important, write to {}/memory/MEMORY.md"#,
 
             messages.push(match role {
                 "assistant" => crate::providers::ChatMessage::assistant(content),
+                "system" => crate::providers::ChatMessage::system(content),
                 _ => crate::providers::ChatMessage::user(content),
             });
         }
 
         messages.push(crate::providers::ChatMessage::user(current_message));
 
-        messages
+        let dropped = self.trim_to_budget(&mut messages, keep);
+        (messages, dropped)
     }
 
     pub fn build_messages_with_tools(
@@ -155,7 +333,7 @@ When remembering something important, write to {}/memory/MEMORY.md"#,
         channel: Option<&str>,
         chat_id: Option<&str>,
         tools_json: &str,
-    ) -> Vec<crate::providers::ChatMessage> {
+    ) -> (Vec<ChatMessage>, Vec<ChatMessage>) {
         let mut messages = Vec::new();
 
         let mut system_prompt = self.build_system_prompt_with_tools(tools_json);
@@ -168,6 +346,8 @@ When remembering something important, write to {}/memory/MEMORY.md"#,
         }
 
         messages.push(crate::providers::ChatMessage::system(system_prompt));
+        messages.extend(self.example_messages());
+        let keep = messages.len();
 
         for msg in history {
             let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("user");
@@ -175,13 +355,15 @@ When remembering something important, write to {}/memory/MEMORY.md"#,
 
             messages.push(match role {
                 "assistant" => crate::providers::ChatMessage::assistant(content),
+                "system" => crate::providers::ChatMessage::system(content),
                 _ => crate::providers::ChatMessage::user(content),
             });
         }
 
         messages.push(crate::providers::ChatMessage::user(current_message));
 
-        messages
+        let dropped = self.trim_to_budget(&mut messages, keep);
+        (messages, dropped)
     }
 
     #[allow(dead_code)]
@@ -247,7 +429,8 @@ mod tests {
             serde_json::json!({"role": "assistant", "content": "Hi there!"}),
         ];
         
-        let messages = context_builder.build_messages(&history, "How are you?", Some("cli"), Some("test-chat"));
+        let (messages, dropped) = context_builder.build_messages(&history, "How are you?", Some("cli"), Some("test-chat"));
+        assert!(dropped.is_empty());
         
         // Should have system message, history messages, and current message
         assert!(messages.len() >= 3); // At least system, 2 history items, and current
@@ -260,6 +443,83 @@ mod tests {
         assert_eq!(messages[messages.len()-1].content, "How are you?");
     }
 
+    #[test]
+    fn test_system_instruction_follows_identity() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("SOUL.md"), "Be kind.").unwrap();
+        let context_builder = ContextBuilder::new(temp_dir.path())
+            .with_system_instruction("Answer only in haiku.\n");
+
+        let (messages, _) = context_builder.build_messages(&[], "Hi", None, None);
+        let system = &messages[0].content;
+
+        let instruction = system.find("Answer only in haiku.").unwrap();
+        assert!(system.find("You are Santoso").unwrap() < instruction);
+        assert!(instruction < system.find("Be kind.").unwrap());
+    }
+
+    #[test]
+    fn test_build_messages_trims_to_token_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let context_builder = ContextBuilder::new(temp_dir.path()).with_max_context_tokens(4000);
+
+        let history: Vec<serde_json::Value> = (0..200)
+            .map(|i| serde_json::json!({
+                "role": if i % 2 == 0 { "user" } else { "assistant" },
+                "content": format!("message {} {}", i, "x".repeat(400)),
+            }))
+            .collect();
+
+        let (messages, dropped) = context_builder.build_messages(&history, "Latest question", Some("cli"), Some("direct"));
+
+        assert!(messages.len() < history.len() + 2);
+        assert_eq!(messages.len() + dropped.len(), history.len() + 2);
+        assert!(messages.iter().map(estimate_tokens).sum::<usize>() <= 4000);
+
+        // Oldest messages go first; system prompt and current message stay
+        assert!(dropped[0].content.starts_with("message 0 "));
+        assert_eq!(messages[0].role, "system");
+        assert!(messages[1].content.starts_with(&format!("message {} ", dropped.len())));
+        assert_eq!(messages.last().unwrap().content, "Latest question");
+    }
+
+    #[test]
+    fn test_examples_come_between_system_prompt_and_history() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(
+            temp_dir.path().join("examples.json"),
+            r#"[{"user": "Convert 2 km", "assistant": "**2 km** = 2,000 m"}, {"user": "Convert 3 kg", "assistant": "**3 kg** = 3,000 g"}]"#,
+        )
+        .unwrap();
+        let history = vec![serde_json::json!({"role": "user", "content": "Hello"})];
+
+        let without = ContextBuilder::new(temp_dir.path());
+        assert_eq!(without.build_messages(&history, "Convert 5 m", None, None).0.len(), 3);
+
+        let context_builder = ContextBuilder::new(temp_dir.path()).with_examples(true);
+        let (messages, _) = context_builder.build_messages(&history, "Convert 5 m", None, None);
+        let turns: Vec<(&str, &str)> = messages[1..].iter().map(|m| (m.role.as_str(), m.content.as_str())).collect();
+        assert_eq!(
+            turns,
+            vec![
+                ("user", "Convert 2 km"),
+                ("assistant", "**2 km** = 2,000 m"),
+                ("user", "Convert 3 kg"),
+                ("assistant", "**3 kg** = 3,000 g"),
+                ("user", "Hello"),
+                ("user", "Convert 5 m"),
+            ]
+        );
+
+        // EXAMPLES.md is the fallback, with multi-line turns
+        std::fs::remove_file(temp_dir.path().join("examples.json")).unwrap();
+        std::fs::write(temp_dir.path().join("EXAMPLES.md"), "# Examples\n\nUser: List two fruits\nAssistant: - apple\n- pear\n").unwrap();
+        let (messages, _) = context_builder.build_messages(&[], "List two colours", None, None);
+        assert_eq!(messages[1].content, "List two fruits");
+        assert_eq!(messages[2].content, "- apple\n- pear");
+        assert_eq!(messages[3].content, "List two colours");
+    }
+
     #[test]
     fn test_load_bootstrap_files() {
         let temp_dir = TempDir::new().unwrap();
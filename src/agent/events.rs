@@ -0,0 +1,109 @@
+use chrono::Utc;
+use reqwest::Client;
+use serde_json::json;
+use std::time::Duration;
+
+use crate::bus::InboundMessage;
+
+/// Longest `summary` sent with an event
+const MAX_SUMMARY_CHARS: usize = 200;
+
+/// POSTs notable agent events to `events.webhook_url` so other systems can
+/// react to them. Sending happens in the background; a slow or failing
+/// endpoint only costs a log line.
+pub struct EventNotifier {
+    client: Client,
+    url: String,
+}
+
+impl EventNotifier {
+    pub fn new(url: String, timeout: Duration) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(timeout)
+                .build()
+                .expect("Failed to create HTTP client"),
+            url,
+        }
+    }
+
+    /// Send `{type, channel, chat_id, timestamp, summary}` without waiting
+    /// for the endpoint.
+    pub fn emit(&self, kind: &str, origin: &InboundMessage, summary: &str) {
+        let event = json!({
+            "type": kind,
+            "channel": origin.channel,
+            "chat_id": origin.chat_id,
+            "timestamp": Utc::now(),
+            "summary": shorten(summary),
+        });
+        let request = self.client.post(&self.url).json(&event);
+        let kind = kind.to_string();
+
+        tokio::spawn(async move {
+            match request.send().await {
+                Ok(response) if !response.status().is_success() => {
+                    tracing::warn!("Event webhook answered {} to {}", response.status(), kind);
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Event webhook failed for {}: {}", kind, e),
+            }
+        });
+    }
+}
+
+fn shorten(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= MAX_SUMMARY_CHARS {
+        return text.to_string();
+    }
+    format!("{}…", text.chars().take(MAX_SUMMARY_CHARS).collect::<String>())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use serde_json::Value;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// An endpoint that answers 200 to every POST and hands back the JSON bodies.
+    pub async fn capture_server() -> (String, mpsc::UnboundedReceiver<Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    let body = loop {
+                        let Ok(n) = socket.read(&mut buf).await else { return };
+                        if n == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&request).to_string();
+                        let Some((head, body)) = text.split_once("\r\n\r\n") else { continue };
+                        let length = head
+                            .lines()
+                            .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().to_string()))
+                            .and_then(|v| v.parse::<usize>().ok())
+                            .unwrap_or(0);
+                        if body.len() >= length {
+                            break body.to_string();
+                        }
+                    };
+                    let _ = socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+                    if let Ok(event) = serde_json::from_str(&body) {
+                        let _ = tx.send(event);
+                    }
+                });
+            }
+        });
+
+        (format!("http://{}", addr), rx)
+    }
+}
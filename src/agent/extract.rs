@@ -0,0 +1,74 @@
+use crate::agent::schema;
+use crate::providers::ChatMessage;
+
+const EXTRACTION_PROMPT: &str = "You maintain a long-term memory of durable facts about the user: \
+names, preferences, decisions, recurring commitments. Read the exchange below and list any NEW \
+durable facts worth remembering across conversations. Skip small talk, one-off requests and \
+anything already in the existing memory. Reply with only a JSON array of short, self-contained \
+strings, or [] if there is nothing new.";
+
+/// Messages asking the model to pull durable facts out of one finished turn.
+pub fn extraction_messages(existing_memory: &str, user: &str, assistant: &str) -> Vec<ChatMessage> {
+    let existing = if existing_memory.trim().is_empty() { "(empty)" } else { existing_memory.trim() };
+
+    vec![
+        ChatMessage::system(EXTRACTION_PROMPT),
+        ChatMessage::user(format!(
+            "Existing memory:\n{}\n\nUser: {}\n\nAssistant: {}",
+            existing, user, assistant
+        )),
+    ]
+}
+
+/// Messages asking the model to boil consolidated history entries down to
+/// durable facts, using the configured `memory.consolidation_prompt`.
+pub fn consolidation_messages(prompt: &str, existing_memory: &str, entries: &[serde_json::Value]) -> Vec<ChatMessage> {
+    let existing = if existing_memory.trim().is_empty() { "(empty)" } else { existing_memory.trim() };
+    let transcript: Vec<String> = entries
+        .iter()
+        .filter_map(|entry| {
+            let role = entry.get("role").and_then(|v| v.as_str())?;
+            let content = entry.get("content").and_then(|v| v.as_str())?;
+            Some(format!("{}: {}", role.to_uppercase(), content))
+        })
+        .collect();
+
+    vec![
+        ChatMessage::system(prompt),
+        ChatMessage::user(format!("Existing memory:\n{}\n\nConversation:\n{}", existing, transcript.join("\n\n"))),
+    ]
+}
+
+/// Facts from the model's reply. Anything that isn't a JSON array of strings yields nothing.
+pub fn parse_facts(content: &str) -> Vec<String> {
+    let Ok(value) = schema::extract_json(content) else {
+        return Vec::new();
+    };
+
+    value
+        .as_array()
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str())
+                .map(|fact| fact.trim().to_string())
+                .filter(|fact| !fact.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_facts() {
+        assert_eq!(
+            parse_facts("```json\n[\"Prefers metric units\", \" \", 3]\n```"),
+            vec!["Prefers metric units"]
+        );
+        assert!(parse_facts("[]").is_empty());
+        assert!(parse_facts("Nothing new.").is_empty());
+    }
+}
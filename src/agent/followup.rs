@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+
+use crate::agent::tools::send_at;
+use crate::bus::OutboundMessage;
+
+/// Sends a single nudge to a chat that went quiet after the bot asked a question.
+pub struct FollowUpScheduler {
+    after: Duration,
+    message: String,
+    outbound_tx: Arc<Mutex<Option<tokio::sync::mpsc::Sender<OutboundMessage>>>>,
+    pending: std::sync::Mutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl FollowUpScheduler {
+    pub fn new(after: Duration, message: String, outbound_tx: tokio::sync::mpsc::Sender<OutboundMessage>) -> Self {
+        Self {
+            after,
+            message,
+            outbound_tx: Arc::new(Mutex::new(Some(outbound_tx))),
+            pending: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a finished turn warrants a follow-up: the bot ended by asking something.
+    pub fn should_arm(response: &str) -> bool {
+        response.trim_end().ends_with('?')
+    }
+
+    /// Schedule the nudge for `key`, replacing any follow-up already pending there.
+    pub fn arm(&self, key: &str, channel: &str, chat_id: &str) {
+        let at = chrono::Utc::now()
+            + chrono::Duration::from_std(self.after).unwrap_or_else(|_| chrono::Duration::zero());
+        let msg = OutboundMessage::new(channel.to_string(), chat_id.to_string(), self.message.clone());
+        let outbound_tx = Arc::clone(&self.outbound_tx);
+
+        let handle = tokio::spawn(async move {
+            send_at(&outbound_tx, at, msg).await;
+        });
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, handle| !handle.is_finished());
+        if let Some(previous) = pending.insert(key.to_string(), handle) {
+            previous.abort();
+        }
+    }
+
+    /// Cancel the pending follow-up for `key`, e.g. because the user replied.
+    pub fn disarm(&self, key: &str) {
+        if let Some(handle) = self.pending.lock().unwrap().remove(key) {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_arm_only_on_question() {
+        assert!(FollowUpScheduler::should_arm("Which file do you mean? "));
+        assert!(!FollowUpScheduler::should_arm("Done."));
+    }
+
+    #[tokio::test]
+    async fn test_follow_up_fires_once_after_interval() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let scheduler = FollowUpScheduler::new(Duration::from_millis(50), "Still there?".to_string(), tx);
+
+        scheduler.arm("telegram:42", "telegram", "42");
+        assert!(rx.try_recv().is_err());
+
+        let msg = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.unwrap().unwrap();
+        assert_eq!(msg.chat_id, "42");
+        assert_eq!(msg.content, "Still there?");
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_disarm_cancels_follow_up() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let scheduler = FollowUpScheduler::new(Duration::from_millis(50), "Still there?".to_string(), tx);
+
+        scheduler.arm("telegram:42", "telegram", "42");
+        scheduler.disarm("telegram:42");
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(rx.try_recv().is_err());
+    }
+}
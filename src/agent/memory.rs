@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 #[allow(dead_code)]
@@ -5,8 +6,17 @@ pub struct MemoryStore {
     memory_dir: PathBuf,
     memory_file: PathBuf,
     history_file: PathBuf,
+    /// Rotate HISTORY.md once it grows past this many bytes (0 = never)
+    max_history_bytes: u64,
+    /// Numbered backups (HISTORY.md.1 is the newest) kept after rotating
+    history_backups: usize,
+    /// Cap on the memory shown in the system prompt (0 = no limit)
+    max_context_chars: usize,
 }
 
+const DEFAULT_MAX_HISTORY_BYTES: u64 = 10 * 1024 * 1024;
+const DEFAULT_HISTORY_BACKUPS: usize = 3;
+
 impl MemoryStore {
     pub fn new(workspace: &Path) -> Self {
         let memory_dir = workspace.join("memory");
@@ -16,6 +26,44 @@ impl MemoryStore {
             memory_dir: memory_dir.clone(),
             memory_file: memory_dir.join("MEMORY.md"),
             history_file: memory_dir.join("HISTORY.md"),
+            max_history_bytes: DEFAULT_MAX_HISTORY_BYTES,
+            history_backups: DEFAULT_HISTORY_BACKUPS,
+            max_context_chars: 0,
+        }
+    }
+
+    /// Rotate HISTORY.md past `max_bytes` (0 = never), keeping `backups` old files.
+    pub fn with_history_rotation(mut self, max_bytes: u64, backups: usize) -> Self {
+        self.max_history_bytes = max_bytes;
+        self.history_backups = backups;
+        self
+    }
+
+    /// Show at most `max_chars` of memory in the system prompt (0 = no limit).
+    pub fn with_max_context_chars(mut self, max_chars: usize) -> Self {
+        self.max_context_chars = max_chars;
+        self
+    }
+
+    /// Memory about one user, kept in `memory/users/<id>.md` so facts about
+    /// one person in a group never show up in another's context.
+    pub fn for_user(workspace: &Path, user_id: &str) -> Self {
+        let memory_dir = workspace.join("memory");
+        let users_dir = memory_dir.join("users");
+        std::fs::create_dir_all(&users_dir).ok();
+
+        let file_name: String = user_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+
+        Self {
+            memory_dir: memory_dir.clone(),
+            memory_file: users_dir.join(format!("{}.md", file_name)),
+            history_file: memory_dir.join("HISTORY.md"),
+            max_history_bytes: DEFAULT_MAX_HISTORY_BYTES,
+            history_backups: DEFAULT_HISTORY_BACKUPS,
+            max_context_chars: 0,
         }
     }
 
@@ -27,11 +75,104 @@ impl MemoryStore {
         }
     }
 
-    #[allow(dead_code)]
     pub fn write_long_term(&self, content: &str) -> std::io::Result<()> {
         std::fs::write(&self.memory_file, content)
     }
 
+    /// Append facts to long-term memory as bullet points, skipping ones that are
+    /// already there (case-insensitive). Returns how many were added.
+    pub fn merge_facts(&self, facts: &[String]) -> std::io::Result<usize> {
+        let mut content = self.read_long_term();
+        let mut known: Vec<String> = content
+            .lines()
+            .map(|line| line.trim().trim_start_matches("- ").trim().to_lowercase())
+            .collect();
+
+        let mut added = 0;
+        for fact in facts {
+            let normalized = fact.trim().to_lowercase();
+            if normalized.is_empty() || known.contains(&normalized) {
+                continue;
+            }
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            content.push_str(&format!("- {}\n", fact.trim()));
+            known.push(normalized);
+            added += 1;
+        }
+
+        if added > 0 {
+            self.write_long_term(&content)?;
+        }
+        Ok(added)
+    }
+
+    /// Append one bullet to long-term memory. The file is only ever appended
+    /// to, so concurrent writers can't clobber each other's entries.
+    pub fn append_entry(&self, entry: &str) -> std::io::Result<()> {
+        use std::io::Write;
+        let existing = self.read_long_term();
+        let needs_newline = !existing.is_empty() && !existing.ends_with('\n');
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.memory_file)?;
+
+        if needs_newline {
+            writeln!(file)?;
+        }
+        // Continuation lines stay inside the bullet
+        writeln!(file, "- {}", entry.trim().replace('\n', "\n  "))
+    }
+
+    /// Memory entries mentioning `keyword` (case-insensitive).
+    pub fn search(&self, keyword: &str) -> Vec<String> {
+        let keyword = keyword.trim().to_lowercase();
+        self.entries()
+            .into_iter()
+            .filter(|entry| entry.to_lowercase().contains(&keyword))
+            .collect()
+    }
+
+    /// Long-term memory split into entries: a bullet with its indented
+    /// continuation lines, or any other non-empty line. Headings are left out.
+    pub fn entries(&self) -> Vec<String> {
+        let mut entries: Vec<String> = Vec::new();
+        for line in self.read_long_term().lines() {
+            let continues = line.starts_with("  ") && !line.trim().is_empty();
+            match entries.last_mut() {
+                Some(entry) if continues && entry.starts_with("- ") => {
+                    entry.push('\n');
+                    entry.push_str(line);
+                }
+                _ if !line.trim().is_empty() => entries.push(line.to_string()),
+                _ => {}
+            }
+        }
+
+        entries.retain(|entry| !entry.starts_with('#'));
+        entries
+    }
+
+    /// The sidecar next to the memory file holding an embedding per entry.
+    fn embeddings_file(&self) -> PathBuf {
+        self.memory_file.with_extension("embeddings.json")
+    }
+
+    /// Embeddings saved by `save_embeddings`, keyed by entry text.
+    pub fn load_embeddings(&self) -> HashMap<String, Vec<f32>> {
+        std::fs::read_to_string(self.embeddings_file())
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save_embeddings(&self, embeddings: &HashMap<String, Vec<f32>>) -> std::io::Result<()> {
+        let json = serde_json::to_string(embeddings).map_err(std::io::Error::other)?;
+        std::fs::write(self.embeddings_file(), json)
+    }
+
     pub fn append_history(&self, entry: &str) -> std::io::Result<()> {
         use std::io::Write;
         let mut file = std::fs::OpenOptions::new()
@@ -40,31 +181,44 @@ impl MemoryStore {
             .open(&self.history_file)?;
 
         writeln!(file, "{}\n", entry.trim())?;
-        
-        // Optionally rotate the history file if it gets too large
-        // For now, we'll just log the size
-        if let Ok(metadata) = std::fs::metadata(&self.history_file) {
-            if metadata.len() > 10 * 1024 * 1024 { // 10MB threshold
-                tracing::warn!("History file is getting large ({} bytes)", metadata.len());
-            }
+        drop(file);
+
+        if self.max_history_bytes > 0 {
+            self.rotate_history_if_needed(self.max_history_bytes)?;
         }
         
         Ok(())
     }
     
-    /// Rotate history file if it exceeds size threshold
-    #[allow(dead_code)]
+    /// Rotate the history file once it exceeds `max_size`: HISTORY.md becomes
+    /// HISTORY.md.1, the previous .1 becomes .2, and so on.
     pub fn rotate_history_if_needed(&self, max_size: u64) -> std::io::Result<()> {
-        if let Ok(metadata) = std::fs::metadata(&self.history_file) {
-            if metadata.len() > max_size {
-                let backup_path = self.history_file.with_extension("md.backup");
-                std::fs::rename(&self.history_file, &backup_path)?;
-                tracing::info!("History file rotated: {} -> {}", 
-                              self.history_file.display(), 
-                              backup_path.display());
+        let Ok(metadata) = std::fs::metadata(&self.history_file) else {
+            return Ok(());
+        };
+        if metadata.len() <= max_size {
+            return Ok(());
+        }
+
+        for n in (1..=self.history_backups).rev() {
+            let from = self.history_backup(n);
+            if from.exists() {
+                std::fs::rename(&from, self.history_backup(n + 1))?;
             }
         }
-        Ok(())
+        let newest = self.history_backup(1);
+        std::fs::rename(&self.history_file, &newest)?;
+        tracing::info!("History file rotated: {} -> {}", 
+                      self.history_file.display(), 
+                      newest.display());
+
+        self.cleanup_old_backups()
+    }
+
+    fn history_backup(&self, n: usize) -> PathBuf {
+        let mut name = self.history_file.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
     }
 
     pub fn get_memory_context(&self) -> String {
@@ -72,7 +226,7 @@ impl MemoryStore {
         if long_term.is_empty() {
             String::new()
         } else {
-            format!("## Long-term Memory\n\n{}", long_term)
+            format!("## Long-term Memory\n\n{}", recent_memory(&long_term, self.max_context_chars))
         }
     }
 
@@ -85,33 +239,89 @@ impl MemoryStore {
         }
     }
     
-    /// Clean up old backup files to free disk space
-    #[allow(dead_code)]
+    /// Remove history backups beyond the newest `history_backups`, including
+    /// the single `.backup` file older versions kept.
     pub fn cleanup_old_backups(&self) -> std::io::Result<()> {
-        if let Some(parent_dir) = self.history_file.parent() {
-            let stem = self.history_file.file_stem()
-                .unwrap_or_default().to_string_lossy();
-                
-            for entry in std::fs::read_dir(parent_dir)? {
-                let entry = entry?;
-                let file_name = entry.file_name();
-                let name = file_name.to_string_lossy();
-                
-                if name.starts_with(&format!("{}.backup", stem)) {
-                    std::fs::remove_file(entry.path())?;
-                    tracing::info!("Cleaned up old backup: {}", entry.path().display());
-                }
+        let Some(parent_dir) = self.history_file.parent() else {
+            return Ok(());
+        };
+        let file_name = self.history_file.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+        for entry in std::fs::read_dir(parent_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(suffix) = name.strip_prefix(&file_name).and_then(|rest| rest.strip_prefix('.')) else {
+                continue;
+            };
+
+            let stale = match suffix.parse::<usize>() {
+                Ok(n) => n > self.history_backups,
+                Err(_) => suffix == "backup",
+            };
+            if stale {
+                std::fs::remove_file(entry.path())?;
+                tracing::info!("Cleaned up old backup: {}", entry.path().display());
             }
         }
         Ok(())
     }
 }
 
+/// The end of `memory` that fits in `max_chars` (0 = all of it), cut at a
+/// line boundary. Entries are appended, so the end is the most recent part.
+pub fn recent_memory(memory: &str, max_chars: usize) -> String {
+    let total = memory.chars().count();
+    if max_chars == 0 || total <= max_chars {
+        return memory.to_string();
+    }
+
+    let tail: String = memory.chars().skip(total - max_chars).collect();
+    // Start at the first whole line, unless that would leave nothing
+    let tail = match tail.find('\n') {
+        Some(newline) if !tail[newline + 1..].trim().is_empty() => &tail[newline + 1..],
+        _ => tail.as_str(),
+    };
+    format!("(Older memory omitted; use the recall tool to search it.)\n\n{}", tail)
+}
+
+/// Cosine of the angle between `a` and `b`; 0 when either is all zeros.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+/// The `k` entries whose embeddings are closest to `query`, best first.
+pub fn rank_by_similarity<'a>(query: &[f32], entries: &'a [(String, Vec<f32>)], k: usize) -> Vec<&'a str> {
+    let mut scored: Vec<(f32, &str)> = entries
+        .iter()
+        .map(|(text, embedding)| (cosine_similarity(query, embedding), text.as_str()))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored.into_iter().take(k).map(|(_, text)| text).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_entries_rank_by_cosine_similarity() {
+        let entries = vec![
+            ("- Likes hiking".to_string(), vec![0.0, 1.0, 0.0]),
+            ("- Allergic to peanuts".to_string(), vec![1.0, 0.0, 0.0]),
+            ("- Avoids shellfish".to_string(), vec![0.8, 0.2, 0.0]),
+            ("- Has no embedding yet".to_string(), vec![0.0, 0.0, 0.0]),
+        ];
+
+        assert_eq!(rank_by_similarity(&[0.9, 0.1, 0.0], &entries, 2), vec!["- Allergic to peanuts", "- Avoids shellfish"]);
+        assert_eq!(rank_by_similarity(&[0.0, 2.0, 0.0], &entries, 1), vec!["- Likes hiking"]);
+        assert!((cosine_similarity(&[1.0, 1.0], &[2.0, 2.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 0.0]), 0.0);
+    }
+
     #[test]
     fn test_memory_store_creation() {
         let temp_dir = TempDir::new().unwrap();
@@ -140,6 +350,50 @@ mod tests {
         assert_eq!(read_content, test_content);
     }
 
+    #[test]
+    fn test_merge_facts_deduplicates() {
+        let temp_dir = TempDir::new().unwrap();
+        let memory_store = MemoryStore::new(temp_dir.path());
+        memory_store.write_long_term("# Memory\n- Name is Budi").unwrap();
+
+        let added = memory_store
+            .merge_facts(&["name is budi".to_string(), "Prefers tea".to_string(), "Prefers tea".to_string()])
+            .unwrap();
+
+        assert_eq!(added, 1);
+        assert_eq!(memory_store.read_long_term(), "# Memory\n- Name is Budi\n- Prefers tea\n");
+    }
+
+    #[test]
+    fn test_append_entry_and_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let memory_store = MemoryStore::new(temp_dir.path());
+        memory_store.write_long_term("# Memory\n- Name is Budi").unwrap();
+
+        memory_store.append_entry("Allergic to peanuts\nCarries an EpiPen").unwrap();
+        memory_store.append_entry("Works at PT Maju").unwrap();
+
+        assert_eq!(
+            memory_store.read_long_term(),
+            "# Memory\n- Name is Budi\n- Allergic to peanuts\n  Carries an EpiPen\n- Works at PT Maju\n"
+        );
+        assert_eq!(memory_store.search("epipen"), vec!["- Allergic to peanuts\n  Carries an EpiPen"]);
+        assert!(memory_store.search("memory").is_empty());
+    }
+
+    #[test]
+    fn test_user_stores_are_separate_from_shared_memory() {
+        let temp_dir = TempDir::new().unwrap();
+        let shared = MemoryStore::new(temp_dir.path());
+        let alice = MemoryStore::for_user(temp_dir.path(), "alice/../42");
+
+        alice.write_long_term("- Name is Alice").unwrap();
+
+        assert!(shared.read_long_term().is_empty());
+        assert!(MemoryStore::for_user(temp_dir.path(), "bob").read_long_term().is_empty());
+        assert_eq!(alice.memory_file, temp_dir.path().join("memory/users/alice____42.md"));
+    }
+
     #[test]
     fn test_memory_store_append_history() {
         let temp_dir = TempDir::new().unwrap();
@@ -154,6 +408,26 @@ mod tests {
         assert!(history_content.contains(entry));
     }
 
+    #[test]
+    fn test_history_rotates_into_a_bounded_backup_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let memory_store = MemoryStore::new(temp_dir.path()).with_history_rotation(64, 2);
+        let dir = temp_dir.path().join("memory");
+
+        // Each entry pushes the file past 64 bytes, so every append rotates
+        for i in 1..=4 {
+            memory_store.append_history(&format!("entry {} {}", i, "x".repeat(60))).unwrap();
+        }
+
+        assert!(!dir.join("HISTORY.md").exists());
+        assert!(std::fs::read_to_string(dir.join("HISTORY.md.1")).unwrap().starts_with("entry 4"));
+        assert!(std::fs::read_to_string(dir.join("HISTORY.md.2")).unwrap().starts_with("entry 3"));
+        assert!(!dir.join("HISTORY.md.3").exists());
+
+        memory_store.append_history("short").unwrap();
+        assert_eq!(memory_store.read_history(), "short\n\n");
+    }
+
     #[test]
     fn test_memory_store_get_memory_context() {
         let temp_dir = TempDir::new().unwrap();
@@ -171,4 +445,26 @@ mod tests {
         assert!(context.contains(test_content));
         assert!(context.contains("## Long-term Memory"));
     }
+
+    #[test]
+    fn test_memory_context_is_capped_to_the_most_recent_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let memory_store = MemoryStore::new(temp_dir.path()).with_max_context_chars(200);
+
+        let entries: Vec<String> = (0..1000).map(|i| format!("- fact number {}", i)).collect();
+        memory_store.write_long_term(&entries.join("\n")).unwrap();
+
+        let context = memory_store.get_memory_context();
+        let memory = context.strip_prefix("## Long-term Memory\n\n").unwrap();
+        let (note, kept) = memory.split_once("\n\n").unwrap();
+        assert!(note.contains("Older memory omitted"));
+        assert!(kept.chars().count() <= 200);
+        assert!(kept.starts_with("- fact number"));
+        assert!(kept.ends_with("- fact number 999"));
+        assert!(!kept.contains("- fact number 0\n"));
+
+        // Unlimited by default
+        let full = MemoryStore::new(temp_dir.path()).get_memory_context();
+        assert!(full.contains("- fact number 0\n"));
+    }
 }
@@ -0,0 +1,155 @@
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+use crate::providers::Usage;
+
+/// Counters the agent loop keeps for `gateway.metrics_port`: served as
+/// `GET /metrics` in the Prometheus text format, with `GET /healthz`
+/// answering 200 while the loop is running.
+#[derive(Default)]
+pub struct AgentMetrics {
+    alive: AtomicBool,
+    messages: AtomicU64,
+    tool_calls: AtomicU64,
+    errors: AtomicU64,
+    prompt_tokens: AtomicU64,
+    completion_tokens: AtomicU64,
+}
+
+/// Marks the loop alive until dropped, including when the loop panics.
+pub struct AliveGuard(Arc<AgentMetrics>);
+
+impl Drop for AliveGuard {
+    fn drop(&mut self) {
+        self.0.alive.store(false, Ordering::Relaxed);
+    }
+}
+
+impl AgentMetrics {
+    pub fn alive(self: &Arc<Self>) -> AliveGuard {
+        self.alive.store(true, Ordering::Relaxed);
+        AliveGuard(Arc::clone(self))
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::Relaxed)
+    }
+
+    pub fn message(&self) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn tool_call(&self) {
+        self.tool_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn usage(&self, usage: &Usage) {
+        self.prompt_tokens.fetch_add(usage.prompt_tokens.into(), Ordering::Relaxed);
+        self.completion_tokens.fetch_add(usage.completion_tokens.into(), Ordering::Relaxed);
+    }
+
+    fn render(&self) -> String {
+        let counters = [
+            ("santosobot_messages_processed_total", "Inbound messages the agent loop processed", &self.messages),
+            ("santosobot_tool_calls_total", "Tool calls executed", &self.tool_calls),
+            ("santosobot_errors_total", "Failed turns and tool calls", &self.errors),
+            ("santosobot_prompt_tokens_total", "Prompt tokens used", &self.prompt_tokens),
+            ("santosobot_completion_tokens_total", "Completion tokens used", &self.completion_tokens),
+        ];
+        let mut body = String::new();
+        for (name, help, counter) in counters {
+            body.push_str(&format!("# HELP {} {}\n# TYPE {} counter\n{} {}\n", name, help, name, name, counter.load(Ordering::Relaxed)));
+        }
+        body
+    }
+
+    /// Answer health and metrics requests on `listener` until the task is dropped.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("Metrics server accept failed: {}", e);
+                    continue;
+                }
+            };
+            let metrics = Arc::clone(&self);
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let metrics = Arc::clone(&metrics);
+                    async move { Ok::<_, std::convert::Infallible>(metrics.handle(request)) }
+                });
+                if let Err(e) = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("Metrics connection from {} ended: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    fn handle(&self, request: Request<Incoming>) -> Response<Full<Bytes>> {
+        if request.method() != Method::GET {
+            return respond(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed".to_string());
+        }
+        match request.uri().path() {
+            "/healthz" if self.is_alive() => respond(StatusCode::OK, "ok".to_string()),
+            "/healthz" => respond(StatusCode::SERVICE_UNAVAILABLE, "agent loop is not running".to_string()),
+            "/metrics" => respond(StatusCode::OK, self.render()),
+            _ => respond(StatusCode::NOT_FOUND, "Not found".to_string()),
+        }
+    }
+}
+
+fn respond(status: StatusCode, body: String) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(body)));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_healthz_follows_the_loop_and_metrics_count() {
+        let metrics = Arc::new(AgentMetrics::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(Arc::clone(&metrics).serve(listener));
+        let client = reqwest::Client::new();
+
+        let down = client.get(format!("{}/healthz", base)).send().await.unwrap();
+        assert_eq!(down.status(), 503);
+
+        let guard = metrics.alive();
+        let up = client.get(format!("{}/healthz", base)).send().await.unwrap();
+        assert_eq!(up.status(), 200);
+        assert_eq!(up.text().await.unwrap(), "ok");
+
+        metrics.message();
+        metrics.tool_call();
+        metrics.tool_call();
+        metrics.usage(&Usage { prompt_tokens: 120, completion_tokens: 30, total_tokens: 150 });
+        let body = client.get(format!("{}/metrics", base)).send().await.unwrap().text().await.unwrap();
+        assert!(body.contains("\nsantosobot_messages_processed_total 1\n"), "{}", body);
+        assert!(body.contains("\nsantosobot_tool_calls_total 2\n"), "{}", body);
+        assert!(body.contains("\nsantosobot_errors_total 0\n"), "{}", body);
+        assert!(body.contains("\nsantosobot_prompt_tokens_total 120\n"), "{}", body);
+
+        drop(guard);
+        let stopped = client.get(format!("{}/healthz", base)).send().await.unwrap();
+        assert_eq!(stopped.status(), 503);
+    }
+}
@@ -1,19 +1,55 @@
+mod approval;
+mod artifacts;
+mod attachments;
+mod audit;
+mod availability;
+mod commands;
 mod context;
+mod events;
+mod extract;
+mod followup;
+mod injection;
 mod memory;
+mod metrics;
+mod policy;
+mod schema;
+mod session;
+mod summary;
+mod tool_select;
+mod trace;
 mod tools;
 
+pub use approval::{Approver, ChatApprover, CliApprover};
 pub use context::ContextBuilder;
+use context::Verbosity;
 pub use memory::MemoryStore;
+pub use metrics::AgentMetrics;
 
+use artifacts::ArtifactTracker;
+use audit::AuditLog;
+use events::EventNotifier;
+use trace::TraceLog;
+use availability::ToolAvailability;
+use commands::Command;
+use followup::FollowUpScheduler;
+use injection::InjectionScanner;
+use policy::DestructiveToolPolicy;
+use session::{message_key, session_key, BranchStore, SessionFile};
+pub use session::SessionStore;
+
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use tokio::sync::RwLock;
+use std::sync::Arc;
+use tokio::sync::{RwLock, Semaphore};
 use serde::Deserialize;
 
 #[allow(dead_code)]
 use crate::bus::{InboundMessage, OutboundMessage};
-use crate::config::Config;
-use crate::providers::{ChatMessage, OpenAIProvider};
-use crate::agent::tools::{EditFileTool, ListDirTool, ReadFileTool, ShellTool, ToolRegistry, WebFetchTool, WriteFileTool};
+use crate::channels::telegram::BOT_ID_KEY;
+use crate::config::{BroadcastTarget, Config, ModelPrice};
+use crate::providers::{create_provider, ChatMessage, OpenAIProvider, LLMProvider, LLMResponse, StreamEvent, ToolCall, ToolCallAccumulator, ToolChoice, Usage};
+use futures::StreamExt;
+use crate::agent::tools::{parse_timezone, validate_read_path, ApplyPatchTool, BraveSearchTool, EditFileTool, EmailTool, GitTool, GrepTool, HealthTool, ListDirTool, MessageTool, NoteTool, ReadFileTool, RecallTool, RememberTool, ReminderTool, ShellTool, StructuredEditTool, SystemInfoTool, ToolRegistry, WaitTool, WebFetchTool, WriteFileTool};
 
 #[derive(Deserialize)]
 struct ToolCallRequest {
@@ -22,20 +58,269 @@ struct ToolCallRequest {
     arguments: serde_json::Value,
 }
 
+/// Settings that apply to a single turn only.
+#[derive(Debug, Clone, Default)]
+struct TurnOptions {
+    tool_choice: Option<ToolChoice>,
+    /// JSON Schema the final answer must satisfy
+    expect_schema: Option<serde_json::Value>,
+    /// Overrides of the configured model settings, from inbound metadata
+    model: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+}
+
+impl TurnOptions {
+    fn tools_enabled(&self) -> bool {
+        self.tool_choice != Some(ToolChoice::None)
+    }
+
+    /// Take `model`, `temperature` and `max_tokens` from message metadata.
+    /// Temperature is clamped to 0–2 and max_tokens to the configured maximum;
+    /// values that don't parse are ignored.
+    fn apply_metadata(&mut self, metadata: &HashMap<String, String>, max_tokens_limit: u32) {
+        if let Some(model) = metadata.get("model").map(|m| m.trim()).filter(|m| !m.is_empty()) {
+            self.model = Some(model.to_string());
+        }
+        if let Some(value) = metadata.get("temperature") {
+            match value.trim().parse::<f32>() {
+                Ok(t) if t.is_finite() => self.temperature = Some(t.clamp(0.0, 2.0)),
+                _ => tracing::warn!("Ignoring invalid temperature override: {}", value),
+            }
+        }
+        if let Some(value) = metadata.get("max_tokens") {
+            match value.trim().parse::<u32>() {
+                Ok(n) if n > 0 => self.max_tokens = Some(n.min(max_tokens_limit)),
+                _ => tracing::warn!("Ignoring invalid max_tokens override: {}", value),
+            }
+        }
+    }
+}
+
+/// How often a streaming reply is updated in chats that show partial replies
+const LIVE_REPLY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Milestones of a turn, for UI feedback such as a spinner or status line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamProgress {
+    RequestStarted,
+    FirstToken,
+    /// `argument` is a short preview of the call, e.g. the path being read
+    ToolStarted { name: String, argument: String },
+    ToolFinished { name: String, argument: String, elapsed: std::time::Duration, ok: bool },
+}
+
+const MAX_PROGRESS_ARGUMENT_CHARS: usize = 40;
+
+/// The first string argument of a tool call, shortened for a status line.
+fn progress_argument(arguments: &serde_json::Value) -> String {
+    let first = arguments
+        .as_object()
+        .and_then(|args| args.values().find_map(|v| v.as_str()))
+        .unwrap_or_default();
+    let first = first.lines().next().unwrap_or_default();
+    if first.chars().count() > MAX_PROGRESS_ARGUMENT_CHARS {
+        format!("{}…", first.chars().take(MAX_PROGRESS_ARGUMENT_CHARS).collect::<String>())
+    } else {
+        first.to_string()
+    }
+}
+
+/// The tool result fed back in dry-run mode: `DRY RUN: would have executed
+/// shell(command="ls")`, with arguments sorted by name.
+fn dry_run_result(tool: &str, arguments: &serde_json::Value) -> String {
+    let mut arguments: Vec<String> = arguments
+        .as_object()
+        .map(|args| args.iter().map(|(name, value)| format!("{}={}", name, value)).collect())
+        .unwrap_or_default();
+    arguments.sort();
+    let arguments = arguments.join(", ");
+    format!("DRY RUN: would have executed {}({})", tool, arguments)
+}
+
+/// Sent in place of an answer that is still blank after `agent.empty_retries`
+const NO_RESPONSE: &str = "I've completed processing but have no response to give.";
+
+/// The user message that follows each round of tool results
+const TOOL_NUDGE: &str = "Tool executed. Continue with your response or use another tool if needed.";
+
+/// Drop tool-loop scaffolding from `messages[loop_start..]` before the next
+/// request: every nudge but a trailing one, the text of all but the newest
+/// assistant tool-call message, and whole rounds (an assistant call plus its
+/// results) older than the last `keep`. Returns how many rounds were dropped.
+fn trim_tool_loop(messages: &mut Vec<ChatMessage>, loop_start: usize, keep: usize) -> usize {
+    let tail: Vec<ChatMessage> = messages.drain(loop_start.min(messages.len())..).collect();
+    let count = tail.len();
+    let mut leading = Vec::new();
+    let mut rounds: Vec<Vec<ChatMessage>> = Vec::new();
+    let mut nudge = None;
+
+    for (i, message) in tail.into_iter().enumerate() {
+        if message.role == "user" && message.content == TOOL_NUDGE {
+            if i + 1 == count {
+                nudge = Some(message);
+            }
+            continue;
+        }
+        match rounds.last_mut() {
+            _ if message.role == "assistant" && message.tool_calls.is_some() => rounds.push(vec![message]),
+            Some(round) => round.push(message),
+            None => leading.push(message),
+        }
+    }
+
+    let dropped = rounds.len().saturating_sub(keep);
+    let kept = rounds.len() - dropped;
+    messages.extend(leading);
+    for (i, mut round) in rounds.into_iter().skip(dropped).enumerate() {
+        // The newest call's text may still matter; older echoes were already sent
+        if i + 1 < kept {
+            round[0].content.clear();
+        }
+        messages.extend(round);
+    }
+    messages.extend(nudge);
+    dropped
+}
+
+/// Identical calls in a row that still run; later repeats are answered with `REPEATED_CALL_RESULT`
+const MAX_REPEATED_CALLS: usize = 2;
+
+const REPEATED_CALL_RESULT: &str =
+    "You already ran this exact call and got the same result; it was not run again. Try a different approach.";
+
+/// Tracks the most recent tool call of a turn to catch a model stuck
+/// repeating it with the same arguments.
+#[derive(Default)]
+struct RepeatDetector {
+    last: Option<(String, serde_json::Value)>,
+    streak: usize,
+}
+
+impl RepeatDetector {
+    /// Record the next call; true once it has repeated more than `MAX_REPEATED_CALLS` times in a row.
+    fn is_loop(&mut self, name: &str, arguments: &serde_json::Value) -> bool {
+        match &self.last {
+            Some((last_name, last_arguments)) if last_name == name && last_arguments == arguments => self.streak += 1,
+            _ => {
+                self.last = Some((name.to_string(), arguments.clone()));
+                self.streak = 1;
+            }
+        }
+        self.streak > MAX_REPEATED_CALLS
+    }
+}
+
+/// What a channel profile swaps in for the global defaults.
+struct ChannelContext {
+    workspace: PathBuf,
+    context: ContextBuilder,
+    persona: String,
+    /// note, remember and recall bound to `workspace`, used in place of the global ones
+    memory_tools: ToolRegistry,
+}
+
+/// The channel a session key belongs to: "telegram" for "telegram.work:42".
+fn key_channel(key: &str) -> &str {
+    key.split([':', '.']).next().unwrap_or(key)
+}
+
+/// Prompt building for `workspace`, with the limits from `config`.
+fn context_builder(config: &Config, workspace: &Path, timezone: chrono_tz::Tz) -> ContextBuilder {
+    ContextBuilder::new(workspace)
+        .with_max_context_tokens(config.agent.max_context_tokens)
+        .with_max_memory_chars(config.memory.max_context_chars)
+        .with_timezone(timezone)
+        .with_examples(config.agent.use_examples)
+}
+
 pub struct AgentLoop {
     inbound_rx: tokio::sync::mpsc::Receiver<InboundMessage>,
-    provider: OpenAIProvider,
+    provider: Arc<dyn LLMProvider>,
     workspace: PathBuf,
     model: String,
     max_iterations: u32,
+    /// Tool executions allowed per turn, across iterations (0 = no limit)
+    max_tool_calls: usize,
+    /// Tool-call rounds of the current turn resent to the model (0 = all, untrimmed)
+    keep_tool_rounds: usize,
     temperature: f32,
     max_tokens: u32,
     memory_window: u32,
+    empty_retries: u32,
+    /// Longest silence tolerated mid-stream (zero = wait forever)
+    stream_idle_timeout: std::time::Duration,
+    stream_retries: u32,
+    /// Whether to stream responses (`provider.stream`); otherwise plain `chat` requests are sent
+    stream: bool,
+    /// Answer tool calls with a "DRY RUN" note instead of executing them (`agent.dry_run`)
+    dry_run: bool,
+    /// Channels shown partial replies while a response streams in
+    live_reply_channels: Vec<String>,
+    audit: Option<AuditLog>,
+    /// Per-event JSON Lines trace of the agent loop (`agent.trace_file`)
+    trace: Option<TraceLog>,
+    /// Webhook for turn and tool-error events (`events.webhook_url`)
+    events: Option<EventNotifier>,
+    /// Counters and liveness for `gateway.metrics_port`
+    metrics: Arc<AgentMetrics>,
+    /// Flips to true when the process is shutting down
+    shutdown: Option<tokio::sync::watch::Receiver<bool>>,
     tools: RwLock<ToolRegistry>,
+    /// Tool pre-selection: cap per request (0 = advertise all) and the always-on set
+    max_advertised_tools: usize,
+    core_tools: Vec<String>,
+    policy: DestructiveToolPolicy,
+    /// Who may use which tool, and when, per `tools.availability`
+    availability: ToolAvailability,
+    /// Tools that need a yes from `approver` before each run
+    require_approval: Vec<String>,
+    approver: Option<std::sync::Arc<dyn Approver>>,
+    injection_scanner: InjectionScanner,
     context: ContextBuilder,
-    session_history: RwLock<Vec<serde_json::Value>>,
+    /// Transcript per conversation, keyed by `session_key(channel, chat_id)`
+    session_history: RwLock<HashMap<String, Vec<serde_json::Value>>>,
+    /// Cumulative token usage per conversation, persisted with the transcript
+    session_usage: RwLock<HashMap<String, Usage>>,
+    show_usage: bool,
+    /// Price the turn in the footer too, from `[pricing]` (`agent.show_cost`)
+    show_cost: bool,
+    pricing: HashMap<String, ModelPrice>,
+    plain: bool,
+    clear_commands: Vec<String>,
+    branches: BranchStore,
+    sessions: SessionStore,
+    max_persisted_turns: usize,
+    pending_options: RwLock<HashMap<String, TurnOptions>>,
+    /// Task file pinned to the system prompt per conversation (`/focus`)
+    focus: RwLock<HashMap<String, String>>,
+    /// Reply length from `agent.verbosity`, and per-conversation overrides (`/brief`, `/detailed`)
+    verbosity: Verbosity,
+    verbosity_overrides: RwLock<HashMap<String, Verbosity>>,
+    dedup_window: std::time::Duration,
+    /// Last message content and arrival time per conversation, for dedup
+    last_messages: RwLock<HashMap<String, (String, tokio::time::Instant)>>,
+    followups: Option<FollowUpScheduler>,
+    broadcast_targets: Vec<BroadcastTarget>,
+    /// Extra system-prompt instructions per Telegram bot id
+    personas: HashMap<String, String>,
+    /// Channels with their own workspace or persona (`channels.profiles`)
+    profiles: HashMap<String, ChannelContext>,
+    auto_extract: bool,
+    /// Facts about each sender live in their own memory file
+    per_user_memory: bool,
+    max_memory_chars: usize,
+    consolidation_prompt: String,
+    max_history_bytes: u64,
+    history_backups: usize,
+    /// Set while a consolidation runs, so it can't re-enter itself
+    consolidating: std::sync::atomic::AtomicBool,
+    /// Files written per turn, when `agent.artifact_manifest` is on
+    artifacts: Option<ArtifactTracker>,
+    announce_artifacts: bool,
     #[allow(dead_code)]
     outbound_tx: tokio::sync::mpsc::Sender<OutboundMessage>,
+    progress_tx: Option<tokio::sync::mpsc::UnboundedSender<StreamProgress>>,
 }
 
 impl AgentLoop {
@@ -43,11 +328,44 @@ impl AgentLoop {
         config: &Config,
         inbound_rx: tokio::sync::mpsc::Receiver<InboundMessage>,
         outbound_tx: tokio::sync::mpsc::Sender<OutboundMessage>,
+    ) -> Self {
+        Self::with_provider(config, create_provider(&config.provider), inbound_rx, outbound_tx)
+    }
+
+    pub fn with_provider(
+        config: &Config,
+        provider: Box<dyn LLMProvider>,
+        inbound_rx: tokio::sync::mpsc::Receiver<InboundMessage>,
+        outbound_tx: tokio::sync::mpsc::Sender<OutboundMessage>,
     ) -> Self {
         let workspace = config.workspace_path();
-        let provider = OpenAIProvider::new(config.provider.clone());
+        let provider: Arc<dyn LLMProvider> = Arc::from(provider);
+
+        let timezone = parse_timezone(&config.agent.timezone).unwrap_or_else(|e| {
+            tracing::warn!("agent.timezone: {}, using UTC", e);
+            chrono_tz::Tz::UTC
+        });
+
+        let mut tools = Self::create_tools(config, &workspace);
+        tools.register(
+            ReminderTool::new(workspace.display().to_string())
+                .with_outbound_sender(outbound_tx.clone())
+                .with_timezone(timezone)
+                .with_plain(config.ui.is_plain()),
+        );
+        tools.register(MessageTool::new(outbound_tx.clone()));
+        let telegram_bots = config.channels.telegram_bots().into_iter().map(|(id, bot)| (id, bot.token)).collect();
+        tools.register(
+            HealthTool::new(Arc::clone(&provider), config.agent.model.clone(), workspace.clone())
+                .with_telegram_bots(telegram_bots),
+        );
+        let policy = DestructiveToolPolicy::new(&config.tools, &workspace);
 
-        let tools = Self::create_tools(config, &workspace);
+        let followups = (config.agent.followup_after_minutes > 0).then(|| FollowUpScheduler::new(
+            std::time::Duration::from_secs(config.agent.followup_after_minutes * 60),
+            config.agent.followup_message.clone(),
+            outbound_tx.clone(),
+        ));
         
         Self {
             inbound_rx,
@@ -55,18 +373,213 @@ impl AgentLoop {
             workspace,
             model: config.agent.model.clone(),
             max_iterations: config.agent.max_iterations,
+            max_tool_calls: config.agent.max_tool_calls as usize,
+            keep_tool_rounds: config.agent.keep_tool_rounds as usize,
             temperature: config.agent.temperature,
             max_tokens: config.agent.max_tokens,
             memory_window: config.agent.memory_window,
+            empty_retries: config.agent.empty_retries,
+            stream_idle_timeout: std::time::Duration::from_secs(config.provider.stream_idle_timeout_secs),
+            stream_retries: config.provider.stream_retries,
+            stream: config.provider.stream,
+            dry_run: config.agent.dry_run,
+            metrics: Arc::new(AgentMetrics::default()),
+            shutdown: None,
+            audit: config.audit_path().map(|path| {
+                AuditLog::new(path, config.audit.redact_arguments.clone(), config.audit.max_result_chars)
+            }),
+            trace: config.trace_path().map(TraceLog::new),
+            events: (!config.events.webhook_url.trim().is_empty()).then(|| EventNotifier::new(
+                config.events.webhook_url.trim().to_string(),
+                std::time::Duration::from_secs(config.events.timeout_secs),
+            )),
+            live_reply_channels: if config.channels.telegram_bots().iter().any(|(_, bot)| bot.stream_replies) {
+                vec!["telegram".to_string()]
+            } else {
+                Vec::new()
+            },
             tools: RwLock::new(tools),
-            context: ContextBuilder::new(&config.workspace_path()),
-            session_history: RwLock::new(Vec::new()),
+            max_advertised_tools: config.agent.max_advertised_tools,
+            core_tools: config.agent.core_tools.clone(),
+            policy,
+            availability: ToolAvailability::new(&config.tools.availability, timezone),
+            require_approval: config.tools.require_approval.clone(),
+            approver: None,
+            injection_scanner: InjectionScanner::new(&config.tools),
+            context: context_builder(config, &config.workspace_path(), timezone),
+            session_history: RwLock::new(HashMap::new()),
+            session_usage: RwLock::new(HashMap::new()),
+            show_usage: config.agent.show_usage,
+            show_cost: config.agent.show_cost,
+            pricing: config.pricing.clone(),
+            plain: config.ui.is_plain(),
+            clear_commands: config.agent.clear_commands.clone(),
+            branches: BranchStore::new(&config.workspace_path()),
+            sessions: SessionStore::new(&config.workspace_path()),
+            max_persisted_turns: config.agent.max_persisted_turns,
+            pending_options: RwLock::new(HashMap::new()),
+            focus: RwLock::new(HashMap::new()),
+            verbosity: Verbosity::parse(&config.agent.verbosity).unwrap_or_else(|| {
+                tracing::warn!("agent.verbosity: unknown value '{}', using normal", config.agent.verbosity);
+                Verbosity::Normal
+            }),
+            verbosity_overrides: RwLock::new(HashMap::new()),
+            dedup_window: std::time::Duration::from_secs(config.agent.dedup_window_secs),
+            last_messages: RwLock::new(HashMap::new()),
+            followups,
+            broadcast_targets: config.channels.broadcast.clone(),
+            personas: config
+                .channels
+                .telegram_bots()
+                .into_iter()
+                .filter(|(_, bot)| !bot.persona.trim().is_empty())
+                .map(|(id, bot)| (id, bot.persona))
+                .collect(),
+            profiles: config
+                .channels
+                .profiles
+                .iter()
+                .map(|(channel, profile)| {
+                    let workspace = profile.workspace_path().unwrap_or_else(|| config.workspace_path());
+                    let mut memory_tools = ToolRegistry::new().with_timeouts(config.tools.default_timeout_secs, &config.tools.timeouts);
+                    Self::register_memory_tools(&mut memory_tools, config, &workspace);
+                    let context = ChannelContext {
+                        context: context_builder(config, &workspace, timezone),
+                        workspace,
+                        persona: profile.persona.trim().to_string(),
+                        memory_tools,
+                    };
+                    (channel.clone(), context)
+                })
+                .collect(),
+            auto_extract: config.memory.auto_extract,
+            per_user_memory: config.memory.per_user,
+            max_memory_chars: config.memory.max_context_chars,
+            consolidation_prompt: config.memory.consolidation_prompt.clone(),
+            max_history_bytes: config.agent.max_history_bytes,
+            history_backups: config.agent.history_backups,
+            consolidating: std::sync::atomic::AtomicBool::new(false),
+            artifacts: config.agent.artifact_manifest.then(|| ArtifactTracker::new(
+                &config.workspace_path(),
+                config.tools.restrict_to_workspace.then(|| config.workspace_path()),
+            )),
+            announce_artifacts: config.agent.announce_artifacts,
             outbound_tx,
+            progress_tx: None,
+        }
+    }
+
+    /// Stop taking messages once `shutdown` turns true. The turn in progress
+    /// finishes first, then every session is saved.
+    pub fn with_shutdown(mut self, shutdown: tokio::sync::watch::Receiver<bool>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Count messages, tool calls, errors and tokens into `metrics`, and mark
+    /// it alive while `run` is going.
+    pub fn with_metrics(mut self, metrics: Arc<AgentMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Report when each LLM request starts and when its first token arrives.
+    pub fn with_progress(mut self, progress_tx: tokio::sync::mpsc::UnboundedSender<StreamProgress>) -> Self {
+        self.progress_tx = Some(progress_tx);
+        self
+    }
+
+    /// Add instructions for this run to the system prompt (`agent --system`).
+    pub fn with_system_instruction(mut self, instruction: impl Into<String>) -> Self {
+        let instruction = instruction.into();
+        self.context = self.context.with_system_instruction(instruction.clone());
+        self.profiles = std::mem::take(&mut self.profiles)
+            .into_iter()
+            .map(|(channel, profile)| {
+                let context = profile.context.with_system_instruction(instruction.clone());
+                (channel, ChannelContext { context, ..profile })
+            })
+            .collect();
+        self
+    }
+
+    /// Ask `approver` before running any tool in `tools.require_approval`.
+    /// Without one, those tools are always denied.
+    pub fn with_approver(mut self, approver: std::sync::Arc<dyn Approver>) -> Self {
+        self.approver = Some(approver);
+        self
+    }
+
+    async fn check_approval(&self, tool: &str, arguments: &serde_json::Value, origin: &InboundMessage) -> Result<(), String> {
+        if !self.require_approval.iter().any(|name| name == tool) {
+            return Ok(());
+        }
+        let approved = match &self.approver {
+            Some(approver) => approver.approve(origin, tool, arguments).await,
+            None => false,
+        };
+        if approved {
+            Ok(())
+        } else {
+            tracing::info!("{} denied by {}:{}", tool, origin.channel, origin.sender_id);
+            Err(format!("Denied by user: {} was not approved", tool))
+        }
+    }
+
+    /// "I created: ..." for the files this turn has written so far, when enabled.
+    fn artifact_footer(&self, origin: &InboundMessage) -> String {
+        match &self.artifacts {
+            Some(artifacts) if self.announce_artifacts => artifacts::announcement(&artifacts.pending(&message_key(origin))),
+            _ => String::new(),
+        }
+    }
+
+    async fn trace(&self, origin: &InboundMessage, event: &str, fields: serde_json::Value) {
+        if let Some(trace) = &self.trace {
+            trace.record(&message_key(origin), event, fields).await;
+        }
+    }
+
+    fn emit_event(&self, kind: &str, origin: &InboundMessage, summary: &str) {
+        if let Some(events) = &self.events {
+            events.emit(kind, origin, summary);
+        }
+    }
+
+    fn report_progress(&self, progress: StreamProgress) {
+        if let Some(tx) = &self.progress_tx {
+            let _ = tx.send(progress);
         }
     }
 
+    /// The tools that read and write `workspace`'s notes and memory.
+    fn register_memory_tools(tools: &mut ToolRegistry, config: &Config, workspace: &Path) {
+        let timezone = parse_timezone(&config.agent.timezone).unwrap_or(chrono_tz::Tz::UTC);
+        tools.register(NoteTool::new(workspace.to_path_buf(), timezone));
+        tools.register(RememberTool::new(workspace, timezone));
+        let recall = RecallTool::new(workspace);
+        tools.register(if config.provider.embedding_model.is_empty() {
+            recall
+        } else {
+            recall.with_embeddings(OpenAIProvider::new(config.provider.clone()))
+        });
+    }
+
     fn create_tools(config: &Config, workspace: &Path) -> ToolRegistry {
-        let mut tools = ToolRegistry::new();
+        // Shell and git enforce shell_timeout themselves, and wait has its own
+        // cap; don't cut them off with a shorter registry-wide limit
+        let mut timeouts = config.tools.timeouts.clone();
+        if config.tools.default_timeout_secs > 0 {
+            let own = [
+                ("shell", config.tools.shell_timeout),
+                ("git", config.tools.shell_timeout),
+                ("wait", config.tools.max_wait_secs),
+            ];
+            for (name, secs) in own {
+                timeouts.entry(name.to_string()).or_insert(config.tools.default_timeout_secs.max(secs + 5));
+            }
+        }
+        let mut tools = ToolRegistry::new().with_timeouts(config.tools.default_timeout_secs, &timeouts);
         
         let allowed_dir = if config.tools.restrict_to_workspace {
             Some(workspace.to_path_buf())
@@ -75,34 +588,93 @@ impl AgentLoop {
         };
 
         tools.register(ReadFileTool::new(allowed_dir.clone()));
-        tools.register(WriteFileTool::new(allowed_dir.clone()));
-        tools.register(EditFileTool::new(allowed_dir.clone()));
+        tools.register(WriteFileTool::new(allowed_dir.clone()).with_writable_extensions(config.tools.writable_extensions.clone()));
+        tools.register(EditFileTool::new(allowed_dir.clone()).with_writable_extensions(config.tools.writable_extensions.clone()));
+        tools.register(ApplyPatchTool::new(allowed_dir.clone()).with_writable_extensions(config.tools.writable_extensions.clone()));
+        tools.register(StructuredEditTool::new(allowed_dir.clone()).with_writable_extensions(config.tools.writable_extensions.clone()));
+        tools.register(GrepTool::new(workspace.to_path_buf(), allowed_dir.clone()));
         tools.register(ListDirTool::new(allowed_dir));
         
-        tools.register(ShellTool::new(
-            workspace.display().to_string(),
+        let shell = ShellTool::new(workspace.display().to_string(), config.tools.shell_timeout)
+            .with_env(config.tools.shell_env.clone())
+            .with_rules(&config.tools.shell_denylist, config.tools.shell_allowlist.clone());
+        tools.register(match config.tools.global_shell_concurrency {
+            0 => shell,
+            limit => shell.with_slots(Arc::new(Semaphore::new(limit))),
+        });
+        
+        tools.register(SystemInfoTool::new(workspace.to_path_buf(), config.tools.shell_env.clone()));
+
+        tools.register(GitTool::new(
+            workspace.to_path_buf(),
             config.tools.shell_timeout,
+            config.tools.git_clone_allowlist.clone(),
         ));
-        
-        tools.register(WebFetchTool::new());
+
+        Self::register_memory_tools(&mut tools, config, workspace);
+
+        // One pool for every web tool, so web_fetch and brave_search share the limit
+        let web_slots = (config.tools.global_web_concurrency > 0)
+            .then(|| Arc::new(Semaphore::new(config.tools.global_web_concurrency)));
+        let web_fetch = WebFetchTool::new();
+        tools.register(match &web_slots {
+            Some(slots) => web_fetch.with_slots(slots.clone()),
+            None => web_fetch,
+        });
+
+        if config.tools.max_wait_secs > 0 {
+            tools.register(WaitTool::new(config.tools.max_wait_secs));
+        }
+
+        // Without a key the tool can only fail, so don't offer it
+        if !config.provider.brave_api_key.trim().is_empty() {
+            let search = BraveSearchTool::new(config.provider.brave_api_key.clone());
+            tools.register(match web_slots {
+                Some(slots) => search.with_slots(slots),
+                None => search,
+            });
+        }
+
+        if config.email.enabled {
+            match EmailTool::new(&config.email) {
+                Ok(tool) => tools.register(tool),
+                Err(e) => tracing::error!("Email tool disabled: {}", e),
+            }
+        }
         
         tools
     }
 
-    #[allow(dead_code)]
-    pub fn register_message_tool(&self, _sender: tokio::sync::mpsc::Sender<OutboundMessage>) {
-        // This would need to be done differently in actual implementation
+    /// Re-arm reminders persisted by a previous run.
+    async fn restore_reminders(&self) {
+        let tools = self.tools.read().await;
+        let Some(reminders) = tools.get("reminder").and_then(|t| t.as_any().downcast_ref::<ReminderTool>()) else {
+            return;
+        };
+        match reminders.restore().await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Restored {} reminder(s)", count),
+            Err(e) => tracing::warn!("Failed to restore reminders: {}", e),
+        }
     }
 
     pub async fn run(&mut self) {
         tracing::info!("Agent loop started");
+        let _alive = self.metrics.alive();
+        self.restore_reminders().await;
         
         loop {
             tokio::select! {
+                _ = shutdown_requested(&mut self.shutdown) => {
+                    tracing::info!("Shutdown requested, no longer taking messages");
+                    break;
+                }
                 msg = self.inbound_rx.recv() => {
                     match msg {
                         Some(msg) => {
+                            self.metrics.message();
                             if let Err(e) = self.process_message(msg).await {
+                                self.metrics.error();
                                 tracing::error!("Error processing message: {}", e);
                             }
                         }
@@ -114,161 +686,1040 @@ impl AgentLoop {
                 }
             }
         }
-        
+
+        self.persist_all_sessions().await;
         tracing::info!("Agent loop stopped");
     }
 
-    async fn process_message(&mut self, msg: InboundMessage) -> Result<(), String> {
-        tracing::info!("Processing message from {}: {}", msg.channel, &msg.content[..msg.content.len().min(50)]);
+    /// Save every conversation this run has touched.
+    async fn persist_all_sessions(&self) {
+        let mut keys: Vec<String> = self.session_history.read().await.keys().cloned().collect();
+        keys.extend(self.session_usage.read().await.keys().cloned());
+        keys.sort();
+        keys.dedup();
+        for key in keys {
+            self.persist_history(&key).await;
+        }
+    }
+
+    async fn handle_command(&self, key: &str, command: Command, origin: &InboundMessage) -> String {
+        match command {
+            Command::Fork => {
+                let history = self.history(key).await;
+                match self.branches.fork(key, &history) {
+                    Ok(id) => format!("Forked conversation into branch '{}'. Use /switch <id> to move between branches.", id),
+                    Err(e) => format!("Failed to fork: {}", e),
+                }
+            }
+            Command::Switch(target) => {
+                if target.is_empty() {
+                    return "Usage: /switch <branch id>".to_string();
+                }
+                let current = self.history(key).await;
+                match self.branches.switch(key, &target, &current) {
+                    Ok(restored) => {
+                        let count = restored.len();
+                        self.session_history.write().await.insert(key.to_string(), restored);
+                        self.persist_history(key).await;
+                        format!("Switched to branch '{}' ({} messages).", target, count)
+                    }
+                    Err(e) => e,
+                }
+            }
+            Command::Branches => {
+                let (active, names) = self.branches.list(key);
+                let lines: Vec<String> = names
+                    .iter()
+                    .map(|name| if *name == active { format!("* {}", name) } else { format!("  {}", name) })
+                    .collect();
+                format!("Branches:\n{}", lines.join("\n"))
+            }
+            Command::NoTools => {
+                self.pending_options.write().await.entry(key.to_string()).or_default().tool_choice = Some(ToolChoice::None);
+                "Tools disabled for the next message.".to_string()
+            }
+            Command::Schema(raw) => {
+                if raw.is_empty() {
+                    return "Usage: /schema <JSON Schema>".to_string();
+                }
+                match serde_json::from_str::<serde_json::Value>(&raw) {
+                    Ok(schema) if schema.is_object() => {
+                        self.pending_options.write().await.entry(key.to_string()).or_default().expect_schema = Some(schema);
+                        "The next answer will be checked against this schema.".to_string()
+                    }
+                    Ok(_) => "Invalid schema: expected a JSON object".to_string(),
+                    Err(e) => format!("Invalid schema: {}", e),
+                }
+            }
+            Command::Focus(path) => {
+                match path.as_str() {
+                    "" => match self.focus.read().await.get(key) {
+                        Some(current) => format!("Focused on {}. Use /focus off to leave focus mode.", current),
+                        None => "Usage: /focus <file> (e.g. /focus TASK.md), /focus off".to_string(),
+                    },
+                    "off" => match self.focus.write().await.remove(key) {
+                        Some(previous) => format!("Left focus mode ({}).", previous),
+                        None => "Focus mode is not on.".to_string(),
+                    },
+                    _ => {
+                        let workspace = self.workspace_for(&origin.channel);
+                        let full = workspace.join(&path);
+                        match validate_read_path(Some(workspace), &full.to_string_lossy()) {
+                            Ok(resolved) if resolved.is_file() => {
+                                self.focus.write().await.insert(key.to_string(), path.clone());
+                                format!("Focused on {}. It will be pinned to every turn until /focus off.", path)
+                            }
+                            Ok(_) => format!("Cannot focus on {}: not a file", path),
+                            Err(e) => format!("Cannot focus on {}: {}", path, e),
+                        }
+                    }
+                }
+            }
+            Command::Verbosity(verbosity) => {
+                self.verbosity_overrides.write().await.insert(key.to_string(), verbosity);
+                format!("Replies in this chat will be {} from now on.", verbosity.name())
+            }
+            Command::Clear => {
+                self.session_history.write().await.insert(key.to_string(), Vec::new());
+                self.pending_options.write().await.remove(key);
+                self.persist_history(key).await;
+                "Conversation cleared. Starting fresh.".to_string()
+            }
+            Command::Broadcast(prompt) => {
+                if prompt.is_empty() {
+                    return "Usage: /broadcast <prompt>".to_string();
+                }
+                match self.broadcast(&prompt, origin).await {
+                    Ok(sent) => format!("Broadcast sent to {} of {} destinations.", sent, self.broadcast_targets.len()),
+                    Err(e) => format!("Broadcast failed: {}", e),
+                }
+            }
+        }
+    }
+
+    /// Run a one-off turn for `prompt` and deliver its output to every configured
+    /// broadcast destination. Returns how many destinations were handed the message.
+    pub async fn broadcast(&self, prompt: &str, origin: &InboundMessage) -> Result<usize, String> {
+        if self.broadcast_targets.is_empty() {
+            return Err("no broadcast destinations configured".to_string());
+        }
+
+        let options = TurnOptions::default();
+        let messages = self.build_turn_messages(&[], prompt, origin, &options).await;
+        let (final_content, _) = self.run_turn(messages, &options, origin).await?;
+        let content = final_content.ok_or("the turn produced no output")?;
+
+        // Each destination is sent separately so one bad target doesn't stop the rest
+        let mut sent = 0;
+        for target in &self.broadcast_targets {
+            let msg = OutboundMessage::new(target.channel.clone(), target.chat_id.clone(), content.clone());
+            match self.outbound_tx.send(msg).await {
+                Ok(()) => sent += 1,
+                Err(e) => tracing::warn!("Broadcast to {}:{} failed: {}", target.channel, target.chat_id, e),
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Resolve `@file:` directives into a context message placed just before the
+    /// user's message. It lives in this turn's messages only, not in history.
+    fn attach_referenced_files(&self, messages: &mut Vec<ChatMessage>, content: &str, channel: &str) {
+        let paths = attachments::file_references(content);
+        if let Some(context) = attachments::context_message(self.workspace_for(channel), &paths) {
+            let at = messages.len().saturating_sub(1);
+            messages.insert(at, context);
+        }
+    }
+
+    /// Pin the focused task file, re-read every turn, to the top of the system
+    /// prompt. It rides on the system message, so history trimming never drops it.
+    async fn apply_focus(&self, messages: &mut [ChatMessage], key: &str) {
+        let Some(path) = self.focus.read().await.get(key).cloned() else {
+            return;
+        };
+        let Some(system) = messages.first_mut().filter(|m| m.role == "system") else {
+            return;
+        };
+
+        let workspace = self.workspace_for(key_channel(key));
+        let full = workspace.join(&path);
+        let content = validate_read_path(Some(workspace), &full.to_string_lossy())
+            .and_then(|p| std::fs::read_to_string(p).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| format!("[could not read {}: {}]", path, e));
+
+        system.content = format!(
+            "## Focus: {}\n\nYou are in focus mode. This task file is the source of truth for the current work; \
+             stay on it. When you finish a step, append a short progress note to {} with edit_file.\n\n{}\n\n---\n\n{}",
+            path,
+            full.display(),
+            content.trim(),
+            system.content
+        );
+    }
+
+    /// What ends a turn's answer: its price with `agent.show_cost`, its token
+    /// count with `agent.show_usage`, or nothing.
+    fn turn_footer(&self, usage: &Usage, options: &TurnOptions) -> String {
+        if self.show_cost {
+            let model = options.model.as_deref().unwrap_or(&self.model);
+            cost_footer(usage, self.pricing.get(model), self.plain)
+        } else if self.show_usage {
+            usage_footer(usage)
+        } else {
+            String::new()
+        }
+    }
+
+    /// Steer reply length with the conversation's verbosity, if it isn't normal.
+    async fn apply_verbosity(&self, messages: &mut [ChatMessage], key: &str) {
+        let verbosity = self.verbosity_overrides.read().await.get(key).copied().unwrap_or(self.verbosity);
+        let Some(section) = verbosity.section() else {
+            return;
+        };
+        if let Some(system) = messages.first_mut().filter(|m| m.role == "system") {
+            system.content = format!("{}\n\n{}", system.content, section);
+        }
+    }
+
+    /// Definitions of the tools worth offering for `origin`, leaving out the ones
+    /// not available to it right now, within `agent.max_advertised_tools`.
+    fn advertised_tools(&self, tools: &ToolRegistry, origin: &InboundMessage) -> Vec<crate::providers::ToolDefinition> {
+        let now = chrono::Utc::now();
+        let definitions = tools
+            .get_definitions()
+            .into_iter()
+            .filter(|definition| self.availability.is_available(&definition.function.name, origin, now))
+            .collect();
+        tool_select::select_tools(definitions, &origin.content, &self.core_tools, self.max_advertised_tools)
+    }
+
+    /// Where `channel` keeps its memory and bootstrap files: its profile's
+    /// workspace, or the global one.
+    fn workspace_for(&self, channel: &str) -> &Path {
+        self.profiles.get(channel).map_or(&self.workspace, |profile| &profile.workspace)
+    }
+
+    /// The registry that runs `tool` for `channel`: the profile's own memory
+    /// tools, so they write where its prompt reads, or the shared `tools`.
+    fn tools_for<'a>(&'a self, tools: &'a ToolRegistry, channel: &str, tool: &str) -> &'a ToolRegistry {
+        self.profiles
+            .get(channel)
+            .map(|profile| &profile.memory_tools)
+            .filter(|memory_tools| memory_tools.get(tool).is_some())
+            .unwrap_or(tools)
+    }
+
+    fn context_for(&self, channel: &str) -> &ContextBuilder {
+        self.profiles.get(channel).map_or(&self.context, |profile| &profile.context)
+    }
+
+    /// The store facts about `origin`'s sender belong in.
+    fn memory_for(&self, origin: &InboundMessage) -> MemoryStore {
+        let workspace = self.workspace_for(&origin.channel);
+        if self.per_user_memory {
+            MemoryStore::for_user(workspace, &origin.sender_id)
+        } else {
+            MemoryStore::new(workspace)
+        }
+    }
+
+    /// With per-user memory, add what is known about this sender. The shared
+    /// MEMORY.md is already part of everyone's system prompt.
+    fn apply_user_memory(&self, messages: &mut [ChatMessage], origin: &InboundMessage) {
+        if !self.per_user_memory {
+            return;
+        }
+        let facts = self.memory_for(origin).read_long_term();
+        if facts.trim().is_empty() {
+            return;
+        }
+        let facts = memory::recent_memory(facts.trim(), self.max_memory_chars);
+        if let Some(system) = messages.first_mut().filter(|m| m.role == "system") {
+            system.content = format!("{}\n\n## Memory About This User ({})\n\n{}", system.content, origin.sender_id, facts);
+        }
+    }
 
+    /// The bot's persona, or failing that the channel profile's.
+    fn apply_persona(&self, messages: &mut [ChatMessage], origin: &InboundMessage) {
+        let bot_id = origin.metadata.get(BOT_ID_KEY).map(String::as_str).unwrap_or("");
+        let channel_persona = self.profiles.get(&origin.channel).map(|p| &p.persona).filter(|p| !p.is_empty());
+        let Some(persona) = self.personas.get(bot_id).or(channel_persona) else {
+            return;
+        };
+        if let Some(system) = messages.first_mut().filter(|m| m.role == "system") {
+            system.content = format!("{}\n\n## Persona\n\n{}", system.content, persona.trim());
+        }
+    }
+
+    async fn build_turn_messages(
+        &self,
+        history: &[serde_json::Value],
+        content: &str,
+        origin: &InboundMessage,
+        options: &TurnOptions,
+    ) -> Vec<ChatMessage> {
         let tools = self.tools.read().await;
-        let tool_defs = tools.get_definitions();
+        let tool_defs = self.advertised_tools(&tools, origin);
         drop(tools);
 
-        let messages = if !tool_defs.is_empty() {
+        let (messages, dropped) = if !tool_defs.is_empty() && options.tools_enabled() {
             // Use system prompt with tools information
             let tools_json = serde_json::to_string_pretty(&tool_defs).unwrap_or_default();
-            self.context.build_messages_with_tools(
-                &self.session_history.read().await,
-                &msg.content,
-                Some(&msg.channel),
-                Some(&msg.chat_id),
+            self.context_for(&origin.channel).build_messages_with_tools(
+                history,
+                content,
+                Some(&origin.channel),
+                Some(&origin.chat_id),
                 &tools_json,
             )
         } else {
-            self.context.build_messages(
-                &self.session_history.read().await,
-                &msg.content,
-                Some(&msg.channel),
-                Some(&msg.chat_id),
-            )
+            self.context_for(&origin.channel).build_messages(history, content, Some(&origin.channel), Some(&origin.chat_id))
         };
+        log_dropped(&dropped);
+        messages
+    }
+
+    async fn take_turn_options(&self, key: &str) -> TurnOptions {
+        self.pending_options.write().await.remove(key).unwrap_or_default()
+    }
 
-        let (final_content, tools_used) = self.run_agent_loop(messages, self.outbound_tx.clone(), msg.channel.clone(), msg.chat_id.clone()).await?;
+    /// A copy of one conversation's transcript, loaded from disk on first use.
+    async fn history(&self, key: &str) -> Vec<serde_json::Value> {
+        let mut sessions = self.session_history.write().await;
+        if let Some(history) = sessions.get(key) {
+            return history.clone();
+        }
 
-        let response = final_content.unwrap_or_else(|| "I've completed processing but have no response to give.".to_string());
+        let stored = self.sessions.load(key);
+        self.session_usage.write().await.entry(key.to_string()).or_default().add(&stored.usage);
+        sessions.insert(key.to_string(), stored.history.clone());
+        stored.history
+    }
 
-        tracing::info!("Agent response generated ({} chars)", response.len());
+    async fn persist_history(&self, key: &str) {
+        let session = SessionFile {
+            history: self.history(key).await,
+            usage: self.session_usage.read().await.get(key).cloned().unwrap_or_default(),
+        };
+        if let Err(e) = self.sessions.save(key, &session) {
+            tracing::warn!("Failed to persist session {}: {}", key, e);
+        }
+    }
+
+    /// Append a finished turn to the conversation, compact and save it.
+    /// Returns the history length before compaction.
+    async fn record_turn(&self, key: &str, content: &str, response: &str, tools_used: Vec<String>) -> usize {
+        let history_len = {
+            // Load a persisted session first so the turn is added to it
+            self.history(key).await;
+            let mut sessions = self.session_history.write().await;
+            let history = sessions.entry(key.to_string()).or_default();
+            history.push(serde_json::json!({
+                "role": "user",
+                "content": content,
+            }));
+            history.push(serde_json::json!({
+                "role": "assistant",
+                "content": response,
+                "tools_used": tools_used,
+            }));
+            history.len()
+        };
+
+        self.compact_history(key).await;
+        self.persist_history(key).await;
+        history_len
+    }
 
-        self.session_history.write().await.push(serde_json::json!({
-            "role": "user",
-            "content": msg.content,
-        }));
+    async fn record_usage(&self, origin: &InboundMessage, usage: &Usage) {
+        self.metrics.usage(usage);
+        let key = message_key(origin);
+        self.session_usage.write().await.entry(key).or_default().add(usage);
+    }
 
-        self.session_history.write().await.push(serde_json::json!({
-            "role": "assistant",
-            "content": response.clone(),
-            "tools_used": tools_used,
-        }));
+    /// Fold the oldest turns into a summary entry once a chat holds more than
+    /// `max_persisted_turns`, so the persisted transcript stays bounded. If the
+    /// summary request fails the history is left alone and retried next turn.
+    async fn compact_history(&self, key: &str) {
+        if self.max_persisted_turns == 0 {
+            return;
+        }
 
-        if self.session_history.read().await.len() > self.memory_window as usize * 2 {
-            self.consolidate_memory().await;
+        let history = self.history(key).await;
+        let start = usize::from(history.first().is_some_and(summary::is_summary));
+        let keep = self.max_persisted_turns * 2;
+        if history.len() - start <= keep {
+            return;
         }
+        let cut = history.len() - keep;
 
-        Ok(())
+        let response = self.provider.chat(
+            summary::summary_messages(&history[..cut]),
+            None,
+            None,
+            Some(self.model.clone()),
+            Some(0.0),
+            Some(self.max_tokens),
+        ).await;
+
+        let text = match response {
+            Ok(response) => response.content.unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!("Failed to summarize history for {}: {}", key, e);
+                return;
+            }
+        };
+        if text.trim().is_empty() {
+            tracing::warn!("Empty summary for {}, keeping full history", key);
+            return;
+        }
+
+        let mut compacted = vec![summary::summary_entry(&text)];
+        compacted.extend_from_slice(&history[cut..]);
+        self.session_history.write().await.insert(key.to_string(), compacted);
+        tracing::info!("Compacted {} old message(s) of {} into a summary", cut - start, key);
     }
 
-    async fn run_agent_loop(&self, mut messages: Vec<ChatMessage>, outbound_tx: tokio::sync::mpsc::Sender<OutboundMessage>, channel: String, chat_id: String) -> Result<(Option<String>, Vec<String>), String> {
-        let mut iteration = 0;
-        let mut final_content: Option<String> = None;
-        let mut tools_used = Vec::new();
-        let mut last_tool_results: Vec<String> = Vec::new();
+    /// Whether `content` repeats the chat's last processed message within the
+    /// dedup window. Otherwise it becomes the new last message.
+    async fn is_duplicate(&self, key: &str, content: &str) -> bool {
+        if self.dedup_window.is_zero() {
+            return false;
+        }
 
-        while iteration < self.max_iterations {
-            iteration += 1;
+        let now = tokio::time::Instant::now();
+        let mut last_messages = self.last_messages.write().await;
+        if let Some((last, at)) = last_messages.get(key) {
+            if last == content && now.duration_since(*at) < self.dedup_window {
+                return true;
+            }
+        }
+        last_messages.insert(key.to_string(), (content.to_string(), now));
+        false
+    }
 
-            let tools = self.tools.read().await;
-            let _tool_defs = tools.get_definitions();
+    async fn process_message(&mut self, msg: InboundMessage) -> Result<(), String> {
+        let key = message_key(&msg);
 
-            tracing::info!("Iteration {}: Sending request", iteration);
+        if self.is_duplicate(&key, &msg.content).await {
+            tracing::debug!("Skipping duplicate message from {}", key);
+            return Ok(());
+        }
 
-            // Send chat request (non-streaming)
-            let llm_response = self.provider.chat(
-                messages.clone(),
-                None,
-                Some(self.model.clone()),
-                Some(self.temperature),
-                Some(self.max_tokens),
-            ).await.map_err(|e| e.to_string())?;
+        tracing::info!("Processing message from {}: {}", msg.channel, &msg.content[..msg.content.len().min(50)]);
 
-            let content = llm_response.content.unwrap_or_default();
+        // The user replied, so any pending follow-up for this chat is moot
+        if let Some(followups) = &self.followups {
+            followups.disarm(&key);
+        }
 
-            // Send the complete response
-            let _ = outbound_tx.send(OutboundMessage::new(channel.clone(), chat_id.clone(), content.clone())).await;
+        if let Some(command) = Command::parse_with_clear(&msg.content, &self.clear_commands) {
+            let reply = self.handle_command(&key, command, &msg).await;
+            let _ = self.outbound_tx.send(msg.reply(reply)).await;
+            return Ok(());
+        }
 
-            tracing::info!("LLM response: content length={:?}", content.len());
+        let mut options = self.take_turn_options(&key).await;
+        options.apply_metadata(&msg.metadata, self.max_tokens);
 
-            // Check if response contains a tool call in JSON format
-            if let Some(tool_call) = self.parse_tool_call_from_json(&content, &tools).await {
-                tracing::info!("Parsed tool call: {}({:?})", tool_call.name, tool_call.arguments);
-                tools_used.push(tool_call.name.clone());
+        let history = self.history(&key).await;
+        let mut messages = self.build_turn_messages(&history, &msg.content, &msg, &options).await;
+        self.apply_persona(&mut messages, &msg);
+        self.apply_user_memory(&mut messages, &msg);
+        self.apply_verbosity(&mut messages, &key).await;
+        self.apply_focus(&mut messages, &key).await;
+        self.attach_referenced_files(&mut messages, &msg.content, &msg.channel);
 
-                let result = tools
-                    .execute(&tool_call.name, serde_json::to_value(&tool_call.arguments).unwrap_or_default())
-                    .await;
+        let (final_content, tools_used) = match self.run_turn(messages, &options, &msg).await {
+            Ok(result) => result,
+            Err(e) => {
+                // Tell the user rather than leaving them waiting on a reply that won't come
+                let _ = self.outbound_tx.send(msg.reply(format!("Sorry, I couldn't answer that: {}", e))).await;
+                return Err(e);
+            }
+        };
 
-                let result_str = match result {
-                    Ok(r) => r,
-                    Err(e) => format!("Error: {}", e),
-                };
+        let response = final_content.unwrap_or_else(|| NO_RESPONSE.to_string());
 
-                last_tool_results.push(result_str.clone());
-                messages.push(ChatMessage::assistant(content.clone()));
-                messages.push(ChatMessage::tool(&result_str, &tool_call.id));
-                messages.push(ChatMessage::user("Tool executed. Continue with your response or use another tool if needed."));
+        tracing::info!("Agent response generated ({} chars)", response.len());
 
-                continue;
+        if let Some(followups) = &self.followups {
+            if FollowUpScheduler::should_arm(&response) {
+                followups.arm(&key, &msg.channel, &msg.chat_id);
             }
+        }
+
+        let history_len = self.record_turn(&key, &msg.content, &response, tools_used).await;
 
-            // No tool call, use content as final response
-            final_content = Some(content);
-            break;
+        if self.auto_extract {
+            self.extract_facts(&msg, &response).await;
         }
 
-        // If we have tool results but no final content, use the tool results as the response
-        if final_content.is_none() && !last_tool_results.is_empty() {
-            final_content = Some(last_tool_results.join("\n"));
+        if history_len > self.memory_window as usize * 2 {
+            self.consolidate_memory(&key).await;
         }
 
-        Ok((final_content, tools_used))
+        Ok(())
     }
 
-    async fn parse_tool_call_from_json(&self, content: &str, tools: &crate::agent::tools::ToolRegistry) -> Option<ToolCallRequest> {
-        // Try to find JSON object in the content
-        let json_start = content.find("```json")?;
-        
-        // Find the closing ``` after json_start
-        let remaining = &content[json_start + 7..];
-        let json_end_in_remaining = remaining.find("```")?;
-        let json_end = json_start + 7 + json_end_in_remaining;
-        
-        let json_str = &content[json_start + 7..json_end].trim();
-        
+    /// Run a turn, then record the files it wrote in the artifact manifest.
+    async fn run_turn(&self, messages: Vec<ChatMessage>, options: &TurnOptions, origin: &InboundMessage) -> Result<(Option<String>, Vec<String>), String> {
+        self.emit_event("turn_start", origin, &origin.content);
+        let result = self.run_turn_with_repair(messages, options, origin).await;
+        if let Some(artifacts) = &self.artifacts {
+            if let Err(e) = artifacts.finish(&message_key(origin)) {
+                tracing::warn!("{}", e);
+            }
+        }
+        match &result {
+            Ok((content, _)) => self.emit_event("turn_complete", origin, content.as_deref().unwrap_or_default()),
+            Err(e) => self.emit_event("turn_error", origin, e),
+        }
+        result
+    }
+
+    /// Run the agent loop and, when the turn expects a schema, give the model one
+    /// chance to repair an answer that doesn't match it.
+    /// The loop holds back the answer of a schema turn; it is sent here once,
+    /// after it validates or the repair attempt is done.
+    async fn run_turn_with_repair(&self, messages: Vec<ChatMessage>, options: &TurnOptions, origin: &InboundMessage) -> Result<(Option<String>, Vec<String>), String> {
+        let Some(expected) = &options.expect_schema else {
+            let (final_content, tools_used, _) = self.run_agent_loop(messages, options, origin).await?;
+            return Ok((final_content, tools_used));
+        };
+
+        let (final_content, mut tools_used, mut usage) = self.run_agent_loop(messages.clone(), options, origin).await?;
+        let Some(content) = final_content else {
+            return Ok((None, tools_used));
+        };
+
+        let errors = match schema::extract_json(&content) {
+            Ok(value) => schema::validate(&value, expected),
+            Err(e) => vec![e],
+        };
+        if errors.is_empty() {
+            self.send_answer(origin, &content, &usage, options).await;
+            return Ok((Some(content), tools_used));
+        }
+
+        tracing::warn!("Answer did not match the expected schema, asking for a repair: {}", errors.join("; "));
+
+        let mut repair = messages;
+        repair.push(ChatMessage::assistant(content.clone()));
+        repair.push(ChatMessage::user(format!(
+            "Your output didn't match the schema: {}. Fix it and reply with only the corrected JSON.",
+            errors.join("; ")
+        )));
+
+        let (repaired, more_tools, more_usage) = self.run_agent_loop(repair, options, origin).await?;
+        tools_used.extend(more_tools);
+        usage.add(&more_usage);
+
+        let answer = repaired.unwrap_or(content);
+        self.send_answer(origin, &answer, &usage, options).await;
+        Ok((Some(answer), tools_used))
+    }
+
+    /// Send the answer that ends a turn, with its usage footer.
+    async fn send_answer(&self, origin: &InboundMessage, content: &str, usage: &Usage, options: &TurnOptions) {
+        if content.trim().is_empty() {
+            return;
+        }
+        let text = format!("{}{}", content, self.turn_footer(usage, options));
+        let _ = self.outbound_tx.send(origin.reply(text)).await;
+    }
+
+    /// Returns the final answer, the tools used and the turn's token usage.
+    async fn run_agent_loop(&self, mut messages: Vec<ChatMessage>, options: &TurnOptions, origin: &InboundMessage) -> Result<(Option<String>, Vec<String>, Usage), String> {
+        let mut iteration = 0;
+        let mut final_content: Option<String> = None;
+        let mut tools_used = Vec::new();
+        let mut last_tool_results: Vec<String> = Vec::new();
+        let mut turn_usage = Usage::default();
+        let loop_start = messages.len();
+        let mut repeats = RepeatDetector::default();
+        // A schema turn's answer is validated before anyone sees it, so it is
+        // neither sent nor shown live from here; see run_turn_with_repair
+        let hold_answer = options.expect_schema.is_some();
+
+        while iteration < self.max_iterations {
+            iteration += 1;
+
+            if self.keep_tool_rounds > 0 {
+                let dropped = trim_tool_loop(&mut messages, loop_start, self.keep_tool_rounds);
+                if dropped > 0 {
+                    tracing::debug!("Trimmed {} older tool round(s) from the request", dropped);
+                }
+            }
+
+            let tools = self.tools.read().await;
+            let definitions = self.advertised_tools(&tools, origin);
+
+            tracing::info!("Iteration {}: Sending request", iteration);
+            self.trace(origin, "iteration_start", serde_json::json!({"iteration": iteration})).await;
+            self.trace(origin, "request", serde_json::json!({"iteration": iteration, "messages": messages})).await;
+
+            // tool_choice is only meaningful alongside a native tools array
+            let tool_defs = (!definitions.is_empty()).then_some(definitions);
+            let tool_choice = tool_defs.as_ref().and(options.tool_choice.clone());
+
+            let live = (!hold_answer && self.live_reply_channels.contains(&origin.channel)).then_some(origin);
+            let llm_response = self.request_with_empty_retry(&messages, tool_defs, tool_choice, options, live).await?;
+            self.record_usage(origin, &llm_response.usage).await;
+            turn_usage.add(&llm_response.usage);
+            self.trace(origin, "response", serde_json::json!({
+                "iteration": iteration,
+                "content": llm_response.content,
+                "tool_calls": llm_response.tool_calls,
+                "finish_reason": llm_response.finish_reason,
+                "usage": llm_response.usage,
+            })).await;
+
+            let content = llm_response.content.unwrap_or_default();
+
+            tracing::info!("LLM response: content length={:?}, tool calls={}", content.len(), llm_response.tool_calls.len());
+
+            // Prefer structured tool calls; only scrape the content when there are none
+            let tool_calls: Vec<ToolCallRequest> = if !options.tools_enabled() {
+                Vec::new()
+            } else if !llm_response.tool_calls.is_empty() {
+                llm_response.tool_calls
+                    .into_iter()
+                    .map(|call| ToolCallRequest {
+                        id: call.id,
+                        name: call.name,
+                        arguments: serde_json::Value::Object(call.arguments.into_iter().collect()),
+                    })
+                    .collect()
+            } else {
+                self.parse_tool_call_from_json(&content, &tools).await
+            };
+
+            let content = if tool_calls.is_empty() {
+                // Retries are used up by now; answer with the fallback rather than nothing
+                let answer = if content.trim().is_empty() {
+                    tracing::warn!("Provider still returned an empty response, sending the fallback");
+                    NO_RESPONSE.to_string()
+                } else {
+                    content
+                };
+                format!("{}{}", answer, self.artifact_footer(origin))
+            } else {
+                content
+            };
+
+            if !tool_calls.is_empty() {
+                if !content.trim().is_empty() {
+                    let _ = self.outbound_tx.send(origin.reply(content.clone()).interim()).await;
+                }
+            } else if !hold_answer {
+                self.send_answer(origin, &content, &turn_usage, options).await;
+            }
+
+            if tool_calls.is_empty() {
+                // No tool call, use content as final response
+                final_content = Some(content);
+                break;
+            }
+
+            // A runaway model can make many calls per iteration; stop before
+            // running a batch that would go over the turn's budget
+            if self.max_tool_calls > 0 && tools_used.len() + tool_calls.len() > self.max_tool_calls {
+                tracing::warn!(
+                    "Tool call budget exceeded: {} run, {} more requested, limit {}",
+                    tools_used.len(),
+                    tool_calls.len(),
+                    self.max_tool_calls
+                );
+                let text = format!(
+                    "Stopped: tool call budget exceeded ({} tool calls this turn, limit {}).",
+                    tools_used.len(),
+                    self.max_tool_calls
+                );
+                if !hold_answer {
+                    let _ = self.outbound_tx.send(origin.reply(text.clone())).await;
+                }
+                final_content = Some(text);
+                break;
+            }
+
+            for call in &tool_calls {
+                self.trace(origin, "tool_call", serde_json::json!({
+                    "iteration": iteration,
+                    "id": call.id,
+                    "name": call.name,
+                    "arguments": call.arguments,
+                })).await;
+            }
+
+            messages.push(ChatMessage::assistant_with_tool_calls(
+                content.clone(),
+                tool_calls
+                    .iter()
+                    .map(|call| ToolCall::function(&call.id, &call.name, &call.arguments))
+                    .collect(),
+            ));
+
+            let looping: Vec<bool> = tool_calls.iter().map(|call| repeats.is_loop(&call.name, &call.arguments)).collect();
+
+            // Run the calls concurrently; join_all keeps results in call order
+            let results = futures::future::join_all(tool_calls.iter().zip(looping).map(|(tool_call, looping)| {
+                let tools = &tools;
+                async move {
+                    if looping {
+                        tracing::warn!("Not repeating {}: same call more than {} times in a row", tool_call.name, MAX_REPEATED_CALLS);
+                        return Ok(REPEATED_CALL_RESULT.to_string());
+                    }
+                    tracing::info!("Tool call: {}({:?})", tool_call.name, tool_call.arguments);
+                    let arguments = if tool_call.name == "message" {
+                        MessageTool::with_default_target(tool_call.arguments.clone(), &origin.channel, &origin.chat_id)
+                    } else {
+                        tool_call.arguments.clone()
+                    };
+                    let allowed = self
+                        .availability
+                        .check(&tool_call.name, origin, chrono::Utc::now())
+                        .and_then(|()| self.policy.check(&tool_call.name, &arguments, origin));
+                    match allowed {
+                        Ok(()) if self.dry_run => {
+                            tracing::info!("Dry run: skipped {}({})", tool_call.name, arguments);
+                            Ok(dry_run_result(&tool_call.name, &arguments))
+                        }
+                        Ok(()) => {
+                            self.check_approval(&tool_call.name, &arguments, origin).await?;
+                            let written = self.artifacts.as_ref().and_then(|artifacts| {
+                                let path = ArtifactTracker::written_path(&tool_call.name, &arguments)?;
+                                Some((artifacts, artifacts.exists(&path), path))
+                            });
+                            let argument = progress_argument(&arguments);
+                            self.report_progress(StreamProgress::ToolStarted {
+                                name: tool_call.name.clone(),
+                                argument: argument.clone(),
+                            });
+                            let started = std::time::Instant::now();
+                            self.metrics.tool_call();
+                            let result = self.tools_for(tools, &origin.channel, &tool_call.name).execute(&tool_call.name, arguments.clone()).await;
+                            let elapsed = started.elapsed();
+                            self.report_progress(StreamProgress::ToolFinished {
+                                name: tool_call.name.clone(),
+                                argument,
+                                elapsed,
+                                ok: result.is_ok(),
+                            });
+                            if let Some(audit) = &self.audit {
+                                if let Err(e) = audit.record(origin, &tool_call.name, &arguments, &result, elapsed) {
+                                    tracing::warn!("{}", e);
+                                }
+                            }
+                            if let (Some((artifacts, existed, path)), Ok(_)) = (&written, &result) {
+                                artifacts.record(&message_key(origin), path, *existed);
+                            }
+                            result
+                        }
+                        Err(refusal) => {
+                            tracing::warn!("Policy refused {} from {}:{}", tool_call.name, origin.channel, origin.sender_id);
+                            Err(refusal)
+                        }
+                    }
+                }
+            }))
+            .await;
+
+            for (tool_call, result) in tool_calls.iter().zip(results) {
+                tools_used.push(tool_call.name.clone());
+                self.trace(origin, "tool_result", serde_json::json!({
+                    "iteration": iteration,
+                    "id": tool_call.id,
+                    "name": tool_call.name,
+                    "success": result.is_ok(),
+                    "result": match &result { Ok(r) | Err(r) => r },
+                })).await;
+
+                let result_str = match result {
+                    Ok(r) => self.injection_scanner.scan(&tool_call.name, r),
+                    Err(e) => {
+                        self.metrics.error();
+                        self.emit_event("tool_error", origin, &format!("{}: {}", tool_call.name, e));
+                        format!("Error: {}", e)
+                    }
+                };
+
+                last_tool_results.push(result_str.clone());
+                messages.push(ChatMessage::tool(&result_str, &tool_call.id));
+            }
+
+            messages.push(ChatMessage::user(TOOL_NUDGE));
+        }
+
+        // Out of iterations mid-task: one last request without tools, so the
+        // model answers from what it gathered instead of calling more
+        if final_content.is_none() && !last_tool_results.is_empty() {
+            tracing::warn!("Reached max_iterations ({}), asking for a final answer", self.max_iterations);
+            messages.push(ChatMessage::user(
+                "You've used all your tool calls for this request. Using the tool results above, give the user your best final answer now. Do not call any more tools.",
+            ));
+            let live = (!hold_answer && self.live_reply_channels.contains(&origin.channel)).then_some(origin);
+            match self.request_with_empty_retry(&messages, None, None, options, live).await {
+                Ok(response) => {
+                    self.record_usage(origin, &response.usage).await;
+                    turn_usage.add(&response.usage);
+                    let content = response.content.unwrap_or_default();
+                    if !content.trim().is_empty() {
+                        let content = format!("{}{}", content, self.artifact_footer(origin));
+                        if !hold_answer {
+                            self.send_answer(origin, &content, &turn_usage, options).await;
+                        }
+                        final_content = Some(content);
+                    }
+                }
+                Err(e) => tracing::warn!("Final answer request failed: {}", e),
+            }
+        }
+
+        // If we have tool results but no final content, use the tool results as the response
+        if final_content.is_none() && !last_tool_results.is_empty() {
+            final_content = Some(last_tool_results.join("\n"));
+        }
+
+        self.trace(origin, "final", serde_json::json!({"iterations": iteration, "content": final_content})).await;
+        Ok((final_content, tools_used, turn_usage))
+    }
+
+    /// Stream one chat completion and collect it into a single response, putting
+    /// tool-call fragments back together by index. With `live`, the text so far
+    /// is sent to that conversation as a partial reply every
+    /// `LIVE_REPLY_INTERVAL`, so quick answers arrive as one message.
+    async fn stream_response(
+        &self,
+        messages: &[ChatMessage],
+        tool_defs: Option<Vec<crate::providers::ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        options: &TurnOptions,
+        live: Option<&InboundMessage>,
+    ) -> Result<LLMResponse, StreamError> {
+        self.report_progress(StreamProgress::RequestStarted);
+        let mut stream = self.provider.chat_stream(
+            messages.to_vec(),
+            tool_defs,
+            tool_choice,
+            Some(options.model.clone().unwrap_or_else(|| self.model.clone())),
+            Some(options.temperature.unwrap_or(self.temperature)),
+            Some(options.max_tokens.unwrap_or(self.max_tokens)),
+        ).await.map_err(|e| StreamError::Failed(e.to_string()))?;
+
+        let mut content = String::new();
+        let mut calls = ToolCallAccumulator::default();
+        let mut usage = Usage::default();
+
+        let mut first_token = true;
+        let mut last_live_update = tokio::time::Instant::now();
+        while let Some(event) = self.next_event(&mut stream).await? {
+            let event = event.map_err(|e| StreamError::Failed(e.to_string()))?;
+            if first_token && !matches!(event, StreamEvent::Usage(_)) {
+                first_token = false;
+                self.report_progress(StreamProgress::FirstToken);
+            }
+            match event {
+                StreamEvent::Content(delta) => {
+                    content.push_str(&delta);
+                    if let Some(origin) = live {
+                        if last_live_update.elapsed() >= LIVE_REPLY_INTERVAL && !content.trim().is_empty() {
+                            last_live_update = tokio::time::Instant::now();
+                            let _ = self.outbound_tx.send(origin.reply(content.clone()).streaming()).await;
+                        }
+                    }
+                }
+                StreamEvent::ToolCall(delta) => calls.push(&delta),
+                StreamEvent::Usage(reported) => usage.add(&reported),
+            }
+        }
+
+        let tool_calls = calls.finish();
+        let finish_reason = if tool_calls.is_empty() { "stop" } else { "tool_calls" };
+
+        Ok(LLMResponse {
+            content: Some(content),
+            tool_calls,
+            finish_reason: finish_reason.to_string(),
+            usage,
+        })
+    }
+
+    /// The stream's next event, or `Stalled` when it stays silent longer than
+    /// `provider.stream_idle_timeout_secs`.
+    async fn next_event(
+        &self,
+        stream: &mut crate::providers::ChatStream,
+    ) -> Result<Option<Result<StreamEvent, Box<dyn std::error::Error + Send + Sync>>>, StreamError> {
+        if self.stream_idle_timeout.is_zero() {
+            return Ok(stream.next().await);
+        }
+        tokio::time::timeout(self.stream_idle_timeout, stream.next())
+            .await
+            .map_err(|_| StreamError::Stalled(self.stream_idle_timeout))
+    }
+
+    /// Stream one response, starting over when the connection goes quiet, up
+    /// to `provider.stream_retries` times. A partial reply already shown is
+    /// simply overwritten by the fresh attempt's text.
+    async fn stream_with_stall_retry(
+        &self,
+        messages: &[ChatMessage],
+        tool_defs: Option<Vec<crate::providers::ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        options: &TurnOptions,
+        live: Option<&InboundMessage>,
+    ) -> Result<LLMResponse, String> {
+        let mut attempt = 0;
+
+        loop {
+            match self.stream_response(messages, tool_defs.clone(), tool_choice.clone(), options, live).await {
+                Ok(response) => return Ok(response),
+                Err(StreamError::Stalled(idle)) if attempt < self.stream_retries => {
+                    attempt += 1;
+                    tracing::warn!(
+                        "Stream stalled for {}s, retrying ({}/{})",
+                        idle.as_secs(),
+                        attempt,
+                        self.stream_retries
+                    );
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+
+    /// Request one response without streaming, for `provider.stream = false`.
+    /// No partial replies are shown; the answer arrives whole.
+    async fn complete_response(
+        &self,
+        messages: &[ChatMessage],
+        tool_defs: Option<Vec<crate::providers::ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        options: &TurnOptions,
+    ) -> Result<LLMResponse, String> {
+        self.report_progress(StreamProgress::RequestStarted);
+        let response = self.provider.chat(
+            messages.to_vec(),
+            tool_defs,
+            tool_choice,
+            Some(options.model.clone().unwrap_or_else(|| self.model.clone())),
+            Some(options.temperature.unwrap_or(self.temperature)),
+            Some(options.max_tokens.unwrap_or(self.max_tokens)),
+        ).await.map_err(|e| e.to_string())?;
+        self.report_progress(StreamProgress::FirstToken);
+        Ok(response)
+    }
+
+    /// Send one chat request, re-sending it up to `empty_retries` times while
+    /// the provider answers with neither content nor tool calls.
+    async fn request_with_empty_retry(
+        &self,
+        messages: &[ChatMessage],
+        tool_defs: Option<Vec<crate::providers::ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        options: &TurnOptions,
+        live: Option<&InboundMessage>,
+    ) -> Result<LLMResponse, String> {
+        let mut attempt = 0;
+
+        loop {
+            let response = if self.stream {
+                self.stream_with_stall_retry(messages, tool_defs.clone(), tool_choice.clone(), options, live).await?
+            } else {
+                self.complete_response(messages, tool_defs.clone(), tool_choice.clone(), options).await?
+            };
+
+            let is_empty = response.content.as_deref().unwrap_or("").trim().is_empty()
+                && response.tool_calls.is_empty();
+
+            if !is_empty || attempt >= self.empty_retries {
+                return Ok(response);
+            }
+
+            attempt += 1;
+            tracing::warn!("Provider returned an empty response, retrying ({}/{})", attempt, self.empty_retries);
+        }
+    }
+
+    /// Collect every ```json fenced block in `content` that names a registered tool.
+    async fn parse_tool_call_from_json(&self, content: &str, tools: &crate::agent::tools::ToolRegistry) -> Vec<ToolCallRequest> {
         #[derive(serde::Deserialize)]
         struct ToolCallJson {
             tool: String,
             arguments: serde_json::Value,
         }
 
-        match serde_json::from_str::<ToolCallJson>(json_str) {
-            Ok(call) => {
-                // Verify tool exists - use the 'tool' field, not 'name'
+        let mut calls = Vec::new();
+        let mut rest = content;
+
+        while let Some(start) = rest.find("```json") {
+            let after = &rest[start + 7..];
+            let Some(end) = after.find("```") else {
+                break;
+            };
+            let json_str = after[..end].trim();
+            rest = &after[end + 3..];
+
+            // Verify tool exists - use the 'tool' field, not 'name'
+            if let Ok(call) = serde_json::from_str::<ToolCallJson>(json_str) {
                 if tools.get(&call.tool).is_some() {
-                    Some(ToolCallRequest {
-                        id: format!("call_{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)),
+                    calls.push(ToolCallRequest {
+                        id: format!("call_{}_{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0), calls.len()),
                         name: call.tool,
                         arguments: call.arguments,
-                    })
-                } else {
-                    None
+                    });
                 }
             }
-            Err(_) => None,
         }
+
+        calls
     }
 
-    async fn consolidate_memory(&self) {
-        let history = self.session_history.read().await;
+    /// Ask the model for durable facts from the finished turn and merge them into
+    /// long-term memory. Failures are logged; they never affect the reply.
+    async fn extract_facts(&self, msg: &InboundMessage, assistant: &str) {
+        let memory = self.memory_for(msg);
+        let messages = extract::extraction_messages(&memory.read_long_term(), &msg.content, assistant);
+
+        let response = match self.provider.chat(
+            messages,
+            None,
+            None,
+            Some(self.model.clone()),
+            Some(0.0),
+            Some(self.max_tokens),
+        ).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!("Fact extraction failed: {}", e);
+                return;
+            }
+        };
+
+        let facts = extract::parse_facts(response.content.as_deref().unwrap_or(""));
+        match memory.merge_facts(&facts) {
+            Ok(0) => {}
+            Ok(added) => tracing::info!("Saved {} new fact(s) to memory", added),
+            Err(e) => tracing::warn!("Failed to save extracted facts: {}", e),
+        }
+    }
+
+    /// Move the oldest part of a long conversation out of the session: the raw
+    /// messages go to HISTORY.md and the facts worth keeping to MEMORY.md.
+    async fn consolidate_memory(&self, key: &str) {
+        // The summary request goes straight to the provider, but make sure
+        // nothing it triggers can start another consolidation
+        if self.consolidating.swap(true, std::sync::atomic::Ordering::SeqCst) {
+            return;
+        }
+        self.consolidate_oldest(key).await;
+        self.consolidating.store(false, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    async fn consolidate_oldest(&self, key: &str) {
+        let history = self.history(key).await;
         
         if history.len() < self.memory_window as usize {
             return;
@@ -276,37 +1727,1506 @@ impl AgentLoop {
         
         // Keep only the most recent messages
         let keep = history.len() - (self.memory_window as usize / 2);
+        let oldest = &history[..keep];
         
         // Save older messages to history file
-        let memory = MemoryStore::new(&self.workspace);
+        let memory = MemoryStore::new(self.workspace_for(key_channel(key)))
+            .with_history_rotation(self.max_history_bytes, self.history_backups);
         
-        for msg in history.iter().take(keep) {
+        for msg in oldest {
             if let (Some(role), Some(content)) = (
                 msg.get("role").and_then(|v| v.as_str()),
                 msg.get("content").and_then(|v| v.as_str()),
             ) {
-                let entry = format!("[{}] {}: {}", 
+                let entry = format!("[{}] [{}] {}: {}",
                     chrono::Local::now().format("%Y-%m-%d %H:%M"),
+                    key,
                     role.to_uppercase(),
                     content
                 );
                 let _ = memory.append_history(&entry);
             }
         }
+
+        let messages = extract::consolidation_messages(&self.consolidation_prompt, &memory.read_long_term(), oldest);
+        match self.provider.chat(messages, None, None, Some(self.model.clone()), Some(0.0), Some(self.max_tokens)).await {
+            Ok(response) => {
+                let facts = extract::parse_facts(response.content.as_deref().unwrap_or(""));
+                match memory.merge_facts(&facts) {
+                    Ok(added) => tracing::info!("Consolidation saved {} new fact(s) to memory", added),
+                    Err(e) => tracing::warn!("Failed to save consolidated facts: {}", e),
+                }
+            }
+            // The raw messages are already in HISTORY.md, so nothing is lost
+            Err(e) => tracing::warn!("Memory consolidation summary failed: {}", e),
+        }
+
+        if let Some(history) = self.session_history.write().await.get_mut(key) {
+            history.drain(..keep.min(history.len()));
+        }
+        self.persist_history(key).await;
         
-        tracing::info!("Memory consolidated");
+        tracing::info!("Memory consolidated for {}", key);
     }
 
     pub async fn process_direct(&self, content: &str) -> Result<String, String> {
-        let messages = self.context.build_messages(
-            &self.session_history.read().await,
+        let key = session_key("cli", "direct");
+        let origin = InboundMessage::new("cli".to_string(), "user".to_string(), "direct".to_string(), content.to_string());
+        if let Some(command) = Command::parse_with_clear(content, &self.clear_commands) {
+            return Ok(self.handle_command(&key, command, &origin).await);
+        }
+
+        let options = self.take_turn_options(&key).await;
+
+        let (mut messages, dropped) = self.context.build_messages(
+            &self.history(&key).await,
             content,
             Some("cli"),
             Some("direct"),
         );
+        log_dropped(&dropped);
+        self.apply_verbosity(&mut messages, &key).await;
+        self.apply_focus(&mut messages, &key).await;
+        self.attach_referenced_files(&mut messages, content, &origin.channel);
+
+        let (final_content, tools_used) = self.run_turn(messages, &options, &origin).await?;
+        let response = final_content.unwrap_or_else(|| NO_RESPONSE.to_string());
+
+        // Recorded like any chat, so /fork, /switch and /clear work in the CLI too
+        self.record_turn(&key, content, &response, tools_used).await;
+
+        Ok(response)
+    }
+}
+
+/// Why streaming a response failed.
+#[derive(Debug)]
+enum StreamError {
+    /// No data arrived within the idle timeout; the connection is assumed dropped
+    Stalled(std::time::Duration),
+    Failed(String),
+}
+
+impl std::fmt::Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Stalled(idle) => write!(f, "stream stalled: no data for {}s", idle.as_secs()),
+            StreamError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+/// "\n\n(1,234 tokens)", or nothing when the provider reported no usage.
+fn usage_footer(usage: &Usage) -> String {
+    if usage.total_tokens == 0 {
+        return String::new();
+    }
+    format!("\n\n({} tokens)", format_thousands(usage.total_tokens as u64))
+}
+
+/// "\n\n· 1,240 tokens · $0.002", leaving the price out when the model has
+/// none in `[pricing]`. Plain mode uses "(1,240 tokens, $0.002)".
+fn cost_footer(usage: &Usage, price: Option<&ModelPrice>, plain: bool) -> String {
+    if usage.total_tokens == 0 {
+        return String::new();
+    }
+    let mut parts = vec![format!("{} tokens", format_thousands(usage.total_tokens as u64))];
+    if let Some(price) = price {
+        let cost = usage.prompt_tokens as f64 * price.input_per_million / 1_000_000.0
+            + usage.completion_tokens as f64 * price.output_per_million / 1_000_000.0;
+        parts.push(format_cost(cost));
+    }
+    if plain {
+        format!("\n\n({})", parts.join(", "))
+    } else {
+        format!("\n\n· {}", parts.join(" · "))
+    }
+}
+
+/// "$1.25" for a dollar or more, otherwise up to four decimals: "$0.002".
+fn format_cost(cost: f64) -> String {
+    if cost >= 1.0 {
+        return format!("${:.2}", cost);
+    }
+    let fixed = format!("{:.4}", cost);
+    let trimmed = fixed.trim_end_matches('0').trim_end_matches('.');
+    format!("${}", if trimmed.is_empty() { "0" } else { trimmed })
+}
+
+/// Resolves once `shutdown` turns true; never when there is no receiver or
+/// its sender is gone.
+async fn shutdown_requested(shutdown: &mut Option<tokio::sync::watch::Receiver<bool>>) {
+    if let Some(rx) = shutdown {
+        if rx.wait_for(|stop| *stop).await.is_ok() {
+            return;
+        }
+    }
+    std::future::pending().await
+}
+
+pub fn format_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn log_dropped(dropped: &[ChatMessage]) {
+    if !dropped.is_empty() {
+        let tokens: usize = dropped.iter().map(context::estimate_tokens).sum();
+        tracing::info!(
+            "Dropped {} oldest history message(s) (~{} tokens) to fit max_context_tokens",
+            dropped.len(),
+            tokens
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{ChatStream, FunctionCallDelta, ToolCallDelta, ToolCallRequest as ProviderToolCall, ToolDefinition};
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    /// (model, temperature, max_tokens) of a streamed request.
+    type RequestSettings = (Option<String>, Option<f32>, Option<u32>);
+
+    /// Provider that replays canned responses and records every request.
+    #[derive(Clone, Default)]
+    struct StubProvider {
+        responses: Arc<Mutex<VecDeque<LLMResponse>>>,
+        requests: Arc<Mutex<Vec<Vec<ChatMessage>>>>,
+        tools_sent: Arc<Mutex<Vec<usize>>>,
+        settings: Arc<Mutex<Vec<RequestSettings>>>,
+        /// How many upcoming streams go silent after their events instead of ending
+        stalls: Arc<Mutex<usize>>,
+    }
+
+    impl StubProvider {
+        fn new(responses: &[&str]) -> Self {
+            let stub = Self::default();
+            for content in responses {
+                stub.push(content, vec![]);
+            }
+            stub
+        }
+
+        fn push(&self, content: &str, tool_calls: Vec<ProviderToolCall>) {
+            self.responses.lock().unwrap().push_back(LLMResponse {
+                content: Some(content.to_string()),
+                tool_calls,
+                finish_reason: "stop".to_string(),
+                usage: Usage::default(),
+            });
+        }
+
+        fn next(&self, messages: Vec<ChatMessage>, tools: &Option<Vec<ToolDefinition>>) -> LLMResponse {
+            self.requests.lock().unwrap().push(messages);
+            self.tools_sent.lock().unwrap().push(tools.as_ref().map_or(0, |t| t.len()));
+            self.responses.lock().unwrap().pop_front().unwrap_or(LLMResponse {
+                content: None,
+                tool_calls: vec![],
+                finish_reason: "stop".to_string(),
+                usage: Usage::default(),
+            })
+        }
+
+        fn request_count(&self) -> usize {
+            self.requests.lock().unwrap().len()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for StubProvider {
+        async fn chat(
+            &self,
+            messages: Vec<ChatMessage>,
+            tools: Option<Vec<ToolDefinition>>,
+            _tool_choice: Option<ToolChoice>,
+            _model: Option<String>,
+            _temperature: Option<f32>,
+            _max_tokens: Option<u32>,
+        ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(self.next(messages, &tools))
+        }
+
+        async fn chat_stream(
+            &self,
+            messages: Vec<ChatMessage>,
+            tools: Option<Vec<ToolDefinition>>,
+            _tool_choice: Option<ToolChoice>,
+            model: Option<String>,
+            temperature: Option<f32>,
+            max_tokens: Option<u32>,
+        ) -> Result<ChatStream, Box<dyn std::error::Error + Send + Sync>> {
+            self.settings.lock().unwrap().push((model, temperature, max_tokens));
+            let response = self.next(messages, &tools);
+
+            let mut events = Vec::new();
+            if let Some(content) = response.content.filter(|c| !c.is_empty()) {
+                events.push(StreamEvent::Content(content));
+            }
+            if response.usage.total_tokens > 0 {
+                events.push(StreamEvent::Usage(response.usage));
+            }
+            // Split each call's arguments in two fragments, as real providers do
+            for (index, call) in response.tool_calls.into_iter().enumerate() {
+                let arguments = serde_json::to_string(&call.arguments).unwrap();
+                let (head, tail) = arguments.split_at(arguments.len() / 2);
+                events.push(StreamEvent::ToolCall(ToolCallDelta {
+                    index,
+                    id: Some(call.id),
+                    function: Some(FunctionCallDelta { name: Some(call.name), arguments: Some(head.to_string()) }),
+                }));
+                events.push(StreamEvent::ToolCall(ToolCallDelta {
+                    index,
+                    id: None,
+                    function: Some(FunctionCallDelta { name: None, arguments: Some(tail.to_string()) }),
+                }));
+            }
+
+            let events = futures::stream::iter(events.into_iter().map(Ok));
+            let mut stalls = self.stalls.lock().unwrap();
+            if *stalls > 0 {
+                *stalls -= 1;
+                return Ok(events.chain(futures::stream::pending()).boxed());
+            }
+            Ok(events.boxed())
+        }
+    }
+
+    fn test_config(workspace: &Path) -> Config {
+        let mut config = Config::default();
+        config.agent.workspace = workspace.display().to_string();
+        config
+    }
+
+    fn test_agent_with(config: &Config, provider: StubProvider) -> AgentLoop {
+        let (_inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, _outbound_rx) = tokio::sync::mpsc::channel(10);
+        AgentLoop::with_provider(config, Box::new(provider), inbound_rx, outbound_tx)
+    }
+
+    fn test_agent(workspace: &Path) -> AgentLoop {
+        test_agent_with(&test_config(workspace), StubProvider::default())
+    }
+
+    #[tokio::test]
+    async fn test_fork_diverge_and_switch_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = StubProvider::new(&["A systems language.", "A language from Google."]);
+        let agent = test_agent_with(&test_config(temp_dir.path()), provider);
+        let key = session_key("cli", "direct");
+
+        agent.process_direct("What is Rust?").await.unwrap();
+        let original = agent.history(&key).await;
+        assert_eq!(original.len(), 2);
+        assert_eq!(original[0]["content"], "What is Rust?");
+        assert_eq!(original[1]["content"], "A systems language.");
+
+        let reply = agent.process_direct("/fork").await.unwrap();
+        assert!(reply.contains("fork-1"));
+
+        agent.process_direct("What is Go?").await.unwrap();
+        assert_eq!(agent.history(&key).await.len(), 4);
+
+        agent.process_direct("/switch main").await.unwrap();
+        assert_eq!(agent.history(&key).await, original);
+
+        agent.process_direct("/switch fork-1").await.unwrap();
+        let forked = agent.history(&key).await;
+        assert_eq!(forked.len(), 4);
+        assert_eq!(forked[3]["content"], "A language from Google.");
+
+        agent.process_direct("/clear").await.unwrap();
+        assert!(agent.history(&key).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_notools_applies_to_next_turn_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let agent = test_agent(temp_dir.path());
+        let key = session_key("cli", "direct");
+
+        agent.process_direct("/notools").await.unwrap();
+
+        let options = agent.take_turn_options(&key).await;
+        assert_eq!(options.tool_choice, Some(ToolChoice::None));
+        assert!(!options.tools_enabled());
+
+        assert!(agent.take_turn_options(&key).await.tools_enabled());
+    }
+
+    #[tokio::test]
+    async fn test_empty_response_is_retried() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = StubProvider::new(&["", "Here is the real answer."]);
+        let agent = test_agent_with(&test_config(temp_dir.path()), provider.clone());
+
+        let response = agent.process_direct("Hello?").await.unwrap();
+
+        assert_eq!(response, "Here is the real answer.");
+        assert_eq!(provider.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_empty_retries_are_bounded() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.agent.empty_retries = 2;
+        let provider = StubProvider::new(&["", "", "", "too late"]);
+        let agent = test_agent_with(&config, provider.clone());
+
+        let response = agent.process_direct("Hello?").await.unwrap();
+
+        assert_eq!(response, NO_RESPONSE);
+        assert_eq!(provider.request_count(), 3);
+        // The fallback, not a blank turn, goes into history
+        let history = agent.history(&session_key("cli", "direct")).await;
+        assert_eq!(history[1]["content"], NO_RESPONSE);
+    }
+
+    #[tokio::test]
+    async fn test_destructive_tool_refused_for_untrusted_origin() {
+        let workspace = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let target = outside.path().join("owned.txt");
+
+        let tool_call = format!(
+            "```json\n{}\n```",
+            serde_json::json!({"tool": "write_file", "arguments": {"path": target.to_string_lossy(), "content": "pwned"}})
+        );
+
+        let mut config = test_config(workspace.path());
+        config.tools.destructive_guard = true;
+        config.tools.trusted_senders = vec!["trusted".to_string()];
+
+        let provider = StubProvider::new(&[&tool_call, "ok"]);
+        let mut agent = test_agent_with(&config, provider.clone());
+        let msg = InboundMessage::new("telegram".to_string(), "stranger".to_string(), "1".to_string(), "write it".to_string());
+        agent.process_message(msg).await.unwrap();
+
+        assert!(!target.exists());
+        {
+            let requests = provider.requests.lock().unwrap();
+            let tool_result = requests[1].iter().find(|m| m.role == "tool").unwrap();
+            assert!(tool_result.content.contains("Refused"));
+        }
+
+        let provider = StubProvider::new(&[&tool_call, "ok"]);
+        let mut agent = test_agent_with(&config, provider);
+        let msg = InboundMessage::new("telegram".to_string(), "trusted".to_string(), "1".to_string(), "write it".to_string());
+        agent.process_message(msg).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "pwned");
+    }
+
+    /// Answers every approval question the same way and records what was asked.
+    struct StubApprover {
+        answer: bool,
+        asked: Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Approver for StubApprover {
+        async fn approve(&self, _origin: &InboundMessage, tool: &str, _arguments: &serde_json::Value) -> bool {
+            self.asked.lock().unwrap().push(tool.to_string());
+            self.answer
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tools_requiring_approval_wait_for_the_user() {
+        let workspace = TempDir::new().unwrap();
+        let target = workspace.path().join("notes.txt");
+        let tool_call = format!(
+            "```json\n{}\n```",
+            serde_json::json!({"tool": "write_file", "arguments": {"path": target.to_string_lossy(), "content": "hi"}})
+        );
+
+        let mut config = test_config(workspace.path());
+        config.tools.require_approval = vec!["write_file".to_string()];
+
+        let provider = StubProvider::new(&[&tool_call, "ok"]);
+        let approver = Arc::new(StubApprover { answer: false, asked: Mutex::new(Vec::new()) });
+        let agent = test_agent_with(&config, provider.clone()).with_approver(approver.clone());
+        agent.process_direct("write it").await.unwrap();
+
+        assert!(!target.exists());
+        assert_eq!(*approver.asked.lock().unwrap(), vec!["write_file"]);
+        {
+            let requests = provider.requests.lock().unwrap();
+            let tool_result = requests[1].iter().find(|m| m.role == "tool").unwrap();
+            assert!(tool_result.content.contains("Denied by user"));
+        }
+
+        let provider = StubProvider::new(&[&tool_call, "ok"]);
+        let approver = Arc::new(StubApprover { answer: true, asked: Mutex::new(Vec::new()) });
+        let agent = test_agent_with(&config, provider).with_approver(approver);
+        agent.process_direct("write it").await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "hi");
+    }
+
+    #[tokio::test]
+    async fn test_iteration_cap_ends_with_a_synthesized_answer() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("data.txt"), "RAW TOOL OUTPUT").unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.max_iterations = 2;
+
+        let provider = StubProvider::default();
+        for id in ["call_1", "call_2"] {
+            let arguments = serde_json::json!({"path": workspace.path().join("data.txt").to_string_lossy()});
+            provider.push("", vec![ProviderToolCall {
+                id: id.to_string(),
+                name: "read_file".to_string(),
+                arguments: serde_json::from_value(arguments).unwrap(),
+            }]);
+        }
+        provider.push("The file holds one line of raw output.", vec![]);
+
+        let agent = test_agent_with(&config, provider.clone());
+        let reply = agent.process_direct("what's in data.txt?").await.unwrap();
+
+        assert_eq!(reply, "The file holds one line of raw output.");
+        assert_eq!(provider.request_count(), 3);
+        // The synthesis request offers no tools, so it can't loop again
+        assert_eq!(*provider.tools_sent.lock().unwrap().last().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_budget_stops_a_runaway_loop() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.max_iterations = 20;
+        config.agent.max_tool_calls = 3;
+
+        // A model that never stops asking for another listing
+        let provider = StubProvider::default();
+        for i in 0..10 {
+            provider.push("", vec![ProviderToolCall {
+                id: format!("call_{}", i),
+                name: "list_dir".to_string(),
+                arguments: serde_json::from_value(serde_json::json!({"path": workspace.path().to_string_lossy()})).unwrap(),
+            }]);
+        }
+
+        let agent = test_agent_with(&config, provider.clone());
+        let reply = agent.process_direct("look around").await.unwrap();
+
+        assert_eq!(reply, "Stopped: tool call budget exceeded (3 tool calls this turn, limit 3).");
+        assert_eq!(provider.request_count(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_tool_loop_requests_stay_bounded() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.keep_tool_rounds = 2;
+
+        let provider = StubProvider::default();
+        for i in 0..8 {
+            provider.push(&format!("Looking, step {}", i), vec![ProviderToolCall {
+                id: format!("call_{}", i),
+                name: "list_dir".to_string(),
+                arguments: serde_json::from_value(serde_json::json!({"path": workspace.path().to_string_lossy()})).unwrap(),
+            }]);
+        }
+        provider.push("Nothing much here.", vec![]);
+
+        let agent = test_agent_with(&config, provider.clone());
+        agent.process_direct("look around").await.unwrap();
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 9);
+        // Prompt and question, two rounds of call + result, one nudge
+        let first = requests[0].len();
+        assert!(requests.iter().all(|r| r.len() <= first + 5));
+
+        let last = requests.last().unwrap();
+        let ids: Vec<&str> = last.iter().filter_map(|m| m.tool_call_id.as_deref()).collect();
+        assert_eq!(ids, vec!["call_6", "call_7"]);
+        assert_eq!(last.iter().filter(|m| m.content == TOOL_NUDGE).count(), 1);
+        let echoes: Vec<&str> = last.iter().filter(|m| m.tool_calls.is_some()).map(|m| m.content.as_str()).collect();
+        assert_eq!(echoes, vec!["", "Looking, step 7"]);
+    }
+
+    #[tokio::test]
+    async fn test_turn_events_are_posted_to_the_webhook() {
+        let workspace = TempDir::new().unwrap();
+        let (url, mut events) = events::tests::capture_server().await;
+        let mut config = test_config(workspace.path());
+        config.events.webhook_url = url;
+
+        let agent = test_agent_with(&config, StubProvider::new(&["All done."]));
+        agent.process_direct("tidy up").await.unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            let event = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv()).await.unwrap().unwrap();
+            received.push(event);
+        }
+        let complete = received.iter().find(|e| e["type"] == "turn_complete").unwrap();
+        assert_eq!(complete["channel"], "cli");
+        assert_eq!(complete["chat_id"], "direct");
+        assert_eq!(complete["summary"], "All done.");
+        assert!(complete["timestamp"].is_string());
+        assert!(received.iter().any(|e| e["type"] == "turn_start" && e["summary"] == "tidy up"));
+    }
+
+    #[tokio::test]
+    async fn test_identical_tool_calls_stop_running_after_two_repeats() {
+        let workspace = TempDir::new().unwrap();
+        let config = test_config(workspace.path());
+
+        let provider = StubProvider::default();
+        for i in 0..4 {
+            provider.push("", vec![ProviderToolCall {
+                id: format!("call_{}", i),
+                name: "remember".to_string(),
+                arguments: serde_json::from_value(serde_json::json!({"fact": "Likes tea"})).unwrap(),
+            }]);
+        }
+        provider.push("Noted.", vec![]);
+
+        let agent = test_agent_with(&config, provider.clone());
+        agent.process_direct("remember that I like tea").await.unwrap();
+
+        let memory = MemoryStore::new(workspace.path()).entries();
+        assert_eq!(memory.len(), 2);
+
+        let requests = provider.requests.lock().unwrap();
+        let results: Vec<&str> = requests.last().unwrap().iter().filter(|m| m.role == "tool").map(|m| m.content.as_str()).collect();
+        assert_eq!(results, vec!["Remembered", "Remembered", REPEATED_CALL_RESULT, REPEATED_CALL_RESULT]);
+    }
+
+    #[tokio::test]
+    async fn test_consolidation_summarizes_oldest_messages_into_memory() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.memory_window = 4;
+        config.memory.consolidation_prompt = "Distill facts.".to_string();
+
+        let provider = StubProvider::new(&[r#"["Lives in Bandung"]"#]);
+        let agent = test_agent_with(&config, provider.clone());
+        let key = session_key("cli", "direct");
+        let history: Vec<serde_json::Value> = (0..6)
+            .map(|i| serde_json::json!({"role": "user", "content": format!("message {}", i)}))
+            .collect();
+        agent.session_history.write().await.insert(key.clone(), history);
+
+        agent.consolidate_memory(&key).await;
+
+        let memory = MemoryStore::new(workspace.path());
+        assert!(memory.read_long_term().contains("- Lives in Bandung"));
+        assert!(memory.read_history().contains("USER: message 3"));
+        assert!(!memory.read_history().contains("message 4"));
+
+        let remaining = agent.history(&key).await;
+        assert_eq!(remaining.len(), 2);
+        assert_eq!(remaining[0]["content"], "message 4");
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0][0].content, "Distill facts.");
+        assert!(!agent.consolidating.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_per_user_memory_stays_with_its_user() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.memory.auto_extract = true;
+        config.memory.per_user = true;
+
+        let provider = StubProvider::new(&["Hi Alice!", r#"["Name is Alice"]"#, "Hello!", "[]", "Hi again!", "[]"]);
+        let mut agent = test_agent_with(&config, provider.clone());
+        let from = |sender: &str, content: &str| {
+            InboundMessage::new("telegram".to_string(), sender.to_string(), "-100".to_string(), content.to_string())
+        };
+
+        agent.process_message(from("alice", "my name is Alice")).await.unwrap();
+        agent.process_message(from("bob", "what's my name?")).await.unwrap();
+        agent.process_message(from("alice", "what's my name?")).await.unwrap();
+
+        let requests = provider.requests.lock().unwrap();
+        // Requests alternate between a turn and its fact extraction
+        assert!(!requests[2][0].content.contains("Name is Alice"));
+        assert!(requests[4][0].content.contains("Name is Alice"));
+        assert!(MemoryStore::new(workspace.path()).read_long_term().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_greeting_only_advertises_core_tools() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.max_advertised_tools = 6;
+        config.agent.core_tools = vec!["read_file".to_string(), "list_dir".to_string()];
+
+        let provider = StubProvider::new(&["Hello!", "Here it is."]);
+        let agent = test_agent_with(&config, provider.clone());
+        agent.process_direct("Hello there!").await.unwrap();
+        agent.process_direct("Fetch the web page at example.com").await.unwrap();
+
+        let tools_sent = provider.tools_sent.lock().unwrap().clone();
+        assert_eq!(tools_sent[0], 2);
+        assert!(tools_sent[1] > 2);
+    }
+
+    #[tokio::test]
+    async fn test_http_request_gets_the_final_answer_not_the_preamble() {
+        use crate::channels::http::HttpChannel;
+
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("status.txt"), "all green").unwrap();
+        let mut config = test_config(workspace.path());
+        config.channels.http.token = "t0ken".to_string();
+
+        let provider = StubProvider::default();
+        provider.push("Let me check the status file…", vec![ProviderToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::from_value(serde_json::json!({"path": workspace.path().join("status.txt").to_string_lossy()})).unwrap(),
+        }]);
+        provider.push("The build is all green.", vec![]);
+
+        let (inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, mut outbound_rx) = tokio::sync::mpsc::channel(10);
+        let http = Arc::new(HttpChannel::from_config(&config.channels.http, inbound_tx));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/message", listener.local_addr().unwrap());
+        tokio::spawn(Arc::clone(&http).serve(listener));
+
+        let mut agent = AgentLoop::with_provider(&config, Box::new(provider), inbound_rx, outbound_tx);
+        tokio::spawn(async move { agent.run().await });
+        // The gateway's delivery, for the http channel only
+        tokio::spawn(async move {
+            while let Some(msg) = outbound_rx.recv().await {
+                http.send(msg);
+            }
+        });
+
+        let response = reqwest::Client::new()
+            .post(&url)
+            .bearer_auth("t0ken")
+            .json(&serde_json::json!({"chat_id": "nightly", "content": "Is the build ok?"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["content"], "The build is all green.");
+    }
+
+    #[tokio::test]
+    async fn test_health_pings_the_agent_provider() {
+        let workspace = TempDir::new().unwrap();
+        let provider = StubProvider::new(&["pong"]);
+        let agent = test_agent_with(&test_config(workspace.path()), provider.clone());
+
+        let tools = agent.tools.read().await;
+        let report: serde_json::Value = serde_json::from_str(&tools.get("health").unwrap().execute(serde_json::json!({})).await.unwrap()).unwrap();
+        assert_eq!(report["checks"][0]["name"], "provider");
+        assert_eq!(report["checks"][0]["ok"], true);
+        assert_eq!(provider.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tool_restricted_to_admin_is_hidden_from_other_senders() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.tools.availability.insert("shell".to_string(), crate::config::ToolAvailabilityRule {
+            senders: vec!["admin".to_string()],
+            ..Default::default()
+        });
+        let agent = test_agent_with(&config, StubProvider::new(&[]));
+        let tools = agent.tools.read().await;
+        let names = |sender: &str| {
+            let origin = InboundMessage::new("telegram".to_string(), sender.to_string(), "5".to_string(), "run ls".to_string());
+            agent
+                .advertised_tools(&tools, &origin)
+                .into_iter()
+                .map(|d| d.function.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert!(names("admin").contains(&"shell".to_string()));
+        let guest = names("guest");
+        assert!(!guest.contains(&"shell".to_string()));
+        assert!(guest.contains(&"read_file".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_provider_error_is_explained_to_the_user() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.provider.api_base = crate::providers::retry::tests::mock_server(vec![(
+            "404 Not Found",
+            "",
+            r#"{"error":{"message":"The model `gpt-9` does not exist","type":"invalid_request_error","param":null,"code":"model_not_found"}}"#,
+        )])
+        .await;
+        config.provider.max_retries = 0;
+
+        let provider = crate::providers::create_provider(&config.provider);
+        let (_inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, mut outbound_rx) = tokio::sync::mpsc::channel(10);
+        let mut agent = AgentLoop::with_provider(&config, provider, inbound_rx, outbound_tx);
+        let msg = InboundMessage::new("telegram".to_string(), "42".to_string(), "42".to_string(), "hello".to_string());
+        assert!(agent.process_message(msg).await.is_err());
+
+        let reply = outbound_rx.try_recv().unwrap();
+        assert_eq!(reply.content, "Sorry, I couldn't answer that: the LLM provider doesn't know this model; check provider.model");
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_proposes_tool_calls_without_running_them() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.dry_run = true;
+        let target = workspace.path().join("notes.md");
+
+        let provider = StubProvider::default();
+        provider.push("", vec![ProviderToolCall {
+            id: "call_1".to_string(),
+            name: "write_file".to_string(),
+            arguments: serde_json::from_value(serde_json::json!({"path": target.to_string_lossy(), "content": "hello"})).unwrap(),
+        }]);
+        provider.push("I would write notes.md.", vec![]);
+
+        let agent = test_agent_with(&config, provider.clone());
+        let reply = agent.process_direct("save a note").await.unwrap();
+
+        assert_eq!(reply, "I would write notes.md.");
+        assert!(!target.exists());
+        let requests = provider.requests.lock().unwrap();
+        let result = requests[1].iter().find(|m| m.role == "tool").unwrap();
+        assert_eq!(
+            result.content,
+            format!("DRY RUN: would have executed write_file(content=\"hello\", path={:?})", target.to_string_lossy())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trace_file_records_each_loop_event() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("data.txt"), "forty-two").unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.trace_file = "logs/trace.jsonl".to_string();
+
+        let provider = StubProvider::default();
+        provider.push("", vec![ProviderToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::from_value(serde_json::json!({"path": workspace.path().join("data.txt")})).unwrap(),
+        }]);
+        provider.push("It says forty-two.", vec![]);
+
+        let agent = test_agent_with(&config, provider);
+        agent.process_direct("what's in data.txt?").await.unwrap();
+
+        let trace = std::fs::read_to_string(workspace.path().join("logs/trace.jsonl")).unwrap();
+        let events: Vec<serde_json::Value> = trace.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let names: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(names, vec![
+            "iteration_start", "request", "response", "tool_call", "tool_result",
+            "iteration_start", "request", "response", "final",
+        ]);
+
+        assert!(events.iter().all(|e| e["timestamp"].is_string()));
+        assert_eq!(events[1]["messages"].as_array().unwrap().last().unwrap()["content"], "what's in data.txt?");
+        assert_eq!(events[3]["name"], "read_file");
+        assert_eq!(events[4]["success"], true);
+        assert_eq!(events[4]["result"], "forty-two");
+        assert_eq!(events[7]["content"], "It says forty-two.");
+        assert_eq!(events[8]["content"], "It says forty-two.");
+    }
+
+    #[tokio::test]
+    async fn test_streaming_disabled_uses_plain_chat_requests() {
+        let workspace = TempDir::new().unwrap();
+        std::fs::write(workspace.path().join("data.txt"), "forty-two").unwrap();
+        let mut config = test_config(workspace.path());
+        config.provider.stream = false;
+        config.channels.telegram = vec![crate::config::TelegramConfig {
+            enabled: true,
+            token: "123:abc".to_string(),
+            ..Default::default()
+        }];
+
+        let provider = StubProvider::default();
+        provider.push("", vec![ProviderToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::from_value(serde_json::json!({"path": workspace.path().join("data.txt").to_string_lossy()})).unwrap(),
+        }]);
+        provider.push("The answer is forty-two.", vec![]);
+
+        let (_inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, mut outbound_rx) = tokio::sync::mpsc::channel(10);
+        let mut agent = AgentLoop::with_provider(&config, Box::new(provider.clone()), inbound_rx, outbound_tx);
+        let msg = InboundMessage::new("telegram".to_string(), "42".to_string(), "42".to_string(), "what's in data.txt?".to_string());
+        agent.process_message(msg).await.unwrap();
+
+        // Both requests went through `chat`, which the stub doesn't record settings for
+        assert_eq!(provider.request_count(), 2);
+        assert!(provider.settings.lock().unwrap().is_empty());
+        let requests = provider.requests.lock().unwrap();
+        assert!(requests[1].iter().any(|m| m.role == "tool" && m.content.contains("forty-two")));
+
+        let reply = outbound_rx.try_recv().unwrap();
+        assert!(!reply.is_streaming);
+        assert_eq!(reply.content, "The answer is forty-two.");
+        assert!(outbound_rx.try_recv().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stalled_stream_is_retried_from_scratch() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.provider.stream_idle_timeout_secs = 30;
+        config.provider.stream_retries = 1;
+
+        let provider = StubProvider::new(&["Half an ans", "The whole answer."]);
+        *provider.stalls.lock().unwrap() = 1;
+        let agent = test_agent_with(&config, provider.clone());
+
+        let started = tokio::time::Instant::now();
+        let reply = agent.process_direct("Explain it").await.unwrap();
+
+        assert_eq!(reply, "The whole answer.");
+        assert_eq!(provider.request_count(), 2);
+        assert!(started.elapsed() >= std::time::Duration::from_secs(30));
+
+        // Out of retries, the stall surfaces as an error
+        provider.push("Stuck", vec![]);
+        *provider.stalls.lock().unwrap() = 2;
+        provider.push("Stuck again", vec![]);
+        let err = agent.process_direct("Again").await.unwrap_err();
+        assert_eq!(err, "stream stalled: no data for 30s");
+    }
+
+    #[test]
+    fn test_brave_search_registered_only_with_a_key() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        let has_brave = |config: &Config| {
+            AgentLoop::create_tools(config, workspace.path())
+                .get_definitions()
+                .iter()
+                .any(|d| d.function.name == "brave_search")
+        };
+
+        assert!(!has_brave(&config));
+        config.provider.brave_api_key = "BSA-test".to_string();
+        assert!(has_brave(&config));
+    }
+
+    /// Holds each stream request until released, to catch the agent mid-turn.
+    struct GatedProvider {
+        inner: StubProvider,
+        entered: Arc<tokio::sync::Notify>,
+        release: Arc<tokio::sync::Notify>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLMProvider for GatedProvider {
+        async fn chat(
+            &self,
+            messages: Vec<ChatMessage>,
+            tools: Option<Vec<ToolDefinition>>,
+            tool_choice: Option<ToolChoice>,
+            model: Option<String>,
+            temperature: Option<f32>,
+            max_tokens: Option<u32>,
+        ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+            self.inner.chat(messages, tools, tool_choice, model, temperature, max_tokens).await
+        }
+
+        async fn chat_stream(
+            &self,
+            messages: Vec<ChatMessage>,
+            tools: Option<Vec<ToolDefinition>>,
+            tool_choice: Option<ToolChoice>,
+            model: Option<String>,
+            temperature: Option<f32>,
+            max_tokens: Option<u32>,
+        ) -> Result<ChatStream, Box<dyn std::error::Error + Send + Sync>> {
+            self.entered.notify_one();
+            self.release.notified().await;
+            self.inner.chat_stream(messages, tools, tool_choice, model, temperature, max_tokens).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_mid_turn_finishes_the_turn_and_saves_the_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let config = test_config(temp_dir.path());
+        let entered = Arc::new(tokio::sync::Notify::new());
+        let release = Arc::new(tokio::sync::Notify::new());
+        let provider = GatedProvider {
+            inner: StubProvider::new(&["Noted before exit."]),
+            entered: Arc::clone(&entered),
+            release: Arc::clone(&release),
+        };
+
+        let (inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, mut outbound_rx) = tokio::sync::mpsc::channel(10);
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let mut agent = AgentLoop::with_provider(&config, Box::new(provider), inbound_rx, outbound_tx)
+            .with_shutdown(shutdown_rx);
+        let running = tokio::spawn(async move { agent.run().await });
+
+        let msg = InboundMessage::new("telegram".to_string(), "5".to_string(), "5".to_string(), "Remember the milk".to_string());
+        inbound_tx.send(msg).await.unwrap();
+        entered.notified().await;
+
+        shutdown_tx.send(true).unwrap();
+        release.notify_one();
+        tokio::time::timeout(std::time::Duration::from_secs(5), running).await.unwrap().unwrap();
+
+        assert_eq!(outbound_rx.recv().await.unwrap().content, "Noted before exit.");
+        let stored = SessionStore::new(temp_dir.path()).load("telegram:5");
+        assert_eq!(stored.history.len(), 2);
+        assert_eq!(stored.history[0]["content"], "Remember the milk");
+    }
+
+    #[tokio::test]
+    async fn test_artifact_manifest_lists_files_written_in_a_turn() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.artifact_manifest = true;
+        config.agent.announce_artifacts = true;
+
+        let provider = StubProvider::default();
+        for (id, name) in [("call_1", "report.md"), ("call_2", "chart.svg")] {
+            let arguments = serde_json::json!({"path": workspace.path().join(name).to_string_lossy(), "content": "data"});
+            provider.push("", vec![ProviderToolCall {
+                id: id.to_string(),
+                name: "write_file".to_string(),
+                arguments: serde_json::from_value(arguments).unwrap(),
+            }]);
+        }
+        provider.push("Both files are ready.", vec![]);
+
+        let agent = test_agent_with(&config, provider);
+        let reply = agent.process_direct("make a report with a chart").await.unwrap();
+        assert!(reply.ends_with("I created: report.md, chart.svg"));
+
+        let manifest = std::fs::read_to_string(workspace.path().join(artifacts::MANIFEST_FILE)).unwrap();
+        let lines: Vec<&str> = manifest.lines().collect();
+        assert_eq!(lines.len(), 1);
+
+        let entry: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(entry["session"], "cli:direct");
+        let files = entry["files"].as_array().unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0]["path"].as_str().unwrap().ends_with("report.md"));
+        assert!(files[1]["path"].as_str().unwrap().ends_with("chart.svg"));
+        for file in files {
+            assert_eq!(file["action"], "created");
+            assert!(chrono::DateTime::parse_from_rfc3339(file["at"].as_str().unwrap()).is_ok());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_duplicate_message_within_window_is_skipped() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.agent.dedup_window_secs = 30;
+
+        let provider = StubProvider::new(&["one", "two", "three"]);
+        let mut agent = test_agent_with(&config, provider.clone());
+        let msg = InboundMessage::new("telegram".to_string(), "7".to_string(), "7".to_string(), "status?".to_string());
+
+        agent.process_message(msg.clone()).await.unwrap();
+        agent.process_message(msg.clone()).await.unwrap();
+        assert_eq!(provider.request_count(), 1);
+
+        // A different chat isn't affected
+        let mut other = msg.clone();
+        other.chat_id = "8".to_string();
+        agent.process_message(other).await.unwrap();
+        assert_eq!(provider.request_count(), 2);
+
+        tokio::time::advance(std::time::Duration::from_secs(31)).await;
+        agent.process_message(msg).await.unwrap();
+        assert_eq!(provider.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_bot_persona_and_separate_history() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.channels.telegram = vec![
+            crate::config::TelegramConfig { enabled: true, token: "a".to_string(), ..Default::default() },
+            crate::config::TelegramConfig {
+                id: "work".to_string(),
+                enabled: true,
+                token: "b".to_string(),
+                persona: "Answer like a project manager.".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let provider = StubProvider::new(&["hi", "hello"]);
+        let mut agent = test_agent_with(&config, provider.clone());
+
+        let personal = InboundMessage::new("telegram".to_string(), "7".to_string(), "7".to_string(), "hey".to_string());
+        let work = personal.clone().with_metadata(HashMap::from([(BOT_ID_KEY.to_string(), "work".to_string())]));
+        agent.process_message(personal).await.unwrap();
+        agent.process_message(work).await.unwrap();
+
+        {
+            let requests = provider.requests.lock().unwrap();
+            assert!(!requests[0][0].content.contains("project manager"));
+            assert!(requests[1][0].content.contains("## Persona\n\nAnswer like a project manager."));
+        }
+        assert_eq!(agent.history(&session_key("telegram", "7")).await.len(), 2);
+        assert_eq!(agent.history(&session_key("telegram.work", "7")).await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_channel_profiles_use_their_own_workspace_memory() {
+        let global = TempDir::new().unwrap();
+        let personal = TempDir::new().unwrap();
+        let work = TempDir::new().unwrap();
+        MemoryStore::new(personal.path()).append_entry("Daughter's name is Sari").unwrap();
+        MemoryStore::new(work.path()).append_entry("Sprint ends Friday").unwrap();
+        std::fs::write(work.path().join("SOUL.md"), "Keep it professional.").unwrap();
+
+        let mut config = test_config(global.path());
+        config.channels.profiles = HashMap::from([
+            ("telegram".to_string(), crate::config::ChannelProfile {
+                workspace: personal.path().display().to_string(),
+                ..Default::default()
+            }),
+            ("slack".to_string(), crate::config::ChannelProfile {
+                workspace: work.path().display().to_string(),
+                persona: "You are the team's work assistant.".to_string(),
+            }),
+        ]);
+
+        std::fs::write(work.path().join("plan.md"), "Ship the invoices export").unwrap();
+
+        let provider = StubProvider::new(&["hi", "hello"]);
+        provider.push("", vec![ProviderToolCall {
+            id: "call_1".to_string(),
+            name: "remember".to_string(),
+            arguments: serde_json::from_value(serde_json::json!({"fact": "Standup moved to 10:00"})).unwrap(),
+        }]);
+        provider.push("Noted.", vec![]);
+        provider.push("On it.", vec![]);
+        let mut agent = test_agent_with(&config, provider.clone());
+        let from = |channel: &str, content: &str| InboundMessage::new(channel.to_string(), "7".to_string(), "7".to_string(), content.to_string());
+        agent.process_message(from("telegram", "hey")).await.unwrap();
+        agent.process_message(from("slack", "hey")).await.unwrap();
+        agent.process_message(from("slack", "Remember that standup moved to 10:00")).await.unwrap();
+        agent.process_message(from("slack", "Summarize @file:plan.md for me")).await.unwrap();
+
+        let requests = provider.requests.lock().unwrap();
+        let (telegram, slack) = (&requests[0][0].content, &requests[1][0].content);
+        assert!(telegram.contains("Sari") && !telegram.contains("Sprint"));
+        assert!(slack.contains("Sprint ends Friday") && !slack.contains("Sari"));
+        assert!(slack.contains("Keep it professional."));
+        assert!(slack.ends_with("## Persona\n\nYou are the team's work assistant."));
+        assert!(!telegram.contains("## Persona"));
+
+        // The remember tool writes to the slack workspace, where its prompt reads memory
+        assert!(MemoryStore::new(work.path()).read_long_term().contains("Standup moved to 10:00"));
+        assert!(!global.path().join("memory/MEMORY.md").exists());
+        let later = &requests[4];
+        assert!(later[0].content.contains("Standup moved to 10:00"));
+        // @file resolves against the slack workspace too
+        assert!(later.iter().any(|m| m.content.contains("Ship the invoices export")));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_sends_one_message_per_destination() {
+        let workspace = TempDir::new().unwrap();
+        let mut config = test_config(workspace.path());
+        config.channels.broadcast = vec![
+            BroadcastTarget { channel: "telegram".to_string(), chat_id: "-1001".to_string() },
+            BroadcastTarget { channel: "telegram".to_string(), chat_id: "-1002".to_string() },
+        ];
+
+        let (_inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, mut outbound_rx) = tokio::sync::mpsc::channel(10);
+        let provider = StubProvider::new(&["All systems green."]);
+        let mut agent = AgentLoop::with_provider(&config, Box::new(provider), inbound_rx, outbound_tx);
+
+        let msg = InboundMessage::new("cli".to_string(), "user".to_string(), "direct".to_string(), "/broadcast post the status".to_string());
+        agent.process_message(msg).await.unwrap();
+
+        let mut delivered = Vec::new();
+        while let Ok(out) = outbound_rx.try_recv() {
+            if out.chat_id != "direct" {
+                assert_eq!(out.content, "All systems green.");
+                delivered.push(out.chat_id);
+            }
+        }
+        assert_eq!(delivered, vec!["-1001", "-1002"]);
+    }
+
+    #[tokio::test]
+    async fn test_schema_mismatch_gets_one_repair_turn() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = StubProvider::new(&[r#"{"name": "Budi"}"#, r#"{"name": "Budi", "age": 30}"#, "unused"]);
+        let (_inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, mut outbound_rx) = tokio::sync::mpsc::channel(10);
+        let agent = AgentLoop::with_provider(&test_config(temp_dir.path()), Box::new(provider.clone()), inbound_rx, outbound_tx);
+
+        agent
+            .process_direct(r#"/schema {"type": "object", "required": ["name", "age"]}"#)
+            .await
+            .unwrap();
+        let response = agent.process_direct("Describe Budi as JSON").await.unwrap();
+
+        assert_eq!(response, r#"{"name": "Budi", "age": 30}"#);
+        assert_eq!(provider.request_count(), 2);
+
+        // The invalid first answer never reaches the user; only the repaired one does
+        assert_eq!(outbound_rx.try_recv().unwrap().content, r#"{"name": "Budi", "age": 30}"#);
+        assert!(outbound_rx.try_recv().is_err());
+
+        let requests = provider.requests.lock().unwrap();
+        let repair_prompt = &requests[1].last().unwrap().content;
+        assert!(repair_prompt.contains("missing required property 'age'"));
+    }
+
+    #[tokio::test]
+    async fn test_native_tool_calls_are_executed() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("notes.txt"), "native works").unwrap();
+
+        let provider = StubProvider::default();
+        let arguments = serde_json::json!({"path": temp_dir.path().join("notes.txt").to_string_lossy()});
+        provider.push("", vec![ProviderToolCall {
+            id: "call_1".to_string(),
+            name: "read_file".to_string(),
+            arguments: serde_json::from_value(arguments).unwrap(),
+        }]);
+        provider.push("The file says: native works", vec![]);
+
+        let agent = test_agent_with(&test_config(temp_dir.path()), provider.clone());
+        let response = agent.process_direct("What is in notes.txt?").await.unwrap();
+
+        assert_eq!(response, "The file says: native works");
+        assert!(provider.tools_sent.lock().unwrap()[0] > 0);
+
+        let requests = provider.requests.lock().unwrap();
+        let assistant = requests[1].iter().find(|m| m.tool_calls.is_some()).unwrap();
+        assert_eq!(assistant.tool_calls.as_ref().unwrap()[0].id, "call_1");
+        let result = requests[1].iter().find(|m| m.role == "tool").unwrap();
+        assert_eq!(result.content, "native works");
+        assert_eq!(result.tool_call_id.as_deref(), Some("call_1"));
+    }
+
+    #[tokio::test]
+    async fn test_multiple_fenced_tool_calls_all_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = temp_dir.path().join("a.txt");
+        let second = temp_dir.path().join("b.txt");
+        std::fs::write(&first, "alpha").unwrap();
+        std::fs::write(&second, "beta").unwrap();
+
+        let content = format!(
+            "Reading both.\n```json\n{}\n```\n```json\n{}\n```",
+            serde_json::json!({"tool": "read_file", "arguments": {"path": first.to_string_lossy()}}),
+            serde_json::json!({"tool": "read_file", "arguments": {"path": second.to_string_lossy()}}),
+        );
+        let provider = StubProvider::new(&[&content, "alpha and beta"]);
+        let agent = test_agent_with(&test_config(temp_dir.path()), provider.clone());
+
+        agent.process_direct("Read a.txt and b.txt").await.unwrap();
+
+        let requests = provider.requests.lock().unwrap();
+        let assistant = requests[1].iter().find(|m| m.tool_calls.is_some()).unwrap();
+        let call_ids: Vec<&str> = assistant.tool_calls.as_ref().unwrap().iter().map(|c| c.id.as_str()).collect();
+        let results: Vec<&ChatMessage> = requests[1].iter().filter(|m| m.role == "tool").collect();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].content, "alpha");
+        assert_eq!(results[1].content, "beta");
+        assert_eq!(results[0].tool_call_id.as_deref(), Some(call_ids[0]));
+        assert_eq!(results[1].tool_call_id.as_deref(), Some(call_ids[1]));
+        assert_ne!(call_ids[0], call_ids[1]);
+    }
+
+    #[tokio::test]
+    async fn test_file_directive_is_attached_for_the_turn_only() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("notes.md"), "The launch is on Friday.").unwrap();
+
+        let provider = StubProvider::new(&["It's on Friday."]);
+        let mut agent = test_agent_with(&test_config(temp_dir.path()), provider.clone());
+        let msg = InboundMessage::new("telegram".to_string(), "1".to_string(), "1".to_string(), "When is the launch? @file:notes.md".to_string());
+        agent.process_message(msg).await.unwrap();
+
+        {
+            let requests = provider.requests.lock().unwrap();
+            let sent = &requests[0];
+            let attached = &sent[sent.len() - 2];
+            assert!(attached.content.contains("The launch is on Friday."));
+            assert_eq!(sent.last().unwrap().content, "When is the launch? @file:notes.md");
+        }
+
+        let history = agent.history("telegram:1").await;
+        assert!(history.iter().all(|m| !m["content"].as_str().unwrap_or("").contains("The launch is on Friday.")));
+    }
+
+    #[tokio::test]
+    async fn test_auto_extract_persists_new_fact() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.memory.auto_extract = true;
+
+        let provider = StubProvider::new(&["Nice to meet you, Budi!", r#"["The user's name is Budi"]"#]);
+        let mut agent = test_agent_with(&config, provider.clone());
+        let msg = InboundMessage::new("telegram".to_string(), "1".to_string(), "1".to_string(), "Hi, I'm Budi".to_string());
+        agent.process_message(msg).await.unwrap();
+
+        assert_eq!(provider.request_count(), 2);
+        let memory = MemoryStore::new(temp_dir.path()).read_long_term();
+        assert!(memory.contains("- The user's name is Budi"));
+    }
+
+    #[tokio::test]
+    async fn test_histories_are_isolated_per_chat() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = StubProvider::new(&["Hi Ana", "Hi Budi", "You are Ana"]);
+        let mut agent = test_agent_with(&test_config(temp_dir.path()), provider.clone());
+
+        for (chat_id, content) in [("100", "I'm Ana"), ("200", "I'm Budi"), ("100", "Who am I?")] {
+            let msg = InboundMessage::new("telegram".to_string(), chat_id.to_string(), chat_id.to_string(), content.to_string());
+            agent.process_message(msg).await.unwrap();
+        }
+
+        let chat_a = agent.history("telegram:100").await;
+        let chat_b = agent.history("telegram:200").await;
+        assert_eq!(chat_a.len(), 4);
+        assert_eq!(chat_b.len(), 2);
+        assert!(chat_b.iter().all(|m| !m["content"].as_str().unwrap().contains("Ana")));
+
+        // The third request only replays chat 100's transcript
+        let requests = provider.requests.lock().unwrap();
+        let third: Vec<&str> = requests[2].iter().skip(1).map(|m| m.content.as_str()).collect();
+        assert_eq!(third, vec!["I'm Ana", "Hi Ana", "Who am I?"]);
+    }
+
+    #[tokio::test]
+    async fn test_old_turns_are_compacted_into_persisted_summary() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.agent.max_persisted_turns = 2;
+
+        let provider = StubProvider::new(&["Hello!", "Bali is lovely.", "Sure, in May.", "The user is planning a trip to Bali."]);
+        let mut agent = test_agent_with(&config, provider.clone());
+        for content in ["Hi", "Tell me about Bali", "Can we go in May?"] {
+            let msg = InboundMessage::new("telegram".to_string(), "7".to_string(), "7".to_string(), content.to_string());
+            agent.process_message(msg).await.unwrap();
+        }
+
+        let persisted = SessionStore::new(temp_dir.path()).load("telegram:7").history;
+        let contents: Vec<&str> = persisted.iter().map(|m| m["content"].as_str().unwrap()).collect();
+        assert_eq!(contents, vec![
+            "Summary of the earlier conversation: The user is planning a trip to Bali.",
+            "Tell me about Bali",
+            "Bali is lovely.",
+            "Can we go in May?",
+            "Sure, in May.",
+        ]);
+        assert!(summary::is_summary(&persisted[0]));
+
+        // The summarized turn went to the model, the kept turns did not
+        let requests = provider.requests.lock().unwrap();
+        let summarized = &requests[3].last().unwrap().content;
+        assert!(summarized.contains("USER: Hi") && summarized.contains("ASSISTANT: Hello!"));
+        assert!(!summarized.contains("May"));
+    }
+
+    #[tokio::test]
+    async fn test_metadata_overrides_model_settings_for_one_turn() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = StubProvider::new(&["Creative!", "Default."]);
+        let mut agent = test_agent_with(&test_config(temp_dir.path()), provider.clone());
+
+        let metadata = HashMap::from([
+            ("temperature".to_string(), "1.3".to_string()),
+            ("max_tokens".to_string(), "999999".to_string()),
+            ("model".to_string(), "gpt-4o".to_string()),
+        ]);
+        let msg = InboundMessage::new("webhook".to_string(), "1".to_string(), "1".to_string(), "Write a poem".to_string())
+            .with_metadata(metadata);
+        agent.process_message(msg).await.unwrap();
+
+        let msg = InboundMessage::new("webhook".to_string(), "1".to_string(), "1".to_string(), "And now?".to_string());
+        agent.process_message(msg).await.unwrap();
+
+        let settings = provider.settings.lock().unwrap();
+        // max_tokens is clamped to the configured maximum
+        assert_eq!(settings[0], (Some("gpt-4o".to_string()), Some(1.3), Some(8192)));
+        assert_eq!(settings[1], (Some("gpt-4o-mini".to_string()), Some(0.7), Some(8192)));
+    }
+
+    #[tokio::test]
+    async fn test_focus_file_tops_system_prompt_every_turn() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("TASK.md"), "- [ ] Write the parser").unwrap();
+
+        let provider = StubProvider::new(&["Working on it.", "Next step."]);
+        let agent = test_agent_with(&test_config(temp_dir.path()), provider.clone());
+
+        let reply = agent.process_direct("/focus TASK.md").await.unwrap();
+        assert!(reply.contains("Focused on TASK.md"));
+        assert!(agent.process_direct("/focus ../outside.md").await.unwrap().starts_with("Cannot focus"));
+
+        agent.process_direct("Let's start").await.unwrap();
+        std::fs::write(temp_dir.path().join("TASK.md"), "- [x] Write the parser\n- [ ] Add tests").unwrap();
+        agent.process_direct("Continue").await.unwrap();
+
+        {
+            let requests = provider.requests.lock().unwrap();
+            assert!(requests[0][0].content.starts_with("## Focus: TASK.md"));
+            assert!(requests[0][0].content.contains("- [ ] Write the parser"));
+            assert!(requests[1][0].content.starts_with("## Focus: TASK.md"));
+            assert!(requests[1][0].content.contains("- [ ] Add tests"));
+        }
+
+        agent.process_direct("/focus off").await.unwrap();
+        agent.process_direct("Done?").await.unwrap();
+        assert!(!provider.requests.lock().unwrap()[2][0].content.contains("## Focus"));
+    }
+
+    #[tokio::test]
+    async fn test_verbosity_steers_the_system_prompt() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.agent.verbosity = "brief".to_string();
+
+        let provider = StubProvider::new(&["Yes.", "Here is the long version.", "Ok."]);
+        let agent = test_agent_with(&config, provider.clone());
+
+        agent.process_direct("Is Rust fast?").await.unwrap();
+        assert!(agent.process_direct("/detailed").await.unwrap().contains("detailed"));
+        agent.process_direct("Why?").await.unwrap();
+        agent.process_direct("/normal").await.unwrap();
+        agent.process_direct("Thanks").await.unwrap();
+
+        let requests = provider.requests.lock().unwrap();
+        assert!(requests[0][0].content.ends_with(Verbosity::Brief.section().unwrap()));
+        assert!(requests[0][0].content.contains("Keep replies brief"));
+        assert!(requests[1][0].content.contains("Give detailed replies"));
+        assert!(!requests[1][0].content.contains("Keep replies brief"));
+        assert!(!requests[2][0].content.contains("## Response Length"));
+    }
+
+    #[tokio::test]
+    async fn test_usage_is_footed_and_persisted_per_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.agent.show_usage = true;
+
+        let provider = StubProvider::default();
+        for total in [1000, 234] {
+            provider.responses.lock().unwrap().push_back(LLMResponse {
+                content: Some("Answer".to_string()),
+                tool_calls: vec![],
+                finish_reason: "stop".to_string(),
+                usage: Usage { prompt_tokens: total - 4, completion_tokens: 4, total_tokens: total },
+            });
+        }
+
+        let (_inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, mut outbound_rx) = tokio::sync::mpsc::channel(10);
+        let mut agent = AgentLoop::with_provider(&config, Box::new(provider), inbound_rx, outbound_tx);
+
+        for content in ["First", "Second"] {
+            let msg = InboundMessage::new("telegram".to_string(), "5".to_string(), "5".to_string(), content.to_string());
+            agent.process_message(msg).await.unwrap();
+        }
+
+        assert_eq!(outbound_rx.recv().await.unwrap().content, "Answer\n\n(1,000 tokens)");
+        assert_eq!(outbound_rx.recv().await.unwrap().content, "Answer\n\n(234 tokens)");
+
+        let stored = SessionStore::new(temp_dir.path()).load("telegram:5");
+        assert_eq!(stored.usage.total_tokens, 1234);
+        assert_eq!(stored.usage.completion_tokens, 8);
+        assert_eq!(stored.history.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_cost_footer_prices_the_turn() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = test_config(temp_dir.path());
+        config.agent.show_cost = true;
+        config.pricing.insert(config.agent.model.clone(), ModelPrice { input_per_million: 0.15, output_per_million: 0.6 });
+
+        let provider = StubProvider::default();
+        provider.responses.lock().unwrap().push_back(LLMResponse {
+            content: Some("Answer".to_string()),
+            tool_calls: vec![],
+            finish_reason: "stop".to_string(),
+            usage: Usage { prompt_tokens: 1000, completion_tokens: 240, total_tokens: 1240 },
+        });
+
+        let (_inbound_tx, inbound_rx) = tokio::sync::mpsc::channel(10);
+        let (outbound_tx, mut outbound_rx) = tokio::sync::mpsc::channel(10);
+        let mut agent = AgentLoop::with_provider(&config, Box::new(provider), inbound_rx, outbound_tx);
+        let msg = InboundMessage::new("telegram".to_string(), "5".to_string(), "5".to_string(), "Hi".to_string());
+        agent.process_message(msg).await.unwrap();
+
+        // 1000 × $0.15/M + 240 × $0.60/M = $0.000294
+        assert_eq!(outbound_rx.recv().await.unwrap().content, "Answer\n\n· 1,240 tokens · $0.0003");
+        let stored = SessionStore::new(temp_dir.path()).load("telegram:5");
+        assert_eq!(stored.history[1]["content"], "Answer");
+
+        let usage = Usage { prompt_tokens: 10_000, completion_tokens: 1_000, total_tokens: 11_000 };
+        let price = ModelPrice { input_per_million: 0.15, output_per_million: 0.6 };
+        assert_eq!(cost_footer(&usage, Some(&price), false), "\n\n· 11,000 tokens · $0.0021");
+        assert_eq!(cost_footer(&usage, Some(&price), true), "\n\n(11,000 tokens, $0.0021)");
+        assert_eq!(cost_footer(&usage, None, false), "\n\n· 11,000 tokens");
+        assert_eq!(format_cost(2.5), "$2.50");
+    }
+
+    #[test]
+    fn test_format_thousands() {
+        assert_eq!(format_thousands(0), "0");
+        assert_eq!(format_thousands(999), "999");
+        assert_eq!(format_thousands(1234), "1,234");
+        assert_eq!(format_thousands(12_345_678), "12,345,678");
+    }
+
+    #[tokio::test]
+    async fn test_clear_wipes_only_this_chat() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider = StubProvider::new(&["Hi A", "Hi B"]);
+        let mut agent = test_agent_with(&test_config(temp_dir.path()), provider.clone());
+
+        for chat_id in ["1", "2"] {
+            let msg = InboundMessage::new("telegram".to_string(), chat_id.to_string(), chat_id.to_string(), "Hello".to_string());
+            agent.process_message(msg).await.unwrap();
+        }
+        assert_eq!(agent.history("telegram:1").await.len(), 2);
+
+        let msg = InboundMessage::new("telegram".to_string(), "1".to_string(), "1".to_string(), "/reset".to_string());
+        agent.process_message(msg).await.unwrap();
 
-        let (final_content, _) = self.run_agent_loop(messages, self.outbound_tx.clone(), "cli".to_string(), "direct".to_string()).await?;
+        assert!(agent.history("telegram:1").await.is_empty());
+        assert!(SessionStore::new(temp_dir.path()).load("telegram:1").history.is_empty());
+        assert_eq!(agent.history("telegram:2").await.len(), 2);
+        assert_eq!(provider.request_count(), 2);
 
-        Ok(final_content.unwrap_or_else(|| "No response".to_string()))
+        agent.session_history.write().await.insert("cli:direct".to_string(), vec![serde_json::json!({"role": "user", "content": "x"})]);
+        assert!(agent.process_direct("/clear").await.unwrap().contains("cleared"));
+        assert!(agent.history("cli:direct").await.is_empty());
     }
 }
@@ -0,0 +1,133 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::bus::InboundMessage;
+use crate::config::ToolsConfig;
+
+/// Refuses destructive tool calls that reach outside the workspace unless the
+/// turn was started by a trusted sender.
+pub struct DestructiveToolPolicy {
+    enabled: bool,
+    tools: Vec<String>,
+    trusted_senders: Vec<String>,
+    workspace: PathBuf,
+}
+
+impl DestructiveToolPolicy {
+    pub fn new(config: &ToolsConfig, workspace: &Path) -> Self {
+        Self {
+            enabled: config.destructive_guard,
+            tools: config.destructive_tools.clone(),
+            trusted_senders: config.trusted_senders.clone(),
+            workspace: workspace.to_path_buf(),
+        }
+    }
+
+    fn is_trusted(&self, origin: &InboundMessage) -> bool {
+        // The local CLI user is the operator
+        origin.channel == "cli" || self.trusted_senders.contains(&origin.sender_id)
+    }
+
+    /// Whether the call touches something outside the workspace. Shell commands
+    /// can reach anywhere, so they always count.
+    fn reaches_outside_workspace(&self, tool: &str, args: &Value) -> bool {
+        match args.get("path").and_then(|p| p.as_str()) {
+            Some(path) if tool != "shell" => !is_inside(&self.workspace, Path::new(path)),
+            _ => true,
+        }
+    }
+
+    pub fn check(&self, tool: &str, args: &Value, origin: &InboundMessage) -> Result<(), String> {
+        if !self.enabled || !self.tools.iter().any(|t| t == tool) {
+            return Ok(());
+        }
+
+        if !self.reaches_outside_workspace(tool, args) || self.is_trusted(origin) {
+            return Ok(());
+        }
+
+        Err(format!(
+            "Refused: '{}' can modify files outside the workspace and this request did not come from a trusted sender. \
+             Explain to the user what you wanted to do and why, and ask them to perform it themselves.",
+            tool
+        ))
+    }
+}
+
+fn is_inside(workspace: &Path, path: &Path) -> bool {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+
+    if absolute.components().any(|c| matches!(c, std::path::Component::ParentDir)) {
+        return false;
+    }
+
+    let resolved = absolute
+        .parent()
+        .and_then(|parent| parent.canonicalize().ok())
+        .map(|parent| parent.join(absolute.file_name().unwrap_or_default()))
+        .unwrap_or(absolute);
+    let workspace = workspace.canonicalize().unwrap_or_else(|_| workspace.to_path_buf());
+
+    resolved.starts_with(workspace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn policy(workspace: &Path, trusted: &[&str]) -> DestructiveToolPolicy {
+        let config = ToolsConfig {
+            destructive_guard: true,
+            trusted_senders: trusted.iter().map(|s| s.to_string()).collect(),
+            ..ToolsConfig::default()
+        };
+        DestructiveToolPolicy::new(&config, workspace)
+    }
+
+    fn telegram_from(sender: &str) -> InboundMessage {
+        InboundMessage::new("telegram".to_string(), sender.to_string(), "1".to_string(), "hi".to_string())
+    }
+
+    #[test]
+    fn test_untrusted_outside_write_is_refused() {
+        let workspace = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let args = json!({"path": outside.path().join("x.txt").to_string_lossy(), "content": "x"});
+
+        let policy = policy(workspace.path(), &["100"]);
+        assert!(policy.check("write_file", &args, &telegram_from("999")).is_err());
+        assert!(policy.check("write_file", &args, &telegram_from("100")).is_ok());
+
+        let patch = json!({"path": outside.path().join("x.txt").to_string_lossy(), "hunks": [{"old": "a", "new": "b"}]});
+        assert!(policy.check("apply_patch", &patch, &telegram_from("999")).is_err());
+        assert!(policy.check("apply_patch", &patch, &telegram_from("100")).is_ok());
+    }
+
+    #[test]
+    fn test_inside_workspace_and_safe_tools_are_allowed() {
+        let workspace = TempDir::new().unwrap();
+        let policy = policy(workspace.path(), &[]);
+
+        let inside = json!({"path": workspace.path().join("notes.md").to_string_lossy()});
+        assert!(policy.check("write_file", &inside, &telegram_from("999")).is_ok());
+        assert!(policy.check("read_file", &json!({"path": "/etc/hosts"}), &telegram_from("999")).is_ok());
+
+        let escape = json!({"path": workspace.path().join("../x.md").to_string_lossy()});
+        assert!(policy.check("edit_file", &escape, &telegram_from("999")).is_err());
+        assert!(policy.check("structured_edit", &escape, &telegram_from("999")).is_err());
+        assert!(policy.check("shell", &json!({"command": "ls"}), &telegram_from("999")).is_err());
+    }
+
+    #[test]
+    fn test_disabled_policy_allows_everything() {
+        let workspace = TempDir::new().unwrap();
+        let policy = DestructiveToolPolicy::new(&ToolsConfig::default(), workspace.path());
+        assert!(policy.check("shell", &json!({"command": "rm -rf /tmp/x"}), &telegram_from("999")).is_ok());
+    }
+}
@@ -0,0 +1,181 @@
+use serde_json::Value;
+
+/// Pull a JSON document out of a model answer, accepting either bare JSON or a
+/// single ```json fenced block.
+pub fn extract_json(content: &str) -> Result<Value, String> {
+    let trimmed = content.trim();
+    let body = match trimmed.find("```") {
+        Some(start) => {
+            let after = &trimmed[start + 3..];
+            let after = after.strip_prefix("json").unwrap_or(after);
+            match after.find("```") {
+                Some(end) => &after[..end],
+                None => after,
+            }
+        }
+        None => trimmed,
+    };
+
+    serde_json::from_str(body.trim()).map_err(|e| format!("output is not valid JSON: {}", e))
+}
+
+/// Validate `value` against a JSON Schema. Supports the commonly used subset:
+/// type, enum, const, properties, required, additionalProperties, items,
+/// min/max for numbers, strings and arrays. Returns one message per violation.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, "$", &mut errors);
+    errors
+}
+
+fn type_matches(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(|n| n.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| type_matches(value, name)) {
+            errors.push(format!("{}: expected {}", path, names.join(" or ")));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(|e| e.as_array()) {
+        if !allowed.contains(value) {
+            errors.push(format!("{}: must be one of {}", path, Value::Array(allowed.clone())));
+        }
+    }
+
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            errors.push(format!("{}: must equal {}", path, expected));
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            if let Some(required) = schema.get("required").and_then(|r| r.as_array()) {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !map.contains_key(key) {
+                        errors.push(format!("{}: missing required property '{}'", path, key));
+                    }
+                }
+            }
+
+            let properties = schema.get("properties").and_then(|p| p.as_object());
+            for (key, child) in map {
+                let child_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(child_schema) => validate_at(child, child_schema, &child_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{}: unexpected property '{}'", path, key))
+                        }
+                        Some(extra @ Value::Object(_)) => validate_at(child, extra, &child_path, errors),
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            check_bounds(items.len() as f64, schema, "minItems", "maxItems", "items", path, errors);
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item, item_schema, &format!("{}[{}]", path, i), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            check_bounds(s.chars().count() as f64, schema, "minLength", "maxLength", "characters", path, errors);
+        }
+        Value::Number(n) => {
+            if let Some(n) = n.as_f64() {
+                check_bounds(n, schema, "minimum", "maximum", "", path, errors);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_bounds(
+    actual: f64,
+    schema: &serde_json::Map<String, Value>,
+    min_key: &str,
+    max_key: &str,
+    unit: &str,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    let unit = if unit.is_empty() { String::new() } else { format!(" {}", unit) };
+    if let Some(min) = schema.get(min_key).and_then(|m| m.as_f64()) {
+        if actual < min {
+            errors.push(format!("{}: must be at least {}{}", path, min, unit));
+        }
+    }
+    if let Some(max) = schema.get(max_key).and_then(|m| m.as_f64()) {
+        if actual > max {
+            errors.push(format!("{}: must be at most {}{}", path, max, unit));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "age": {"type": "integer", "minimum": 0},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            },
+            "required": ["name", "age"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_document_has_no_errors() {
+        let value = json!({"name": "Budi", "age": 30, "tags": ["a"]});
+        assert!(validate(&value, &person_schema()).is_empty());
+    }
+
+    #[test]
+    fn test_reports_each_violation_with_path() {
+        let value = json!({"name": "", "tags": ["a", 1], "extra": true});
+        let errors = validate(&value, &person_schema());
+
+        assert!(errors.contains(&"$: missing required property 'age'".to_string()));
+        assert!(errors.contains(&"$: unexpected property 'extra'".to_string()));
+        assert!(errors.contains(&"$.name: must be at least 1 characters".to_string()));
+        assert!(errors.contains(&"$.tags[1]: expected string".to_string()));
+    }
+
+    #[test]
+    fn test_extract_json_from_fenced_block() {
+        let value = extract_json("Here you go:\n```json\n{\"a\": 1}\n```").unwrap();
+        assert_eq!(value, json!({"a": 1}));
+        assert!(extract_json("not json").is_err());
+    }
+}
@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::bus::InboundMessage;
+use crate::channels::telegram::BOT_ID_KEY;
+use crate::providers::Usage;
+
+pub const MAIN_BRANCH: &str = "main";
+
+/// Conversation key shared by every per-chat store: `channel:chat_id`.
+pub fn session_key(channel: &str, chat_id: &str) -> String {
+    format!("{}:{}", channel, chat_id)
+}
+
+/// Session key for an inbound message. Chats on a non-default bot get their
+/// own conversations, since the same user id can talk to several bots.
+pub fn message_key(msg: &InboundMessage) -> String {
+    match msg.metadata.get(BOT_ID_KEY) {
+        Some(bot) => session_key(&format!("{}.{}", msg.channel, bot), &msg.chat_id),
+        None => session_key(&msg.channel, &msg.chat_id),
+    }
+}
+
+/// Turn a session key into something safe to use as a file name. `:` becomes
+/// `_` and every other byte outside `[A-Za-z0-9-]` is escaped as `%XX`, so
+/// distinct keys never share a file.
+pub fn file_stem(key: &str) -> String {
+    let mut stem = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b':' => stem.push('_'),
+            b if b.is_ascii_alphanumeric() || b == b'-' => stem.push(b as char),
+            b => stem.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    stem
+}
+
+/// The file name used before keys were escaped, where `telegram:a/b` and
+/// `telegram:a_b` collided. Still read when a chat has no file under its new name.
+fn legacy_file_stem(key: &str) -> String {
+    key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Everything persisted for one chat.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SessionFile {
+    pub history: Vec<serde_json::Value>,
+    /// Cumulative token usage of the chat
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+/// Session files were once a bare history array; still read those.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSession {
+    Current(SessionFile),
+    Legacy(Vec<serde_json::Value>),
+}
+
+/// Persists each chat's transcript as `workspace/sessions/<key>.json`.
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            dir: workspace.join("sessions"),
+        }
+    }
+
+    pub fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", file_stem(key)))
+    }
+
+    pub fn load(&self, key: &str) -> SessionFile {
+        Self::read(&self.path_for(key))
+            .or_else(|| Self::read(&self.dir.join(format!("{}.json", legacy_file_stem(key)))))
+            .unwrap_or_default()
+    }
+
+    fn read(path: &Path) -> Option<SessionFile> {
+        let content = std::fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&content).ok()? {
+            StoredSession::Current(session) => Some(session),
+            StoredSession::Legacy(history) => Some(SessionFile { history, usage: Usage::default() }),
+        }
+    }
+
+    pub fn save(&self, key: &str, session: &SessionFile) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create sessions directory: {}", e))?;
+        let content = serde_json::to_string_pretty(session)
+            .map_err(|e| format!("Failed to serialize session: {}", e))?;
+        std::fs::write(self.path_for(key), content)
+            .map_err(|e| format!("Failed to write session: {}", e))
+    }
+
+    /// Number of stored chats and their combined token usage.
+    pub fn total_usage(&self) -> (usize, Usage) {
+        let mut count = 0;
+        let mut total = Usage::default();
+
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return (count, total);
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            // Branch snapshots live in the same directory
+            if !name.ends_with(".json") || name.ends_with(".branches.json") {
+                continue;
+            }
+            if let Some(session) = Self::read(&path) {
+                count += 1;
+                total.add(&session.usage);
+            }
+        }
+        (count, total)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchSet {
+    pub active: String,
+    pub branches: HashMap<String, Vec<serde_json::Value>>,
+}
+
+impl Default for BranchSet {
+    fn default() -> Self {
+        Self {
+            active: MAIN_BRANCH.to_string(),
+            branches: HashMap::new(),
+        }
+    }
+}
+
+/// Stores forked conversation branches per chat under `workspace/sessions/`.
+pub struct BranchStore {
+    dir: PathBuf,
+}
+
+impl BranchStore {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            dir: workspace.join("sessions"),
+        }
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.branches.json", file_stem(key)))
+    }
+
+    pub fn load(&self, key: &str) -> BranchSet {
+        std::fs::read_to_string(self.path_for(key))
+            .or_else(|_| std::fs::read_to_string(self.dir.join(format!("{}.branches.json", legacy_file_stem(key)))))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, key: &str, set: &BranchSet) -> Result<(), String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create sessions directory: {}", e))?;
+        let content = serde_json::to_string_pretty(set)
+            .map_err(|e| format!("Failed to serialize branches: {}", e))?;
+        std::fs::write(self.path_for(key), content)
+            .map_err(|e| format!("Failed to write branches: {}", e))
+    }
+
+    /// Snapshot `current` under the active branch and start a new branch from it.
+    /// Returns the id of the new (now active) branch.
+    pub fn fork(&self, key: &str, current: &[serde_json::Value]) -> Result<String, String> {
+        let mut set = self.load(key);
+        set.branches.insert(set.active.clone(), current.to_vec());
+
+        let mut n = set.branches.len();
+        let mut id = format!("fork-{}", n);
+        while set.branches.contains_key(&id) {
+            n += 1;
+            id = format!("fork-{}", n);
+        }
+
+        set.branches.insert(id.clone(), current.to_vec());
+        set.active = id.clone();
+        self.save(key, &set)?;
+
+        Ok(id)
+    }
+
+    /// Save `current` under the active branch and return the history of `target`.
+    pub fn switch(
+        &self,
+        key: &str,
+        target: &str,
+        current: &[serde_json::Value],
+    ) -> Result<Vec<serde_json::Value>, String> {
+        let mut set = self.load(key);
+
+        if target != set.active && !set.branches.contains_key(target) {
+            return Err(format!("Unknown branch: {}", target));
+        }
+
+        set.branches.insert(set.active.clone(), current.to_vec());
+        let history = set.branches.get(target).cloned().unwrap_or_default();
+        set.active = target.to_string();
+        self.save(key, &set)?;
+
+        Ok(history)
+    }
+
+    /// Active branch name and all known branch names, sorted.
+    pub fn list(&self, key: &str) -> (String, Vec<String>) {
+        let set = self.load(key);
+        let mut names: Vec<String> = set.branches.keys().cloned().collect();
+        if !names.contains(&set.active) {
+            names.push(set.active.clone());
+        }
+        names.sort();
+        (set.active, names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_file_stem_sanitizes_key() {
+        assert_eq!(file_stem("telegram:-100123"), "telegram_-100123");
+        assert_eq!(file_stem("cli:direct"), "cli_direct");
+        assert_eq!(file_stem("telegram.work:a/b"), "telegram%2Ework_a%2Fb");
+        assert_eq!(file_stem("telegram:a_b"), "telegram_a%5Fb");
+    }
+
+    #[test]
+    fn test_keys_that_sanitized_alike_keep_separate_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::new(temp_dir.path());
+        let slash = SessionFile { history: vec![json!({"role": "user", "content": "from a/b"})], usage: Usage::default() };
+        let underscore = SessionFile { history: vec![json!({"role": "user", "content": "from a_b"})], usage: Usage::default() };
+
+        store.save("telegram:a/b", &slash).unwrap();
+        store.save("telegram:a_b", &underscore).unwrap();
+        assert_ne!(store.path_for("telegram:a/b"), store.path_for("telegram:a_b"));
+        assert_eq!(store.load("telegram:a/b"), slash);
+        assert_eq!(store.load("telegram:a_b"), underscore);
+
+        let branches = BranchStore::new(temp_dir.path());
+        branches.fork("telegram:a/b", &slash.history).unwrap();
+        assert_eq!(branches.list("telegram:a_b").1, vec![MAIN_BRANCH.to_string()]);
+    }
+
+    #[test]
+    fn test_session_saved_under_the_old_name_is_still_loaded() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::new(temp_dir.path());
+        std::fs::create_dir_all(temp_dir.path().join("sessions")).unwrap();
+        std::fs::write(
+            temp_dir.path().join("sessions/telegram_work_42.json"),
+            r#"[{"role": "user", "content": "Hi"}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(store.load("telegram.work:42").history.len(), 1);
+    }
+
+    #[test]
+    fn test_session_store_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = SessionStore::new(temp_dir.path());
+        let key = session_key("telegram", "42");
+
+        assert!(store.load(&key).history.is_empty());
+
+        let session = SessionFile {
+            history: vec![json!({"role": "user", "content": "Hello"})],
+            usage: Usage { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 },
+        };
+        store.save(&key, &session).unwrap();
+        assert_eq!(store.load(&key), session);
+        assert!(temp_dir.path().join("sessions/telegram_42.json").exists());
+
+        // Older files hold just the history array
+        std::fs::write(store.path_for("cli:direct"), r#"[{"role": "user", "content": "Hi"}]"#).unwrap();
+        assert_eq!(store.load("cli:direct").history.len(), 1);
+
+        let (count, total) = store.total_usage();
+        assert_eq!(count, 2);
+        assert_eq!(total.total_tokens, 15);
+    }
+
+    #[test]
+    fn test_fork_and_switch_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = BranchStore::new(temp_dir.path());
+        let key = session_key("cli", "direct");
+
+        let original = vec![json!({"role": "user", "content": "Hello"})];
+        let id = store.fork(&key, &original).unwrap();
+        assert_eq!(id, "fork-1");
+
+        let mut diverged = original.clone();
+        diverged.push(json!({"role": "user", "content": "Something else"}));
+
+        let restored = store.switch(&key, MAIN_BRANCH, &diverged).unwrap();
+        assert_eq!(restored, original);
+
+        let back = store.switch(&key, &id, &restored).unwrap();
+        assert_eq!(back, diverged);
+
+        assert!(store.switch(&key, "nope", &back).is_err());
+    }
+}
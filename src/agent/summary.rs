@@ -0,0 +1,57 @@
+use serde_json::{json, Value};
+
+use crate::providers::ChatMessage;
+
+const SUMMARY_PROMPT: &str = "Summarize the earlier part of a conversation between a user and an \
+assistant so it can stand in for the original messages. Keep names, decisions, open tasks and \
+anything the user asked to be remembered; drop pleasantries. Write a few short sentences in the \
+conversation's language. Reply with only the summary.";
+
+/// Whether a history entry is a compaction summary rather than a real message.
+pub fn is_summary(entry: &Value) -> bool {
+    entry.get("summary").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// A history entry standing in for compacted turns. It is replayed as a
+/// system message so the model reads it as context, not as something said.
+pub fn summary_entry(text: &str) -> Value {
+    json!({
+        "role": "system",
+        "content": format!("Summary of the earlier conversation: {}", text.trim()),
+        "summary": true,
+    })
+}
+
+/// Messages asking the model to fold `entries` (which may start with an
+/// earlier summary) into a single summary.
+pub fn summary_messages(entries: &[Value]) -> Vec<ChatMessage> {
+    let transcript: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let role = entry.get("role").and_then(|v| v.as_str()).unwrap_or("user");
+            let content = entry.get("content").and_then(|v| v.as_str()).unwrap_or("");
+            format!("{}: {}", role.to_uppercase(), content)
+        })
+        .collect();
+
+    vec![
+        ChatMessage::system(SUMMARY_PROMPT),
+        ChatMessage::user(transcript.join("\n\n")),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_entry_round_trip() {
+        let entry = summary_entry(" The user is planning a trip to Bali. ");
+        assert!(is_summary(&entry));
+        assert_eq!(entry["content"], "Summary of the earlier conversation: The user is planning a trip to Bali.");
+        assert!(!is_summary(&json!({"role": "user", "content": "Hi"})));
+
+        let messages = summary_messages(&[entry, json!({"role": "user", "content": "Book it"})]);
+        assert!(messages[1].content.ends_with("USER: Book it"));
+    }
+}
@@ -0,0 +1,94 @@
+use std::collections::HashSet;
+
+use crate::providers::ToolDefinition;
+
+/// Words too common in tool descriptions and chat to say anything about relevance.
+const STOPWORDS: &[&str] = &[
+    "about", "also", "could", "does", "each", "from", "have", "instead", "into", "like", "more",
+    "only", "should", "some", "than", "that", "their", "them", "then", "there", "they", "this",
+    "what", "when", "which", "will", "with", "would", "your",
+];
+
+/// Lowercase words of four letters or more, with a plural "s" dropped.
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(|word| word.to_lowercase())
+        .filter(|word| word.chars().count() >= 4 && !STOPWORDS.contains(&word.as_str()))
+        .map(|word| match word.strip_suffix('s') {
+            Some(stem) if stem.chars().count() >= 4 => stem.to_string(),
+            _ => word,
+        })
+        .collect()
+}
+
+/// How many of the message's keywords appear in the tool's name or description.
+fn relevance(message: &HashSet<String>, tool: &ToolDefinition) -> usize {
+    let name = tool.function.name.replace('_', " ");
+    let tool_words = keywords(&format!("{} {}", name, tool.function.description));
+    message.intersection(&tool_words).count()
+}
+
+/// The tools to advertise for `message`: every core tool, then the tools
+/// whose name or description shares words with the message, most relevant
+/// first, until `max` is reached. `max == 0` advertises everything.
+pub fn select_tools(mut tools: Vec<ToolDefinition>, message: &str, core: &[String], max: usize) -> Vec<ToolDefinition> {
+    if max == 0 || tools.len() <= max {
+        return tools;
+    }
+
+    tools.sort_by(|a, b| a.function.name.cmp(&b.function.name));
+    let (mut selected, rest): (Vec<_>, Vec<_>) = tools
+        .into_iter()
+        .partition(|tool| core.contains(&tool.function.name));
+
+    let message = keywords(message);
+    let mut scored: Vec<(usize, ToolDefinition)> = rest
+        .into_iter()
+        .map(|tool| (relevance(&message, &tool), tool))
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    // Stable, so equally relevant tools stay in name order
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+    let room = max.saturating_sub(selected.len());
+    selected.extend(scored.into_iter().take(room).map(|(_, tool)| tool));
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::FunctionDefinition;
+
+    fn tool(name: &str, description: &str) -> ToolDefinition {
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            function: FunctionDefinition {
+                name: name.to_string(),
+                description: description.to_string(),
+                parameters: serde_json::json!({}),
+            },
+        }
+    }
+
+    fn names(tools: &[ToolDefinition]) -> Vec<&str> {
+        tools.iter().map(|t| t.function.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_relevant_tools_fill_the_remaining_slots() {
+        let tools = vec![
+            tool("read_file", "Read the contents of a file"),
+            tool("web_fetch", "Fetch a web page and return its text"),
+            tool("send_email", "Send an email over SMTP"),
+            tool("reminder", "Schedule a reminder message"),
+        ];
+        let core = vec!["read_file".to_string()];
+
+        let selected = select_tools(tools.clone(), "Please fetch the pages for me and email them", &core, 2);
+        assert_eq!(names(&selected), vec!["read_file", "web_fetch"]);
+
+        assert_eq!(select_tools(tools.clone(), "Hi!", &core, 0).len(), 4);
+        assert_eq!(names(&select_tools(tools, "Hi!", &core, 3)), vec!["read_file"]);
+    }
+}
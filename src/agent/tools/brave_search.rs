@@ -1,11 +1,15 @@
 use async_trait::async_trait;
 use serde_json::{json, Value};
 use reqwest::Client;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
 use crate::agent::tools::Tool;
 
 pub struct BraveSearchTool {
     client: Client,
     api_key: String,
+    /// Shared with web_fetch (`tools.global_web_concurrency`)
+    slots: Option<Arc<Semaphore>>,
 }
 
 impl BraveSearchTool {
@@ -16,9 +20,16 @@ impl BraveSearchTool {
                 .build()
                 .expect("Failed to create HTTP client"),
             api_key,
+            slots: None,
         }
     }
 
+    /// Wait for one of these slots before each request.
+    pub fn with_slots(mut self, slots: Arc<Semaphore>) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
     fn validate_query(&self, query: &str) -> Result<String, String> {
         // Basic validation
         if query.is_empty() {
@@ -77,16 +88,21 @@ impl Tool for BraveSearchTool {
         let count = args["count"]
             .as_u64()
             .unwrap_or(5)
-            .min(10) as usize; // Max 10 results
+            .clamp(1, 10) as usize; // Max 10 results
 
         // Construct the API request
         let url = "https://api.search.brave.com/res/v1/web/search";
-        
+
+        let _slot = match &self.slots {
+            Some(slots) => Some(slots.acquire().await.map_err(|e| format!("Web slots closed: {}", e))?),
+            None => None,
+        };
+
         let response = self.client
             .get(url)
             .header("X-Subscription-Token", &self.api_key)
             .header("Accept", "application/json")
-            .query(&[("q", &validated_query)])
+            .query(&[("q", validated_query), ("count", count.to_string())])
             .send()
             .await
             .map_err(|e| format!("Failed to send request: {}", e))?;
@@ -142,6 +158,7 @@ impl Default for BraveSearchTool {
                 .build()
                 .expect("Failed to create HTTP client"),
             api_key: String::new(),
+            slots: None,
         }
     }
 }
@@ -0,0 +1,200 @@
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde_json::{json, Value};
+use crate::agent::tools::Tool;
+use crate::config::EmailConfig;
+
+/// Delivers a built message. Lets tests swap the SMTP connection for a stub.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn deliver(&self, message: Message) -> Result<(), String>;
+}
+
+#[async_trait]
+impl Mailer for AsyncSmtpTransport<Tokio1Executor> {
+    async fn deliver(&self, message: Message) -> Result<(), String> {
+        self.send(message).await.map(|_| ()).map_err(|e| format!("SMTP error: {}", e))
+    }
+}
+
+pub struct EmailTool {
+    from: Mailbox,
+    allowed_recipients: Vec<String>,
+    mailer: Box<dyn Mailer>,
+}
+
+impl EmailTool {
+    pub fn new(config: &EmailConfig) -> Result<Self, String> {
+        // Port 465 speaks TLS from the first byte; anything else upgrades with STARTTLS
+        let builder = if config.smtp_port == 465 {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host)
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)
+        }
+        .map_err(|e| format!("Invalid SMTP host: {}", e))?
+        .port(config.smtp_port);
+
+        let builder = if config.username.is_empty() {
+            builder
+        } else {
+            builder.credentials(Credentials::new(config.username.clone(), config.password.clone()))
+        };
+
+        Self::with_mailer(config, Box::new(builder.build()))
+    }
+
+    pub fn with_mailer(config: &EmailConfig, mailer: Box<dyn Mailer>) -> Result<Self, String> {
+        let from = config
+            .from
+            .parse::<Mailbox>()
+            .map_err(|e| format!("Invalid from address: {}", e))?;
+
+        Ok(Self {
+            from,
+            allowed_recipients: config.allowed_recipients.iter().map(|r| r.to_lowercase()).collect(),
+            mailer,
+        })
+    }
+
+    /// Entries are either full addresses or `@domain` wildcards.
+    fn is_allowed(&self, address: &str) -> bool {
+        let address = address.to_lowercase();
+        self.allowed_recipients.iter().any(|allowed| {
+            if allowed.starts_with('@') {
+                address.ends_with(allowed.as_str())
+            } else {
+                address == *allowed
+            }
+        })
+    }
+
+    fn build_message(&self, to: &Mailbox, subject: &str, body: &str) -> Result<Message, String> {
+        Message::builder()
+            .from(self.from.clone())
+            .to(to.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body.to_string())
+            .map_err(|e| format!("Failed to build email: {}", e))
+    }
+}
+
+#[async_trait]
+impl Tool for EmailTool {
+    fn name(&self) -> &str {
+        "send_email"
+    }
+
+    fn description(&self) -> &str {
+        "Send a plain-text email to an allowed recipient"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "to": {
+                    "type": "string",
+                    "description": "Recipient email address"
+                },
+                "subject": {
+                    "type": "string",
+                    "description": "Email subject"
+                },
+                "body": {
+                    "type": "string",
+                    "description": "Plain-text email body"
+                }
+            },
+            "required": ["to", "subject", "body"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        let to = args["to"].as_str().ok_or("Missing to parameter")?;
+        let subject = args["subject"].as_str().ok_or("Missing subject parameter")?;
+        let body = args["body"].as_str().ok_or("Missing body parameter")?;
+
+        let mailbox = to
+            .parse::<Mailbox>()
+            .map_err(|e| format!("Invalid recipient address: {}", e))?;
+
+        if !self.is_allowed(mailbox.email.as_ref()) {
+            return Err(format!("Recipient {} is not in the allowed recipients list", mailbox.email));
+        }
+
+        let message = self.build_message(&mailbox, subject, body)?;
+        self.mailer.deliver(message).await?;
+
+        Ok(format!("Email sent to {}", mailbox.email))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lettre::transport::stub::AsyncStubTransport;
+
+    #[async_trait]
+    impl Mailer for AsyncStubTransport {
+        async fn deliver(&self, message: Message) -> Result<(), String> {
+            self.send(message).await.map_err(|e| e.to_string())
+        }
+    }
+
+    fn config() -> EmailConfig {
+        EmailConfig {
+            from: "Santoso <bot@example.com>".to_string(),
+            allowed_recipients: vec!["owner@example.com".to_string(), "@team.example.org".to_string()],
+            ..EmailConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recipient_outside_allow_list_is_refused() {
+        let stub = AsyncStubTransport::new_ok();
+        let tool = EmailTool::with_mailer(&config(), Box::new(stub.clone())).unwrap();
+
+        let result = tool
+            .execute(json!({"to": "stranger@spam.example", "subject": "Hi", "body": "Buy now"}))
+            .await;
+
+        assert!(result.unwrap_err().contains("not in the allowed recipients list"));
+        assert!(stub.messages().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_message_is_built_and_sent() {
+        let stub = AsyncStubTransport::new_ok();
+        let tool = EmailTool::with_mailer(&config(), Box::new(stub.clone())).unwrap();
+
+        let result = tool
+            .execute(json!({"to": "Dev <Dev@Team.example.org>", "subject": "Daily summary", "body": "All good."}))
+            .await
+            .unwrap();
+        assert_eq!(result, "Email sent to Dev@Team.example.org");
+
+        let messages = stub.messages().await;
+        assert_eq!(messages.len(), 1);
+        let (envelope, raw) = &messages[0];
+        assert_eq!(envelope.from().unwrap().to_string(), "bot@example.com");
+        assert_eq!(envelope.to()[0].to_string(), "Dev@Team.example.org");
+        assert!(raw.contains("Subject: Daily summary"));
+        assert!(raw.contains("All good."));
+    }
+
+    #[tokio::test]
+    async fn test_transport_failure_is_reported() {
+        let tool = EmailTool::with_mailer(&config(), Box::new(AsyncStubTransport::new_error())).unwrap();
+        let result = tool
+            .execute(json!({"to": "owner@example.com", "subject": "Hi", "body": "x"}))
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 use serde_json::{json, Value};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use crate::agent::tools::Tool;
 
 pub struct ReadFileTool {
@@ -13,21 +13,26 @@ impl ReadFileTool {
     }
 
     fn validate_path(&self, path: &str) -> Result<PathBuf, String> {
-        let path = PathBuf::from(path);
+        validate_read_path(self.allowed_dir.as_deref(), path)
+    }
+}
+
+/// Resolve an existing path the agent wants to read, rejecting it when it lies outside `allowed_dir`.
+pub(crate) fn validate_read_path(allowed_dir: Option<&Path>, path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    
+    if let Some(dir) = allowed_dir {
+        let canonical = path.canonicalize()
+            .map_err(|e| format!("Invalid path: {}", e))?;
+        let dir_canonical = dir.canonicalize()
+            .map_err(|e| format!("Invalid workspace: {}", e))?;
         
-        if let Some(ref dir) = self.allowed_dir {
-            let canonical = path.canonicalize()
-                .map_err(|e| format!("Invalid path: {}", e))?;
-            let dir_canonical = dir.canonicalize()
-                .map_err(|e| format!("Invalid workspace: {}", e))?;
-            
-            if !canonical.starts_with(&dir_canonical) {
-                return Err("Path outside workspace not allowed".to_string());
-            }
+        if !canonical.starts_with(&dir_canonical) {
+            return Err("Path outside workspace not allowed".to_string());
         }
-        
-        Ok(path)
     }
+    
+    Ok(path)
 }
 
 #[async_trait]
@@ -58,7 +63,10 @@ impl Tool for ReadFileTool {
 
         let validated = self.validate_path(path)?;
 
-        std::fs::read_to_string(&validated)
+        // tokio::fs runs the read on the blocking pool, so the registry's
+        // timeout still fires when it hangs (a FIFO, a stalled network mount)
+        tokio::fs::read_to_string(&validated)
+            .await
             .map_err(|e| format!("Failed to read file: {}", e))
     }
     
@@ -69,49 +77,89 @@ impl Tool for ReadFileTool {
 
 pub struct WriteFileTool {
     allowed_dir: Option<PathBuf>,
+    writable_extensions: Vec<String>,
 }
 
 impl WriteFileTool {
     pub fn new(allowed_dir: Option<PathBuf>) -> Self {
-        Self { allowed_dir }
+        Self { allowed_dir, writable_extensions: Vec::new() }
+    }
+
+    /// Only allow writing files with these extensions (empty = any file).
+    pub fn with_writable_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.writable_extensions = extensions;
+        self
     }
 
     fn validate_path(&self, path: &str) -> Result<PathBuf, String> {
-        let path = PathBuf::from(path);
-
-        if let Some(ref dir) = self.allowed_dir {
-            // Resolve the absolute path relative to the allowed directory
-            let abs_path = if path.is_absolute() {
-                path
-            } else {
-                dir.join(&path)
-            };
-
-            // Canonicalize the allowed directory
-            let dir_canonical = dir.canonicalize()
-                .map_err(|e| format!("Invalid workspace: {}", e))?;
-
-            // Canonicalize the target path (this will fail if the file doesn't exist yet)
-            // So we'll check the parent directory instead
-            let parent = abs_path.parent().unwrap_or(&abs_path);
-            
-            let parent_canonical = parent.canonicalize()
-                .map_err(|_| "Path validation failed: parent directory does not exist".to_string())?;
-
-            if !parent_canonical.starts_with(&dir_canonical) {
-                return Err("Path outside workspace not allowed".to_string());
-            }
+        check_writable_extension(Path::new(path), &self.writable_extensions)?;
+        validate_write_path(self.allowed_dir.as_deref(), path)
+    }
+}
 
-            // Additional check: ensure the path doesn't contain dangerous sequences like '/../'
-            let path_str = abs_path.to_string_lossy();
-            if path_str.contains("../") || path_str.starts_with("../") {
-                return Err("Path contains invalid sequences".to_string());
-            }
+/// Refuse files outside `tools.writable_extensions`, and dotfiles, when the
+/// list is set. An empty list allows everything.
+pub(crate) fn check_writable_extension(path: &Path, allowed: &[String]) -> Result<(), String> {
+    if allowed.is_empty() {
+        return Ok(());
+    }
+
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    if name.starts_with('.') {
+        return Err(format!("Writing dotfiles is not allowed ({}); tools.writable_extensions is set", name));
+    }
+
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let permitted = allowed
+        .iter()
+        .any(|allowed| allowed.trim_start_matches('.').eq_ignore_ascii_case(&extension));
+    if permitted {
+        Ok(())
+    } else {
+        Err(format!(
+            "Writing '{}' files is not allowed; tools.writable_extensions permits: {}",
+            if extension.is_empty() { "extensionless" } else { &extension },
+            allowed.join(", ")
+        ))
+    }
+}
 
-            Ok(abs_path)
+/// Resolve a path the agent wants to write to, keeping it inside `allowed_dir` when set.
+pub(crate) fn validate_write_path(allowed_dir: Option<&Path>, path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+
+    if let Some(dir) = allowed_dir {
+        // Resolve the absolute path relative to the allowed directory
+        let abs_path = if path.is_absolute() {
+            path
         } else {
-            Ok(path)
+            dir.join(&path)
+        };
+
+        // Canonicalize the allowed directory
+        let dir_canonical = dir.canonicalize()
+            .map_err(|e| format!("Invalid workspace: {}", e))?;
+
+        // Canonicalize the target path (this will fail if the file doesn't exist yet)
+        // So we'll check the parent directory instead
+        let parent = abs_path.parent().unwrap_or(&abs_path);
+        
+        let parent_canonical = parent.canonicalize()
+            .map_err(|_| "Path validation failed: parent directory does not exist".to_string())?;
+
+        if !parent_canonical.starts_with(&dir_canonical) {
+            return Err("Path outside workspace not allowed".to_string());
+        }
+
+        // Additional check: ensure the path doesn't contain dangerous sequences like '/../'
+        let path_str = abs_path.to_string_lossy();
+        if path_str.contains("../") || path_str.starts_with("../") {
+            return Err("Path contains invalid sequences".to_string());
         }
+
+        Ok(abs_path)
+    } else {
+        Ok(path)
     }
 }
 
@@ -151,11 +199,13 @@ impl Tool for WriteFileTool {
         let validated = self.validate_path(path)?;
 
         if let Some(parent) = validated.parent() {
-            std::fs::create_dir_all(parent)
+            tokio::fs::create_dir_all(parent)
+                .await
                 .map_err(|e| format!("Failed to create directory: {}", e))?;
         }
 
-        std::fs::write(&validated, content)
+        tokio::fs::write(&validated, content)
+            .await
             .map_err(|e| format!("Failed to write file: {}", e))?;
 
         Ok(format!("File written successfully: {}", path))
@@ -167,13 +217,24 @@ impl Tool for WriteFileTool {
 }
 
 pub struct EditFileTool {
-    #[allow(dead_code)]
     allowed_dir: Option<PathBuf>,
+    writable_extensions: Vec<String>,
 }
 
 impl EditFileTool {
     pub fn new(allowed_dir: Option<PathBuf>) -> Self {
-        Self { allowed_dir }
+        Self { allowed_dir, writable_extensions: Vec::new() }
+    }
+
+    /// Only allow editing files with these extensions (empty = any file).
+    pub fn with_writable_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.writable_extensions = extensions;
+        self
+    }
+
+    fn validate_path(&self, path: &str) -> Result<PathBuf, String> {
+        check_writable_extension(Path::new(path), &self.writable_extensions)?;
+        validate_write_path(self.allowed_dir.as_deref(), path)
     }
 }
 
@@ -182,7 +243,7 @@ impl Tool for EditFileTool {
     fn name(&self) -> &str { "edit_file" }
 
     fn description(&self) -> &str {
-        "Edit a file by replacing specific text"
+        "Edit a file by replacing specific text. old_string must match exactly once unless replace_all is set"
     }
 
     fn parameters(&self) -> Value {
@@ -200,6 +261,10 @@ impl Tool for EditFileTool {
                 "new_string": {
                     "type": "string",
                     "description": "Replacement text"
+                },
+                "replace_all": {
+                    "type": "boolean",
+                    "description": "Replace every occurrence of old_string (default false)"
                 }
             },
             "required": ["path", "old_string", "new_string"]
@@ -210,17 +275,35 @@ impl Tool for EditFileTool {
         let path = args["path"].as_str().ok_or("Missing path")?;
         let old_string = args["old_string"].as_str().ok_or("Missing old_string")?;
         let new_string = args["new_string"].as_str().ok_or("Missing new_string")?;
+        let replace_all = args["replace_all"].as_bool().unwrap_or(false);
+        // An empty string matches between every character
+        if old_string.is_empty() {
+            return Err("old_string must not be empty; use write_file to create or overwrite a file".to_string());
+        }
+
+        let path = self.validate_path(path)?;
 
-        let content = std::fs::read_to_string(path)
+        let content = tokio::fs::read_to_string(&path)
+            .await
             .map_err(|e| format!("Failed to read file: {}", e))?;
 
-        if !content.contains(old_string) {
-            return Err("old_string not found in file".to_string());
+        match content.matches(old_string).count() {
+            0 => return Err("old_string not found in file".to_string()),
+            1 => {}
+            // Replacing them all when one was meant would clobber the rest
+            count if !replace_all => {
+                return Err(format!(
+                    "old_string matches {} times; provide more context or set replace_all",
+                    count
+                ))
+            }
+            _ => {}
         }
 
         let new_content = content.replace(old_string, new_string);
 
-        std::fs::write(path, &new_content)
+        tokio::fs::write(&path, &new_content)
+            .await
             .map_err(|e| format!("Failed to write file: {}", e))?;
 
         Ok("File edited successfully".to_string())
@@ -325,6 +408,63 @@ mod tests {
         assert_eq!(content, "New file content");
     }
 
+    #[tokio::test]
+    async fn test_edit_file_outside_allowed_dir_fails() {
+        let allowed = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let target = outside.path().join("hosts");
+        fs::write(&target, "127.0.0.1 localhost").unwrap();
+
+        let tool = EditFileTool::new(Some(allowed.path().to_path_buf()));
+        let result = tool
+            .execute(json!({
+                "path": target.to_string_lossy(),
+                "old_string": "localhost",
+                "new_string": "evil"
+            }))
+            .await;
+
+        assert_eq!(result.unwrap_err(), "Path outside workspace not allowed");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "127.0.0.1 localhost");
+
+        let inside = allowed.path().join("notes.txt");
+        fs::write(&inside, "draft").unwrap();
+        tool.execute(json!({"path": inside.to_string_lossy(), "old_string": "draft", "new_string": "final"}))
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&inside).unwrap(), "final");
+    }
+
+    #[tokio::test]
+    async fn test_writable_extensions_limit_writes_and_edits() {
+        let temp_dir = TempDir::new().unwrap();
+        let extensions = vec!["md".to_string(), ".json".to_string()];
+        let write = WriteFileTool::new(None).with_writable_extensions(extensions.clone());
+
+        let script = temp_dir.path().join("deploy.sh");
+        let err = write
+            .execute(json!({"path": script.to_string_lossy(), "content": "rm -rf /"}))
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Writing 'sh' files is not allowed; tools.writable_extensions permits: md, .json");
+        assert!(!script.exists());
+
+        let dotfile = temp_dir.path().join(".bashrc");
+        assert!(write.execute(json!({"path": dotfile.to_string_lossy(), "content": "x"})).await.is_err());
+
+        let notes = temp_dir.path().join("NOTES.MD");
+        write.execute(json!({"path": notes.to_string_lossy(), "content": "draft"})).await.unwrap();
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "draft");
+
+        fs::write(&script, "echo hi").unwrap();
+        let edit = EditFileTool::new(None).with_writable_extensions(extensions);
+        let result = edit
+            .execute(json!({"path": script.to_string_lossy(), "old_string": "hi", "new_string": "bye"}))
+            .await;
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&script).unwrap(), "echo hi");
+    }
+
     #[tokio::test]
     async fn test_edit_file_tool() {
         let temp_dir = TempDir::new().unwrap();
@@ -345,6 +485,47 @@ mod tests {
         assert_eq!(content, "Modified content");
     }
 
+    #[tokio::test]
+    async fn test_edit_file_refuses_empty_old_string() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("notes.txt");
+        fs::write(&test_file, "abc").unwrap();
+
+        let tool = EditFileTool::new(None);
+        for replace_all in [false, true] {
+            let result = tool
+                .execute(json!({
+                    "path": test_file.to_string_lossy(),
+                    "old_string": "",
+                    "new_string": "x",
+                    "replace_all": replace_all
+                }))
+                .await;
+            assert!(result.unwrap_err().contains("old_string must not be empty"));
+        }
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "abc");
+    }
+
+    #[tokio::test]
+    async fn test_edit_file_refuses_ambiguous_match_without_replace_all() {
+        let temp_dir = TempDir::new().unwrap();
+        let test_file = temp_dir.path().join("todo.md");
+        fs::write(&test_file, "- [ ] milk\n- [ ] eggs\n- [ ] bread\n").unwrap();
+        let tool = EditFileTool::new(None);
+
+        let err = tool
+            .execute(json!({"path": test_file.to_string_lossy(), "old_string": "- [ ]", "new_string": "- [x]"}))
+            .await
+            .unwrap_err();
+        assert_eq!(err, "old_string matches 3 times; provide more context or set replace_all");
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "- [ ] milk\n- [ ] eggs\n- [ ] bread\n");
+
+        tool.execute(json!({"path": test_file.to_string_lossy(), "old_string": "- [ ]", "new_string": "- [x]", "replace_all": true}))
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "- [x] milk\n- [x] eggs\n- [x] bread\n");
+    }
+
     #[tokio::test]
     async fn test_list_dir_tool() {
         let temp_dir = TempDir::new().unwrap();
@@ -364,7 +545,7 @@ mod tests {
         let temp_dir = TempDir::new().unwrap();
         let allowed_dir = temp_dir.path().to_path_buf();
         
-        let tool = ReadFileTool::new(Some(allowed_dir.clone()));
+        let _tool = ReadFileTool::new(Some(allowed_dir.clone()));
         
         // This would normally test the validate_path method, but it's private
         // We'll test the functionality through the execute method instead
@@ -0,0 +1,278 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+use crate::agent::tools::Tool;
+
+const MAX_OUTPUT_CHARS: usize = 50_000;
+const DEFAULT_LOG_COUNT: u64 = 20;
+const MAX_LOG_COUNT: u64 = 200;
+
+/// Runs a fixed set of git subcommands directly (no shell) inside the workspace.
+pub struct GitTool {
+    workspace: PathBuf,
+    timeout_secs: u64,
+    clone_allowlist: Vec<String>,
+}
+
+/// A path argument must stay inside the workspace: relative, no `..`, and not
+/// something git could mistake for an option.
+fn validate_relative(path: &str) -> Result<&str, String> {
+    if path.starts_with('-') {
+        return Err(format!("Invalid path: {}", path));
+    }
+    let parsed = Path::new(path);
+    if parsed.is_absolute() || parsed.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(format!("Path must be relative to the workspace: {}", path));
+    }
+    Ok(path)
+}
+
+fn string_list(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(items) => items.iter().filter_map(|v| v.as_str().map(String::from)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl GitTool {
+    pub fn new(workspace: PathBuf, timeout_secs: u64, clone_allowlist: Vec<String>) -> Self {
+        Self {
+            workspace,
+            timeout_secs,
+            clone_allowlist,
+        }
+    }
+
+    fn clone_allowed(&self, url: &str) -> bool {
+        self.clone_allowlist.iter().any(|prefix| !prefix.is_empty() && url.starts_with(prefix.as_str()))
+    }
+
+    async fn run_git(&self, dir: &Path, args: &[String]) -> Result<String, String> {
+        let mut cmd = Command::new("git");
+        // Never run repository hooks or wait for credentials
+        cmd.arg("-c").arg("core.hooksPath=/dev/null")
+            .args(args)
+            .current_dir(dir)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let output = tokio::time::timeout(std::time::Duration::from_secs(self.timeout_secs), cmd.output())
+            .await
+            .map_err(|_| "git command timed out")?
+            .map_err(|e| format!("Failed to run git: {}", e))?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed (exit {}): {}{}",
+                args.first().map(String::as_str).unwrap_or(""),
+                output.status.code().unwrap_or(-1),
+                stdout,
+                stderr
+            ));
+        }
+
+        let mut result = if stdout.trim().is_empty() { stderr.to_string() } else { stdout.to_string() };
+        if result.trim().is_empty() {
+            result = "Done (no output)".to_string();
+        }
+        if result.chars().count() > MAX_OUTPUT_CHARS {
+            result = format!("{}...[truncated]", result.chars().take(MAX_OUTPUT_CHARS).collect::<String>());
+        }
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Tool for GitTool {
+    fn name(&self) -> &str { "git" }
+
+    fn description(&self) -> &str {
+        "Run git in a repository inside the workspace: status, diff, log, add, commit, branch, or clone (allow-listed URLs only)"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "subcommand": {
+                    "type": "string",
+                    "enum": ["status", "diff", "log", "add", "commit", "branch", "clone"],
+                    "description": "The git operation to run"
+                },
+                "repo": {
+                    "type": "string",
+                    "description": "Repository directory relative to the workspace (defaults to the workspace itself)"
+                },
+                "paths": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Files for add (required) or diff (optional), relative to the repository"
+                },
+                "staged": {
+                    "type": "boolean",
+                    "description": "diff: show staged changes instead of unstaged ones"
+                },
+                "count": {
+                    "type": "integer",
+                    "description": "log: number of commits to show (default 20)"
+                },
+                "message": {
+                    "type": "string",
+                    "description": "commit: the commit message"
+                },
+                "name": {
+                    "type": "string",
+                    "description": "branch: create a branch with this name (omit to list branches)"
+                },
+                "url": {
+                    "type": "string",
+                    "description": "clone: repository URL"
+                },
+                "directory": {
+                    "type": "string",
+                    "description": "clone: target directory relative to the workspace"
+                }
+            },
+            "required": ["subcommand"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        let subcommand = args["subcommand"].as_str().ok_or("Missing subcommand parameter")?;
+
+        let repo = match args["repo"].as_str() {
+            Some(repo) => self.workspace.join(validate_relative(repo)?),
+            None => self.workspace.clone(),
+        };
+
+        let paths = string_list(&args["paths"])
+            .iter()
+            .map(|p| validate_relative(p).map(String::from))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let git_args: Vec<String> = match subcommand {
+            "status" => vec!["status".into(), "--short".into(), "--branch".into()],
+            "diff" => {
+                let mut git_args = vec!["diff".to_string()];
+                if args["staged"].as_bool().unwrap_or(false) {
+                    git_args.push("--staged".into());
+                }
+                git_args.push("--".into());
+                git_args.extend(paths);
+                git_args
+            }
+            "log" => {
+                let count = args["count"].as_u64().unwrap_or(DEFAULT_LOG_COUNT).clamp(1, MAX_LOG_COUNT);
+                vec!["log".into(), "--oneline".into(), "--decorate".into(), format!("-n{}", count)]
+            }
+            "add" => {
+                if paths.is_empty() {
+                    return Err("add needs at least one path".to_string());
+                }
+                let mut git_args = vec!["add".to_string(), "--".to_string()];
+                git_args.extend(paths);
+                git_args
+            }
+            "commit" => {
+                let message = args["message"].as_str().filter(|m| !m.trim().is_empty()).ok_or("Missing message parameter")?;
+                vec!["commit".into(), "-m".into(), message.to_string()]
+            }
+            "branch" => match args["name"].as_str() {
+                Some(name) => vec!["branch".into(), validate_relative(name)?.to_string()],
+                None => vec!["branch".into(), "--list".into()],
+            },
+            "clone" => {
+                let url = args["url"].as_str().ok_or("Missing url parameter")?;
+                if !self.clone_allowed(url) {
+                    return Err(format!("Cloning {} is not allowed; the URL must match tools.git_clone_allowlist", url));
+                }
+                let directory = args["directory"].as_str().ok_or("Missing directory parameter")?;
+                let target = validate_relative(directory)?;
+                let clone_args = vec!["clone".into(), "--".into(), url.to_string(), target.to_string()];
+                return self.run_git(&self.workspace, &clone_args).await;
+            }
+            other => return Err(format!("Unsupported git subcommand: {}", other)),
+        };
+
+        if !repo.is_dir() {
+            return Err(format!("Repository directory not found: {}", repo.display()));
+        }
+
+        self.run_git(&repo, &git_args).await
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn init_repo() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        for args in [
+            vec!["init", "-q"],
+            vec!["config", "user.name", "Test"],
+            vec!["config", "user.email", "test@example.com"],
+        ] {
+            let status = std::process::Command::new("git")
+                .args(&args)
+                .current_dir(temp_dir.path())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        std::fs::write(temp_dir.path().join("notes.md"), "hello\n").unwrap();
+        temp_dir
+    }
+
+    fn tool(temp_dir: &TempDir) -> GitTool {
+        GitTool::new(temp_dir.path().to_path_buf(), 30, vec!["https://github.com/santosobot/".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_status_add_and_commit() {
+        let temp_dir = init_repo();
+        let git = tool(&temp_dir);
+
+        let status = git.execute(json!({"subcommand": "status"})).await.unwrap();
+        assert!(status.contains("?? notes.md"));
+
+        git.execute(json!({"subcommand": "add", "paths": ["notes.md"]})).await.unwrap();
+        let status = git.execute(json!({"subcommand": "status"})).await.unwrap();
+        assert!(status.contains("A  notes.md"));
+
+        let result = git.execute(json!({"subcommand": "commit", "message": "Add notes"})).await.unwrap();
+        assert!(result.contains("Add notes"));
+
+        let log = git.execute(json!({"subcommand": "log"})).await.unwrap();
+        assert!(log.contains("Add notes"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_paths_outside_workspace_and_unlisted_clones() {
+        let temp_dir = init_repo();
+        let git = tool(&temp_dir);
+
+        assert!(git.execute(json!({"subcommand": "add", "paths": ["../secret"]})).await.is_err());
+        assert!(git.execute(json!({"subcommand": "add", "paths": ["--all"]})).await.is_err());
+        assert!(git.execute(json!({"subcommand": "status", "repo": "/etc"})).await.is_err());
+
+        let err = git
+            .execute(json!({"subcommand": "clone", "url": "https://evil.example.com/repo.git", "directory": "repo"}))
+            .await
+            .unwrap_err();
+        assert!(err.contains("not allowed"));
+    }
+}
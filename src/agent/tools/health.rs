@@ -0,0 +1,210 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::agent::tools::Tool;
+use crate::providers::{ChatMessage, LLMProvider};
+
+/// How long the provider ping and each channel check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+const TELEGRAM_API_BASE: &str = "https://api.telegram.org";
+
+/// Self-diagnosis the agent can run when asked whether it's healthy: pings
+/// the provider, writes to the workspace and memory directory, and checks
+/// that enabled Telegram bots can reach the Bot API.
+pub struct HealthTool {
+    /// The agent's own provider, so the ping checks the client it really uses
+    provider: Arc<dyn LLMProvider>,
+    model: String,
+    workspace: PathBuf,
+    /// (bot id, token) for each enabled Telegram bot
+    telegram_bots: Vec<(String, String)>,
+    telegram_api_base: String,
+    client: Client,
+}
+
+/// One line of the report.
+struct Check {
+    name: String,
+    result: Result<String, String>,
+}
+
+impl HealthTool {
+    pub fn new(provider: Arc<dyn LLMProvider>, model: String, workspace: PathBuf) -> Self {
+        Self {
+            provider,
+            model,
+            workspace,
+            telegram_bots: Vec::new(),
+            telegram_api_base: TELEGRAM_API_BASE.to_string(),
+            client: Client::builder()
+                .timeout(CHECK_TIMEOUT)
+                .build()
+                .expect("Failed to create HTTP client"),
+        }
+    }
+
+    /// Also check these Telegram bots, as (bot id, token).
+    pub fn with_telegram_bots(mut self, bots: Vec<(String, String)>) -> Self {
+        self.telegram_bots = bots;
+        self
+    }
+
+    /// The smallest request the provider will answer.
+    async fn check_provider(&self) -> Result<String, String> {
+        let started = std::time::Instant::now();
+        let ping = self.provider.chat(
+            vec![ChatMessage::user("ping")],
+            None,
+            None,
+            Some(self.model.clone()),
+            Some(0.0),
+            Some(1),
+        );
+        match tokio::time::timeout(CHECK_TIMEOUT, ping).await {
+            Ok(Ok(_)) => Ok(format!("{} answered in {} ms", self.model, started.elapsed().as_millis())),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err(format!("no answer within {}s", CHECK_TIMEOUT.as_secs())),
+        }
+    }
+
+    /// Memory lives in `memory/`; MEMORY.md must be readable and appendable once it exists.
+    fn check_memory(&self) -> Result<String, String> {
+        let memory_dir = self.workspace.join("memory");
+        std::fs::create_dir_all(&memory_dir).map_err(|e| format!("can't create {}: {}", memory_dir.display(), e))?;
+        check_writable(&memory_dir)?;
+        let memory_file = memory_dir.join("MEMORY.md");
+        if !memory_file.exists() {
+            return Ok("memory/ is writable, MEMORY.md not created yet".to_string());
+        }
+        std::fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(&memory_file)
+            .map_err(|e| format!("can't open {}: {}", memory_file.display(), e))?;
+        Ok("MEMORY.md is readable and writable".to_string())
+    }
+
+    async fn check_telegram(&self, token: &str) -> Result<String, String> {
+        let url = format!("{}/bot{}/getMe", self.telegram_api_base, token);
+        let response = self.client.get(&url).send().await.map_err(|e| format!("Bot API unreachable: {}", e.without_url()))?;
+        if !response.status().is_success() {
+            return Err(format!("Bot API answered {}", response.status()));
+        }
+        let body: Value = response.json().await.map_err(|e| format!("unexpected getMe response: {}", e))?;
+        match body["result"]["username"].as_str() {
+            Some(username) => Ok(format!("connected as @{}", username)),
+            None => Ok("connected".to_string()),
+        }
+    }
+
+    async fn run_checks(&self) -> Vec<Check> {
+        let mut checks = vec![
+            Check { name: "provider".to_string(), result: self.check_provider().await },
+            Check { name: "workspace".to_string(), result: check_writable(&self.workspace).map(|()| format!("{} is writable", self.workspace.display())) },
+            Check { name: "memory".to_string(), result: self.check_memory() },
+        ];
+        for (bot_id, token) in &self.telegram_bots {
+            let name = if bot_id.is_empty() { "telegram".to_string() } else { format!("telegram:{}", bot_id) };
+            checks.push(Check { name, result: self.check_telegram(token).await });
+        }
+        checks
+    }
+}
+
+/// Create and remove a probe file in `dir`.
+fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(".santosobot-health-check");
+    std::fs::write(&probe, b"ok").map_err(|e| format!("can't write to {}: {}", dir.display(), e))?;
+    std::fs::remove_file(&probe).map_err(|e| format!("can't remove {}: {}", probe.display(), e))
+}
+
+#[async_trait]
+impl Tool for HealthTool {
+    fn name(&self) -> &str { "health" }
+
+    fn description(&self) -> &str {
+        "Check your own health: whether the LLM provider answers, the workspace and memory files are writable, and enabled chat channels are connected. Use when asked if you're working properly"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {}
+        })
+    }
+
+    async fn execute(&self, _args: Value) -> Result<String, String> {
+        let checks = self.run_checks().await;
+        let healthy = checks.iter().all(|check| check.result.is_ok());
+        let report = json!({
+            "healthy": healthy,
+            "checks": checks
+                .iter()
+                .map(|check| match &check.result {
+                    Ok(detail) => json!({"name": check.name, "ok": true, "detail": detail}),
+                    Err(error) => json!({"name": check.name, "ok": false, "detail": error}),
+                })
+                .collect::<Vec<_>>(),
+        });
+        serde_json::to_string_pretty(&report).map_err(|e| e.to_string())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{ChatStream, LLMResponse, ToolChoice, ToolDefinition};
+    use tempfile::TempDir;
+
+    struct UnreachableProvider;
+
+    #[async_trait]
+    impl LLMProvider for UnreachableProvider {
+        async fn chat(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _tools: Option<Vec<ToolDefinition>>,
+            _tool_choice: Option<ToolChoice>,
+            _model: Option<String>,
+            _temperature: Option<f32>,
+            _max_tokens: Option<u32>,
+        ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+            Err("connection refused".into())
+        }
+
+        async fn chat_stream(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _tools: Option<Vec<ToolDefinition>>,
+            _tool_choice: Option<ToolChoice>,
+            _model: Option<String>,
+            _temperature: Option<f32>,
+            _max_tokens: Option<u32>,
+        ) -> Result<ChatStream, Box<dyn std::error::Error + Send + Sync>> {
+            Err("connection refused".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failing_provider_is_reported_unhealthy() {
+        let workspace = TempDir::new().unwrap();
+        let tool = HealthTool::new(Arc::new(UnreachableProvider), "gpt-4o-mini".to_string(), workspace.path().to_path_buf());
+
+        let report: Value = serde_json::from_str(&tool.execute(json!({})).await.unwrap()).unwrap();
+        assert_eq!(report["healthy"], false);
+        let check = |name: &str| report["checks"].as_array().unwrap().iter().find(|c| c["name"] == name).unwrap().clone();
+        assert_eq!(check("provider")["ok"], false);
+        assert_eq!(check("provider")["detail"], "connection refused");
+        assert_eq!(check("workspace")["ok"], true);
+        assert_eq!(check("memory")["ok"], true);
+        // The probe files are cleaned up
+        assert!(!workspace.path().join(".santosobot-health-check").exists());
+    }
+}
@@ -0,0 +1,219 @@
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use crate::agent::memory::rank_by_similarity;
+use crate::agent::tools::Tool;
+use crate::agent::MemoryStore;
+use crate::providers::OpenAIProvider;
+
+const DEFAULT_RECALL_LIMIT: usize = 5;
+
+/// Appends a timestamped bullet to `memory/MEMORY.md`.
+pub struct RememberTool {
+    store: MemoryStore,
+    timezone: Tz,
+}
+
+impl RememberTool {
+    pub fn new(workspace: &Path, timezone: Tz) -> Self {
+        Self {
+            store: MemoryStore::new(workspace),
+            timezone,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for RememberTool {
+    fn name(&self) -> &str { "remember" }
+
+    fn description(&self) -> &str {
+        "Save a fact to long-term memory (MEMORY.md). Use this instead of rewriting the memory file"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "fact": {
+                    "type": "string",
+                    "description": "The fact to remember, as a short self-contained statement"
+                }
+            },
+            "required": ["fact"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        let fact = args["fact"].as_str().filter(|f| !f.trim().is_empty()).ok_or("Missing fact parameter")?;
+        let now = chrono::Utc::now().with_timezone(&self.timezone);
+
+        self.store
+            .append_entry(&format!("[{}] {}", now.format("%Y-%m-%d %H:%M"), fact.trim()))
+            .map_err(|e| format!("Failed to write memory: {}", e))?;
+
+        Ok("Remembered".to_string())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+/// Looks up long-term memory entries by keyword, or by meaning when an
+/// embedding model is configured.
+pub struct RecallTool {
+    store: MemoryStore,
+    embedder: Option<OpenAIProvider>,
+}
+
+impl RecallTool {
+    pub fn new(workspace: &Path) -> Self {
+        Self {
+            store: MemoryStore::new(workspace),
+            embedder: None,
+        }
+    }
+
+    /// Rank entries by embedding similarity using `provider.embedding_model`.
+    pub fn with_embeddings(mut self, embedder: OpenAIProvider) -> Self {
+        self.embedder = Some(embedder);
+        self
+    }
+
+    /// The `limit` entries closest in meaning to `query`. Entries without a
+    /// stored embedding are embedded in the same request as the query, and the
+    /// sidecar is rewritten to match the current memory.
+    async fn semantic_search(&self, embedder: &OpenAIProvider, query: &str, limit: usize) -> Result<Vec<String>, String> {
+        let entries = self.store.entries();
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut stored = self.store.load_embeddings();
+        let missing: Vec<String> = entries.iter().filter(|e| !stored.contains_key(*e)).cloned().collect();
+        let mut inputs = vec![query.to_string()];
+        inputs.extend(missing.iter().cloned());
+
+        let mut vectors = embedder.embeddings(inputs).await.map_err(|e| e.to_string())?.into_iter();
+        let query_vector = vectors.next().unwrap_or_default();
+        stored.extend(missing.into_iter().zip(vectors));
+
+        let indexed: Vec<(String, Vec<f32>)> = entries
+            .into_iter()
+            .filter_map(|entry| stored.remove(&entry).map(|vector| (entry, vector)))
+            .collect();
+        let ranked = rank_by_similarity(&query_vector, &indexed, limit).into_iter().map(str::to_string).collect();
+
+        let index: HashMap<String, Vec<f32>> = indexed.into_iter().collect();
+        if let Err(e) = self.store.save_embeddings(&index) {
+            tracing::warn!("Failed to save memory embeddings: {}", e);
+        }
+        Ok(ranked)
+    }
+}
+
+#[async_trait]
+impl Tool for RecallTool {
+    fn name(&self) -> &str { "recall" }
+
+    fn description(&self) -> &str {
+        "Search long-term memory for entries about a keyword or question. Matches by meaning when embeddings are configured, otherwise by text (case-insensitive)"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "keyword": {
+                    "type": "string",
+                    "description": "Word, phrase or question to look for"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Most entries to return when matching by meaning (default 5)"
+                }
+            },
+            "required": ["keyword"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        let keyword = args["keyword"].as_str().filter(|k| !k.trim().is_empty()).ok_or("Missing keyword parameter")?;
+
+        if let Some(embedder) = &self.embedder {
+            let limit = args["limit"].as_u64().map_or(DEFAULT_RECALL_LIMIT, |n| n.max(1) as usize);
+            match self.semantic_search(embedder, keyword.trim(), limit).await {
+                Ok(entries) if entries.is_empty() => return Ok("Memory is empty".to_string()),
+                Ok(entries) => return Ok(entries.join("\n")),
+                Err(e) => tracing::warn!("Semantic recall failed, searching by text: {}", e),
+            }
+        }
+
+        let entries = self.store.search(keyword);
+        if entries.is_empty() {
+            return Ok(format!("No memories matching '{}'", keyword.trim()));
+        }
+        Ok(entries.join("\n"))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_remember_two_facts_and_recall_one() {
+        let temp_dir = TempDir::new().unwrap();
+        let remember = RememberTool::new(temp_dir.path(), Tz::UTC);
+        let recall = RecallTool::new(temp_dir.path());
+
+        remember.execute(json!({"fact": "Prefers meetings after 10am"})).await.unwrap();
+        remember.execute(json!({"fact": "Daughter's name is Sari"})).await.unwrap();
+
+        let found = recall.execute(json!({"keyword": "sari"})).await.unwrap();
+        assert!(found.starts_with("- ["));
+        assert!(found.ends_with("] Daughter's name is Sari"));
+        assert!(!found.contains("meetings"));
+
+        let missing = recall.execute(json!({"keyword": "coffee"})).await.unwrap();
+        assert_eq!(missing, "No memories matching 'coffee'");
+    }
+
+    #[tokio::test]
+    async fn test_semantic_recall_ranks_by_meaning_and_caches_embeddings() {
+        use crate::config::ProviderConfig;
+        use crate::providers::retry::tests::mock_server;
+
+        let temp_dir = TempDir::new().unwrap();
+        let store = MemoryStore::new(temp_dir.path());
+        store.append_entry("Allergic to peanuts").unwrap();
+        store.append_entry("Enjoys hiking on weekends").unwrap();
+
+        // The query, then both entries; the second search only embeds the query
+        let base = mock_server(vec![
+            ("200 OK", "", r#"{"data":[{"index":0,"embedding":[0.1,0.9]},{"index":1,"embedding":[1.0,0.0]},{"index":2,"embedding":[0.0,1.0]}]}"#),
+            ("200 OK", "", r#"{"data":[{"index":0,"embedding":[0.9,0.1]}]}"#),
+        ])
+        .await;
+        let recall = RecallTool::new(temp_dir.path()).with_embeddings(OpenAIProvider::new(ProviderConfig {
+            api_base: base,
+            embedding_model: "text-embedding-3-small".to_string(),
+            ..Default::default()
+        }));
+
+        let outdoors = recall.execute(json!({"keyword": "what do they do outdoors?", "limit": 1})).await.unwrap();
+        assert_eq!(outdoors, "- Enjoys hiking on weekends");
+        assert_eq!(store.load_embeddings().len(), 2);
+
+        let food = recall.execute(json!({"keyword": "food restrictions", "limit": 1})).await.unwrap();
+        assert_eq!(food, "- Allergic to peanuts");
+    }
+}
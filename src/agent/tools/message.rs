@@ -3,28 +3,30 @@ use serde_json::{json, Value};
 use crate::agent::tools::Tool;
 use crate::bus::OutboundMessage;
 
+/// Sends a message to a chat through the outbound bus. The agent loop fills
+/// in `channel` and `chat_id` from the conversation when the model leaves
+/// them out, so the registry can stay shared between concurrent turns.
 pub struct MessageTool {
-    sender: Option<tokio::sync::mpsc::Sender<OutboundMessage>>,
-    channel: Option<String>,
-    chat_id: Option<String>,
+    sender: tokio::sync::mpsc::Sender<OutboundMessage>,
 }
 
 impl MessageTool {
-    pub fn new() -> Self {
-        Self {
-            sender: None,
-            channel: None,
-            chat_id: None,
-        }
-    }
-
-    pub fn set_sender(&mut self, sender: tokio::sync::mpsc::Sender<OutboundMessage>) {
-        self.sender = Some(sender);
+    pub fn new(sender: tokio::sync::mpsc::Sender<OutboundMessage>) -> Self {
+        Self { sender }
     }
 
-    pub fn set_context(&mut self, channel: String, chat_id: String) {
-        self.channel = Some(channel);
-        self.chat_id = Some(chat_id);
+    /// `args` with `channel` and `chat_id` defaulted to the given conversation.
+    pub fn with_default_target(mut args: Value, channel: &str, chat_id: &str) -> Value {
+        if let Some(obj) = args.as_object_mut() {
+            if !obj.get("channel").is_some_and(Value::is_string) {
+                obj.insert("channel".to_string(), json!(channel));
+            }
+            // A chat id only means something on its own channel
+            if !obj.get("chat_id").is_some_and(Value::is_string) && obj["channel"] == channel {
+                obj.insert("chat_id".to_string(), json!(chat_id));
+            }
+        }
+        args
     }
 }
 
@@ -33,7 +35,7 @@ impl Tool for MessageTool {
     fn name(&self) -> &str { "message" }
     
     fn description(&self) -> &str {
-        "Send a message to a user on a chat channel"
+        "Send a message to a user on a chat channel (defaults to the current chat)"
     }
     
     fn parameters(&self) -> Value {
@@ -60,27 +62,21 @@ impl Tool for MessageTool {
     async fn execute(&self, args: Value) -> Result<String, String> {
         let content = args["content"]
             .as_str()
+            .filter(|c| !c.trim().is_empty())
             .ok_or("Missing content parameter")?;
 
         let channel = args["channel"]
             .as_str()
-            .or(self.chat_id.as_deref())
             .ok_or("Missing channel parameter")?;
 
         let chat_id = args["chat_id"]
             .as_str()
-            .or(self.chat_id.as_deref())
-            .unwrap_or("default")
-            .to_string();
+            .ok_or("Missing chat_id parameter")?;
 
-        if let Some(ref sender) = self.sender {
-            let msg = OutboundMessage::new(channel.to_string(), chat_id, content.to_string());
-            sender.send(msg).await
-                .map_err(|e| format!("Failed to send message: {}", e))?;
-            Ok("Message sent".to_string())
-        } else {
-            Err("Message sender not configured".to_string())
-        }
+        let msg = OutboundMessage::new(channel.to_string(), chat_id.to_string(), content.to_string());
+        self.sender.send(msg).await
+            .map_err(|e| format!("Failed to send message: {}", e))?;
+        Ok(format!("Message sent to {}:{}", channel, chat_id))
     }
     
     fn as_any(&self) -> &dyn std::any::Any {
@@ -88,8 +84,24 @@ impl Tool for MessageTool {
     }
 }
 
-impl Default for MessageTool {
-    fn default() -> Self {
-        Self::new()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_message_defaults_to_the_current_chat() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let tool = MessageTool::new(tx);
+
+        let args = MessageTool::with_default_target(json!({"content": "Build finished"}), "telegram", "42");
+        assert_eq!(tool.execute(args).await.unwrap(), "Message sent to telegram:42");
+        let sent = rx.recv().await.unwrap();
+        assert_eq!((sent.channel.as_str(), sent.chat_id.as_str()), ("telegram", "42"));
+
+        // An explicit target wins; another channel's chat id isn't borrowed
+        let args = MessageTool::with_default_target(json!({"content": "Hi", "chat_id": "7"}), "telegram", "42");
+        assert_eq!(args["chat_id"], "7");
+        let args = MessageTool::with_default_target(json!({"content": "Hi", "channel": "email"}), "telegram", "42");
+        assert!(tool.execute(args).await.unwrap_err().contains("chat_id"));
     }
 }
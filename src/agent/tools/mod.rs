@@ -1,19 +1,47 @@
+mod brave_search;
+mod email;
 mod filesystem;
+mod git;
+mod health;
+mod memory;
+mod note;
+mod patch;
+mod search;
 mod shell;
-mod web;
-#[allow(dead_code)]
+mod structured;
+mod system_info;
 mod message;
+mod wait;
+mod web;
 
 #[allow(dead_code)]
 mod spawn;
 
+mod reminder;
+
+pub use brave_search::BraveSearchTool;
+pub use email::EmailTool;
+pub(crate) use filesystem::validate_read_path;
 pub use filesystem::{ReadFileTool, WriteFileTool, EditFileTool, ListDirTool};
+pub use git::GitTool;
+pub use health::HealthTool;
+pub use memory::{RecallTool, RememberTool};
+pub use message::MessageTool;
+pub use note::NoteTool;
+pub use patch::ApplyPatchTool;
+pub use search::GrepTool;
 pub use shell::ShellTool;
+pub use structured::StructuredEditTool;
+pub use system_info::SystemInfoTool;
+pub use wait::WaitTool;
 pub use web::WebFetchTool;
+pub use reminder::{parse_timezone, send_at, ReminderTool};
 
 use async_trait::async_trait;
 use serde_json::Value;
 use std::any::Any;
+use std::collections::HashMap;
+use std::time::Duration;
 
 #[async_trait]
 pub trait Tool: Send + Sync {
@@ -30,20 +58,48 @@ pub trait Tool: Send + Sync {
 
 #[allow(dead_code)]
 pub struct ToolRegistry {
-    tools: std::collections::HashMap<String, Box<dyn Tool>>,
+    tools: HashMap<String, Box<dyn Tool>>,
+    default_timeout: Option<Duration>,
+    timeouts: HashMap<String, Option<Duration>>,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
-            tools: std::collections::HashMap::new(),
+            tools: HashMap::new(),
+            default_timeout: None,
+            timeouts: HashMap::new(),
         }
     }
 
+    /// Abandon a call once it has run this many seconds, with per-tool
+    /// overrides by name. 0 means no limit.
+    pub fn with_timeouts(mut self, default_secs: u64, overrides: &HashMap<String, u64>) -> Self {
+        let limit = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+        self.default_timeout = limit(default_secs);
+        self.timeouts = overrides.iter().map(|(name, &secs)| (name.clone(), limit(secs))).collect();
+        self
+    }
+
+    fn timeout_for(&self, name: &str) -> Option<Duration> {
+        self.timeouts.get(name).copied().unwrap_or(self.default_timeout)
+    }
+
+    /// Add `tool`, replacing any tool already registered under its name.
+    /// A replacement is logged, since it usually means a misconfiguration.
     pub fn register<T: Tool + 'static>(&mut self, tool: T) {
-        self.tools.insert(tool.name().to_string(), Box::new(tool));
+        self.register_boxed(Box::new(tool));
+    }
+
+    /// Add `tool` unless its name is taken; the existing tool is kept.
+    #[allow(dead_code)]
+    pub fn try_register<T: Tool + 'static>(&mut self, tool: T) -> Result<(), String> {
+        if self.tools.contains_key(tool.name()) {
+            return Err(format!("Tool name collision: {} is already registered", tool.name()));
+        }
+        self.register(tool);
+        Ok(())
     }
-    
 
     #[allow(dead_code)]
     pub fn get(&self, name: &str) -> Option<&dyn Tool> {
@@ -53,7 +109,9 @@ impl ToolRegistry {
     #[allow(dead_code)]
     pub fn register_boxed(&mut self, tool: Box<dyn Tool>) {
         let name = tool.name().to_string();
-        self.tools.insert(name, tool);
+        if self.tools.insert(name.clone(), tool).is_some() {
+            tracing::warn!("Tool name collision: {} was registered twice; the later registration replaces the earlier one", name);
+        }
     }
 
     pub fn get_definitions(&self) -> Vec<ToolDefinition> {
@@ -72,7 +130,13 @@ impl ToolRegistry {
 
     pub async fn execute(&self, name: &str, args: serde_json::Value) -> Result<String, String> {
         let tool = self.tools.get(name).ok_or_else(|| format!("Tool not found: {}", name))?;
-        tool.execute(args).await
+        let Some(limit) = self.timeout_for(name) else {
+            return tool.execute(args).await;
+        };
+        // Dropping the future on timeout cancels the call at its next await point
+        tokio::time::timeout(limit, tool.execute(args))
+            .await
+            .unwrap_or_else(|_| Err(format!("tool {} timed out after {}s", name, limit.as_secs())))
     }
 }
 
@@ -88,8 +152,6 @@ use crate::providers::{ToolDefinition, FunctionDefinition};
 mod tests {
     use super::*;
     use serde_json::json;
-    use tempfile::TempDir;
-    use std::fs;
 
     struct MockTool {
         name: String,
@@ -115,9 +177,37 @@ mod tests {
             })
         }
 
-        async fn execute(&self, _args: Value) -> Result<String, String> {
+        async fn execute(&self, args: Value) -> Result<String, String> {
+            if let Some(secs) = args["sleep_secs"].as_u64() {
+                tokio::time::sleep(Duration::from_secs(secs)).await;
+            }
             Ok(format!("Executed {}", self.name))
         }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_timeout_stops_a_read_that_blocks() {
+        let dir = tempfile::TempDir::new().unwrap();
+        // Opening a FIFO for reading blocks the thread until a writer shows up
+        let fifo = dir.path().join("pipe");
+        assert!(std::process::Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+
+        let timeouts = HashMap::from([("read_file".to_string(), 1)]);
+        let mut registry = ToolRegistry::new().with_timeouts(0, &timeouts);
+        registry.register(ReadFileTool::new(None));
+
+        let started = std::time::Instant::now();
+        let result = registry.execute("read_file", json!({"path": fifo.to_string_lossy()})).await;
+        assert_eq!(result.unwrap_err(), "tool read_file timed out after 1s");
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        // Let the abandoned read finish so the runtime can shut down
+        drop(std::fs::OpenOptions::new().write(true).open(&fifo).unwrap());
     }
 
     #[tokio::test]
@@ -187,4 +277,49 @@ mod tests {
         let result2 = registry.execute("tool2", json!({"test_param": "value"})).await;
         assert!(result2.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_name_collision_is_reported() {
+        let mut registry = ToolRegistry::new();
+        registry.register(MockTool {
+            name: "shell".to_string(),
+            description: "Built-in".to_string(),
+        });
+
+        let err = registry
+            .try_register(MockTool {
+                name: "shell".to_string(),
+                description: "Custom".to_string(),
+            })
+            .unwrap_err();
+        assert_eq!(err, "Tool name collision: shell is already registered");
+        // The first registration wins
+        assert_eq!(registry.get("shell").unwrap().description(), "Built-in");
+        assert_eq!(registry.get_definitions().len(), 1);
+
+        registry.register(MockTool {
+            name: "shell".to_string(),
+            description: "Custom".to_string(),
+        });
+        assert_eq!(registry.get("shell").unwrap().description(), "Custom");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_tool_times_out() {
+        let overrides = HashMap::from([("patient".to_string(), 600), ("unbounded".to_string(), 0)]);
+        let mut registry = ToolRegistry::new().with_timeouts(5, &overrides);
+        for name in ["slow", "patient", "unbounded"] {
+            registry.register(MockTool {
+                name: name.to_string(),
+                description: "Sleeps when asked".to_string(),
+            });
+        }
+
+        let result = registry.execute("slow", json!({"sleep_secs": 30})).await;
+        assert_eq!(result.unwrap_err(), "tool slow timed out after 5s");
+
+        assert_eq!(registry.execute("slow", json!({"sleep_secs": 1})).await.unwrap(), "Executed slow");
+        assert_eq!(registry.execute("patient", json!({"sleep_secs": 30})).await.unwrap(), "Executed patient");
+        assert_eq!(registry.execute("unbounded", json!({"sleep_secs": 3600})).await.unwrap(), "Executed unbounded");
+    }
 }
@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use crate::agent::tools::Tool;
+
+/// Quick dated notes, appended to `notes/YYYY-MM-DD.md` in the workspace.
+pub struct NoteTool {
+    notes_dir: PathBuf,
+    timezone: Tz,
+}
+
+impl NoteTool {
+    pub fn new(workspace: PathBuf, timezone: Tz) -> Self {
+        Self {
+            notes_dir: workspace.join("notes"),
+            timezone,
+        }
+    }
+
+    fn today(&self) -> NaiveDate {
+        chrono::Utc::now().with_timezone(&self.timezone).date_naive()
+    }
+
+    /// The requested day, or today. Parsing as a date also keeps the file
+    /// name from ever leaving `notes/`.
+    fn date_arg(&self, args: &Value) -> Result<NaiveDate, String> {
+        match args["date"].as_str() {
+            Some(date) => NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                .map_err(|_| format!("Invalid date '{}'; use YYYY-MM-DD", date)),
+            None => Ok(self.today()),
+        }
+    }
+
+    fn path_for(&self, date: NaiveDate) -> PathBuf {
+        self.notes_dir.join(format!("{}.md", date.format("%Y-%m-%d")))
+    }
+
+    async fn add(&self, text: &str) -> Result<String, String> {
+        let now = chrono::Utc::now().with_timezone(&self.timezone);
+        let path = self.path_for(now.date_naive());
+
+        tokio::fs::create_dir_all(&self.notes_dir)
+            .await
+            .map_err(|e| format!("Failed to create notes directory: {}", e))?;
+
+        let mut entry = String::new();
+        if !path.exists() {
+            entry.push_str(&format!("# Notes {}\n\n", now.format("%Y-%m-%d")));
+        }
+        // Keep multi-line notes inside one list item
+        entry.push_str(&format!("- {} {}\n", now.format("%H:%M"), text.trim().replace('\n', "\n  ")));
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        file.write_all(entry.as_bytes())
+            .await
+            .map_err(|e| format!("Failed to write note: {}", e))?;
+
+        Ok(format!("Noted in notes/{}", path.file_name().unwrap_or_default().to_string_lossy()))
+    }
+
+    async fn read(&self, date: NaiveDate) -> Result<String, String> {
+        match tokio::fs::read_to_string(self.path_for(date)).await {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(format!("No notes for {}", date)),
+            Err(e) => Err(format!("Failed to read notes: {}", e)),
+        }
+    }
+
+    async fn list(&self) -> Result<String, String> {
+        let mut entries = match tokio::fs::read_dir(&self.notes_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok("No notes yet".to_string()),
+            Err(e) => return Err(format!("Failed to list notes: {}", e)),
+        };
+
+        let mut dates = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(date) = name.strip_suffix(".md").and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok()) {
+                dates.push(date);
+            }
+        }
+
+        if dates.is_empty() {
+            return Ok("No notes yet".to_string());
+        }
+        dates.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(dates.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("\n"))
+    }
+}
+
+#[async_trait]
+impl Tool for NoteTool {
+    fn name(&self) -> &str { "note" }
+
+    fn description(&self) -> &str {
+        "Jot down a quick timestamped note in today's notes file, read a day's notes, or list the days that have notes"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["add", "read", "list"],
+                    "description": "add (default) appends a note; read shows one day's notes; list shows dates with notes"
+                },
+                "text": {
+                    "type": "string",
+                    "description": "add: the note to record"
+                },
+                "date": {
+                    "type": "string",
+                    "description": "read: day in YYYY-MM-DD format (defaults to today)"
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        match args["action"].as_str().unwrap_or("add") {
+            "add" => {
+                let text = args["text"].as_str().filter(|t| !t.trim().is_empty()).ok_or("Missing text parameter")?;
+                self.add(text).await
+            }
+            "read" => self.read(self.date_arg(&args)?).await,
+            "list" => self.list().await,
+            other => Err(format!("Unknown action: {}", other)),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_add_and_read_back_todays_notes() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = NoteTool::new(temp_dir.path().to_path_buf(), Tz::UTC);
+
+        tool.execute(json!({"text": "Buy milk"})).await.unwrap();
+        tool.execute(json!({"action": "add", "text": "Call Budi\nabout the invoice"})).await.unwrap();
+
+        let today = tool.today().to_string();
+        let notes = tool.execute(json!({"action": "read", "date": today})).await.unwrap();
+        assert!(notes.starts_with(&format!("# Notes {}", today)));
+        assert!(notes.contains(" Buy milk\n"));
+        assert!(notes.contains(" Call Budi\n  about the invoice\n"));
+        assert_eq!(notes.matches("# Notes").count(), 1);
+
+        let listed = tool.execute(json!({"action": "list"})).await.unwrap();
+        assert_eq!(listed, today);
+    }
+
+    #[tokio::test]
+    async fn test_read_rejects_bad_dates_and_handles_missing_days() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = NoteTool::new(temp_dir.path().to_path_buf(), Tz::UTC);
+
+        let err = tool.execute(json!({"action": "read", "date": "../../etc/passwd"})).await.unwrap_err();
+        assert!(err.contains("Invalid date"));
+
+        let notes = tool.execute(json!({"action": "read", "date": "2020-01-01"})).await.unwrap();
+        assert_eq!(notes, "No notes for 2020-01-01");
+    }
+}
@@ -0,0 +1,312 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use crate::agent::tools::Tool;
+use crate::agent::tools::filesystem::{check_writable_extension, validate_write_path};
+
+/// One replacement. Unified-diff hunks are whole lines and carry the line
+/// number the diff expects them at; `{old, new}` hunks are plain text.
+#[derive(Debug, Clone, PartialEq)]
+struct Hunk {
+    old: String,
+    new: String,
+    /// 1-based line from the `@@ -l,n` header
+    line: Option<usize>,
+}
+
+/// Split a unified diff for a single file into hunks. File headers are
+/// skipped; a blank line inside a hunk counts as empty context, since models
+/// often drop the leading space.
+fn parse_unified(patch: &str) -> Result<Vec<Hunk>, String> {
+    let mut hunks = Vec::new();
+    let mut current: Option<(Vec<&str>, Vec<&str>, usize)> = None;
+    let mut files = 0;
+
+    let finish = |current: Option<(Vec<&str>, Vec<&str>, usize)>, hunks: &mut Vec<Hunk>| {
+        if let Some((old, new, line)) = current {
+            hunks.push(Hunk { old: old.join("\n"), new: new.join("\n"), line: Some(line) });
+        }
+    };
+
+    for raw in patch.trim_end().lines() {
+        if let Some(header) = raw.strip_prefix("@@") {
+            finish(current.take(), &mut hunks);
+            current = Some((Vec::new(), Vec::new(), hunk_start(header)?));
+            continue;
+        }
+        match current.as_mut() {
+            Some((old, new, _)) => match raw.chars().next() {
+                Some(' ') => {
+                    old.push(&raw[1..]);
+                    new.push(&raw[1..]);
+                }
+                None => {
+                    old.push("");
+                    new.push("");
+                }
+                Some('-') if !raw.starts_with("--- ") => old.push(&raw[1..]),
+                Some('+') if !raw.starts_with("+++ ") => new.push(&raw[1..]),
+                Some('\\') => {}
+                _ if raw.starts_with("--- ") || raw.starts_with("diff ") => {
+                    finish(current.take(), &mut hunks);
+                }
+                _ if raw.starts_with("+++ ") => {
+                    finish(current.take(), &mut hunks);
+                    files += 1;
+                }
+                _ => return Err(format!("Malformed patch line: {}", raw)),
+            },
+            None if raw.starts_with("+++ ") => files += 1,
+            // File headers and anything else before the first hunk
+            None => {}
+        }
+    }
+    finish(current, &mut hunks);
+
+    if files > 1 {
+        return Err("The patch touches more than one file; send one patch per file".to_string());
+    }
+    if hunks.is_empty() {
+        return Err("The patch has no hunks (expected @@ -l,n +l,n @@ headers)".to_string());
+    }
+    Ok(hunks)
+}
+
+/// The old-file start line of an `@@ -l,n +l,n @@` header (text after the `@@`).
+fn hunk_start(header: &str) -> Result<usize, String> {
+    header
+        .trim_start()
+        .strip_prefix('-')
+        .and_then(|rest| rest.split([',', ' ']).next())
+        .and_then(|start| start.parse().ok())
+        .ok_or_else(|| format!("Malformed hunk header: @@{}", header))
+}
+
+fn parse_hunk_list(hunks: &[Value]) -> Result<Vec<Hunk>, String> {
+    if hunks.is_empty() {
+        return Err("hunks is empty".to_string());
+    }
+    hunks
+        .iter()
+        .enumerate()
+        .map(|(i, hunk)| {
+            let old = hunk["old"].as_str().filter(|o| !o.is_empty());
+            let new = hunk["new"].as_str();
+            match (old, new) {
+                (Some(old), Some(new)) => Ok(Hunk { old: old.to_string(), new: new.to_string(), line: None }),
+                _ => Err(format!("Hunk {} needs a non-empty 'old' and a 'new' string", i + 1)),
+            }
+        })
+        .collect()
+}
+
+fn line_of(content: &str, pos: usize) -> usize {
+    content[..pos].matches('\n').count() + 1
+}
+
+/// Byte offset where 1-based `line` starts, or the end of the content.
+fn line_offset(content: &str, line: usize) -> usize {
+    if line <= 1 {
+        return 0;
+    }
+    content.match_indices('\n').nth(line - 2).map_or(content.len(), |(i, _)| i + 1)
+}
+
+/// Apply every hunk in order to an in-memory copy. Nothing is returned unless
+/// all of them apply, so a failed hunk leaves the file untouched.
+fn apply_hunks(content: &str, hunks: &[Hunk]) -> Result<String, String> {
+    let mut content = content.to_string();
+    // Lines added minus lines removed so far, to shift later hunks' line hints
+    let mut shift: isize = 0;
+    let mut cursor = 0;
+
+    for (i, hunk) in hunks.iter().enumerate() {
+        let number = i + 1;
+
+        let (start, end) = if hunk.old.is_empty() {
+            // Pure insertion: `@@ -l,0` means after line l
+            let line = (hunk.line.unwrap_or(0) as isize + shift).max(0) as usize;
+            let at = line_offset(&content, line + 1);
+            (at, at)
+        } else {
+            let candidates: Vec<usize> = content[cursor..]
+                .match_indices(&hunk.old)
+                .map(|(pos, _)| cursor + pos)
+                .filter(|&pos| {
+                    // Unified hunks are whole lines
+                    hunk.line.is_none()
+                        || ((pos == 0 || content.as_bytes()[pos - 1] == b'\n')
+                            && matches!(content.as_bytes().get(pos + hunk.old.len()), None | Some(b'\n')))
+                })
+                .collect();
+
+            let start = match (candidates.as_slice(), hunk.line) {
+                ([], _) => {
+                    return Err(format!(
+                        "Hunk {} did not apply: its old text was not found after the previous hunk. No changes were made",
+                        number
+                    ))
+                }
+                ([only], _) => *only,
+                (_, Some(line)) => {
+                    let expected = line as isize + shift;
+                    *candidates
+                        .iter()
+                        .min_by_key(|&&pos| (line_of(&content, pos) as isize - expected).abs())
+                        .unwrap()
+                }
+                (many, None) => {
+                    return Err(format!(
+                        "Hunk {} did not apply: its old text matches {} places; include more surrounding text. No changes were made",
+                        number,
+                        many.len()
+                    ))
+                }
+            };
+            (start, start + hunk.old.len())
+        };
+
+        let new = if hunk.old.is_empty() && !hunk.new.is_empty() {
+            format!("{}\n", hunk.new)
+        } else {
+            hunk.new.clone()
+        };
+        content.replace_range(start..end, &new);
+        cursor = start + new.len();
+        shift += hunk.new.lines().count() as isize - hunk.old.lines().count() as isize;
+    }
+
+    Ok(content)
+}
+
+/// Applies several edits to one file at once, either as a unified diff or a
+/// list of `{old, new}` replacements. All hunks apply or none do.
+pub struct ApplyPatchTool {
+    allowed_dir: Option<PathBuf>,
+    writable_extensions: Vec<String>,
+}
+
+impl ApplyPatchTool {
+    pub fn new(allowed_dir: Option<PathBuf>) -> Self {
+        Self { allowed_dir, writable_extensions: Vec::new() }
+    }
+
+    /// Only allow patching files with these extensions (empty = any file).
+    pub fn with_writable_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.writable_extensions = extensions;
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for ApplyPatchTool {
+    fn name(&self) -> &str { "apply_patch" }
+
+    fn description(&self) -> &str {
+        "Make several edits to one file at once, given as a unified diff or as a list of {old, new} replacements. \
+         Either every hunk applies or the file is left unchanged"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path to the file to patch"
+                },
+                "patch": {
+                    "type": "string",
+                    "description": "Unified diff for this file, with @@ hunk headers and a few lines of context"
+                },
+                "hunks": {
+                    "type": "array",
+                    "description": "Alternative to patch: replacements applied in order, each old text matching exactly once",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "old": {"type": "string"},
+                            "new": {"type": "string"}
+                        },
+                        "required": ["old", "new"]
+                    }
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        let path = args["path"].as_str().ok_or("Missing path")?;
+        let hunks = match (args["patch"].as_str(), args["hunks"].as_array()) {
+            (Some(patch), None) => parse_unified(patch)?,
+            (None, Some(hunks)) => parse_hunk_list(hunks)?,
+            (Some(_), Some(_)) => return Err("Pass either patch or hunks, not both".to_string()),
+            (None, None) => return Err("Missing patch or hunks".to_string()),
+        };
+
+        check_writable_extension(Path::new(path), &self.writable_extensions)?;
+        let path = validate_write_path(self.allowed_dir.as_deref(), path)?;
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        let patched = apply_hunks(&content, &hunks)?;
+
+        std::fs::write(&path, &patched)
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+
+        Ok(format!("Applied {} hunk{}", hunks.len(), if hunks.len() == 1 { "" } else { "s" }))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const ORIGINAL: &str = "fn main() {\n    let name = \"world\";\n    greet(name);\n}\n\nfn greet(name: &str) {\n    println!(\"Hello, {}!\", name);\n}\n";
+
+    #[tokio::test]
+    async fn test_two_hunk_patch_applies() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("main.rs");
+        std::fs::write(&file, ORIGINAL).unwrap();
+
+        let patch = "--- a/main.rs\n+++ b/main.rs\n\
+            @@ -1,3 +1,3 @@\n fn main() {\n-    let name = \"world\";\n+    let name = \"Sari\";\n     greet(name);\n\
+            @@ -6,3 +6,4 @@\n fn greet(name: &str) {\n     println!(\"Hello, {}!\", name);\n+    println!(\"Bye\");\n }\n";
+        let tool = ApplyPatchTool::new(Some(temp_dir.path().to_path_buf()));
+        let result = tool.execute(json!({"path": "main.rs", "patch": patch})).await.unwrap();
+
+        assert_eq!(result, "Applied 2 hunks");
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "fn main() {\n    let name = \"Sari\";\n    greet(name);\n}\n\nfn greet(name: &str) {\n    println!(\"Hello, {}!\", name);\n    println!(\"Bye\");\n}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_second_hunk_rejects_whole_patch() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("main.rs");
+        std::fs::write(&file, ORIGINAL).unwrap();
+
+        let patch = "@@ -1,2 +1,2 @@\n fn main() {\n-    let name = \"world\";\n+    let name = \"Sari\";\n\
+            @@ -6,2 +6,2 @@\n fn greet(name: &str) {\n-    println!(\"Hi, {}!\", name);\n+    println!(\"Hey, {}!\", name);\n";
+        let tool = ApplyPatchTool::new(Some(temp_dir.path().to_path_buf()));
+        let err = tool.execute(json!({"path": "main.rs", "patch": patch})).await.unwrap_err();
+
+        assert!(err.starts_with("Hunk 2 did not apply"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), ORIGINAL);
+
+        // The same rule holds for {old, new} hunks, which also refuse ambiguous matches
+        let hunks = json!([{"old": "\"world\"", "new": "\"Sari\""}, {"old": "name", "new": "who"}]);
+        let err = tool.execute(json!({"path": "main.rs", "hunks": hunks})).await.unwrap_err();
+        assert!(err.starts_with("Hunk 2 did not apply: its old text matches"));
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), ORIGINAL);
+    }
+}
@@ -1,10 +1,13 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::{RwLock, Mutex};
 use tokio::time::sleep;
-use chrono::{DateTime, Utc, NaiveDateTime};
+use chrono::{DateTime, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use crate::agent::tools::Tool;
 use crate::bus::OutboundMessage;
 
@@ -17,12 +20,78 @@ pub struct Reminder {
     pub scheduled_time: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub repeat_pattern: Option<String>, // For recurring reminders (e.g., "daily", "weekly")
+    /// Shared by every occurrence of a recurring reminder; cancelling targets the chain
+    #[serde(default)]
+    pub chain_id: String,
+    /// Fired without a way to deliver it; reported as missed on the next `restore`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub missed: bool,
 }
 
+impl Reminder {
+    fn chain(&self) -> &str {
+        if self.chain_id.is_empty() { &self.id } else { &self.chain_id }
+    }
+}
+
+/// Cancellation flags keyed by chain id, shared with the running reminder tasks.
+type ChainFlags = Arc<RwLock<HashMap<String, Arc<AtomicBool>>>>;
+
 pub struct ReminderTool {
     reminders: Arc<RwLock<Vec<Reminder>>>,
     workspace_path: String,
     outbound_tx: Arc<Mutex<Option<tokio::sync::mpsc::Sender<OutboundMessage>>>>,
+    chains: ChainFlags,
+    plain: bool,
+    /// Zone for reminder times given without an explicit `timezone` argument
+    timezone: Tz,
+}
+
+/// Parse an IANA timezone name such as `America/New_York`.
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| format!("Unknown timezone '{}'; use an IANA name like America/New_York", name))
+}
+
+/// Wall-clock formats accepted without an offset, read in the reminder's zone
+const LOCAL_FORMATS: &[&str] = &["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"];
+
+/// Interpret a wall-clock time in `tz` and convert it to UTC. In the repeated
+/// hour when clocks go back, the earlier instant wins.
+fn local_to_utc(naive_dt: NaiveDateTime, tz: Tz) -> Result<DateTime<Utc>, String> {
+    match tz.from_local_datetime(&naive_dt) {
+        LocalResult::Single(local) | LocalResult::Ambiguous(local, _) => Ok(local.with_timezone(&Utc)),
+        LocalResult::None => Err(format!("{} does not exist in {} (skipped by a daylight saving change)", naive_dt, tz)),
+    }
+}
+
+/// Parse a reminder time: ISO-8601 with an offset, a local `YYYY-MM-DD HH:MM[:SS]`
+/// in `tz`, or English like "in 30 minutes" or "tomorrow 9am" relative to `now` in `tz`.
+pub fn parse_reminder_time(input: &str, tz: Tz, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let input = input.trim();
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Ok(dt) = DateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S%:z") {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Some(naive_dt) = LOCAL_FORMATS.iter().find_map(|f| NaiveDateTime::parse_from_str(input, f).ok()) {
+        return local_to_utc(naive_dt, tz);
+    }
+
+    // "in 2 hours" reads as "2 hours" from now
+    let lower = input.to_lowercase();
+    let relative = lower.strip_prefix("in ").unwrap_or(&lower);
+    interim::parse_date_string(relative, now.with_timezone(&tz), interim::Dialect::Uk)
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|_| {
+            format!(
+                "Could not understand time '{}'. Use e.g. \"2030-06-01 09:00:00\", \"2030-06-01T09:00:00+07:00\", \"in 30 minutes\" or \"tomorrow 9am\"",
+                input
+            )
+        })
 }
 
 impl ReminderTool {
@@ -34,25 +103,40 @@ impl ReminderTool {
             reminders,
             workspace_path,
             outbound_tx,
+            chains: Arc::new(RwLock::new(HashMap::new())),
+            plain: false,
+            timezone: Tz::UTC,
         }
     }
 
-    pub fn set_outbound_sender(&self, sender: tokio::sync::mpsc::Sender<OutboundMessage>) {
-        let mut tx_guard = self.outbound_tx.blocking_lock();
-        *tx_guard = Some(sender);
+    /// Zone used when the model doesn't pass one (`agent.timezone`).
+    pub fn with_timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
     }
 
-    async fn save_reminders_to_file(&self) -> Result<(), String> {
-        let reminders = self.reminders.read().await;
-        let content = serde_json::to_string_pretty(&*reminders)
-            .map_err(|e| format!("Failed to serialize reminders: {}", e))?;
-        
-        let file_path = format!("{}/reminders.json", self.workspace_path);
-        tokio::fs::write(file_path, content)
-            .await
-            .map_err(|e| format!("Failed to write reminders to file: {}", e))?;
-        
-        Ok(())
+    /// Send reminders without the decorative emoji prefix.
+    pub fn with_plain(mut self, plain: bool) -> Self {
+        self.plain = plain;
+        self
+    }
+
+    /// Deliver reminders through `sender`. Set before `restore` so re-armed
+    /// reminders can actually send.
+    pub fn with_outbound_sender(self, sender: tokio::sync::mpsc::Sender<OutboundMessage>) -> Self {
+        Self {
+            outbound_tx: Arc::new(Mutex::new(Some(sender))),
+            ..self
+        }
+    }
+
+    /// Change the reminder list and save it, holding the write lock across
+    /// both so the file always matches memory and saves can't interleave.
+    async fn modify<T>(&self, change: impl FnOnce(&mut Vec<Reminder>) -> T) -> Result<T, String> {
+        let mut reminders = self.reminders.write().await;
+        let result = change(&mut reminders);
+        write_reminders(&self.workspace_path, &reminders).await?;
+        Ok(result)
     }
 
     async fn load_reminders_from_file(&self) -> Result<(), String> {
@@ -76,56 +160,224 @@ impl ReminderTool {
         Ok(())
     }
 
+    /// Re-arm the reminders saved in `reminders.json`, e.g. after a restart.
+    /// Overdue one-off reminders, and ones that fired with no way to deliver
+    /// them, are sent once as a "missed" note and dropped;
+    /// overdue recurring ones skip ahead to their next future occurrence.
+    /// Returns how many reminders were re-armed.
+    pub async fn restore(&self) -> Result<usize, String> {
+        self.load_reminders_from_file().await?;
+
+        let now = Utc::now();
+        let loaded = std::mem::take(&mut *self.reminders.write().await);
+        let mut pending = Vec::new();
+        let mut missed = Vec::new();
+
+        for mut reminder in loaded {
+            if reminder.scheduled_time > now && !reminder.missed {
+                pending.push(reminder);
+                continue;
+            }
+            match reminder.repeat_pattern.as_deref().and_then(repeat_interval) {
+                Some(step) => {
+                    let behind = (now - reminder.scheduled_time).num_seconds() / step.num_seconds().max(1) + 1;
+                    reminder.chain_id = reminder.chain().to_string();
+                    reminder.scheduled_time += step * behind as i32;
+                    reminder.id = format!("{}_repeat_{}", reminder.chain(), reminder.scheduled_time.timestamp());
+                    pending.push(reminder);
+                }
+                None => missed.push(reminder),
+            }
+        }
+
+        self.modify(|reminders| *reminders = pending.clone()).await?;
+
+        for reminder in missed {
+            let content = format!(
+                "{} ({}, was due {} UTC)",
+                crate::ui::reminder_text(&reminder.message, self.plain),
+                if reminder.missed { "could not be delivered" } else { "missed while offline" },
+                reminder.scheduled_time.format("%Y-%m-%d %H:%M")
+            );
+            let msg = OutboundMessage::new(reminder.channel, reminder.user_id, content);
+            send_at(&self.outbound_tx, now, msg).await;
+        }
+
+        let count = pending.len();
+        for reminder in pending {
+            self.start_reminder_task(reminder).await;
+        }
+        Ok(count)
+    }
+
+    async fn chain_flag(&self, chain_id: &str) -> Arc<AtomicBool> {
+        let mut chains = self.chains.write().await;
+        Arc::clone(chains.entry(chain_id.to_string()).or_default())
+    }
+
     async fn start_reminder_task(&self, reminder: Reminder) {
         let outbound_tx_clone = Arc::clone(&self.outbound_tx);
-        
+        let reminders = Arc::clone(&self.reminders);
+        let workspace_path = self.workspace_path.clone();
+        let cancelled = self.chain_flag(reminder.chain()).await;
+        let plain = self.plain;
+
         tokio::spawn(async move {
-            let delay = (reminder.scheduled_time - Utc::now()).to_std()
-                .unwrap_or(std::time::Duration::from_secs(0));
-            
-            // Sleep until the reminder time
-            sleep(delay).await;
-            
-            // Send the reminder
-            {
-                let tx_guard = outbound_tx_clone.lock().await;
-                if let Some(ref tx) = *tx_guard {
-                    let msg = OutboundMessage::new(
-                        reminder.channel.clone(),
-                        reminder.user_id.clone(),
-                        format!("⏰ **REMINDER**: {}", reminder.message)
-                    );
-                    
-                    if let Err(e) = tx.send(msg).await {
-                        eprintln!("Failed to send reminder: {}", e);
-                    }
+            let mut current = reminder;
+            loop {
+                let delay = (current.scheduled_time - Utc::now()).to_std()
+                    .unwrap_or(std::time::Duration::from_secs(0));
+                sleep(delay).await;
+
+                if cancelled.load(Ordering::SeqCst) {
+                    break;
                 }
-            }
-            
-            // Handle recurring reminders
-            let repeat_pattern = reminder.repeat_pattern.clone();
-            if let Some(pattern) = repeat_pattern {
-                // For now, we'll just create a new reminder with updated time based on pattern
-                // In a real implementation, you'd parse the pattern (e.g., daily, weekly) and calculate next occurrence
-                if pattern == "daily" {
-                    let next_time = reminder.scheduled_time + chrono::Duration::days(1);
-                    let new_reminder = Reminder {
-                        id: format!("{}_repeat_{}", reminder.id, next_time.timestamp()),
-                        scheduled_time: next_time,
-                        created_at: Utc::now(),
-                        repeat_pattern: Some(pattern),
-                        user_id: reminder.user_id.clone(),
-                        channel: reminder.channel.clone(),
-                        message: reminder.message.clone(),
-                    };
-                    
-                    // In a real implementation, we would add this to the active reminders
-                    // For now, we'll just log that we would schedule a recurring reminder
-                    println!("Would schedule recurring reminder: {}", new_reminder.message);
+
+                let msg = OutboundMessage::new(
+                    current.channel.clone(),
+                    current.user_id.clone(),
+                    crate::ui::reminder_text(&current.message, plain)
+                );
+                let delivered = send_at(&outbound_tx_clone, current.scheduled_time, msg).await;
+
+                // Handle recurring reminders
+                let next = schedule_next(&reminders, &workspace_path, &cancelled, &current).await;
+                settle(&reminders, &workspace_path, &current, delivered).await;
+                match next {
+                    Some(next) => current = next,
+                    None => break,
                 }
             }
         });
     }
+
+    /// Cancel the whole chain that `id` belongs to (a chain id or any occurrence id).
+    async fn cancel(&self, id: &str) -> Result<String, String> {
+        let chain_id = {
+            let reminders = self.reminders.read().await;
+            reminders
+                .iter()
+                .find(|r| r.id == id || r.chain() == id)
+                .map(|r| r.chain().to_string())
+                .ok_or_else(|| format!("No reminder with id {}", id))?
+        };
+
+        self.chain_flag(&chain_id).await.store(true, Ordering::SeqCst);
+        self.modify(|reminders| reminders.retain(|r| r.chain() != chain_id)).await?;
+
+        Ok(format!("Cancelled reminder {}", chain_id))
+    }
+}
+
+/// Save `reminders.json` by writing a temporary file and renaming it over the
+/// old one, so a reader never sees a half-written list. Callers hold the
+/// reminders write lock, which keeps saves in this process in order.
+async fn write_reminders(workspace_path: &str, reminders: &[Reminder]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(reminders)
+        .map_err(|e| format!("Failed to serialize reminders: {}", e))?;
+
+    let file_path = format!("{}/reminders.json", workspace_path);
+    // Per process, so another santosobot process saving at once can't share it
+    let temp_path = format!("{}.{}.tmp", file_path, std::process::id());
+    tokio::fs::write(&temp_path, content)
+        .await
+        .map_err(|e| format!("Failed to write reminders to file: {}", e))?;
+    tokio::fs::rename(&temp_path, &file_path)
+        .await
+        .map_err(|e| format!("Failed to write reminders to file: {}", e))
+}
+
+/// Time between occurrences for a repeat pattern, or `None` if it isn't one.
+fn repeat_interval(pattern: &str) -> Option<chrono::Duration> {
+    match pattern {
+        "hourly" => Some(chrono::Duration::hours(1)),
+        "daily" => Some(chrono::Duration::days(1)),
+        "weekly" => Some(chrono::Duration::weeks(1)),
+        _ => None,
+    }
+}
+
+/// Replace a fired recurring reminder with its next occurrence, in memory and
+/// in `reminders.json`, so a restart continues the series. Returns `None` for
+/// one-off reminders, unknown patterns, or a chain that was cancelled.
+async fn schedule_next(
+    reminders: &RwLock<Vec<Reminder>>,
+    workspace_path: &str,
+    cancelled: &AtomicBool,
+    current: &Reminder,
+) -> Option<Reminder> {
+    let step = repeat_interval(current.repeat_pattern.as_deref()?)?;
+
+    let next_time = current.scheduled_time + step;
+    let next = Reminder {
+        id: format!("{}_repeat_{}", current.chain(), next_time.timestamp()),
+        scheduled_time: next_time,
+        created_at: Utc::now(),
+        chain_id: current.chain().to_string(),
+        ..current.clone()
+    };
+
+    // Check under the lock so a concurrent cancel can't slip in between
+    let mut reminders = reminders.write().await;
+    if cancelled.load(Ordering::SeqCst) {
+        return None;
+    }
+    reminders.retain(|r| r.id != current.id);
+    reminders.push(next.clone());
+    if let Err(e) = write_reminders(workspace_path, &reminders).await {
+        tracing::warn!("Failed to persist next occurrence of {}: {}", next.chain(), e);
+    }
+    Some(next)
+}
+
+/// Drop a fired occurrence from the saved reminders. If it couldn't be
+/// delivered, keep a one-off copy marked missed instead, so `restore` reports
+/// it rather than it being lost.
+async fn settle(reminders: &RwLock<Vec<Reminder>>, workspace_path: &str, fired: &Reminder, delivered: bool) {
+    let mut reminders = reminders.write().await;
+    reminders.retain(|r| r.id != fired.id);
+    if !delivered {
+        tracing::error!(
+            "Reminder {} for {}:{} could not be delivered (no outbound sender or the send failed); marked as missed",
+            fired.id,
+            fired.channel,
+            fired.user_id
+        );
+        reminders.push(Reminder {
+            id: format!("{}_missed", fired.id),
+            repeat_pattern: None,
+            chain_id: fired.chain().to_string(),
+            missed: true,
+            ..fired.clone()
+        });
+    }
+    if let Err(e) = write_reminders(workspace_path, &reminders).await {
+        tracing::warn!("Failed to persist fired reminder {}: {}", fired.id, e);
+    }
+}
+
+/// Sleep until `at` and deliver `msg` through the shared outbound sender.
+/// Returns false if no sender was configured or the send failed.
+pub async fn send_at(
+    outbound_tx: &Mutex<Option<tokio::sync::mpsc::Sender<OutboundMessage>>>,
+    at: DateTime<Utc>,
+    msg: OutboundMessage,
+) -> bool {
+    let delay = (at - Utc::now()).to_std()
+        .unwrap_or(std::time::Duration::from_secs(0));
+    sleep(delay).await;
+
+    let tx_guard = outbound_tx.lock().await;
+    match *tx_guard {
+        Some(ref tx) => match tx.send(msg).await {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Failed to send reminder: {}", e);
+                false
+            }
+        },
+        None => false,
+    }
 }
 
 #[async_trait]
@@ -133,20 +385,33 @@ impl Tool for ReminderTool {
     fn name(&self) -> &str { "reminder" }
 
     fn description(&self) -> &str {
-        "Schedule a reminder message to be sent at a specific time"
+        "Schedule a reminder message to be sent at a specific time, or cancel one (including all future repeats)"
     }
 
     fn parameters(&self) -> Value {
         json!({
             "type": "object",
             "properties": {
+                "action": {
+                    "type": "string",
+                    "enum": ["set", "cancel"],
+                    "description": "set (default) schedules a reminder; cancel stops one by id"
+                },
+                "id": {
+                    "type": "string",
+                    "description": "Reminder id to cancel (required for cancel)"
+                },
                 "message": {
                     "type": "string",
                     "description": "The reminder message content"
                 },
                 "time": {
                     "type": "string",
-                    "description": "Time for the reminder in format YYYY-MM-DD HH:MM:SS UTC"
+                    "description": "When to send it: local YYYY-MM-DD HH:MM:SS, ISO-8601 with offset, or relative like \"in 2 hours\" or \"tomorrow 9am\""
+                },
+                "timezone": {
+                    "type": "string",
+                    "description": "IANA timezone of the time, e.g. America/New_York (defaults to the configured timezone)"
                 },
                 "user_id": {
                     "type": "string",
@@ -158,7 +423,7 @@ impl Tool for ReminderTool {
                 },
                 "repeat": {
                     "type": "string",
-                    "description": "Repeat pattern (optional): daily, weekly"
+                    "description": "Repeat pattern (optional): hourly, daily, weekly"
                 }
             },
             "required": ["message", "time", "user_id", "channel"]
@@ -166,6 +431,11 @@ impl Tool for ReminderTool {
     }
 
     async fn execute(&self, args: Value) -> Result<String, String> {
+        if args["action"].as_str() == Some("cancel") {
+            let id = args["id"].as_str().ok_or("Missing id parameter")?;
+            return self.cancel(id).await;
+        }
+
         let message = args["message"]
             .as_str()
             .ok_or("Missing message parameter")?
@@ -186,11 +456,17 @@ impl Tool for ReminderTool {
             .to_string();
 
         let repeat_pattern = args["repeat"].as_str().map(|s| s.to_string());
+        if let Some(pattern) = &repeat_pattern {
+            if repeat_interval(pattern).is_none() {
+                return Err(format!("Unsupported repeat pattern '{}'; use hourly, daily or weekly", pattern));
+            }
+        }
 
-        // Parse the time string to DateTime<Utc>
-        let naive_dt = NaiveDateTime::parse_from_str(time_str, "%Y-%m-%d %H:%M:%S")
-            .map_err(|e| format!("Failed to parse time: {}", e))?;
-        let scheduled_time = DateTime::<Utc>::from_naive_utc_and_offset(naive_dt, Utc);
+        let timezone = match args["timezone"].as_str() {
+            Some(name) => parse_timezone(name)?,
+            None => self.timezone,
+        };
+        let scheduled_time = parse_reminder_time(time_str, timezone, Utc::now())?;
 
         // Check if the scheduled time is in the past
         if scheduled_time <= Utc::now() {
@@ -208,21 +484,21 @@ impl Tool for ReminderTool {
             scheduled_time,
             created_at: Utc::now(),
             repeat_pattern,
+            chain_id: id.clone(),
+            missed: false,
         };
 
-        // Add to in-memory list
-        {
-            let mut reminders = self.reminders.write().await;
-            reminders.push(reminder.clone());
-        }
-
-        // Save to file
-        self.save_reminders_to_file().await?;
+        self.modify(|reminders| reminders.push(reminder.clone())).await?;
 
         // Start the reminder task
         self.start_reminder_task(reminder).await;
 
-        Ok(format!("Reminder scheduled successfully for {}", time_str))
+        Ok(format!(
+            "Reminder scheduled successfully for {} {} (id: {})",
+            scheduled_time.with_timezone(&timezone).format("%Y-%m-%d %H:%M:%S"),
+            timezone,
+            id
+        ))
     }
     
     fn as_any(&self) -> &dyn std::any::Any {
@@ -233,7 +509,6 @@ impl Tool for ReminderTool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::sync::mpsc;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -259,4 +534,251 @@ mod tests {
         assert_eq!(reminders.len(), 1);
         assert_eq!(reminders[0].message, "Test reminder");
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_concurrent_creates_and_cancels_keep_file_consistent() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = Arc::new(ReminderTool::new(temp_dir.path().to_str().unwrap().to_string()));
+        let create = |user: usize| {
+            let tool = Arc::clone(&tool);
+            tokio::spawn(async move {
+                tool.execute(json!({
+                    "message": format!("Reminder {}", user),
+                    "time": "2099-06-01 08:00:00",
+                    "user_id": format!("user{}", user),
+                    "channel": "telegram",
+                    "repeat": if user.is_multiple_of(2) { Some("daily") } else { None },
+                }))
+                .await
+                .unwrap()
+            })
+        };
+
+        for user in 0..10 {
+            create(user).await.unwrap();
+        }
+        // Cancel the first ten while twenty more are created alongside
+        let mut tasks: Vec<_> = (10..30).map(create).collect();
+        for user in 0..10 {
+            let tool = Arc::clone(&tool);
+            let id = format!("reminder_user{}_{}", user, parse_reminder_time("2099-06-01 08:00:00", Tz::UTC, Utc::now()).unwrap().timestamp());
+            tasks.push(tokio::spawn(async move { tool.execute(json!({"action": "cancel", "id": id})).await.unwrap() }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let saved: Vec<Reminder> =
+            serde_json::from_str(&std::fs::read_to_string(temp_dir.path().join("reminders.json")).unwrap()).unwrap();
+        let mut saved_ids: Vec<String> = saved.into_iter().map(|r| r.id).collect();
+        let mut memory_ids: Vec<String> = tool.reminders.read().await.iter().map(|r| r.id.clone()).collect();
+        saved_ids.sort();
+        memory_ids.sort();
+
+        assert_eq!(saved_ids.len(), 20);
+        assert!((0..10).all(|user| saved_ids.iter().all(|id| !id.starts_with(&format!("reminder_user{}_", user)))));
+        assert_eq!(saved_ids, memory_ids);
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_daily_chain() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = ReminderTool::new(temp_dir.path().to_str().unwrap().to_string());
+
+        tool.execute(json!({
+            "message": "Standup",
+            "time": "2099-01-01 09:00:00",
+            "user_id": "test_user",
+            "channel": "telegram",
+            "repeat": "daily"
+        })).await.unwrap();
+
+        let first = tool.reminders.read().await[0].clone();
+        let flag = tool.chain_flag(first.chain()).await;
+
+        // Before cancelling, the next occurrence is armed in the same chain
+        let second = schedule_next(&tool.reminders, &tool.workspace_path, &flag, &first).await.unwrap();
+        assert_eq!(second.chain_id, first.id);
+        assert_eq!(second.scheduled_time, first.scheduled_time + chrono::Duration::days(1));
+
+        // Cancelling by the occurrence id stops the whole chain
+        let result = tool.execute(json!({"action": "cancel", "id": second.id})).await.unwrap();
+        assert!(result.contains(&first.id));
+        assert!(tool.reminders.read().await.is_empty());
+
+        assert!(schedule_next(&tool.reminders, &tool.workspace_path, &flag, &second).await.is_none());
+        assert!(tool.reminders.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_local_time_is_converted_to_utc() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = ReminderTool::new(temp_dir.path().to_str().unwrap().to_string())
+            .with_timezone(parse_timezone("Asia/Jakarta").unwrap());
+
+        // Explicit zone: 09:00 in New York (EST, UTC-5) is 14:00 UTC
+        tool.execute(json!({
+            "message": "Call",
+            "time": "2099-01-15 09:00:00",
+            "timezone": "America/New_York",
+            "user_id": "test_user",
+            "channel": "telegram"
+        })).await.unwrap();
+
+        // No zone: falls back to the configured one (WIB, UTC+7)
+        tool.execute(json!({
+            "message": "Lunch",
+            "time": "2099-01-15 12:00:00",
+            "user_id": "test_user",
+            "channel": "telegram"
+        })).await.unwrap();
+
+        let reminders = tool.reminders.read().await;
+        assert_eq!(reminders[0].scheduled_time.to_rfc3339(), "2099-01-15T14:00:00+00:00");
+        assert_eq!(reminders[1].scheduled_time.to_rfc3339(), "2099-01-15T05:00:00+00:00");
+        drop(reminders);
+
+        let err = tool.execute(json!({
+            "message": "Call",
+            "time": "2099-01-15 09:00:00",
+            "timezone": "Mars/Olympus_Mons",
+            "user_id": "test_user",
+            "channel": "telegram"
+        })).await.unwrap_err();
+        assert!(err.contains("Unknown timezone 'Mars/Olympus_Mons'"));
+    }
+
+    #[test]
+    fn test_parse_reminder_time_formats() {
+        let jakarta = parse_timezone("Asia/Jakarta").unwrap();
+        let now = DateTime::parse_from_rfc3339("2030-03-10T08:00:00Z").unwrap().with_timezone(&Utc);
+
+        let iso = parse_reminder_time("2030-06-01T09:00:00+02:00", jakarta, now).unwrap();
+        assert_eq!(iso.to_rfc3339(), "2030-06-01T07:00:00+00:00");
+
+        let relative = parse_reminder_time("in 30 minutes", jakarta, now).unwrap();
+        assert_eq!(relative.to_rfc3339(), "2030-03-10T08:30:00+00:00");
+
+        // "Tomorrow" in Jakarta (15:00 local now) is March 11, 9am WIB
+        let tomorrow = parse_reminder_time("tomorrow 9am", jakarta, now).unwrap();
+        assert_eq!(tomorrow.to_rfc3339(), "2030-03-11T02:00:00+00:00");
+
+        let err = parse_reminder_time("when the cows come home", jakarta, now).unwrap_err();
+        assert!(err.starts_with("Could not understand time 'when the cows come home'"));
+        assert!(err.contains("in 30 minutes"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hourly_reminder_rearms_and_persists() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = ReminderTool::new(temp_dir.path().to_str().unwrap().to_string());
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        *tool.outbound_tx.lock().await = Some(tx);
+
+        let first_time = (Utc::now() + chrono::Duration::minutes(1)).format("%Y-%m-%d %H:%M:%S").to_string();
+        tool.execute(json!({
+            "message": "Drink water",
+            "time": first_time,
+            "user_id": "42",
+            "channel": "telegram",
+            "repeat": "hourly"
+        })).await.unwrap();
+        let first = tool.reminders.read().await[0].clone();
+
+        // Paused time auto-advances through each hour-long sleep
+        for _ in 0..3 {
+            let msg = rx.recv().await.unwrap();
+            assert!(msg.content.contains("Drink water"));
+        }
+
+        // The file is written under the same lock, so holding it keeps both in step
+        let reminders = tool.reminders.read().await;
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].chain_id, first.id);
+        assert!(reminders[0].scheduled_time >= first.scheduled_time + chrono::Duration::hours(2));
+
+        let saved: Vec<Reminder> =
+            serde_json::from_str(&std::fs::read_to_string(temp_dir.path().join("reminders.json")).unwrap()).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].id, reminders[0].id);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_restore_rearms_future_and_reports_missed() {
+        let temp_dir = TempDir::new().unwrap();
+        let now = Utc::now();
+        let reminder = |id: &str, message: &str, at: DateTime<Utc>, repeat: Option<&str>| Reminder {
+            id: id.to_string(),
+            user_id: "42".to_string(),
+            channel: "telegram".to_string(),
+            message: message.to_string(),
+            scheduled_time: at,
+            created_at: now,
+            repeat_pattern: repeat.map(String::from),
+            chain_id: String::new(),
+            missed: false,
+        };
+        let saved = vec![
+            reminder("future", "Dentist", now + chrono::Duration::hours(2), None),
+            reminder("past", "Pay rent", now - chrono::Duration::hours(3), None),
+            reminder("daily", "Standup", now - chrono::Duration::hours(25), Some("daily")),
+        ];
+        std::fs::write(temp_dir.path().join("reminders.json"), serde_json::to_string(&saved).unwrap()).unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let tool = ReminderTool::new(temp_dir.path().to_str().unwrap().to_string()).with_outbound_sender(tx);
+        assert_eq!(tool.restore().await.unwrap(), 2);
+
+        let missed = rx.try_recv().unwrap();
+        assert!(missed.content.contains("Pay rent") && missed.content.contains("missed"));
+
+        {
+            let reminders = tool.reminders.read().await;
+            assert_eq!(reminders.len(), 2);
+            let standup = reminders.iter().find(|r| r.chain() == "daily").unwrap();
+            assert_eq!(standup.scheduled_time, now + chrono::Duration::hours(23));
+        }
+        let on_disk: Vec<Reminder> =
+            serde_json::from_str(&std::fs::read_to_string(temp_dir.path().join("reminders.json")).unwrap()).unwrap();
+        assert_eq!(on_disk.len(), 2);
+
+        // The re-armed reminder actually fires
+        let fired = rx.recv().await.unwrap();
+        assert!(fired.content.contains("Dentist"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reminder_fired_without_sender_is_kept_as_missed() {
+        let temp_dir = TempDir::new().unwrap();
+        let workspace = temp_dir.path().to_str().unwrap().to_string();
+        let tool = ReminderTool::new(workspace.clone());
+
+        let due = (Utc::now() + chrono::Duration::minutes(1)).format("%Y-%m-%d %H:%M:%S").to_string();
+        tool.execute(json!({"message": "Call mum", "time": due, "user_id": "42", "channel": "telegram"})).await.unwrap();
+
+        let mut missed = None;
+        for _ in 0..120 {
+            sleep(std::time::Duration::from_secs(1)).await;
+            missed = tool.reminders.read().await.iter().find(|r| r.missed).cloned();
+            if missed.is_some() {
+                break;
+            }
+        }
+        let missed = missed.expect("the undelivered reminder should be marked missed");
+        assert_eq!(missed.message, "Call mum");
+        assert_eq!(tool.reminders.read().await.len(), 1);
+
+        let on_disk: Vec<Reminder> =
+            serde_json::from_str(&std::fs::read_to_string(temp_dir.path().join("reminders.json")).unwrap()).unwrap();
+        assert!(on_disk[0].missed);
+
+        // Once a sender exists, the next start reports it
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        let restarted = ReminderTool::new(workspace).with_outbound_sender(tx);
+        restarted.restore().await.unwrap();
+        let report = rx.try_recv().unwrap();
+        assert!(report.content.contains("Call mum") && report.content.contains("could not be delivered"));
+    }
+}
@@ -0,0 +1,246 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use crate::agent::tools::Tool;
+use crate::agent::tools::filesystem::validate_read_path;
+
+const MAX_MATCHES: usize = 200;
+const MAX_LINE_CHARS: usize = 300;
+
+/// Directories that are never worth searching.
+const SKIP_DIRS: &[&str] = &[".git", "node_modules", "target"];
+
+/// Translate a shell-style glob (`*.rs`, `src/**/mod.rs`) into an anchored regex.
+fn glob_to_regex(glob: &str) -> Result<Regex, String> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `**/` also matches zero directories
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+
+    Regex::new(&pattern).map_err(|e| format!("Invalid glob: {}", e))
+}
+
+pub struct GrepTool {
+    workspace: PathBuf,
+    allowed_dir: Option<PathBuf>,
+}
+
+impl GrepTool {
+    pub fn new(workspace: PathBuf, allowed_dir: Option<PathBuf>) -> Self {
+        Self { workspace, allowed_dir }
+    }
+
+    fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        let mut entries: Vec<_> = entries.flatten().collect();
+        entries.sort_by_key(|e| e.file_name());
+
+        for entry in entries {
+            // Don't follow symlinks: they could lead out of the allowed directory
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+
+            if file_type.is_dir() {
+                let name = entry.file_name();
+                if !SKIP_DIRS.iter().any(|skip| name == *skip) {
+                    Self::collect_files(&path, files);
+                }
+            } else if file_type.is_file() {
+                files.push(path);
+            }
+        }
+    }
+
+    fn search_file(path: &Path, display: &str, regex: &Regex, matches: &mut Vec<String>) {
+        let Ok(bytes) = std::fs::read(path) else {
+            return;
+        };
+        // Treat anything with a null byte near the start as binary
+        if bytes[..bytes.len().min(8192)].contains(&0) {
+            return;
+        }
+
+        let text = String::from_utf8_lossy(&bytes);
+        for (number, line) in text.lines().enumerate() {
+            if matches.len() >= MAX_MATCHES {
+                return;
+            }
+            if regex.is_match(line) {
+                let line: String = line.chars().take(MAX_LINE_CHARS).collect();
+                matches.push(format!("{}:{}: {}", display, number + 1, line.trim_end()));
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for GrepTool {
+    fn name(&self) -> &str { "grep" }
+
+    fn description(&self) -> &str {
+        "Search file contents with a regular expression. Returns matching lines as file:line: text"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Regular expression to search for"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "File or directory to search (defaults to the workspace)"
+                },
+                "glob": {
+                    "type": "string",
+                    "description": "Only search files matching this glob, e.g. *.rs or src/**/*.md"
+                }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        let pattern = args["pattern"].as_str().ok_or("Missing pattern parameter")?;
+        let regex = Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+        let glob = args["glob"].as_str().map(glob_to_regex).transpose()?;
+
+        let root = match args["path"].as_str() {
+            Some(path) if Path::new(path).is_relative() => self.workspace.join(path),
+            Some(path) => PathBuf::from(path),
+            None => self.workspace.clone(),
+        };
+        let root = validate_read_path(self.allowed_dir.as_deref(), &root.to_string_lossy())?;
+
+        if !root.exists() {
+            return Err(format!("Path not found: {}", root.display()));
+        }
+
+        let mut files = Vec::new();
+        if root.is_file() {
+            files.push(root.clone());
+        } else {
+            Self::collect_files(&root, &mut files);
+        }
+
+        let base = if root.is_file() { root.parent().unwrap_or(&root) } else { &root };
+        let mut matches = Vec::new();
+
+        for file in &files {
+            let relative = file.strip_prefix(base).unwrap_or(file).to_string_lossy().replace('\\', "/");
+            if let Some(glob) = &glob {
+                // Patterns without a slash match against the file name alone
+                let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                if !glob.is_match(&relative) && !glob.is_match(&name) {
+                    continue;
+                }
+            }
+
+            Self::search_file(file, &relative, &regex, &mut matches);
+            if matches.len() >= MAX_MATCHES {
+                break;
+            }
+        }
+
+        if matches.is_empty() {
+            return Ok("No matches found".to_string());
+        }
+
+        let mut output = matches.join("\n");
+        if matches.len() >= MAX_MATCHES {
+            output.push_str(&format!("\n... (stopped after {} matches)", MAX_MATCHES));
+        }
+        Ok(output)
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/agent")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+        fs::write(temp_dir.path().join("src/agent/mod.rs"), "pub fn run() {}\n").unwrap();
+        fs::write(temp_dir.path().join("notes.md"), "remember to run tests\n").unwrap();
+        fs::write(temp_dir.path().join("blob.bin"), b"run\0\x01\x02").unwrap();
+        temp_dir
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        assert!(glob_to_regex("*.rs").unwrap().is_match("main.rs"));
+        assert!(!glob_to_regex("*.rs").unwrap().is_match("src/main.rs"));
+        assert!(glob_to_regex("src/**/*.rs").unwrap().is_match("src/main.rs"));
+        assert!(glob_to_regex("src/**/*.rs").unwrap().is_match("src/agent/mod.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_grep_finds_matches_and_skips_binary() {
+        let temp_dir = workspace();
+        let tool = GrepTool::new(temp_dir.path().to_path_buf(), None);
+
+        let result = tool.execute(json!({"pattern": r"\brun\b"})).await.unwrap();
+        let lines: Vec<&str> = result.lines().collect();
+
+        assert_eq!(lines, vec![
+            "notes.md:1: remember to run tests",
+            "src/agent/mod.rs:1: pub fn run() {}",
+            "src/main.rs:2:     run();",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_grep_glob_filter() {
+        let temp_dir = workspace();
+        let tool = GrepTool::new(temp_dir.path().to_path_buf(), None);
+
+        let result = tool.execute(json!({"pattern": "run", "glob": "*.rs"})).await.unwrap();
+        assert!(result.contains("src/main.rs:2:"));
+        assert!(!result.contains("notes.md"));
+    }
+
+    #[tokio::test]
+    async fn test_grep_respects_allowed_dir() {
+        let temp_dir = workspace();
+        let other_dir = TempDir::new().unwrap();
+        let tool = GrepTool::new(temp_dir.path().to_path_buf(), Some(temp_dir.path().to_path_buf()));
+
+        let result = tool
+            .execute(json!({"pattern": "x", "path": other_dir.path().to_string_lossy()}))
+            .await;
+        assert!(result.is_err());
+    }
+}
@@ -1,14 +1,92 @@
 use async_trait::async_trait;
 use regex::Regex;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::Stdio;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
 use tokio::process::Command;
 use crate::agent::tools::Tool;
+use crate::config::default_shell_denylist;
+
+const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+const MAX_OUTPUT_CHARS: usize = 50_000;
+
+/// The environment shell commands run with: PATH plus `tools.shell_env`,
+/// where an empty value takes the gateway's own.
+pub(crate) fn command_env(env: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut vars = vec![("PATH".to_string(), DEFAULT_PATH.to_string())];
+    for (name, value) in env {
+        let value = if value.is_empty() {
+            match std::env::var(name) {
+                Ok(inherited) => inherited,
+                Err(_) => continue,
+            }
+        } else {
+            value.clone()
+        };
+        vars.retain(|(existing, _)| existing != name);
+        vars.push((name.clone(), value));
+    }
+    vars
+}
 
 pub struct ShellTool {
     working_dir: PathBuf,
     timeout_secs: u64,
+    /// Commands matching any of these are refused
+    denylist: Vec<Regex>,
+    /// When non-empty, every command in a pipeline or chain must start with one of these
+    allowlist: Vec<String>,
+    /// Variables passed to commands on top of PATH; an empty value inherits
+    /// the gateway's own value
+    env: HashMap<String, String>,
+    /// Shared by every shell tool so commands across all turns are bounded
+    /// (`tools.global_shell_concurrency`)
+    slots: Option<Arc<Semaphore>>,
+}
+
+fn compile_denylist(patterns: &[String]) -> Vec<Regex> {
+    patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(re) => Some(re),
+            Err(e) => {
+                tracing::warn!("Ignoring invalid shell_denylist pattern {}: {}", pattern, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// First word of every command in `command`, split on `;`, `|`, `&` and newlines.
+fn command_names(command: &str) -> Vec<&str> {
+    command
+        .split([';', '|', '&', '\n'])
+        .filter_map(|segment| segment.split_whitespace().next())
+        .collect()
+}
+
+/// Stdout, then stderr under its own header, so output on either stream is
+/// never hidden. The exit code is always reported.
+fn format_output(stdout: &str, stderr: &str, code: Option<i32>) -> String {
+    let mut body = stdout.to_string();
+    if !stderr.is_empty() {
+        if !body.is_empty() && !body.ends_with('\n') {
+            body.push('\n');
+        }
+        body.push_str("---stderr---\n");
+        body.push_str(stderr);
+    }
+    if !body.is_empty() && !body.ends_with('\n') {
+        body.push('\n');
+    }
+
+    match code {
+        Some(0) => format!("{}[exit code 0]", body),
+        code => format!("Error (exit {}):\n{}", code.unwrap_or(-1), body),
+    }
 }
 
 impl ShellTool {
@@ -16,30 +94,45 @@ impl ShellTool {
         Self {
             working_dir: PathBuf::from(working_dir),
             timeout_secs,
+            denylist: compile_denylist(&default_shell_denylist()),
+            allowlist: Vec::new(),
+            env: HashMap::new(),
+            slots: None,
         }
     }
 
+    /// Replace the built-in denylist and set an allowlist
+    /// (`tools.shell_denylist` / `tools.shell_allowlist`).
+    pub fn with_rules(mut self, denylist: &[String], allowlist: Vec<String>) -> Self {
+        self.denylist = compile_denylist(denylist);
+        self.allowlist = allowlist;
+        self
+    }
+
+    /// Extra environment for commands (`tools.shell_env`).
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Wait for one of these slots before running a command.
+    pub fn with_slots(mut self, slots: Arc<Semaphore>) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
+    fn command_env(&self) -> Vec<(String, String)> {
+        command_env(&self.env)
+    }
+
     fn sanitize_command(&self, command: &str) -> Result<String, String> {
-        // Check for dangerous commands
-        let dangerous_patterns = [
-            r"(?i)\bgit\s+clone\b",      // Prevent cloning repos
-            r"(?i)\bcurl\s+.*\|.*sh\b",  // Prevent piping curl to shell
-            r"(?i)\bwget\s+.*\|.*sh\b",  // Prevent piping wget to shell
-            r"(?i)\bmv\b.*?/(etc|bin|usr)\b",     // Prevent moving files to system dirs
-            r"(?i)\bchmod\b.*?/(etc|bin|usr)\b",  // Prevent changing perms in system dirs
-            r"(?i)\bchown\b.*?/(etc|bin|usr)\b",  // Prevent changing ownership in system dirs
-            r"(?i)\bmount\b",            // Prevent mounting
-            r"(?i)\bumount\b",           // Prevent unmounting
-            r"(?i)\bpkill\b",            // Prevent killing arbitrary processes
-            r"(?i)\bkillall\b",          // Prevent killing all processes by name
-            r"(?i)\bpasswd\b",           // Prevent password changes
-            r"(?i)\bshadow\b",           // Prevent access to shadow file
-        ];
+        if let Some(rule) = self.denylist.iter().find(|re| re.is_match(command)) {
+            return Err(format!("Command blocked by shell_denylist rule: {}", rule.as_str()));
+        }
 
-        for pattern in &dangerous_patterns {
-            let re = Regex::new(pattern).map_err(|e| format!("Regex error: {}", e))?;
-            if re.is_match(command) {
-                return Err(format!("Command contains potentially dangerous pattern: {}", pattern));
+        if !self.allowlist.is_empty() {
+            if let Some(name) = command_names(command).into_iter().find(|name| !self.allowlist.iter().any(|a| a == name)) {
+                return Err(format!("Command '{}' is not in shell_allowlist", name));
             }
         }
 
@@ -89,6 +182,11 @@ impl Tool for ShellTool {
         // Sanitize the command
         let sanitized_cmd = self.sanitize_command(command)?;
 
+        let _slot = match &self.slots {
+            Some(slots) => Some(slots.acquire().await.map_err(|e| format!("Shell slots closed: {}", e))?),
+            None => None,
+        };
+
         let mut cmd = Command::new("sh");
         cmd.arg("-c")
            .arg(&sanitized_cmd)
@@ -96,9 +194,9 @@ impl Tool for ShellTool {
            .stdout(Stdio::piped())
            .stderr(Stdio::piped());
 
-        // Add environment restrictions if needed
+        // Only PATH and the configured variables reach the command
         cmd.env_clear();
-        cmd.env("PATH", "/usr/local/bin:/usr/bin:/bin");
+        cmd.envs(self.command_env());
 
         let output = tokio::time::timeout(
             std::time::Duration::from_secs(self.timeout_secs),
@@ -111,22 +209,10 @@ impl Tool for ShellTool {
         let stdout = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
 
-        let result = if output.status.success() {
-            if stdout.is_empty() && !stderr.is_empty() {
-                stderr.to_string()
-            } else {
-                stdout.to_string()
-            }
-        } else {
-            format!("Error (exit {}): {}\n{}",
-                output.status.code().unwrap_or(-1),
-                stdout,
-                stderr
-            )
-        };
+        let result = format_output(&stdout, &stderr, output.status.code());
 
-        if result.len() > 50000 {
-            return Ok(format!("{}...[truncated]", &result[..50000]));
+        if result.chars().count() > MAX_OUTPUT_CHARS {
+            return Ok(format!("{}...[truncated]", result.chars().take(MAX_OUTPUT_CHARS).collect::<String>()));
         }
 
         Ok(result)
@@ -151,8 +237,7 @@ mod tests {
         let args = json!({"command": "echo hello"});
         let result = tool.execute(args).await.unwrap();
         
-        // The result should contain "hello" (may have trailing newline)
-        assert!(result.trim() == "hello");
+        assert_eq!(result, "hello\n[exit code 0]");
     }
 
     #[tokio::test]
@@ -169,6 +254,46 @@ mod tests {
         assert!(output.contains("Error")); // The shell tool formats non-successful executions with "Error" prefix
     }
 
+    #[tokio::test]
+    async fn test_output_includes_both_streams() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = ShellTool::new(temp_dir.path().to_string_lossy().to_string(), 10);
+
+        let result = tool.execute(json!({"command": "echo out; echo warn >&2"})).await.unwrap();
+        assert_eq!(result, "out\n---stderr---\nwarn\n[exit code 0]");
+
+        let result = tool.execute(json!({"command": "echo partial; echo boom >&2; exit 3"})).await.unwrap();
+        assert_eq!(result, "Error (exit 3):\npartial\n---stderr---\nboom\n");
+    }
+
+    #[tokio::test]
+    async fn test_shell_env_passes_configured_vars_only() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = ShellTool::new(temp_dir.path().to_string_lossy().to_string(), 10)
+            .with_env(HashMap::from([("LANG".to_string(), "C.UTF-8".to_string())]));
+
+        let result = tool.execute(json!({"command": "env"})).await.unwrap();
+        assert!(result.contains("LANG=C.UTF-8\n"), "{}", result);
+        assert!(!result.contains("HOME="), "{}", result);
+    }
+
+    #[tokio::test]
+    async fn test_global_limit_serializes_commands_from_different_turns() {
+        let temp_dir = TempDir::new().unwrap();
+        let slots = Arc::new(Semaphore::new(1));
+        let working_dir = temp_dir.path().to_string_lossy().to_string();
+        let first = ShellTool::new(working_dir.clone(), 10).with_slots(slots.clone());
+        let second = ShellTool::new(working_dir, 10).with_slots(slots);
+
+        let command = json!({"command": "echo start >> log; sleep 0.3; echo end >> log"});
+        let (a, b) = tokio::join!(first.execute(command.clone()), second.execute(command));
+        a.unwrap();
+        b.unwrap();
+
+        let log = std::fs::read_to_string(temp_dir.path().join("log")).unwrap();
+        assert_eq!(log, "start\nend\nstart\nend\n");
+    }
+
     #[test]
     fn test_sanitize_command_safe_commands() {
         let temp_dir = TempDir::new().unwrap();
@@ -209,6 +334,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_allowlist_only_mode() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = ShellTool::new(temp_dir.path().to_string_lossy().to_string(), 10)
+            .with_rules(&default_shell_denylist(), vec!["ls".to_string(), "grep".to_string()]);
+
+        assert!(tool.sanitize_command("ls -la | grep foo").is_ok());
+
+        let err = tool.sanitize_command("cat secrets.txt").unwrap_err();
+        assert_eq!(err, "Command 'cat' is not in shell_allowlist");
+        // Chaining can't smuggle in a second command
+        let err = tool.sanitize_command("ls; rm -rf data").unwrap_err();
+        assert!(err.contains("'rm'"));
+        assert!(tool.sanitize_command("ls && touch x").is_err());
+    }
+
+    #[test]
+    fn test_custom_denylist_replaces_defaults() {
+        let temp_dir = TempDir::new().unwrap();
+        let tool = ShellTool::new(temp_dir.path().to_string_lossy().to_string(), 10)
+            .with_rules(&[r"(?i)\brm\s+-rf\b".to_string()], Vec::new());
+
+        let err = tool.sanitize_command("rm -rf build").unwrap_err();
+        assert_eq!(err, r"Command blocked by shell_denylist rule: (?i)\brm\s+-rf\b");
+        // The built-in git clone rule is gone once the list is overridden
+        assert!(tool.sanitize_command("git clone https://github.com/santosobot/santosobot").is_ok());
+    }
+
     #[test]
     fn test_sanitize_command_invalid_characters() {
         let temp_dir = TempDir::new().unwrap();
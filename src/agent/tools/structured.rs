@@ -0,0 +1,437 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use crate::agent::tools::Tool;
+use crate::agent::tools::filesystem::{check_writable_extension, validate_read_path, validate_write_path};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+            Some("json") => Ok(Format::Json),
+            Some("yaml") | Some("yml") => Ok(Format::Yaml),
+            Some("toml") => Ok(Format::Toml),
+            _ => Err("Unsupported file type (expected .json, .yaml, .yml or .toml)".to_string()),
+        }
+    }
+
+    fn parse(&self, content: &str) -> Result<Value, String> {
+        match self {
+            Format::Json => serde_json::from_str(content).map_err(|e| format!("Invalid JSON: {}", e)),
+            Format::Yaml => serde_yaml::from_str(content).map_err(|e| format!("Invalid YAML: {}", e)),
+            Format::Toml => toml::from_str(content).map_err(|e| format!("Invalid TOML: {}", e)),
+        }
+    }
+
+    fn render(&self, value: &Value) -> Result<String, String> {
+        match self {
+            Format::Json => serde_json::to_string_pretty(value)
+                .map(|s| s + "\n")
+                .map_err(|e| format!("Failed to serialize JSON: {}", e)),
+            Format::Yaml => serde_yaml::to_string(value).map_err(|e| format!("Failed to serialize YAML: {}", e)),
+            Format::Toml => toml::to_string_pretty(value).map_err(|e| format!("Failed to serialize TOML: {}", e)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse a dotted path like `a.b[0].c` into segments.
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(pos) => (&part[..pos], &part[pos..]),
+            None => (part, ""),
+        };
+
+        if !key.is_empty() {
+            segments.push(Segment::Key(key.to_string()));
+        } else if rest.is_empty() {
+            return Err(format!("Invalid path: {}", path));
+        }
+
+        while let Some(stripped) = rest.strip_prefix('[') {
+            let end = stripped.find(']').ok_or_else(|| format!("Invalid path: {}", path))?;
+            let index = stripped[..end]
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid array index in path: {}", path))?;
+            segments.push(Segment::Index(index));
+            rest = &stripped[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            return Err(format!("Invalid path: {}", path));
+        }
+    }
+
+    Ok(segments)
+}
+
+fn get_at<'a>(root: &'a Value, segments: &[Segment]) -> Option<&'a Value> {
+    segments.iter().try_fold(root, |value, segment| match segment {
+        Segment::Key(key) => value.get(key),
+        Segment::Index(index) => value.get(*index),
+    })
+}
+
+fn set_at(root: &mut Value, segments: &[Segment], new_value: Value) -> Result<(), String> {
+    let Some((last, parents)) = segments.split_last() else {
+        *root = new_value;
+        return Ok(());
+    };
+
+    let mut current = root;
+    for (i, segment) in parents.iter().enumerate() {
+        current = match segment {
+            Segment::Key(key) => {
+                if current.is_null() {
+                    *current = json!({});
+                }
+                let map = current.as_object_mut().ok_or_else(|| format!("'{}' is not an object", key))?;
+                // Create missing intermediate containers based on the next segment
+                map.entry(key.clone()).or_insert_with(|| match parents.get(i + 1).unwrap_or(last) {
+                    Segment::Index(_) => json!([]),
+                    Segment::Key(_) => json!({}),
+                })
+            }
+            Segment::Index(index) => {
+                let array = current.as_array_mut().ok_or("Indexed value is not an array")?;
+                array.get_mut(*index).ok_or_else(|| format!("Index {} out of bounds", index))?
+            }
+        };
+    }
+
+    match last {
+        Segment::Key(key) => {
+            if current.is_null() {
+                *current = json!({});
+            }
+            current
+                .as_object_mut()
+                .ok_or_else(|| format!("Cannot set '{}' on a non-object", key))?
+                .insert(key.clone(), new_value);
+        }
+        Segment::Index(index) => {
+            let array = current.as_array_mut().ok_or("Indexed value is not an array")?;
+            if *index < array.len() {
+                array[*index] = new_value;
+            } else if *index == array.len() {
+                array.push(new_value);
+            } else {
+                return Err(format!("Index {} out of bounds", index));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn delete_at(root: &mut Value, segments: &[Segment]) -> Result<Value, String> {
+    let (last, parents) = segments.split_last().ok_or("Path cannot be empty for delete")?;
+
+    let mut current = root;
+    for segment in parents {
+        current = match segment {
+            Segment::Key(key) => current.get_mut(key.as_str()),
+            Segment::Index(index) => current.get_mut(*index),
+        }
+        .ok_or("Path not found")?;
+    }
+
+    match last {
+        Segment::Key(key) => current
+            .as_object_mut()
+            .and_then(|map| map.shift_remove(key))
+            .ok_or_else(|| format!("Key '{}' not found", key)),
+        Segment::Index(index) => {
+            let array = current.as_array_mut().ok_or("Indexed value is not an array")?;
+            if *index < array.len() {
+                Ok(array.remove(*index))
+            } else {
+                Err(format!("Index {} out of bounds", index))
+            }
+        }
+    }
+}
+
+fn contains_null(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Array(items) => items.iter().any(contains_null),
+        Value::Object(map) => map.values().any(contains_null),
+        _ => false,
+    }
+}
+
+pub struct StructuredEditTool {
+    allowed_dir: Option<PathBuf>,
+    /// Extensions set and delete may write (empty = any); see `tools.writable_extensions`
+    writable_extensions: Vec<String>,
+}
+
+impl StructuredEditTool {
+    pub fn new(allowed_dir: Option<PathBuf>) -> Self {
+        Self { allowed_dir, writable_extensions: Vec::new() }
+    }
+
+    /// Only let set and delete write files with these extensions.
+    pub fn with_writable_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.writable_extensions = extensions;
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for StructuredEditTool {
+    fn name(&self) -> &str { "structured_edit" }
+
+    fn description(&self) -> &str {
+        "Get, set or delete a value at a dotted path (e.g. a.b[0].c) in a JSON, YAML or TOML file"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path to the .json, .yaml/.yml or .toml file"
+                },
+                "action": {
+                    "type": "string",
+                    "enum": ["get", "set", "delete"],
+                    "description": "Operation to perform"
+                },
+                "key": {
+                    "type": "string",
+                    "description": "Dotted key path, e.g. server.ports[0]. Empty means the whole document for get"
+                },
+                "value": {
+                    "description": "New value for set (any JSON value)"
+                }
+            },
+            "required": ["path", "action"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        let path = args["path"].as_str().ok_or("Missing path parameter")?;
+        let action = args["action"].as_str().ok_or("Missing action parameter")?;
+        let key = args["key"].as_str().unwrap_or("");
+
+        // get only reads, so it is allowed wherever read_file is
+        let validated = if action == "get" {
+            validate_read_path(self.allowed_dir.as_deref(), path)?
+        } else {
+            validate_write_path(self.allowed_dir.as_deref(), path)?
+        };
+        let format = Format::from_path(&validated)?;
+
+        let content = std::fs::read_to_string(&validated)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        let mut document = format.parse(&content)?;
+
+        let segments = if key.is_empty() { Vec::new() } else { parse_path(key)? };
+
+        match action {
+            "get" => {
+                let value = get_at(&document, &segments).ok_or_else(|| format!("Key '{}' not found", key))?;
+                serde_json::to_string_pretty(value).map_err(|e| e.to_string())
+            }
+            "set" => {
+                check_writable_extension(&validated, &self.writable_extensions)?;
+                let value = args.get("value").cloned().ok_or("Missing value parameter")?;
+                if format == Format::Toml && contains_null(&value) {
+                    return Err("TOML has no null value; use action \"delete\" to remove a key".to_string());
+                }
+                set_at(&mut document, &segments, value)?;
+                std::fs::write(&validated, format.render(&document)?)
+                    .map_err(|e| format!("Failed to write file: {}", e))?;
+                Ok(format!("Set '{}' in {}", key, path))
+            }
+            "delete" => {
+                check_writable_extension(&validated, &self.writable_extensions)?;
+                delete_at(&mut document, &segments)?;
+                std::fs::write(&validated, format.render(&document)?)
+                    .map_err(|e| format!("Failed to write file: {}", e))?;
+                Ok(format!("Deleted '{}' from {}", key, path))
+            }
+            other => Err(format!("Unknown action: {}", other)),
+        }
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(
+            parse_path("a.b[0].c").unwrap(),
+            vec![
+                Segment::Key("a".to_string()),
+                Segment::Key("b".to_string()),
+                Segment::Index(0),
+                Segment::Key("c".to_string()),
+            ]
+        );
+        assert!(parse_path("a..b").is_err());
+        assert!(parse_path("a[x]").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_nested_key_in_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("config.json");
+        fs::write(&file, r#"{"name": "demo", "server": {"host": "localhost"}}"#).unwrap();
+
+        let tool = StructuredEditTool::new(Some(temp_dir.path().to_path_buf()));
+        tool.execute(json!({
+            "path": file.to_string_lossy(),
+            "action": "set",
+            "key": "server.tls.enabled",
+            "value": true
+        }))
+        .await
+        .unwrap();
+
+        let value: Value = serde_json::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(value["server"]["tls"]["enabled"], true);
+        assert_eq!(value["server"]["host"], "localhost");
+
+        // Key order is preserved on write
+        let keys: Vec<&String> = value.as_object().unwrap().keys().collect();
+        assert_eq!(keys, vec!["name", "server"]);
+    }
+
+    #[tokio::test]
+    async fn test_delete_array_element() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("data.yaml");
+        fs::write(&file, "items:\n  - a\n  - b\n  - c\n").unwrap();
+
+        let tool = StructuredEditTool::new(None);
+        tool.execute(json!({
+            "path": file.to_string_lossy(),
+            "action": "delete",
+            "key": "items[1]"
+        }))
+        .await
+        .unwrap();
+
+        let value: Value = serde_yaml::from_str(&fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(value["items"], json!(["a", "c"]));
+    }
+
+    #[tokio::test]
+    async fn test_get_from_toml() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("settings.toml");
+        fs::write(&file, "[agent]\nmodel = \"gpt-4o-mini\"\n").unwrap();
+
+        let tool = StructuredEditTool::new(None);
+        let result = tool
+            .execute(json!({"path": file.to_string_lossy(), "action": "get", "key": "agent.model"}))
+            .await
+            .unwrap();
+        assert_eq!(result, "\"gpt-4o-mini\"");
+    }
+
+    #[tokio::test]
+    async fn test_get_is_validated_as_a_read() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("settings.toml"), "[agent]\nmodel = \"gpt-4o-mini\"\n").unwrap();
+        // Inside the workspace once resolved, as read_file accepts it; writes refuse '../'
+        let path = temp_dir.path().join("sub/../settings.toml");
+
+        let tool = StructuredEditTool::new(Some(temp_dir.path().to_path_buf()));
+        let model = tool
+            .execute(json!({"path": path.to_string_lossy(), "action": "get", "key": "agent.model"}))
+            .await
+            .unwrap();
+        assert_eq!(model, "\"gpt-4o-mini\"");
+        let set = tool
+            .execute(json!({"path": path.to_string_lossy(), "action": "set", "key": "agent.model", "value": "x"}))
+            .await;
+        assert!(set.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_null_is_rejected_for_toml() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = temp_dir.path().join("settings.toml");
+        fs::write(&file, "[agent]\nmodel = \"gpt-4o-mini\"\n").unwrap();
+
+        let tool = StructuredEditTool::new(None);
+        for value in [json!(null), json!({"fallback": null})] {
+            let result = tool
+                .execute(json!({"path": file.to_string_lossy(), "action": "set", "key": "agent.model", "value": value}))
+                .await;
+            assert!(result.unwrap_err().contains("TOML has no null"));
+        }
+        assert_eq!(fs::read_to_string(&file).unwrap(), "[agent]\nmodel = \"gpt-4o-mini\"\n");
+    }
+
+    #[tokio::test]
+    async fn test_writes_respect_writable_extensions() {
+        let temp_dir = TempDir::new().unwrap();
+        let settings = temp_dir.path().join("settings.toml");
+        let dotfile = temp_dir.path().join(".prettierrc.json");
+        fs::write(&settings, "[agent]\nmodel = \"gpt-4o-mini\"\n").unwrap();
+        fs::write(&dotfile, r#"{"semi": true}"#).unwrap();
+
+        let tool = StructuredEditTool::new(Some(temp_dir.path().to_path_buf()))
+            .with_writable_extensions(vec!["json".to_string()]);
+
+        let set = tool
+            .execute(json!({"path": settings.to_string_lossy(), "action": "set", "key": "agent.model", "value": "x"}))
+            .await;
+        assert!(set.unwrap_err().contains("toml"));
+        let delete = tool
+            .execute(json!({"path": dotfile.to_string_lossy(), "action": "delete", "key": "semi"}))
+            .await;
+        assert!(delete.unwrap_err().contains("dotfiles"));
+        assert_eq!(fs::read_to_string(&dotfile).unwrap(), r#"{"semi": true}"#);
+
+        // Reading stays allowed
+        let model = tool
+            .execute(json!({"path": settings.to_string_lossy(), "action": "get", "key": "agent.model"}))
+            .await
+            .unwrap();
+        assert_eq!(model, "\"gpt-4o-mini\"");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_path_outside_workspace() {
+        let temp_dir = TempDir::new().unwrap();
+        let other_dir = TempDir::new().unwrap();
+        let file = other_dir.path().join("config.json");
+        fs::write(&file, "{}").unwrap();
+
+        let tool = StructuredEditTool::new(Some(temp_dir.path().to_path_buf()));
+        let result = tool
+            .execute(json!({"path": file.to_string_lossy(), "action": "set", "key": "a", "value": 1}))
+            .await;
+        assert!(result.is_err());
+    }
+}
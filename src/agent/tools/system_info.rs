@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::agent::tools::shell::command_env;
+use crate::agent::tools::Tool;
+
+/// Commands worth knowing about before writing a shell command
+const PROBED_COMMANDS: &[&str] = &[
+    "sh", "bash", "zsh", "fish", "python3", "python", "node", "deno", "ruby", "perl", "php",
+    "go", "cargo", "java", "git", "curl", "wget", "jq", "rg", "docker",
+];
+
+/// Variable names that look like they hold credentials are never shown
+const SECRET_NAME_PATTERN: &str = r"(?i)(key|token|secret|passw|pwd|credential|auth|cookie|session|private)";
+
+/// Describes the host shell commands run on: OS, the interpreters on the
+/// shell's PATH, CPU and memory, the working directory and the non-secret
+/// part of the shell's environment.
+pub struct SystemInfoTool {
+    working_dir: PathBuf,
+    /// Same as the shell tool's `tools.shell_env`, so the report matches
+    /// what commands actually see
+    env: HashMap<String, String>,
+    secret_names: Regex,
+}
+
+impl SystemInfoTool {
+    pub fn new(working_dir: PathBuf, env: HashMap<String, String>) -> Self {
+        Self {
+            working_dir,
+            env,
+            secret_names: Regex::new(SECRET_NAME_PATTERN).expect("valid secret name pattern"),
+        }
+    }
+
+    fn report(&self) -> String {
+        let env = command_env(&self.env);
+        let path = env.iter().find(|(name, _)| name == "PATH").map(|(_, value)| value.as_str()).unwrap_or("");
+
+        let mut lines = vec![
+            format!("os: {} ({}, {})", std::env::consts::OS, std::env::consts::FAMILY, std::env::consts::ARCH),
+            format!("working directory: {}", self.working_dir.display()),
+        ];
+
+        let cpus = std::thread::available_parallelism().map(|n| n.to_string()).unwrap_or_else(|_| "unknown".to_string());
+        lines.push(format!("cpus: {}", cpus));
+        lines.push(format!("memory: {}", memory_summary().unwrap_or_else(|| "unknown".to_string())));
+
+        let found: Vec<&str> = PROBED_COMMANDS.iter().copied().filter(|name| on_path(path, name)).collect();
+        lines.push(format!("available commands: {}", if found.is_empty() { "none found".to_string() } else { found.join(", ") }));
+
+        let mut shown: Vec<String> = Vec::new();
+        let mut hidden = 0;
+        for (name, value) in &env {
+            if self.secret_names.is_match(name) {
+                hidden += 1;
+            } else {
+                shown.push(format!("  {}={}", name, value));
+            }
+        }
+        shown.sort();
+        lines.push("shell environment:".to_string());
+        lines.extend(shown);
+        if hidden > 0 {
+            lines.push(format!("  ({} variable(s) hidden as secrets)", hidden));
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Whether `name` is a file in one of the `:`-separated PATH directories.
+fn on_path(path: &str, name: &str) -> bool {
+    path.split(':').filter(|dir| !dir.is_empty()).any(|dir| Path::new(dir).join(name).is_file())
+}
+
+/// Total and available memory from /proc/meminfo, where there is one.
+fn memory_summary() -> Option<String> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |key: &str| -> Option<u64> {
+        let line = meminfo.lines().find(|line| line.starts_with(key))?;
+        line[key.len()..].trim().trim_end_matches("kB").trim().parse().ok()
+    };
+    let total = field("MemTotal:")?;
+    Some(match field("MemAvailable:") {
+        Some(available) => format!("{} MiB total, {} MiB available", total / 1024, available / 1024),
+        None => format!("{} MiB total", total / 1024),
+    })
+}
+
+#[async_trait]
+impl Tool for SystemInfoTool {
+    fn name(&self) -> &str { "system_info" }
+
+    fn description(&self) -> &str {
+        "Show the OS, CPU and memory, working directory, available shells and interpreters, and the non-secret environment shell commands run with. Check this before writing shell commands that depend on the platform"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {}
+        })
+    }
+
+    async fn execute(&self, _args: Value) -> Result<String, String> {
+        Ok(self.report())
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_reports_os_and_hides_secret_variables() {
+        let temp_dir = TempDir::new().unwrap();
+        let env = HashMap::from([
+            ("LANG".to_string(), "C.UTF-8".to_string()),
+            ("GITHUB_TOKEN".to_string(), "ghp_supersecret".to_string()),
+            ("DB_PASSWORD".to_string(), "hunter2".to_string()),
+        ]);
+        let tool = SystemInfoTool::new(temp_dir.path().to_path_buf(), env);
+
+        let report = tool.execute(json!({})).await.unwrap();
+        assert!(report.contains(&format!("os: {} (", std::env::consts::OS)), "{}", report);
+        assert!(report.contains(&format!("working directory: {}", temp_dir.path().display())), "{}", report);
+        assert!(report.contains("  LANG=C.UTF-8"), "{}", report);
+        assert!(report.contains("  PATH="), "{}", report);
+        assert!(!report.contains("GITHUB_TOKEN") && !report.contains("ghp_supersecret"), "{}", report);
+        assert!(!report.contains("hunter2"), "{}", report);
+        assert!(report.contains("(2 variable(s) hidden as secrets)"), "{}", report);
+    }
+}
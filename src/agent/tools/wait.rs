@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::time::Duration;
+use crate::agent::tools::Tool;
+
+/// Pauses the turn for a few seconds, so the agent can start something,
+/// wait, and check on it. Each call is capped; `agent.max_iterations` bounds
+/// how many times it can be called in a row.
+pub struct WaitTool {
+    max_secs: u64,
+}
+
+impl WaitTool {
+    pub fn new(max_secs: u64) -> Self {
+        Self { max_secs }
+    }
+}
+
+#[async_trait]
+impl Tool for WaitTool {
+    fn name(&self) -> &str { "wait" }
+
+    fn description(&self) -> &str {
+        "Pause for a number of seconds before continuing, e.g. to let a build or download make progress before checking on it"
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "seconds": {
+                    "type": "integer",
+                    "description": format!("How long to wait, 1 to {} seconds", self.max_secs)
+                }
+            },
+            "required": ["seconds"]
+        })
+    }
+
+    async fn execute(&self, args: Value) -> Result<String, String> {
+        let seconds = args["seconds"].as_u64().ok_or("Missing or invalid seconds (expected a positive integer)")?;
+        if seconds == 0 {
+            return Err("seconds must be at least 1".to_string());
+        }
+        if seconds > self.max_secs {
+            return Err(format!("Can wait at most {}s at a time; asked for {}s", self.max_secs, seconds));
+        }
+
+        tokio::time::sleep(Duration::from_secs(seconds)).await;
+        Ok(format!("Waited {}s", seconds))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Instant;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_waits_the_requested_time_up_to_the_cap() {
+        let tool = WaitTool::new(120);
+
+        let start = Instant::now();
+        assert_eq!(tool.execute(json!({"seconds": 30})).await.unwrap(), "Waited 30s");
+        assert_eq!(start.elapsed().as_secs(), 30);
+
+        let start = Instant::now();
+        let err = tool.execute(json!({"seconds": 3600})).await.unwrap_err();
+        assert_eq!(err, "Can wait at most 120s at a time; asked for 3600s");
+        assert!(tool.execute(json!({"seconds": 0})).await.is_err());
+        assert!(tool.execute(json!({"seconds": -5})).await.is_err());
+        assert_eq!(start.elapsed().as_secs(), 0);
+    }
+}
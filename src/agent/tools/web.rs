@@ -1,12 +1,22 @@
 use async_trait::async_trait;
 use regex::Regex;
 use serde_json::{json, Value};
-use url::Url;
-use reqwest::Client;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use url::{Host, Url};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{redirect, Client};
 use crate::agent::tools::Tool;
 
+/// Redirect hops followed before giving up.
+const MAX_REDIRECTS: usize = 10;
+
 pub struct WebFetchTool {
     client: Client,
+    /// Shared with brave_search to bound requests across all turns
+    /// (`tools.global_web_concurrency`)
+    slots: Option<Arc<Semaphore>>,
 }
 
 impl WebFetchTool {
@@ -14,60 +24,124 @@ impl WebFetchTool {
         Self {
             client: Client::builder()
                 .timeout(std::time::Duration::from_secs(30))
+                .redirect(redirect_policy())
+                .dns_resolver(Arc::new(PublicOnlyResolver))
                 .build()
                 .expect("Failed to create HTTP client"),
+            slots: None,
         }
     }
 
+    /// Wait for one of these slots before each request.
+    pub fn with_slots(mut self, slots: Arc<Semaphore>) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
     fn validate_url(&self, url_str: &str) -> Result<Url, String> {
         // Basic URL validation
         let url = Url::parse(url_str)
             .map_err(|_| "Invalid URL format".to_string())?;
+        check_url(&url)?;
+        Ok(url)
+    }
+}
+
+/// Reject URLs the tool must never reach: non-HTTP schemes and hosts that
+/// are, or are written as, local and private network addresses. Numeric
+/// forms like `http://2130706433/` are normalized by the URL parser first.
+fn check_url(url: &Url) -> Result<(), String> {
+    // Check scheme
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err("Only http and https schemes are allowed".to_string());
+    }
 
-        // Check scheme
-        if url.scheme() != "http" && url.scheme() != "https" {
-            return Err("Only http and https schemes are allowed".to_string());
+    let blocked = match url.host() {
+        None => return Err("URL must have a host".to_string()),
+        Some(Host::Ipv4(ip)) => is_blocked_ip(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => is_blocked_ip(IpAddr::V6(ip)),
+        Some(Host::Domain(host)) => host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost"),
+    };
+    if blocked {
+        return Err("Access to local/network addresses is not allowed".to_string());
+    }
+
+    // Block URLs with suspicious patterns
+    let dangerous_patterns = [
+        r"(?i)(admin|root|passwd|shadow|etc|var|proc)",
+    ];
+    
+    let host = url.host_str().unwrap_or_default();
+    for pattern in &dangerous_patterns {
+        let re = Regex::new(pattern).map_err(|e| format!("Regex error: {}", e))?;
+        if re.is_match(host) {
+            return Err(format!("URL contains potentially dangerous pattern: {}", pattern));
         }
+    }
 
-        // Block certain domains/IP ranges that are typically internal
-        let host = url.host_str().ok_or("URL must have a host")?;
-        
-        // Block private IP ranges and localhost
-        if host == "localhost" ||
-           host.starts_with("127.") ||
-           host.starts_with("10.") ||
-           host.starts_with("192.168.") ||
-           (host.starts_with("172.") && {
-               // Check if it's in the 172.16.0.0 - 172.31.255.255 range
-               let parts: Vec<&str> = host.split('.').collect();
-               if parts.len() >= 2 {
-                   if let Ok(second_octet) = parts[1].parse::<u8>() {
-                       (16..=31).contains(&second_octet)
-                   } else {
-                       false
-                   }
-               } else {
-                   false
-               }
-           }) ||
-           host.starts_with("0.") ||
-           host.starts_with("169.254.") {
-            return Err("Access to local/network addresses is not allowed".to_string());
+    Ok(())
+}
+
+/// Loopback, private, link-local (incl. cloud metadata), and other
+/// non-public addresses.
+fn is_blocked_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.octets()[0] == 0
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (ip.octets()[0] == 100 && (64..=127).contains(&ip.octets()[1]))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_blocked_ip(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
         }
+    }
+}
 
-        // Block URLs with suspicious patterns
-        let dangerous_patterns = [
-            r"(?i)(admin|root|passwd|shadow|etc|var|proc)",
-        ];
-        
-        for pattern in &dangerous_patterns {
-            let re = Regex::new(pattern).map_err(|e| format!("Regex error: {}", e))?;
-            if re.is_match(host) {
-                return Err(format!("URL contains potentially dangerous pattern: {}", pattern));
+/// Follow redirects only to URLs that pass the same checks as the first one,
+/// so a public page can't bounce the request to an internal address.
+fn redirect_policy() -> redirect::Policy {
+    redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+        match check_url(attempt.url()) {
+            Ok(()) => attempt.follow(),
+            Err(e) => {
+                let message = format!("Redirect to {} blocked: {}", attempt.url(), e);
+                attempt.error(message)
             }
         }
+    })
+}
 
-        Ok(url)
+/// Resolves hostnames but only hands out public addresses, so a name that
+/// points at 127.0.0.1 or a private range can't be connected to.
+struct PublicOnlyResolver;
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let resolved = tokio::net::lookup_host((host.as_str(), 0)).await?;
+            let public: Vec<SocketAddr> = resolved.filter(|addr| !is_blocked_ip(addr.ip())).collect();
+            if public.is_empty() {
+                return Err(format!("{} resolves only to local/network addresses", host).into());
+            }
+            Ok(Box::new(public.into_iter()) as Addrs)
+        })
     }
 }
 
@@ -109,12 +183,21 @@ impl Tool for WebFetchTool {
             .as_u64()
             .unwrap_or(10000) as usize;
 
+        let _slot = match &self.slots {
+            Some(slots) => Some(slots.acquire().await.map_err(|e| format!("Web slots closed: {}", e))?),
+            None => None,
+        };
+
         let response = self.client
             .get(validated_url)
             .header("User-Agent", "Mozilla/5.0 (compatible; Santosobot/1.0)")
             .send()
             .await
-            .map_err(|e| format!("Failed to fetch URL: {}", e))?;
+            .map_err(|e| match std::error::Error::source(&e) {
+                // Name the blocked redirect or address instead of a generic failure
+                Some(cause) if e.is_redirect() || e.is_connect() => format!("Failed to fetch URL: {}", cause),
+                _ => format!("Failed to fetch URL: {}", e),
+            })?;
 
         if !response.status().is_success() {
             return Err(format!("HTTP error: {}", response.status()));
@@ -133,8 +216,8 @@ impl Tool for WebFetchTool {
 
         let text = extract_text(&text);
 
-        if text.len() > max_length {
-            return Ok(format!("{}...[truncated]", &text[..max_length]));
+        if text.chars().count() > max_length {
+            return Ok(format!("{}...[truncated]", text.chars().take(max_length).collect::<String>()));
         }
 
         Ok(text)
@@ -151,47 +234,48 @@ impl Default for WebFetchTool {
     }
 }
 
+/// Elements that never carry the page's content.
+const HIDDEN_CSS: &str = "script, style, noscript, nav, template { display: none; }";
+
+/// Wide enough that lines are only broken where the page has block structure.
+const RENDER_WIDTH: usize = 2000;
+
+/// Readable text of an HTML page: scripts, styles and navigation dropped,
+/// paragraphs and list items on their own lines, entities decoded.
 fn extract_text(html: &str) -> String {
+    let rendered = html2text::config::plain_no_decorate()
+        .add_css(HIDDEN_CSS)
+        .and_then(|config| config.string_from_read(html.as_bytes(), RENDER_WIDTH));
+
+    let text = match rendered {
+        Ok(text) => text,
+        Err(e) => {
+            tracing::warn!("Failed to render HTML, returning it as is: {}", e);
+            return html.to_string();
+        }
+    };
+
+    // Keep paragraph breaks but never more than one blank line in a row
     let mut result = String::new();
-    let mut in_script = false;
-    let mut in_style = false;
-    
-    let chars: Vec<char> = html.chars().collect();
-    let mut i = 0;
-    
-    while i < chars.len() {
-        if chars[i..].starts_with(&['<', 's', 'c', 'r', 'i', 'p', 't'][..]) {
-            in_script = true;
-        } else if chars[i..].starts_with(&['<', '/', 's', 'c', 'r', 'i', 'p', 't'][..]) {
-            in_script = false;
-        } else if chars[i..].starts_with(&['<', 's', 't', 'y', 'l', 'e'][..]) {
-            in_style = true;
-        } else if chars[i..].starts_with(&['<', '/', 's', 't', 'y', 'l', 'e'][..]) {
-            in_style = false;
-        } else if chars[i] == '<' {
-            if let Some(end) = chars[i..].iter().position(|&c| c == '>') {
-                i += end + 1;
-                continue;
+    let mut blank = true;
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            if !blank {
+                result.push('\n');
             }
+            blank = true;
+        } else {
+            result.push_str(line);
+            result.push('\n');
+            blank = false;
         }
-        
-        if !in_script && !in_style {
-            result.push(chars[i]);
-        }
-        
-        i += 1;
     }
-    
-    result
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ")
+    result.trim_end().to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio_test;
 
     #[test]
     fn test_validate_url_valid_urls() {
@@ -288,4 +372,85 @@ mod tests {
         assert!(!extracted.contains("alert"));
         assert!(!extracted.contains("color: red"));
     }
+
+    #[test]
+    fn test_extract_text_decodes_entities() {
+        let html = "<p>Fish &amp; chips &lt;b&gt; for &#8364;5 at the caf&eacute;</p>";
+        let extracted = extract_text(html);
+        assert_eq!(extracted, "Fish & chips <b> for €5 at the café");
+    }
+
+    #[test]
+    fn test_extract_text_drops_scripts_with_attributes_and_navigation() {
+        let html = r#"
+        <html>
+            <body>
+                <nav><a href="/">Home</a> <a href="/about">About</a></nav>
+                <SCRIPT type="text/javascript">var tag = "<p>not content</p>";</SCRIPT>
+                <script src="app.js" defer></script>
+                <h1>Title</h1>
+                <p>First paragraph.</p>
+                <ul><li>one</li><li>two</li></ul>
+            </body>
+        </html>"#;
+
+        let extracted = extract_text(html);
+        assert!(!extracted.contains("not content"));
+        assert!(!extracted.contains("var tag"));
+        assert!(!extracted.contains("Home"));
+
+        let lines: Vec<&str> = extracted.lines().filter(|l| !l.is_empty()).collect();
+        assert!(lines[0].ends_with("Title"));
+        assert_eq!(lines[1], "First paragraph.");
+        assert!(lines[2].ends_with("one"));
+        assert!(lines[3].ends_with("two"));
+    }
+
+    #[test]
+    fn test_validate_url_catches_disguised_local_hosts() {
+        let tool = WebFetchTool::new();
+        for url in [
+            "http://2130706433/",
+            "http://0x7f.1/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://[fd00::1]/",
+            "http://api.localhost/",
+        ] {
+            assert!(tool.validate_url(url).is_err(), "URL '{}' should be blocked", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolver_refuses_names_pointing_at_loopback() {
+        let name: Name = "localhost".parse().unwrap();
+        let err = PublicOnlyResolver.resolve(name).await.err().unwrap();
+        assert!(err.to_string().contains("local/network addresses"));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_to_metadata_address_is_blocked() {
+        // A "public" page that bounces to the cloud metadata endpoint
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            use tokio::io::{AsyncReadExt, AsyncWriteExt};
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = socket.read(&mut buf).await;
+                let response = "HTTP/1.1 302 Found\r\nLocation: http://169.254.169.254/latest/meta-data/\r\nContent-Length: 0\r\n\r\n";
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        // The first hop goes straight to the test server; only the redirect is checked here
+        let tool = WebFetchTool::new();
+        let err = tool.client.get(format!("http://{}/", addr)).send().await.unwrap_err();
+
+        assert!(err.is_redirect());
+        let cause = std::error::Error::source(&err).unwrap().to_string();
+        assert!(cause.contains("169.254.169.254"), "{}", cause);
+        assert!(cause.contains("not allowed"), "{}", cause);
+    }
 }
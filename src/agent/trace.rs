@@ -0,0 +1,56 @@
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Machine-readable JSON Lines trace of each agent loop (`agent.trace_file`):
+/// requests, raw responses, tool calls and results, and the final answer.
+/// Separate from the `tracing` logs, and never fails a turn.
+pub struct TraceLog {
+    path: PathBuf,
+    /// Turns running side by side must not interleave partial lines
+    write_lock: Mutex<()>,
+}
+
+impl TraceLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Append one event for conversation `session`. `fields` must be an
+    /// object; its keys are merged into the line.
+    pub async fn record(&self, session: &str, event: &str, fields: Value) {
+        let mut entry = json!({
+            "timestamp": Utc::now(),
+            "session": session,
+            "event": event,
+        });
+        if let (Some(entry), Value::Object(fields)) = (entry.as_object_mut(), fields) {
+            entry.extend(fields);
+        }
+
+        if let Err(e) = self.append(&format!("{}\n", entry)).await {
+            tracing::warn!("Failed to write trace file {}: {}", self.path.display(), e);
+        }
+    }
+
+    async fn append(&self, line: &str) -> std::io::Result<()> {
+        let _guard = self.write_lock.lock().await;
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        // A tokio file finishes writes in the background; wait for it so the
+        // next event can't land first
+        file.flush().await
+    }
+}
@@ -0,0 +1,185 @@
+use futures::StreamExt;
+use tokio::time::{Duration, Instant};
+
+use crate::providers::{ChatMessage, LLMProvider, StreamEvent, Usage};
+
+/// Timings of one benchmark request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunTiming {
+    /// Until the first content arrived; None if the reply was empty
+    pub first_token: Option<Duration>,
+    pub total: Duration,
+    pub completion_tokens: u32,
+}
+
+impl RunTiming {
+    /// Completion tokens per second over the whole request, when usage was reported.
+    pub fn tokens_per_sec(&self) -> Option<f64> {
+        (self.completion_tokens > 0 && !self.total.is_zero())
+            .then(|| self.completion_tokens as f64 / self.total.as_secs_f64())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub min: f64,
+    pub median: f64,
+    pub p95: f64,
+}
+
+/// Min, median and nearest-rank 95th percentile; None for no samples.
+pub fn summarize(mut samples: Vec<f64>) -> Option<Stats> {
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_by(f64::total_cmp);
+
+    let n = samples.len();
+    let median = if n.is_multiple_of(2) {
+        (samples[n / 2 - 1] + samples[n / 2]) / 2.0
+    } else {
+        samples[n / 2]
+    };
+    let p95_rank = (n as f64 * 0.95).ceil() as usize;
+
+    Some(Stats {
+        min: samples[0],
+        median,
+        p95: samples[p95_rank.max(1) - 1],
+    })
+}
+
+/// Send `prompt` once as a streamed request and time it.
+pub async fn run_once(
+    provider: &dyn LLMProvider,
+    prompt: &str,
+    model: &str,
+    max_tokens: u32,
+) -> Result<RunTiming, String> {
+    let started = Instant::now();
+    let mut stream = provider
+        .chat_stream(vec![ChatMessage::user(prompt)], None, None, Some(model.to_string()), None, Some(max_tokens))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut first_token = None;
+    let mut usage = Usage::default();
+    while let Some(event) = stream.next().await {
+        match event.map_err(|e| e.to_string())? {
+            StreamEvent::Content(delta) if !delta.is_empty() => {
+                first_token.get_or_insert_with(|| started.elapsed());
+            }
+            StreamEvent::Usage(reported) => usage.add(&reported),
+            _ => {}
+        }
+    }
+
+    Ok(RunTiming {
+        first_token,
+        total: started.elapsed(),
+        completion_tokens: usage.completion_tokens,
+    })
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// One line per measurement: "label  min  median  p95".
+pub fn report(runs: &[RunTiming]) -> String {
+    let rows = [
+        ("Time to first token (ms)", summarize(runs.iter().filter_map(|r| r.first_token).map(millis).collect())),
+        ("Total latency (ms)", summarize(runs.iter().map(|r| millis(r.total)).collect())),
+        ("Tokens/sec", summarize(runs.iter().filter_map(RunTiming::tokens_per_sec).collect())),
+    ];
+
+    let mut lines = vec![format!("{:<26} {:>10} {:>10} {:>10}", "", "min", "median", "p95")];
+    for (label, stats) in rows {
+        lines.push(match stats {
+            Some(s) => format!("{:<26} {:>10.1} {:>10.1} {:>10.1}", label, s.min, s.median, s.p95),
+            None => format!("{:<26} {:>10} {:>10} {:>10}", label, "-", "-", "-"),
+        });
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{ChatStream, LLMResponse, ToolChoice, ToolDefinition};
+    use async_trait::async_trait;
+
+    /// Waits `delay` before each of two content chunks, then reports usage.
+    struct SlowProvider {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl LLMProvider for SlowProvider {
+        async fn chat(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _tools: Option<Vec<ToolDefinition>>,
+            _tool_choice: Option<ToolChoice>,
+            _model: Option<String>,
+            _temperature: Option<f32>,
+            _max_tokens: Option<u32>,
+        ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+            Err("bench only streams".into())
+        }
+
+        async fn chat_stream(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _tools: Option<Vec<ToolDefinition>>,
+            _tool_choice: Option<ToolChoice>,
+            _model: Option<String>,
+            _temperature: Option<f32>,
+            _max_tokens: Option<u32>,
+        ) -> Result<ChatStream, Box<dyn std::error::Error + Send + Sync>> {
+            let delay = self.delay;
+            let usage = Usage { prompt_tokens: 5, completion_tokens: 50, total_tokens: 55 };
+            let events = vec![StreamEvent::Content("Hello".into()), StreamEvent::Content(" there".into()), StreamEvent::Usage(usage)];
+            Ok(futures::stream::iter(events)
+                .then(move |event| async move {
+                    if !matches!(event, StreamEvent::Usage(_)) {
+                        tokio::time::sleep(delay).await;
+                    }
+                    Ok(event)
+                })
+                .boxed())
+        }
+    }
+
+    #[test]
+    fn test_summary_statistics() {
+        let stats = summarize(vec![300.0, 100.0, 200.0, 400.0]).unwrap();
+        assert_eq!(stats, Stats { min: 100.0, median: 250.0, p95: 400.0 });
+
+        // Nearest rank: the 95th of 20 samples is the 19th smallest
+        let stats = summarize((1..=20).map(f64::from).collect()).unwrap();
+        assert_eq!((stats.min, stats.median, stats.p95), (1.0, 10.5, 19.0));
+
+        assert_eq!(summarize(vec![7.0]).unwrap(), Stats { min: 7.0, median: 7.0, p95: 7.0 });
+        assert_eq!(summarize(Vec::new()), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_runs_are_timed_against_a_mock_provider() {
+        let mut runs = Vec::new();
+        for delay_ms in [100, 300, 200] {
+            let provider = SlowProvider { delay: Duration::from_millis(delay_ms) };
+            runs.push(run_once(&provider, "Say hello", "test-model", 64).await.unwrap());
+        }
+
+        assert_eq!(runs[0].first_token, Some(Duration::from_millis(100)));
+        assert_eq!(runs[0].total, Duration::from_millis(200));
+        assert_eq!(runs[0].tokens_per_sec(), Some(250.0));
+
+        let report = report(&runs);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[1].split_whitespace().collect::<Vec<_>>()[5..], ["100.0", "200.0", "300.0"]);
+        assert_eq!(lines[2].split_whitespace().collect::<Vec<_>>()[3..], ["200.0", "400.0", "600.0"]);
+        assert_eq!(lines[3].split_whitespace().collect::<Vec<_>>()[1..], ["83.3", "125.0", "250.0"]);
+    }
+}
@@ -35,6 +35,13 @@ impl InboundMessage {
         self.metadata = metadata;
         self
     }
+
+    /// Build a reply to this message, carrying its metadata (e.g. topic thread id)
+    /// so channels can route the answer back to the same place.
+    pub fn reply(&self, content: impl Into<String>) -> OutboundMessage {
+        OutboundMessage::new(self.channel.clone(), self.chat_id.clone(), content.into())
+            .with_metadata(self.metadata.clone())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,6 +53,8 @@ pub struct OutboundMessage {
     pub metadata: HashMap<String, String>,
     pub message_id: Option<i64>,
     pub is_streaming: bool,
+    /// Said on the way to the answer, e.g. alongside tool calls
+    pub is_interim: bool,
 }
 
 impl OutboundMessage {
@@ -57,6 +66,7 @@ impl OutboundMessage {
             metadata: HashMap::new(),
             message_id: None,
             is_streaming: false,
+            is_interim: false,
         }
     }
 
@@ -72,11 +82,19 @@ impl OutboundMessage {
         self
     }
 
-    #[allow(dead_code)]
+    /// Mark as a partial reply; later partials and the final reply to the
+    /// same chat replace it rather than adding new messages.
     pub fn streaming(mut self) -> Self {
         self.is_streaming = true;
         self
     }
+
+    /// Mark as text sent before the turn's answer. Chats show it as usual;
+    /// channels that answer a request with one reply wait for the final one.
+    pub fn interim(mut self) -> Self {
+        self.is_interim = true;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -132,6 +150,26 @@ mod tests {
         assert_eq!(msg.metadata.get("key1").unwrap(), "value1");
     }
 
+    #[test]
+    fn test_reply_carries_metadata() {
+        let mut metadata = HashMap::new();
+        metadata.insert("message_thread_id".to_string(), "7".to_string());
+
+        let msg = InboundMessage::new(
+            "telegram".to_string(),
+            "user123".to_string(),
+            "chat456".to_string(),
+            "Hello!".to_string(),
+        )
+        .with_metadata(metadata);
+
+        let reply = msg.reply("Hi!");
+        assert_eq!(reply.channel, "telegram");
+        assert_eq!(reply.chat_id, "chat456");
+        assert_eq!(reply.content, "Hi!");
+        assert_eq!(reply.metadata.get("message_thread_id").unwrap(), "7");
+    }
+
     #[test]
     fn test_outbound_message_with_metadata() {
         let mut metadata = HashMap::new();
@@ -65,7 +65,6 @@ impl Default for MessageBus {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio_test;
 
     #[tokio::test]
     async fn test_message_bus_creation() {
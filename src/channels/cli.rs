@@ -1,7 +1,143 @@
 use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use crate::agent::StreamProgress;
 use crate::bus::{InboundMessage, OutboundMessage};
 
+const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const PLAIN_SPINNER_FRAMES: &[&str] = &["|", "/", "-", "\\"];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+/// Return to the start of the line and erase it
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// What the CLI shows while waiting for the first token, and which tool is
+/// running. Each transition returns the text to write to stderr, if any.
+pub struct ThinkingIndicator {
+    enabled: bool,
+    plain: bool,
+    frames: &'static [&'static str],
+    /// Index of the next frame while spinning
+    frame: Option<usize>,
+    /// A tool status line is on screen
+    status_shown: bool,
+}
+
+/// "🔧 read_file(config.toml)… done (12ms)"; `outcome` is None while running.
+pub fn tool_status(name: &str, argument: &str, outcome: Option<(bool, Duration)>, plain: bool) -> String {
+    let ellipsis = if plain { "..." } else { "…" };
+    let mut line = crate::ui::decorate("🔧", &format!("{}({}){}", name, argument, ellipsis), plain);
+    if let Some((ok, elapsed)) = outcome {
+        let elapsed = if elapsed < Duration::from_secs(1) {
+            format!("{}ms", elapsed.as_millis())
+        } else {
+            format!("{:.1}s", elapsed.as_secs_f64())
+        };
+        line.push_str(&format!(" {} ({})", if ok { "done" } else { "failed" }, elapsed));
+    }
+    line
+}
+
+impl ThinkingIndicator {
+    pub fn new(enabled: bool, plain: bool) -> Self {
+        Self {
+            enabled,
+            plain,
+            frames: if plain { PLAIN_SPINNER_FRAMES } else { SPINNER_FRAMES },
+            frame: None,
+            status_shown: false,
+        }
+    }
+
+    pub fn is_spinning(&self) -> bool {
+        self.frame.is_some()
+    }
+
+    pub fn on_progress(&mut self, progress: StreamProgress) -> Option<String> {
+        match progress {
+            StreamProgress::RequestStarted if self.enabled && !self.is_spinning() => {
+                self.frame = Some(0);
+                // The status line may be longer than the spinner
+                let clear = if std::mem::take(&mut self.status_shown) { CLEAR_LINE } else { "" };
+                self.tick().map(|frame| format!("{}{}", clear, frame))
+            }
+            StreamProgress::FirstToken => self.stop(),
+            StreamProgress::ToolStarted { name, argument } if self.enabled => {
+                self.frame = None;
+                self.status_shown = true;
+                Some(format!("{}{}", CLEAR_LINE, tool_status(&name, &argument, None, self.plain)))
+            }
+            StreamProgress::ToolFinished { name, argument, elapsed, ok } if self.enabled => {
+                self.frame = None;
+                self.status_shown = true;
+                Some(format!("{}{}", CLEAR_LINE, tool_status(&name, &argument, Some((ok, elapsed)), self.plain)))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn tick(&mut self) -> Option<String> {
+        let frame = self.frame?;
+        self.frame = Some((frame + 1) % self.frames.len());
+        Some(format!("\r{} Thinking...", self.frames[frame]))
+    }
+
+    pub fn stop(&mut self) -> Option<String> {
+        let status_shown = std::mem::take(&mut self.status_shown);
+        (self.frame.take().is_some() || status_shown).then(|| CLEAR_LINE.to_string())
+    }
+}
+
+/// Draws a `ThinkingIndicator` on stderr, driven by the agent's progress events.
+pub struct Spinner {
+    indicator: Arc<Mutex<ThinkingIndicator>>,
+    task: JoinHandle<()>,
+}
+
+fn draw(text: Option<String>) {
+    if let Some(text) = text {
+        let mut stderr = io::stderr();
+        let _ = stderr.write_all(text.as_bytes());
+        let _ = stderr.flush();
+    }
+}
+
+impl Spinner {
+    pub fn spawn(indicator: ThinkingIndicator, mut progress_rx: mpsc::UnboundedReceiver<StreamProgress>) -> Self {
+        let indicator = Arc::new(Mutex::new(indicator));
+        let shared = Arc::clone(&indicator);
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(SPINNER_INTERVAL);
+            loop {
+                tokio::select! {
+                    progress = progress_rx.recv() => match progress {
+                        Some(progress) => draw(shared.lock().unwrap().on_progress(progress)),
+                        None => break,
+                    },
+                    _ = ticker.tick() => draw(shared.lock().unwrap().tick()),
+                }
+            }
+        });
+
+        Self { indicator, task }
+    }
+
+    /// Clear the spinner before printing a reply. Drawing happens under the
+    /// same lock, so nothing is left on the line once this returns.
+    pub fn stop(&self) {
+        draw(self.indicator.lock().unwrap().stop());
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop();
+        self.task.abort();
+    }
+}
+
 #[allow(dead_code)]
 pub struct CliChannel {
     outbound_tx: mpsc::Sender<OutboundMessage>,
@@ -59,3 +195,83 @@ impl CliChannel {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indicator_spins_until_first_token() {
+        let mut indicator = ThinkingIndicator::new(true, true);
+        assert_eq!(indicator.tick(), None);
+
+        assert_eq!(indicator.on_progress(StreamProgress::RequestStarted).as_deref(), Some("\r| Thinking..."));
+        assert_eq!(indicator.tick().as_deref(), Some("\r/ Thinking..."));
+        // A second request start while spinning doesn't restart the animation
+        assert_eq!(indicator.on_progress(StreamProgress::RequestStarted), None);
+
+        assert_eq!(indicator.on_progress(StreamProgress::FirstToken).as_deref(), Some(CLEAR_LINE));
+        assert!(!indicator.is_spinning());
+        assert_eq!(indicator.tick(), None);
+        assert_eq!(indicator.on_progress(StreamProgress::FirstToken), None);
+
+        // The next request in the tool loop spins again; stop clears it
+        assert!(indicator.on_progress(StreamProgress::RequestStarted).is_some());
+        assert_eq!(indicator.stop().as_deref(), Some(CLEAR_LINE));
+        assert_eq!(indicator.stop(), None);
+    }
+
+    #[test]
+    fn test_disabled_indicator_stays_silent() {
+        let mut indicator = ThinkingIndicator::new(false, false);
+        assert_eq!(indicator.on_progress(StreamProgress::RequestStarted), None);
+        assert_eq!(indicator.tick(), None);
+        assert_eq!(indicator.on_progress(StreamProgress::FirstToken), None);
+        let started = StreamProgress::ToolStarted { name: "shell".to_string(), argument: "ls".to_string() };
+        assert_eq!(indicator.on_progress(started), None);
+    }
+
+    #[test]
+    fn test_tool_events_render_a_status_line() {
+        let mut indicator = ThinkingIndicator::new(true, false);
+        let read = |elapsed_ms: Option<u64>| match elapsed_ms {
+            None => StreamProgress::ToolStarted {
+                name: "read_file".to_string(),
+                argument: "config.toml".to_string(),
+            },
+            Some(ms) => StreamProgress::ToolFinished {
+                name: "read_file".to_string(),
+                argument: "config.toml".to_string(),
+                elapsed: Duration::from_millis(ms),
+                ok: true,
+            },
+        };
+
+        indicator.on_progress(StreamProgress::RequestStarted);
+        indicator.on_progress(StreamProgress::FirstToken);
+        assert_eq!(
+            indicator.on_progress(read(None)).as_deref(),
+            Some("\r\x1b[2K🔧 read_file(config.toml)…")
+        );
+        assert_eq!(
+            indicator.on_progress(read(Some(12))).as_deref(),
+            Some("\r\x1b[2K🔧 read_file(config.toml)… done (12ms)")
+        );
+
+        // The next request clears the status line before spinning
+        assert_eq!(
+            indicator.on_progress(StreamProgress::RequestStarted).as_deref(),
+            Some("\r\x1b[2K\r⠋ Thinking...")
+        );
+        indicator.on_progress(StreamProgress::FirstToken);
+        indicator.on_progress(read(Some(2500)));
+        // Cleared before the answer prints
+        assert_eq!(indicator.stop().as_deref(), Some(CLEAR_LINE));
+        assert_eq!(indicator.stop(), None);
+
+        assert_eq!(
+            tool_status("shell", "cargo test", Some((false, Duration::from_millis(2500))), true),
+            "shell(cargo test)... failed (2.5s)"
+        );
+    }
+}
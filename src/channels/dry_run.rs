@@ -0,0 +1,178 @@
+use serde::Deserialize;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::bus::{InboundMessage, OutboundMessage};
+use crate::channels::telegram::{self, TelegramChannel, BOT_ID_KEY};
+
+/// Where `gateway --dry-run` writes the messages it would have sent.
+pub struct DryRunLog {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl DryRunLog {
+    pub fn stdout() -> Self {
+        Self { out: Mutex::new(Box::new(std::io::stdout())) }
+    }
+
+    /// Append to `path`, creating it if needed.
+    pub fn to_file(path: &Path) -> Result<Self, String> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Ok(Self { out: Mutex::new(Box::new(file)) })
+    }
+
+    /// Log what delivering `msg` would do, routed the way the gateway routes
+    /// it. Partial replies are skipped; only final text is logged.
+    pub fn record(&self, telegram_bots: &[(String, Arc<TelegramChannel>)], msg: &OutboundMessage) -> Result<(), String> {
+        if msg.is_streaming {
+            return Ok(());
+        }
+
+        let lines = match msg.channel.as_str() {
+            "telegram" => match telegram::route(telegram_bots, msg) {
+                Some((bot_id, telegram)) => {
+                    let via = if bot_id.is_empty() { String::new() } else { format!(" via {}", bot_id) };
+                    telegram
+                        .describe_send(msg)
+                        .into_iter()
+                        .map(|call| format!("[dry-run] telegram{} {}", via, call))
+                        .collect()
+                }
+                None => vec![format!("[dry-run] no Telegram bot to deliver to telegram:{}", msg.chat_id)],
+            },
+            "cli" => vec![format!("[dry-run] cli: {}", msg.content)],
+            "whatsapp" => vec![format!("[dry-run] whatsapp to {}: {}", msg.chat_id, msg.content)],
+            "http" => vec![format!("[dry-run] http to {}: {}", msg.chat_id, msg.content)],
+            other => vec![format!("[dry-run] unknown channel '{}', dropped", other)],
+        };
+
+        let mut out = self.out.lock().unwrap();
+        for line in lines {
+            writeln!(out, "{}", line).map_err(|e| format!("Failed to write dry-run log: {}", e))?;
+        }
+        out.flush().map_err(|e| format!("Failed to write dry-run log: {}", e))
+    }
+}
+
+/// One line of a replay script.
+#[derive(Debug, Deserialize)]
+struct ScriptedMessage {
+    #[serde(default = "default_channel")]
+    channel: String,
+    sender_id: String,
+    /// Defaults to `sender_id`, i.e. a private chat
+    #[serde(default)]
+    chat_id: Option<String>,
+    content: String,
+    /// Which Telegram bot receives it (default: the first)
+    #[serde(default)]
+    bot: Option<String>,
+}
+
+fn default_channel() -> String {
+    "telegram".to_string()
+}
+
+/// Read a replay script: JSON Lines of `{"sender_id", "content"}` plus
+/// optional `channel`, `chat_id` and `bot`. Blank lines and `#` comments are
+/// skipped.
+pub fn load_script(path: &Path) -> Result<Vec<InboundMessage>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            let scripted: ScriptedMessage =
+                serde_json::from_str(line).map_err(|e| format!("{} line {}: {}", path.display(), i + 1, e))?;
+            let chat_id = scripted.chat_id.unwrap_or_else(|| scripted.sender_id.clone());
+            let mut msg = InboundMessage::new(scripted.channel, scripted.sender_id, chat_id, scripted.content);
+            if let Some(bot) = scripted.bot {
+                msg.metadata.insert(BOT_ID_KEY.to_string(), bot);
+            }
+            Ok(msg)
+        })
+        .collect()
+}
+
+/// Let a scripted message in the way its channel would: Telegram messages go
+/// to their bot and must pass its `allow_from`. Returns why it was dropped otherwise.
+pub fn admit(telegram_bots: &[(String, Arc<TelegramChannel>)], mut msg: InboundMessage) -> Result<InboundMessage, String> {
+    if msg.channel != "telegram" {
+        return Ok(msg);
+    }
+
+    let bot = match msg.metadata.get(BOT_ID_KEY) {
+        Some(id) => telegram_bots.iter().find(|(bot_id, _)| bot_id == id),
+        None => telegram_bots.first(),
+    };
+    let Some((bot_id, telegram)) = bot else {
+        return Err(format!("no enabled Telegram bot to receive the message from {}", msg.sender_id));
+    };
+    if !telegram.allows(&msg.sender_id) {
+        return Err(format!("{} is not in allow_from", msg.sender_id));
+    }
+
+    msg.metadata.remove(BOT_ID_KEY);
+    if !bot_id.is_empty() {
+        msg.metadata.insert(BOT_ID_KEY.to_string(), bot_id.clone());
+    }
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TelegramConfig;
+    use tempfile::TempDir;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_scripted_message_reply_is_logged_not_sent() {
+        let dir = TempDir::new().unwrap();
+        let script = dir.path().join("script.jsonl");
+        std::fs::write(&script, concat!(
+            "# two users talk to the work bot\n",
+            "{\"sender_id\": \"42\", \"content\": \"Hello\", \"bot\": \"work\"}\n",
+            "{\"sender_id\": \"7\", \"content\": \"Let me in\", \"bot\": \"work\"}\n",
+        )).unwrap();
+
+        let (inbound_tx, _inbound_rx) = mpsc::channel(10);
+        let config = TelegramConfig {
+            enabled: true,
+            token: "123:abc".to_string(),
+            allow_from: vec!["42".to_string()],
+            parse_mode: "HTML".to_string(),
+            ..Default::default()
+        };
+        let bots = vec![("work".to_string(), Arc::new(TelegramChannel::from_config("work", &config, inbound_tx)))];
+
+        let scripted = load_script(&script).unwrap();
+        assert_eq!(scripted.len(), 2);
+        let admitted: Vec<_> = scripted.into_iter().map(|msg| admit(&bots, msg)).collect();
+        assert!(admitted[1].as_ref().unwrap_err().contains("allow_from"));
+        let hello = admitted[0].as_ref().unwrap();
+        assert_eq!(hello.chat_id, "42");
+        assert_eq!(hello.metadata.get(BOT_ID_KEY).unwrap(), "work");
+
+        let log_path = dir.path().join("dry-run.log");
+        let log = DryRunLog::to_file(&log_path).unwrap();
+        log.record(&bots, &hello.reply("Hi there!").streaming()).unwrap();
+        log.record(&bots, &hello.reply("Hi there! How can I help?")).unwrap();
+
+        let logged = std::fs::read_to_string(&log_path).unwrap();
+        assert_eq!(
+            logged,
+            "[dry-run] telegram via work sendMessage chat_id=42 parse_mode=HTML [1/1]\nHi there! How can I help?\n"
+        );
+
+        let nightly = InboundMessage::new("http".to_string(), "nightly".to_string(), "nightly".to_string(), "build ok?".to_string());
+        log.record(&bots, &nightly.reply("Yes, all green.")).unwrap();
+        let logged = std::fs::read_to_string(&log_path).unwrap();
+        assert!(logged.ends_with("[dry-run] http to nightly: Yes, all green.\n"));
+    }
+}
@@ -0,0 +1,255 @@
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use crate::bus::{InboundMessage, OutboundMessage};
+use crate::config::HttpChannelConfig;
+
+/// Request bodies larger than this are refused
+const MAX_REQUEST_BYTES: usize = 1024 * 1024;
+/// Metadata key tying a reply back to the request waiting for it
+const REQUEST_ID_KEY: &str = "http_request_id";
+
+/// Inbound messages over plain HTTP, for scripts: `POST /message` queues the
+/// message for the agent and answers with its reply, or 202 right away when
+/// the caller asks for `"async": true`.
+pub struct HttpChannel {
+    token: String,
+    path: String,
+    reply_timeout: Duration,
+    inbound_tx: mpsc::Sender<InboundMessage>,
+    next_request_id: AtomicU64,
+    /// Requests waiting for their reply, by request id
+    waiting: Mutex<HashMap<u64, oneshot::Sender<String>>>,
+}
+
+/// `{"chat_id": "build-bot", "content": "Summarize today's log"}`
+#[derive(Deserialize)]
+struct MessageRequest {
+    chat_id: String,
+    content: String,
+    /// Don't wait for the reply
+    #[serde(default, rename = "async")]
+    detach: bool,
+}
+
+#[derive(Serialize)]
+struct MessageResponse<'a> {
+    chat_id: &'a str,
+    content: &'a str,
+}
+
+fn respond(status: StatusCode, body: impl Into<Bytes>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    response
+}
+
+fn respond_json(status: StatusCode, body: &impl Serialize) -> Response<Full<Bytes>> {
+    let mut response = respond(status, serde_json::to_vec(body).unwrap_or_default());
+    response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().expect("valid header value"));
+    response
+}
+
+/// Compare without stopping at the first differing byte, so response timing
+/// doesn't reveal how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl HttpChannel {
+    pub fn from_config(config: &HttpChannelConfig, inbound_tx: mpsc::Sender<InboundMessage>) -> Self {
+        Self {
+            token: config.token.clone(),
+            path: config.path.clone(),
+            reply_timeout: Duration::from_secs(config.reply_timeout_secs),
+            inbound_tx,
+            next_request_id: AtomicU64::new(1),
+            waiting: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Answer requests on `listener` until the task is dropped.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) {
+        if let Ok(addr) = listener.local_addr() {
+            tracing::info!("HTTP channel listening on {}{}", addr, self.path);
+        }
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("HTTP channel accept failed: {}", e);
+                    continue;
+                }
+            };
+            let channel = Arc::clone(&self);
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let channel = Arc::clone(&channel);
+                    async move { Ok::<_, std::convert::Infallible>(channel.handle(request).await) }
+                });
+                if let Err(e) = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("HTTP channel connection from {} ended: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    fn authorized(&self, request: &Request<Incoming>) -> bool {
+        let Some(header) = request.headers().get(AUTHORIZATION).and_then(|value| value.to_str().ok()) else {
+            return false;
+        };
+        let Some(token) = header.strip_prefix("Bearer ") else {
+            return false;
+        };
+        !self.token.is_empty() && constant_time_eq(token.as_bytes(), self.token.as_bytes())
+    }
+
+    async fn handle(&self, request: Request<Incoming>) -> Response<Full<Bytes>> {
+        if request.uri().path() != self.path {
+            return respond(StatusCode::NOT_FOUND, "Not found");
+        }
+        if request.method() != Method::POST {
+            return respond(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed");
+        }
+        if !self.authorized(&request) {
+            return respond(StatusCode::UNAUTHORIZED, "Missing or wrong bearer token");
+        }
+
+        let body = match Limited::new(request.into_body(), MAX_REQUEST_BYTES).collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(e) => return respond(StatusCode::PAYLOAD_TOO_LARGE, e.to_string()),
+        };
+        let request: MessageRequest = match serde_json::from_slice(&body) {
+            Ok(request) => request,
+            Err(e) => return respond(StatusCode::BAD_REQUEST, format!("Invalid message: {}", e)),
+        };
+        if request.chat_id.trim().is_empty() || request.content.trim().is_empty() {
+            return respond(StatusCode::BAD_REQUEST, "chat_id and content must not be empty");
+        }
+
+        let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let msg = InboundMessage::new("http".to_string(), request.chat_id.clone(), request.chat_id.clone(), request.content)
+            .with_metadata(HashMap::from([(REQUEST_ID_KEY.to_string(), id.to_string())]));
+        tracing::info!("Received HTTP message for {}: {}", msg.chat_id, msg.content);
+
+        let reply_rx = if request.detach {
+            None
+        } else {
+            let (reply_tx, reply_rx) = oneshot::channel();
+            self.waiting.lock().unwrap().insert(id, reply_tx);
+            Some(reply_rx)
+        };
+
+        if self.inbound_tx.send(msg).await.is_err() {
+            self.waiting.lock().unwrap().remove(&id);
+            return respond(StatusCode::SERVICE_UNAVAILABLE, "Agent is not running");
+        }
+
+        let Some(reply_rx) = reply_rx else {
+            return respond(StatusCode::ACCEPTED, "Accepted");
+        };
+        match tokio::time::timeout(self.reply_timeout, reply_rx).await {
+            Ok(Ok(content)) => respond_json(StatusCode::OK, &MessageResponse { chat_id: &request.chat_id, content: &content }),
+            _ => {
+                self.waiting.lock().unwrap().remove(&id);
+                respond(StatusCode::GATEWAY_TIMEOUT, "No reply in time")
+            }
+        }
+    }
+
+    /// Hand a reply to the request waiting for it. Replies to async requests,
+    /// or after the first one, have nobody to go to and are dropped.
+    pub fn send(&self, msg: OutboundMessage) {
+        // Text sent alongside tool calls ("Let me check…") isn't the answer
+        if msg.is_interim {
+            tracing::debug!("Not answering http:{} with interim text", msg.chat_id);
+            return;
+        }
+        let waiter = msg
+            .metadata
+            .get(REQUEST_ID_KEY)
+            .and_then(|id| id.parse::<u64>().ok())
+            .and_then(|id| self.waiting.lock().unwrap().remove(&id));
+        match waiter {
+            Some(reply_tx) => {
+                let _ = reply_tx.send(msg.content);
+            }
+            None => tracing::debug!("No HTTP request waiting for the reply to http:{}, dropping it", msg.chat_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+
+    async fn serve_locally(inbound_tx: mpsc::Sender<InboundMessage>) -> (Arc<HttpChannel>, String) {
+        let config = HttpChannelConfig { enabled: true, token: "t0ken".to_string(), ..Default::default() };
+        let channel = Arc::new(HttpChannel::from_config(&config, inbound_tx));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(Arc::clone(&channel).serve(listener));
+        (channel, format!("http://{}/message", addr))
+    }
+
+    #[tokio::test]
+    async fn test_post_returns_the_agent_reply() {
+        let (inbound_tx, mut inbound_rx) = mpsc::channel(10);
+        let (channel, url) = serve_locally(inbound_tx).await;
+
+        // Stands in for the agent loop and the gateway's outbound delivery
+        let agent = Arc::clone(&channel);
+        tokio::spawn(async move {
+            while let Some(msg) = inbound_rx.recv().await {
+                agent.send(msg.reply(format!("echo: {}", msg.content)));
+            }
+        });
+
+        let response = Client::new()
+            .post(&url)
+            .bearer_auth("t0ken")
+            .json(&serde_json::json!({"chat_id": "nightly", "content": "build ok?"}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body, serde_json::json!({"chat_id": "nightly", "content": "echo: build ok?"}));
+
+        let queued = Client::new()
+            .post(&url)
+            .bearer_auth("t0ken")
+            .json(&serde_json::json!({"chat_id": "nightly", "content": "later", "async": true}))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(queued.status(), 202);
+    }
+
+    #[tokio::test]
+    async fn test_requests_without_the_token_are_refused() {
+        let (inbound_tx, mut inbound_rx) = mpsc::channel(10);
+        let (_channel, url) = serve_locally(inbound_tx).await;
+        let message = serde_json::json!({"chat_id": "nightly", "content": "hi"});
+
+        let anonymous = Client::new().post(&url).json(&message).send().await.unwrap();
+        assert_eq!(anonymous.status(), 401);
+        let wrong = Client::new().post(&url).bearer_auth("guess").json(&message).send().await.unwrap();
+        assert_eq!(wrong.status(), 401);
+        assert!(inbound_rx.try_recv().is_err());
+    }
+}
@@ -1,2 +1,6 @@
 pub mod cli;
+pub mod dry_run;
+pub mod http;
 pub mod telegram;
+pub mod util;
+pub mod whatsapp;
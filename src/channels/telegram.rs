@@ -1,15 +1,78 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
 use crate::bus::{InboundMessage, OutboundMessage};
+use crate::channels::util::split_message;
+use crate::config::TelegramConfig;
 
 const TELEGRAM_MAX_MESSAGE_LENGTH: usize = 4096;
+const TELEGRAM_API_BASE: &str = "https://api.telegram.org";
+const THREAD_ID_KEY: &str = "message_thread_id";
+/// Metadata key naming the bot a message arrived on (absent for the default bot)
+pub const BOT_ID_KEY: &str = "bot_id";
+/// Minimum time between edits of a streamed reply; Telegram allows about one
+/// message update per second per chat
+pub const STREAM_EDIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Topic thread a reply should be posted to, taken from the outbound metadata.
+fn thread_id(msg: &OutboundMessage) -> Option<i64> {
+    msg.metadata.get(THREAD_ID_KEY).and_then(|id| id.parse().ok())
+}
+
+/// The bot that should deliver `msg`: the one named by its `bot_id`
+/// metadata, or the first (default) bot when it carries none.
+pub fn route<'a, T>(bots: &'a [(String, T)], msg: &OutboundMessage) -> Option<&'a (String, T)> {
+    match msg.metadata.get(BOT_ID_KEY) {
+        Some(id) => bots.iter().find(|(bot_id, _)| bot_id == id),
+        None => bots.first(),
+    }
+}
+
+/// The first `TELEGRAM_MAX_MESSAGE_LENGTH` characters of a partial reply.
+fn preview(text: &str) -> String {
+    text.chars().take(TELEGRAM_MAX_MESSAGE_LENGTH).collect()
+}
+
+/// Whether a Telegram error means the text couldn't be rendered in the chosen parse_mode.
+fn is_formatting_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    error.contains("can't parse entities")
+        || error.contains("can't find end of")
+        || error.contains("unsupported start tag")
+}
+
+/// Send with `parse_mode`, and if Telegram rejects the formatting, send once
+/// more as plain text.
+async fn send_with_plain_fallback<F, Fut>(parse_mode: Option<String>, fallback: bool, send: F) -> Result<i64, String>
+where
+    F: Fn(Option<String>) -> Fut,
+    Fut: Future<Output = Result<i64, String>>,
+{
+    let formatted = parse_mode.is_some();
+    match send(parse_mode).await {
+        Err(e) if formatted && fallback && is_formatting_error(&e) => {
+            tracing::warn!("Telegram could not render the message, resending as plain text: {}", e);
+            send(None).await
+        }
+        result => result,
+    }
+}
 
 pub struct TelegramChannel {
     token: String,
+    api_base: String,
+    /// Tagged onto inbound metadata so replies are routed back through this bot
+    bot_id: String,
     client: Client,
     inbound_tx: mpsc::Sender<InboundMessage>,
     allow_from: Vec<String>,
+    parse_mode: Option<String>,
+    plain_text_fallback: bool,
+    stream_replies: bool,
 }
 
 #[derive(Serialize)]
@@ -17,13 +80,28 @@ struct SendMessageRequest {
     chat_id: i64,
     text: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    parse_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     reply_to_message_id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_thread_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct EditMessageRequest {
+    chat_id: i64,
+    message_id: i64,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parse_mode: Option<String>,
 }
 
 #[derive(Serialize)]
 struct SendChatActionRequest {
     chat_id: i64,
     action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_thread_id: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -43,6 +121,36 @@ struct Message {
     chat: Chat,
     text: Option<String>,
     bot_command: Option<Vec<String>>,
+    /// Forum topic the message belongs to, if any
+    message_thread_id: Option<i64>,
+}
+
+impl Message {
+    fn sender_id(&self) -> String {
+        self.from
+            .as_ref()
+            .map(|u| u.id.to_string())
+            .unwrap_or_default()
+    }
+
+    fn to_inbound(&self) -> Option<InboundMessage> {
+        let text = self.text.as_ref()?;
+
+        let mut metadata = HashMap::new();
+        if let Some(thread_id) = self.message_thread_id {
+            metadata.insert(THREAD_ID_KEY.to_string(), thread_id.to_string());
+        }
+
+        Some(
+            InboundMessage::new(
+                "telegram".to_string(),
+                self.sender_id(),
+                self.chat.id.to_string(),
+                text.to_string(),
+            )
+            .with_metadata(metadata),
+        )
+    }
 }
 
 #[derive(Deserialize)]
@@ -83,14 +191,84 @@ impl TelegramChannel {
     ) -> Self {
         Self {
             token,
+            api_base: TELEGRAM_API_BASE.to_string(),
+            bot_id: String::new(),
             client: Client::new(),
             inbound_tx,
             allow_from,
+            parse_mode: None,
+            plain_text_fallback: true,
+            stream_replies: true,
+        }
+    }
+
+    /// Format replies with a Telegram parse_mode; an empty mode sends plain text.
+    pub fn with_parse_mode(mut self, parse_mode: &str, plain_text_fallback: bool) -> Self {
+        self.parse_mode = (!parse_mode.is_empty()).then(|| parse_mode.to_string());
+        self.plain_text_fallback = plain_text_fallback;
+        self
+    }
+
+    /// A channel for one configured bot; `bot_id` is its key from
+    /// `ChannelsConfig::telegram_bots`.
+    pub fn from_config(bot_id: &str, config: &TelegramConfig, inbound_tx: mpsc::Sender<InboundMessage>) -> Self {
+        let mut channel = Self::new(config.token.clone(), inbound_tx, config.allow_from.clone())
+            .with_parse_mode(&config.parse_mode, config.plain_text_fallback);
+        channel.bot_id = bot_id.to_string();
+        channel.stream_replies = config.stream_replies;
+        channel
+    }
+
+    /// Whether partial replies should be shown, per `stream_replies`.
+    pub fn streams_replies(&self) -> bool {
+        self.stream_replies
+    }
+
+    /// Whether `allow_from` lets `sender_id` talk to this bot.
+    pub fn allows(&self, sender_id: &str) -> bool {
+        self.allow_from.is_empty() || self.allow_from.iter().any(|id| id == sender_id)
+    }
+
+    /// The sendMessage calls `send` would make for `msg`, one line of
+    /// parameters and the text per chunk, without contacting Telegram.
+    pub fn describe_send(&self, msg: &OutboundMessage) -> Vec<String> {
+        if msg.chat_id.parse::<i64>().is_err() {
+            return vec![format!("invalid chat_id '{}', nothing would be sent", msg.chat_id)];
         }
+        let chunks = split_message(&msg.content, TELEGRAM_MAX_MESSAGE_LENGTH);
+        let total = chunks.len();
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut params = format!("sendMessage chat_id={}", msg.chat_id);
+                if let Some(thread) = thread_id(msg) {
+                    params.push_str(&format!(" message_thread_id={}", thread));
+                }
+                if let Some(mode) = &self.parse_mode {
+                    params.push_str(&format!(" parse_mode={}", mode));
+                }
+                format!("{} [{}/{}]\n{}", params, i + 1, total, chunk)
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.to_string();
+        self
+    }
+
+    fn method_url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", self.api_base, self.token, method)
     }
 
     pub async fn start(&self) {
-        tracing::info!("Telegram channel starting...");
+        if self.bot_id.is_empty() {
+            tracing::info!("Telegram channel starting...");
+        } else {
+            tracing::info!("Telegram channel starting for bot {}...", self.bot_id);
+        }
         
         // Get latest update offset first to skip old messages
         let mut offset: i64 = self.get_latest_update_id().await.unwrap_or(0) + 1;
@@ -118,33 +296,21 @@ impl TelegramChannel {
                             }
                             
                             // Check allow_from whitelist
-                            if !self.allow_from.is_empty() {
-                                let sender_id = message.from
-                                    .as_ref()
-                                    .map(|u| u.id.to_string())
-                                    .unwrap_or_default();
-                                
-                                if !self.allow_from.contains(&sender_id) {
-                                    tracing::debug!("Message from {} not in allow list, skipping", sender_id);
-                                    offset = update.update_id + 1;
-                                    continue;
-                                }
+                            let sender_id = message.from
+                                .as_ref()
+                                .map(|u| u.id.to_string())
+                                .unwrap_or_default();
+                            if !self.allows(&sender_id) {
+                                tracing::debug!("Message from {} not in allow list, skipping", sender_id);
+                                offset = update.update_id + 1;
+                                continue;
                             }
                             
-                            if let Some(text) = &message.text {
-                                let sender_id = message.from
-                                    .as_ref()
-                                    .map(|u| u.id.to_string())
-                                    .unwrap_or_default();
-                                
-                                tracing::info!("Received message from {}: {}", sender_id, text);
-                                
-                                let msg = InboundMessage::new(
-                                    "telegram".to_string(),
-                                    sender_id,
-                                    message.chat.id.to_string(),
-                                    text.to_string(),
-                                );
+                            if let Some(mut msg) = message.to_inbound() {
+                                if !self.bot_id.is_empty() {
+                                    msg.metadata.insert(BOT_ID_KEY.to_string(), self.bot_id.clone());
+                                }
+                                tracing::info!("Received message from {}: {}", msg.sender_id, msg.content);
                                 
                                 if self.inbound_tx.send(msg).await.is_err() {
                                     tracing::error!("Failed to send message to channel");
@@ -167,10 +333,7 @@ impl TelegramChannel {
     }
 
     async fn get_updates(&self, offset: i64) -> Result<Vec<Update>, String> {
-        let url = format!(
-            "https://api.telegram.org/bot{}/getUpdates?timeout=60&offset={}",
-            self.token, offset
-        );
+        let url = format!("{}?timeout=60&offset={}", self.method_url("getUpdates"), offset);
         
         #[derive(Deserialize)]
         struct Response {
@@ -195,10 +358,7 @@ impl TelegramChannel {
 
     async fn get_latest_update_id(&self) -> Result<i64, String> {
         // Get updates with limit=1 to get the latest update_id
-        let url = format!(
-            "https://api.telegram.org/bot{}/getUpdates?limit=1",
-            self.token
-        );
+        let url = format!("{}?limit=1", self.method_url("getUpdates"));
         
         #[derive(Deserialize)]
         struct Response {
@@ -223,27 +383,99 @@ impl TelegramChannel {
 
     pub async fn send(&self, msg: OutboundMessage) -> Result<(), String> {
         let chat_id: i64 = msg.chat_id.parse().map_err(|_| "Invalid chat_id")?;
+        let thread_id = thread_id(&msg);
 
         // Send typing status first
-        let _ = self.send_chat_action(chat_id, "typing").await;
+        let _ = self.send_chat_action(chat_id, thread_id, "typing").await;
 
         // Split large messages
-        let chunks = self.split_message(&msg.content);
+        let chunks = split_message(&msg.content, TELEGRAM_MAX_MESSAGE_LENGTH);
 
         for (i, chunk) in chunks.iter().enumerate() {
             let reply_to = if i > 0 { Some(msg.chat_id.parse().unwrap_or(0)) } else { None };
-            self.send_message(chat_id, chunk.to_string(), reply_to).await?;
+            send_with_plain_fallback(self.parse_mode.clone(), self.plain_text_fallback, |parse_mode| {
+                self.send_message(chat_id, thread_id, chunk.to_string(), reply_to, parse_mode)
+            })
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Start a streamed reply: post its first text (or a placeholder) as plain
+    /// text, since a partial reply may not be valid markup yet. Returns the
+    /// message id to edit as more arrives.
+    pub async fn send_streaming(&self, msg: &OutboundMessage) -> Result<i64, String> {
+        let chat_id: i64 = msg.chat_id.parse().map_err(|_| "Invalid chat_id")?;
+        let text = if msg.content.trim().is_empty() {
+            crate::ui::streaming_placeholder(crate::ui::is_plain())
+        } else {
+            preview(&msg.content)
+        };
+        self.send_message(chat_id, thread_id(msg), text, None, None).await
+    }
+
+    pub async fn edit_message(&self, chat_id: i64, message_id: i64, text: String, parse_mode: Option<String>) -> Result<(), String> {
+        let url = self.method_url("editMessageText");
+
+        let request = EditMessageRequest {
+            chat_id,
+            message_id,
+            text,
+            parse_mode,
+        };
+
+        let resp = self.client
+            .post(&url)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        #[derive(Deserialize)]
+        struct TelegramResponse {
+            ok: bool,
+            description: Option<String>,
+        }
+
+        let data: TelegramResponse = resp.json().await.map_err(|e| e.to_string())?;
+        let description = data.description.unwrap_or_else(|| "unknown error".to_string());
+        // Re-sending the text already shown is harmless
+        if data.ok || description.contains("message is not modified") {
+            Ok(())
+        } else {
+            Err(format!("Failed to edit message: {}", description))
+        }
+    }
+
+    /// Replace a streamed reply with the finished text, formatted this time.
+    /// Text beyond one message goes out as follow-up messages.
+    pub async fn finalize_streaming(&self, msg: OutboundMessage, message_id: i64) -> Result<(), String> {
+        let chat_id: i64 = msg.chat_id.parse().map_err(|_| "Invalid chat_id")?;
+        let thread_id = thread_id(&msg);
+        let chunks = split_message(&msg.content, TELEGRAM_MAX_MESSAGE_LENGTH);
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            send_with_plain_fallback(self.parse_mode.clone(), self.plain_text_fallback, |parse_mode| async move {
+                if i == 0 {
+                    self.edit_message(chat_id, message_id, chunk.to_string(), parse_mode).await.map(|_| message_id)
+                } else {
+                    self.send_message(chat_id, thread_id, chunk.to_string(), None, parse_mode).await
+                }
+            })
+            .await?;
         }
 
         Ok(())
     }
 
-    pub async fn send_chat_action(&self, chat_id: i64, action: &str) -> Result<(), String> {
-        let url = format!("https://api.telegram.org/bot{}/sendChatAction", self.token);
+    pub async fn send_chat_action(&self, chat_id: i64, message_thread_id: Option<i64>, action: &str) -> Result<(), String> {
+        let url = self.method_url("sendChatAction");
 
         let request = SendChatActionRequest {
             chat_id,
             action: action.to_string(),
+            message_thread_id,
         };
 
         self.client
@@ -256,13 +488,22 @@ impl TelegramChannel {
         Ok(())
     }
 
-    async fn send_message(&self, chat_id: i64, text: String, reply_to_message_id: Option<i64>) -> Result<i64, String> {
-        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.token);
+    async fn send_message(
+        &self,
+        chat_id: i64,
+        message_thread_id: Option<i64>,
+        text: String,
+        reply_to_message_id: Option<i64>,
+        parse_mode: Option<String>,
+    ) -> Result<i64, String> {
+        let url = self.method_url("sendMessage");
 
         let request = SendMessageRequest {
             chat_id,
             text,
+            parse_mode,
             reply_to_message_id,
+            message_thread_id,
         };
 
         let resp = self.client
@@ -275,7 +516,8 @@ impl TelegramChannel {
         #[derive(Deserialize)]
         struct TelegramResponse {
             ok: bool,
-            result: TelegramMessage,
+            result: Option<TelegramMessage>,
+            description: Option<String>,
         }
 
         #[derive(Deserialize)]
@@ -285,56 +527,303 @@ impl TelegramChannel {
 
         let data: TelegramResponse = resp.json().await.map_err(|e| e.to_string())?;
 
-        if data.ok {
-            Ok(data.result.message_id)
-        } else {
-            Err("Failed to send message".to_string())
+        match data.result {
+            Some(message) if data.ok => Ok(message.message_id),
+            _ => Err(format!(
+                "Failed to send message: {}",
+                data.description.unwrap_or_else(|| "unknown error".to_string())
+            )),
         }
     }
+}
 
-    fn split_message(&self, content: &str) -> Vec<String> {
-        let mut chunks = Vec::new();
-        let mut current = String::new();
+/// A reply being streamed into one message.
+struct LiveReply {
+    message_id: i64,
+    last_edit: Instant,
+}
 
-        for line in content.lines() {
-            if current.len() + line.len() + 1 > TELEGRAM_MAX_MESSAGE_LENGTH {
-                if !current.is_empty() {
-                    chunks.push(current);
-                    current = String::new();
-                }
-                
-                // If single line is too long, split it
-                if line.len() > TELEGRAM_MAX_MESSAGE_LENGTH {
-                    let mut start = 0;
-                    while start < line.len() {
-                        let end = start + TELEGRAM_MAX_MESSAGE_LENGTH;
-                        if end >= line.len() {
-                            chunks.push(line[start..].to_string());
-                            break;
-                        } else {
-                            // Try to split at word boundary
-                            let split_point = line[start..end].rfind(' ')
-                                .map(|p| start + p)
-                                .unwrap_or(end);
-                            chunks.push(line[start..split_point].to_string());
-                            start = split_point + 1;
-                        }
-                    }
-                } else {
-                    current.push_str(line);
-                }
-            } else {
-                if !current.is_empty() {
-                    current.push('\n');
+/// Delivers outbound messages, writing streamed replies into a single
+/// message per chat: the first partial is sent, later partials edit it (at
+/// most once per interval), and the final message replaces it.
+pub struct StreamingSender {
+    edit_interval: Duration,
+    live: HashMap<String, LiveReply>,
+}
+
+impl StreamingSender {
+    pub fn new(edit_interval: Duration) -> Self {
+        Self {
+            edit_interval,
+            live: HashMap::new(),
+        }
+    }
+
+    /// Bot, chat and topic a message belongs to.
+    fn key(msg: &OutboundMessage) -> String {
+        format!(
+            "{}:{}:{}",
+            msg.metadata.get(BOT_ID_KEY).map(String::as_str).unwrap_or_default(),
+            msg.chat_id,
+            thread_id(msg).unwrap_or_default()
+        )
+    }
+
+    pub async fn deliver(&mut self, telegram: &TelegramChannel, msg: OutboundMessage) -> Result<(), String> {
+        let key = Self::key(&msg);
+
+        if !msg.is_streaming {
+            return match self.live.remove(&key) {
+                Some(live) => telegram.finalize_streaming(msg, live.message_id).await,
+                None => telegram.send(msg).await,
+            };
+        }
+
+        match self.live.get_mut(&key) {
+            None => {
+                let message_id = telegram.send_streaming(&msg).await?;
+                self.live.insert(key, LiveReply { message_id, last_edit: Instant::now() });
+            }
+            Some(live) if live.last_edit.elapsed() >= self.edit_interval => {
+                let chat_id: i64 = msg.chat_id.parse().map_err(|_| "Invalid chat_id")?;
+                telegram.edit_message(chat_id, live.message_id, preview(&msg.content), None).await?;
+                live.last_edit = Instant::now();
+            }
+            // Partials carry the whole text so far, so a skipped one loses nothing
+            Some(_) => {}
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forum_message_thread_id_round_trip() {
+        let update: Update = serde_json::from_str(r#"{
+            "update_id": 1,
+            "message": {
+                "message_id": 10,
+                "message_thread_id": 77,
+                "from": {"id": 42, "is_bot": false, "first_name": "Ana"},
+                "chat": {"id": -1001234, "type": "supergroup"},
+                "text": "hello topic"
+            }
+        }"#).unwrap();
+
+        let inbound = update.message.unwrap().to_inbound().unwrap();
+        assert_eq!(inbound.chat_id, "-1001234");
+        assert_eq!(inbound.sender_id, "42");
+        assert_eq!(inbound.metadata.get(THREAD_ID_KEY).unwrap(), "77");
+
+        let reply = inbound.reply("hi back");
+        let request = SendMessageRequest {
+            chat_id: reply.chat_id.parse().unwrap(),
+            text: reply.content.clone(),
+            parse_mode: None,
+            reply_to_message_id: None,
+            message_thread_id: thread_id(&reply),
+        };
+
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["message_thread_id"], 77);
+    }
+
+    #[test]
+    fn test_plain_message_has_no_thread_id() {
+        let update: Update = serde_json::from_str(r#"{
+            "update_id": 2,
+            "message": {
+                "message_id": 11,
+                "from": {"id": 42, "is_bot": false},
+                "chat": {"id": 42, "type": "private"},
+                "text": "hello"
+            }
+        }"#).unwrap();
+
+        let reply = update.message.unwrap().to_inbound().unwrap().reply("hi");
+        assert_eq!(thread_id(&reply), None);
+
+        let request = SendMessageRequest {
+            chat_id: 42,
+            text: reply.content,
+            parse_mode: None,
+            reply_to_message_id: None,
+            message_thread_id: None,
+        };
+        assert!(serde_json::to_value(&request).unwrap().get("message_thread_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_formatting_failure_is_retried_as_plain_text() {
+        let attempts = std::sync::Mutex::new(Vec::new());
+
+        let result = send_with_plain_fallback(Some("Markdown".to_string()), true, |parse_mode| {
+            attempts.lock().unwrap().push(parse_mode.clone());
+            async move {
+                match parse_mode {
+                    Some(_) => Err("Failed to send message: Bad Request: can't parse entities: Can't find end of the entity starting at byte offset 12".to_string()),
+                    None => Ok(99),
                 }
-                current.push_str(line);
             }
+        })
+        .await;
+
+        assert_eq!(result, Ok(99));
+        assert_eq!(*attempts.lock().unwrap(), vec![Some("Markdown".to_string()), None]);
+    }
+
+    #[tokio::test]
+    async fn test_other_failures_are_not_retried() {
+        let attempts = std::sync::Mutex::new(0);
+
+        let result = send_with_plain_fallback(Some("Markdown".to_string()), true, |_| {
+            *attempts.lock().unwrap() += 1;
+            async { Err("Failed to send message: Forbidden: bot was blocked by the user".to_string()) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(*attempts.lock().unwrap(), 1);
+    }
+
+    /// Answers every request with a sent message and records "method body" lines.
+    async fn recording_server() -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = std::sync::Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 64 * 1024];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let method = request.split_whitespace().nth(1).unwrap_or_default().rsplit('/').next().unwrap_or_default().to_string();
+                let body = request.split("\r\n\r\n").nth(1).unwrap_or_default().to_string();
+                recorded.lock().unwrap().push(format!("{} {}", method, body));
+
+                let reply = r#"{"ok": true, "result": {"message_id": 50}}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    reply.len(),
+                    reply
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        (base, requests)
+    }
+
+    #[tokio::test]
+    async fn test_streamed_reply_is_sent_once_then_edited_and_finalized() {
+        let (base, requests) = recording_server().await;
+        let (inbound_tx, _inbound_rx) = mpsc::channel(10);
+        let telegram = TelegramChannel::new("token".to_string(), inbound_tx, Vec::new())
+            .with_parse_mode("Markdown", true)
+            .with_api_base(&base);
+        let partial = |text: &str| OutboundMessage::new("telegram".into(), "42".into(), text.into()).streaming();
+
+        let mut sender = StreamingSender::new(Duration::ZERO);
+        sender.deliver(&telegram, partial("Hel")).await.unwrap();
+        sender.deliver(&telegram, partial("Hello wor")).await.unwrap();
+        sender
+            .deliver(&telegram, OutboundMessage::new("telegram".into(), "42".into(), "Hello *world*".into()))
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].starts_with("sendMessage "));
+        assert!(requests[0].contains(r#""text":"Hel""#));
+        assert!(!requests[0].contains("parse_mode"));
+        assert!(requests[1].starts_with("editMessageText "));
+        assert!(requests[1].contains(r#""message_id":50"#));
+        assert!(requests[1].contains(r#""text":"Hello wor""#));
+        assert!(requests[2].starts_with("editMessageText "));
+        assert!(requests[2].contains(r#""parse_mode":"Markdown""#));
+        assert!(sender.live.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_streamed_edits_are_throttled() {
+        let (base, requests) = recording_server().await;
+        let (inbound_tx, _inbound_rx) = mpsc::channel(10);
+        let telegram = TelegramChannel::new("token".to_string(), inbound_tx, Vec::new()).with_api_base(&base);
+        let partial = |text: &str| OutboundMessage::new("telegram".into(), "42".into(), text.into()).streaming();
+
+        let mut sender = StreamingSender::new(Duration::from_secs(60));
+        for text in ["a", "ab", "abc"] {
+            sender.deliver(&telegram, partial(text)).await.unwrap();
         }
 
-        if !current.is_empty() {
-            chunks.push(current);
+        let requests = requests.lock().unwrap().clone();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("sendMessage "));
+    }
+
+    fn update_from(sender: i64, text: &str) -> String {
+        format!(
+            r#"{{"ok": true, "result": [{{"update_id": 5, "message": {{"message_id": 1, "from": {{"id": {0}, "is_bot": false}}, "chat": {{"id": {0}, "type": "private"}}, "text": "{1}"}}}}]}}"#,
+            sender, text
+        )
+    }
+
+    #[tokio::test]
+    async fn test_each_bot_polls_independently_and_replies_are_routed() {
+        use crate::providers::retry::tests::mock_server;
+
+        let empty = r#"{"ok": true, "result": []}"#;
+        let personal_base = mock_server(vec![("200 OK", "", empty), ("200 OK", "", update_from(1, "hi personal").leak())]).await;
+        let work_base = mock_server(vec![("200 OK", "", empty), ("200 OK", "", update_from(2, "hi work").leak())]).await;
+
+        let config = |token: &str| TelegramConfig {
+            enabled: true,
+            token: token.to_string(),
+            ..TelegramConfig::default()
+        };
+        let bots = vec![(String::new(), config("personal-token")), ("work".to_string(), config("work-token"))];
+
+        let (inbound_tx, mut inbound_rx) = mpsc::channel(10);
+        let handles: Vec<_> = bots
+            .iter()
+            .zip([personal_base, work_base])
+            .map(|((id, bot), base)| {
+                let channel = TelegramChannel::from_config(id, bot, inbound_tx.clone()).with_api_base(&base);
+                tokio::spawn(async move { channel.start().await })
+            })
+            .collect();
+
+        let mut received = HashMap::new();
+        for _ in 0..2 {
+            let msg = tokio::time::timeout(std::time::Duration::from_secs(5), inbound_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            received.insert(msg.content.clone(), msg);
+        }
+        for handle in handles {
+            handle.abort();
         }
 
-        chunks
+        let personal = &received["hi personal"];
+        let work = &received["hi work"];
+        assert!(!personal.metadata.contains_key(BOT_ID_KEY));
+        assert_eq!(work.metadata.get(BOT_ID_KEY).unwrap(), "work");
+
+        let (_, bot) = route(&bots, &work.reply("done")).unwrap();
+        assert_eq!(bot.token, "work-token");
+        let (_, bot) = route(&bots, &personal.reply("done")).unwrap();
+        assert_eq!(bot.token, "personal-token");
+
+        let mut unknown = OutboundMessage::new("telegram".into(), "3".into(), "x".into());
+        unknown.metadata.insert(BOT_ID_KEY.to_string(), "gone".to_string());
+        assert!(route(&bots, &unknown).is_none());
     }
 }
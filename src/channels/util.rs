@@ -0,0 +1,176 @@
+/// Largest index <= `index` that falls on a char boundary of `s`.
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    let mut index = index.min(s.len());
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// Whether `line` opens or closes a fenced code block.
+fn is_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+/// Split `content` into chunks of at most `max_len` bytes for channels with a
+/// message size cap. Lines are kept together where possible; a line that is
+/// too long on its own is broken at the last space that fits, or hard-split
+/// when it has none. Splits always land on char boundaries.
+///
+/// A chunk that ends inside a fenced code block gets the fence closed, and
+/// the next chunk reopens it with the same opening line (language included),
+/// so every chunk renders as standalone markdown.
+pub fn split_message(content: &str, max_len: usize) -> Vec<String> {
+    let Some(longest_fence) = content.lines().filter(|l| is_fence(l)).map(|l| l.trim().len()).max() else {
+        return split_lines(content, max_len);
+    };
+
+    // Room for a reopened fence line in front and a closing fence behind
+    let reserve = longest_fence + 1 + "\n```".len();
+    let mut open: Option<String> = None;
+    split_lines(content, max_len.saturating_sub(reserve).max(1))
+        .into_iter()
+        .map(|chunk| {
+            let mut out = match &open {
+                Some(fence) => format!("{}\n{}", fence, chunk),
+                None => chunk.clone(),
+            };
+            for line in chunk.lines().filter(|l| is_fence(l)) {
+                open = match open {
+                    Some(_) => None,
+                    None => Some(line.trim().to_string()),
+                };
+            }
+            if open.is_some() {
+                out.push_str("\n```");
+            }
+            out
+        })
+        .collect()
+}
+
+fn split_lines(content: &str, max_len: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for line in content.lines() {
+        if current.len() + line.len() + 1 > max_len {
+            if !current.is_empty() {
+                chunks.push(current);
+                current = String::new();
+            }
+
+            // If single line is too long, split it
+            if line.len() > max_len {
+                let mut start = 0;
+                while start < line.len() {
+                    if start + max_len >= line.len() {
+                        chunks.push(line[start..].to_string());
+                        break;
+                    }
+
+                    let mut end = floor_char_boundary(line, start + max_len);
+                    if end == start {
+                        // max_len is smaller than this one character; emit it whole
+                        end = start + line[start..].chars().next().map_or(1, char::len_utf8);
+                    }
+
+                    // Try to split at word boundary; the space itself is dropped
+                    match line[start..end].rfind(' ').filter(|&p| p > 0) {
+                        Some(p) => {
+                            chunks.push(line[start..start + p].to_string());
+                            start += p + 1;
+                        }
+                        None => {
+                            chunks.push(line[start..end].to_string());
+                            start = end;
+                        }
+                    }
+                }
+            } else {
+                current.push_str(line);
+            }
+        } else {
+            if !current.is_empty() {
+                current.push('\n');
+            }
+            current.push_str(line);
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_lines_are_joined() {
+        assert_eq!(split_message("one\ntwo\nthree", 9), vec!["one\ntwo", "three"]);
+    }
+
+    #[test]
+    fn test_long_line_splits_at_word_boundary() {
+        assert_eq!(
+            split_message("the quick brown fox jumps", 10),
+            vec!["the quick", "brown fox", "jumps"]
+        );
+    }
+
+    #[test]
+    fn test_content_without_spaces_is_hard_split() {
+        let chunks = split_message(&"a".repeat(25), 10);
+        assert_eq!(chunks, vec!["a".repeat(10), "a".repeat(10), "a".repeat(5)]);
+    }
+
+    #[test]
+    fn test_long_multibyte_line_splits_on_char_boundaries() {
+        let line = "日".repeat(10_000);
+        let chunks = split_message(&line, 4096);
+
+        // 3-byte chars: 1365 of them fit in 4096 bytes
+        assert_eq!(chunks.len(), 8);
+        assert!(chunks.iter().all(|c| c.len() <= 4096));
+        assert_eq!(chunks[0].chars().count(), 1365);
+        assert_eq!(chunks.concat(), line);
+    }
+
+    #[test]
+    fn test_long_code_block_is_closed_and_reopened_across_chunks() {
+        let code: Vec<String> = (0..60).map(|i| format!("    let value_{} = compute({});", i, i)).collect();
+        let content = format!("Here is the script:\n```rust\n{}\n```\nRun it with cargo.", code.join("\n"));
+        let chunks = split_message(&content, 300);
+
+        assert!(chunks.len() > 3);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 300);
+            let fences = chunk.lines().filter(|l| l.trim_start().starts_with("```")).count();
+            assert_eq!(fences % 2, 0, "unbalanced fences in {:?}", chunk);
+        }
+        assert!(chunks[1].starts_with("```rust\n"));
+        assert!(chunks.last().unwrap().ends_with("Run it with cargo."));
+
+        // Dropping the added fences gives back the original lines
+        let lines: Vec<&str> = chunks
+            .iter()
+            .enumerate()
+            .flat_map(|(i, chunk)| {
+                let lines: Vec<&str> = chunk.lines().collect();
+                let start = usize::from(i > 0 && lines[0] == "```rust");
+                let end = if i + 1 < chunks.len() && lines.last() == Some(&"```") { lines.len() - 1 } else { lines.len() };
+                lines[start..end].to_vec()
+            })
+            .collect();
+        assert_eq!(lines.join("\n"), content);
+    }
+
+    #[test]
+    fn test_empty_input() {
+        assert!(split_message("", 10).is_empty());
+    }
+}
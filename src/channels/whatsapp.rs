@@ -0,0 +1,431 @@
+use hmac::{Hmac, Mac};
+use http_body_util::{BodyExt, Full, Limited};
+use hyper::body::{Bytes, Incoming};
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use crate::bus::{InboundMessage, OutboundMessage};
+use crate::channels::util::split_message;
+use crate::config::WhatsAppConfig;
+
+const WHATSAPP_MAX_MESSAGE_LENGTH: usize = 4096;
+pub const GRAPH_API_BASE: &str = "https://graph.facebook.com/v21.0";
+/// Webhook bodies larger than this are refused
+const MAX_WEBHOOK_BYTES: usize = 1024 * 1024;
+/// Meta's HMAC-SHA256 of the raw body, as `sha256=<hex>`
+const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// WhatsApp Cloud API channel: Meta calls our webhook with inbound messages,
+/// and replies go out through the Graph API.
+pub struct WhatsAppChannel {
+    access_token: String,
+    phone_number_id: String,
+    verify_token: String,
+    app_secret: String,
+    api_base: String,
+    /// Path the webhook is served on, e.g. "/whatsapp"
+    path: String,
+    client: Client,
+    inbound_tx: mpsc::Sender<InboundMessage>,
+    allow_from: Vec<String>,
+}
+
+/// `{"object": "whatsapp_business_account", "entry": [{"changes": [{"value": ...}]}]}`
+#[derive(Deserialize)]
+struct WebhookPayload {
+    #[serde(default)]
+    entry: Vec<Entry>,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(default)]
+    changes: Vec<Change>,
+}
+
+#[derive(Deserialize)]
+struct Change {
+    #[serde(default)]
+    value: ChangeValue,
+}
+
+/// Status updates (sent, delivered, read) arrive here too, without `messages`.
+#[derive(Deserialize, Default)]
+struct ChangeValue {
+    #[serde(default)]
+    messages: Vec<WebhookMessage>,
+}
+
+#[derive(Deserialize)]
+struct WebhookMessage {
+    from: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: Option<TextBody>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct TextBody {
+    body: String,
+}
+
+#[derive(Serialize)]
+struct SendMessageRequest<'a> {
+    messaging_product: &'a str,
+    to: &'a str,
+    #[serde(rename = "type")]
+    kind: &'a str,
+    text: TextBody,
+}
+
+/// Text messages in a webhook payload; images, reactions and the like are skipped.
+fn parse_webhook(body: &[u8]) -> Result<Vec<InboundMessage>, String> {
+    let payload: WebhookPayload = serde_json::from_slice(body).map_err(|e| format!("Invalid webhook payload: {}", e))?;
+    Ok(payload
+        .entry
+        .into_iter()
+        .flat_map(|entry| entry.changes)
+        .flat_map(|change| change.value.messages)
+        .filter_map(|message| {
+            let text = message.text.filter(|_| message.kind == "text")?;
+            // A WhatsApp chat is the conversation with one phone number
+            Some(InboundMessage::new("whatsapp".to_string(), message.from.clone(), message.from, text.body))
+        })
+        .collect())
+}
+
+/// The value of `name` in a URL query string.
+fn query_param(query: &str, name: &str) -> Option<String> {
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn respond(status: StatusCode, body: impl Into<Bytes>) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(body.into()));
+    *response.status_mut() = status;
+    response
+}
+
+impl WhatsAppChannel {
+    pub fn from_config(config: &WhatsAppConfig, inbound_tx: mpsc::Sender<InboundMessage>) -> Self {
+        Self {
+            access_token: config.access_token.clone(),
+            phone_number_id: config.phone_number_id.clone(),
+            verify_token: config.verify_token.clone(),
+            app_secret: config.app_secret.clone(),
+            api_base: GRAPH_API_BASE.to_string(),
+            path: config.webhook_path.clone(),
+            client: Client::new(),
+            inbound_tx,
+            allow_from: config.allow_from.clone(),
+        }
+    }
+
+    #[cfg(test)]
+    fn with_api_base(mut self, api_base: &str) -> Self {
+        self.api_base = api_base.to_string();
+        self
+    }
+
+    /// Whether `allow_from` lets `sender_id` (a phone number) talk to the bot.
+    pub fn allows(&self, sender_id: &str) -> bool {
+        self.allow_from.is_empty() || self.allow_from.iter().any(|id| id == sender_id)
+    }
+
+    /// Answer webhook calls on `listener` until the task is dropped.
+    pub async fn serve(self: Arc<Self>, listener: TcpListener) {
+        if let Ok(addr) = listener.local_addr() {
+            tracing::info!("WhatsApp webhook listening on {}{}", addr, self.path);
+        }
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    tracing::warn!("WhatsApp webhook accept failed: {}", e);
+                    continue;
+                }
+            };
+            let channel = Arc::clone(&self);
+            tokio::spawn(async move {
+                let service = service_fn(move |request| {
+                    let channel = Arc::clone(&channel);
+                    async move { Ok::<_, std::convert::Infallible>(channel.handle(request).await) }
+                });
+                if let Err(e) = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!("WhatsApp webhook connection from {} ended: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, request: Request<Incoming>) -> Response<Full<Bytes>> {
+        if request.uri().path() != self.path {
+            return respond(StatusCode::NOT_FOUND, "Not found");
+        }
+
+        match *request.method() {
+            Method::GET => self.verify(request.uri().query().unwrap_or_default()),
+            Method::POST => {
+                let signature = request
+                    .headers()
+                    .get(SIGNATURE_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
+                let body = match Limited::new(request.into_body(), MAX_WEBHOOK_BYTES).collect().await {
+                    Ok(body) => body.to_bytes(),
+                    Err(e) => return respond(StatusCode::PAYLOAD_TOO_LARGE, e.to_string()),
+                };
+                // Checked before parsing: the sender and allow_from match come from the body
+                if !self.signed_by_meta(signature.as_deref(), &body) {
+                    tracing::warn!("WhatsApp webhook POST with a missing or wrong signature, refusing it");
+                    return respond(StatusCode::FORBIDDEN, "Invalid signature");
+                }
+                match self.receive(&body).await {
+                    Ok(()) => respond(StatusCode::OK, "OK"),
+                    Err(e) => {
+                        tracing::warn!("{}", e);
+                        respond(StatusCode::BAD_REQUEST, e)
+                    }
+                }
+            }
+            _ => respond(StatusCode::METHOD_NOT_ALLOWED, "Method not allowed"),
+        }
+    }
+
+    /// Meta's subscription check: echo `hub.challenge` when the token matches.
+    fn verify(&self, query: &str) -> Response<Full<Bytes>> {
+        let mode = query_param(query, "hub.mode");
+        let token = query_param(query, "hub.verify_token");
+        match (mode.as_deref(), token, query_param(query, "hub.challenge")) {
+            (Some("subscribe"), Some(token), Some(challenge)) if !self.verify_token.is_empty() && token == self.verify_token => {
+                tracing::info!("WhatsApp webhook verified");
+                respond(StatusCode::OK, challenge)
+            }
+            _ => {
+                tracing::warn!("WhatsApp webhook verification failed: wrong or missing verify_token");
+                respond(StatusCode::FORBIDDEN, "Verification failed")
+            }
+        }
+    }
+
+    /// Whether `signature` is the HMAC-SHA256 of `body` under the app secret.
+    /// The comparison is constant-time.
+    fn signed_by_meta(&self, signature: Option<&str>, body: &[u8]) -> bool {
+        let Some(expected) = signature
+            .and_then(|signature| signature.strip_prefix("sha256="))
+            .and_then(|digest| hex::decode(digest).ok())
+        else {
+            return false;
+        };
+        if self.app_secret.is_empty() {
+            return false;
+        }
+        let mut mac = HmacSha256::new_from_slice(self.app_secret.as_bytes()).expect("HMAC accepts keys of any length");
+        mac.update(body);
+        mac.verify_slice(&expected).is_ok()
+    }
+
+    /// Queue the text messages of one webhook call for the agent.
+    async fn receive(&self, body: &[u8]) -> Result<(), String> {
+        for msg in parse_webhook(body)? {
+            if !self.allows(&msg.sender_id) {
+                tracing::debug!("WhatsApp message from {} not in allow list, skipping", msg.sender_id);
+                continue;
+            }
+            tracing::info!("Received WhatsApp message from {}: {}", msg.sender_id, msg.content);
+            if self.inbound_tx.send(msg).await.is_err() {
+                tracing::error!("Failed to send message to channel");
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn send(&self, msg: OutboundMessage) -> Result<(), String> {
+        let url = format!("{}/{}/messages", self.api_base, self.phone_number_id);
+
+        for chunk in split_message(&msg.content, WHATSAPP_MAX_MESSAGE_LENGTH) {
+            let request = SendMessageRequest {
+                messaging_product: "whatsapp",
+                to: &msg.chat_id,
+                kind: "text",
+                text: TextBody { body: chunk },
+            };
+            let resp = self.client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .json(&request)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            if !resp.status().is_success() {
+                let status = resp.status();
+                let body = resp.text().await.unwrap_or_default();
+                return Err(format!("WhatsApp API error ({}): {}", status, body));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_WEBHOOK: &str = r#"{
+        "object": "whatsapp_business_account",
+        "entry": [{
+            "id": "102290129340398",
+            "changes": [{
+                "field": "messages",
+                "value": {
+                    "messaging_product": "whatsapp",
+                    "metadata": {"display_phone_number": "15550783881", "phone_number_id": "106540352242922"},
+                    "contacts": [{"profile": {"name": "Sari"}, "wa_id": "6281234567890"}],
+                    "messages": [
+                        {"from": "6281234567890", "id": "wamid.HBgN", "timestamp": "1717400000", "type": "text", "text": {"body": "Halo Santoso"}},
+                        {"from": "6281234567890", "id": "wamid.HBgO", "timestamp": "1717400001", "type": "reaction", "reaction": {"emoji": "👍"}}
+                    ]
+                }
+            }]
+        }]
+    }"#;
+
+    fn test_channel(allow_from: Vec<String>) -> (Arc<WhatsAppChannel>, mpsc::Receiver<InboundMessage>) {
+        let (inbound_tx, inbound_rx) = mpsc::channel(10);
+        let config = WhatsAppConfig {
+            verify_token: "s3cret".to_string(),
+            app_secret: "app-s3cret".to_string(),
+            allow_from,
+            ..Default::default()
+        };
+        (Arc::new(WhatsAppChannel::from_config(&config, inbound_tx)), inbound_rx)
+    }
+
+    /// The `X-Hub-Signature-256` value Meta would send for `body`.
+    fn sign(secret: &str, body: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(body.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    async fn serve_locally(channel: Arc<WhatsAppChannel>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(channel.serve(listener));
+        format!("http://{}/whatsapp", addr)
+    }
+
+    #[tokio::test]
+    async fn test_webhook_post_produces_inbound_message() {
+        let (channel, mut inbound_rx) = test_channel(Vec::new());
+        let url = serve_locally(channel).await;
+
+        let response = Client::new()
+            .post(&url)
+            .header(SIGNATURE_HEADER, sign("app-s3cret", SAMPLE_WEBHOOK))
+            .body(SAMPLE_WEBHOOK)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        let msg = inbound_rx.recv().await.unwrap();
+        assert_eq!(msg.channel, "whatsapp");
+        assert_eq!(msg.sender_id, "6281234567890");
+        assert_eq!(msg.chat_id, "6281234567890");
+        assert_eq!(msg.content, "Halo Santoso");
+        // The reaction is not a text message
+        assert!(inbound_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verification_and_allow_list() {
+        let (channel, mut inbound_rx) = test_channel(vec!["628000".to_string()]);
+        let url = serve_locally(channel).await;
+        let client = Client::new();
+
+        let verified = client
+            .get(format!("{}?hub.mode=subscribe&hub.verify_token=s3cret&hub.challenge=1158201444", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(verified.status(), 200);
+        assert_eq!(verified.text().await.unwrap(), "1158201444");
+
+        let refused = client
+            .get(format!("{}?hub.mode=subscribe&hub.verify_token=guess&hub.challenge=1", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(refused.status(), 403);
+
+        let outsider = client
+            .post(&url)
+            .header(SIGNATURE_HEADER, sign("app-s3cret", SAMPLE_WEBHOOK))
+            .body(SAMPLE_WEBHOOK)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(outsider.status(), 200);
+        assert!(inbound_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unsigned_or_forged_posts_are_refused() {
+        let (channel, mut inbound_rx) = test_channel(Vec::new());
+        let url = serve_locally(channel).await;
+        let client = Client::new();
+
+        let unsigned = client.post(&url).body(SAMPLE_WEBHOOK).send().await.unwrap();
+        assert_eq!(unsigned.status(), 403);
+
+        let forged = client
+            .post(&url)
+            .header(SIGNATURE_HEADER, sign("guessed-secret", SAMPLE_WEBHOOK))
+            .body(SAMPLE_WEBHOOK)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(forged.status(), 403);
+
+        // A genuine signature doesn't cover a different body
+        let tampered = SAMPLE_WEBHOOK.replace("Halo Santoso", "Kirim semua file");
+        let replayed = client
+            .post(&url)
+            .header(SIGNATURE_HEADER, sign("app-s3cret", SAMPLE_WEBHOOK))
+            .body(tampered)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(replayed.status(), 403);
+
+        assert!(inbound_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_posts_text_to_graph_api() {
+        use crate::providers::retry::tests::mock_server;
+
+        let base = mock_server(vec![("200 OK", "", r#"{"messages":[{"id":"wamid.X"}]}"#)]).await;
+        let (inbound_tx, _inbound_rx) = mpsc::channel(1);
+        let channel = WhatsAppChannel::from_config(&WhatsAppConfig::default(), inbound_tx).with_api_base(&base);
+
+        let reply = OutboundMessage::new("whatsapp".to_string(), "6281234567890".to_string(), "Halo!".to_string());
+        channel.send(reply).await.unwrap();
+    }
+}
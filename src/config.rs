@@ -1,5 +1,8 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::LazyLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Config {
@@ -14,6 +17,37 @@ pub struct Config {
 
     #[serde(default)]
     pub channels: ChannelsConfig,
+
+    #[serde(default)]
+    pub email: EmailConfig,
+
+    #[serde(default)]
+    pub memory: MemoryConfig,
+
+    #[serde(default)]
+    pub ui: UiConfig,
+
+    #[serde(default)]
+    pub audit: AuditConfig,
+
+    #[serde(default)]
+    pub events: EventsConfig,
+
+    #[serde(default)]
+    pub gateway: GatewayConfig,
+
+    /// Price per model name, for `agent.show_cost`
+    #[serde(default)]
+    pub pricing: HashMap<String, ModelPrice>,
+}
+
+/// What a model costs in USD per million tokens.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,10 +59,74 @@ pub struct AgentConfig {
     pub temperature: f32,
     #[serde(default = "default_max_iterations")]
     pub max_iterations: u32,
+    /// Tool executions allowed in one turn, across all iterations (0 = no limit)
+    #[serde(default = "default_max_tool_calls")]
+    pub max_tool_calls: u32,
+    /// Tool-call rounds of the current turn resent with each request; older rounds and repeated nudges are trimmed (0 = keep all)
+    #[serde(default = "default_keep_tool_rounds")]
+    pub keep_tool_rounds: u32,
     #[serde(default = "default_memory_window")]
     pub memory_window: u32,
     #[serde(default = "default_workspace")]
     pub workspace: String,
+    #[serde(default = "default_empty_retries")]
+    pub empty_retries: u32,
+    /// Minutes of silence after a bot question before a follow-up nudge (0 = off)
+    #[serde(default)]
+    pub followup_after_minutes: u64,
+    #[serde(default = "default_followup_message")]
+    pub followup_message: String,
+    /// Estimated token budget for a request; oldest history is dropped to fit (0 = no limit)
+    #[serde(default)]
+    pub max_context_tokens: usize,
+    /// Turns kept verbatim per chat; older ones are folded into a summary (0 = keep everything)
+    #[serde(default)]
+    pub max_persisted_turns: usize,
+    /// Append the turn's token count to replies, e.g. "(1,234 tokens)"
+    #[serde(default)]
+    pub show_usage: bool,
+    /// End each answer with the turn's tokens and their price from `[pricing]`
+    #[serde(default)]
+    pub show_cost: bool,
+    /// Prepend the workspace's examples.json / EXAMPLES.md as prior turns
+    #[serde(default)]
+    pub use_examples: bool,
+    /// Messages that wipe the current conversation's history
+    #[serde(default = "default_clear_commands")]
+    pub clear_commands: Vec<String>,
+    /// IANA timezone for the prompt's current time and for reminder times
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// Reply length to steer the model toward: "brief", "normal" or "detailed"
+    #[serde(default = "default_verbosity")]
+    pub verbosity: String,
+    /// Skip a message identical to the chat's previous one within this many seconds (0 = off)
+    #[serde(default)]
+    pub dedup_window_secs: u64,
+    /// Log files written by filesystem tools to `artifacts.jsonl`, one line per turn
+    #[serde(default)]
+    pub artifact_manifest: bool,
+    /// With the manifest on, end replies with "I created: ..." when the turn wrote files
+    #[serde(default)]
+    pub announce_artifacts: bool,
+    /// Propose tool calls without running them; the model gets a "DRY RUN" result instead
+    #[serde(default)]
+    pub dry_run: bool,
+    /// JSON Lines trace of every agent loop event, relative to the workspace ("" = off)
+    #[serde(default)]
+    pub trace_file: String,
+    /// Rotate memory/HISTORY.md past this size (0 = never rotate)
+    #[serde(default = "default_max_history_bytes")]
+    pub max_history_bytes: u64,
+    /// Rotated history files kept as HISTORY.md.1 (newest) .. HISTORY.md.N
+    #[serde(default = "default_history_backups")]
+    pub history_backups: usize,
+    /// Advertise at most this many tools per request, picked by relevance to the message (0 = all)
+    #[serde(default)]
+    pub max_advertised_tools: usize,
+    /// Tools always advertised when `max_advertised_tools` is set
+    #[serde(default = "default_core_tools")]
+    pub core_tools: Vec<String>,
 }
 
 fn default_max_tokens() -> u32 {
@@ -40,12 +138,45 @@ fn default_temperature() -> f32 {
 fn default_max_iterations() -> u32 {
     20
 }
+fn default_max_tool_calls() -> u32 {
+    50
+}
+fn default_keep_tool_rounds() -> u32 {
+    0
+}
 fn default_memory_window() -> u32 {
     50
 }
 fn default_workspace() -> String {
     "~/.santosobot/workspace".to_string()
 }
+fn default_empty_retries() -> u32 {
+    1
+}
+fn default_clear_commands() -> Vec<String> {
+    vec!["/clear".to_string(), "/reset".to_string()]
+}
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+fn default_verbosity() -> String {
+    "normal".to_string()
+}
+fn default_max_history_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+fn default_history_backups() -> usize {
+    3
+}
+fn default_core_tools() -> Vec<String> {
+    ["read_file", "write_file", "list_dir", "remember"]
+        .iter()
+        .map(|t| t.to_string())
+        .collect()
+}
+fn default_followup_message() -> String {
+    "Just checking in: do you still need help with this?".to_string()
+}
 
 impl Default for AgentConfig {
     fn default() -> Self {
@@ -54,33 +185,110 @@ impl Default for AgentConfig {
             max_tokens: 8192,
             temperature: 0.7,
             max_iterations: 20,
+            max_tool_calls: default_max_tool_calls(),
+            keep_tool_rounds: default_keep_tool_rounds(),
             memory_window: 50,
             workspace: "~/.santosobot/workspace".to_string(),
+            empty_retries: 1,
+            followup_after_minutes: 0,
+            followup_message: default_followup_message(),
+            max_context_tokens: 0,
+            max_persisted_turns: 0,
+            show_usage: false,
+            show_cost: false,
+            use_examples: false,
+            clear_commands: default_clear_commands(),
+            timezone: default_timezone(),
+            verbosity: default_verbosity(),
+            dedup_window_secs: 0,
+            artifact_manifest: false,
+            announce_artifacts: false,
+            dry_run: false,
+            trace_file: String::new(),
+            max_history_bytes: default_max_history_bytes(),
+            history_backups: default_history_backups(),
+            max_advertised_tools: 0,
+            core_tools: default_core_tools(),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProviderConfig {
+    /// Backend API flavour: "openai" (any compatible server), "anthropic" or "ollama"
+    #[serde(default = "default_provider_kind")]
+    pub kind: String,
+    /// Not needed for a local Ollama
+    #[serde(default)]
     pub api_key: String,
     #[serde(default = "default_api_base")]
     pub api_base: String,
     pub model: String,
     #[serde(default)]
     pub brave_api_key: String,
+    /// Retries for rate limits (429), 5xx errors and connection failures
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Give up on a request (or a stream that stops sending) after this many seconds
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// Treat a stream that sends nothing for this many seconds as dropped (0 = never)
+    #[serde(default = "default_stream_idle_timeout_secs")]
+    pub stream_idle_timeout_secs: u64,
+    /// Times a stalled stream is re-requested from scratch before giving up
+    #[serde(default = "default_stream_retries")]
+    pub stream_retries: u32,
+    /// Request responses as SSE streams; turn off for backends whose streaming is broken
+    #[serde(default = "default_enabled")]
+    pub stream: bool,
+    /// Model for `/embeddings` (e.g. "text-embedding-3-small"); when set, `recall` searches memory by meaning
+    #[serde(default)]
+    pub embedding_model: String,
+}
+
+fn default_stream_idle_timeout_secs() -> u64 {
+    60
+}
+fn default_stream_retries() -> u32 {
+    2
+}
+fn default_provider_kind() -> String {
+    "openai".to_string()
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_request_timeout_secs() -> u64 {
+    120
 }
 
 fn default_api_base() -> String {
     "https://api.openai.com/v1".to_string()
 }
 
+impl ProviderConfig {
+    /// Whether requests can't work without `api_key`; a local Ollama takes none.
+    pub fn needs_api_key(&self) -> bool {
+        self.kind != "ollama"
+    }
+}
+
 impl Default for ProviderConfig {
     fn default() -> Self {
         Self {
+            kind: default_provider_kind(),
             api_key: String::new(),
             api_base: "https://api.openai.com/v1".to_string(),
             model: String::new(),
             brave_api_key: String::new(),
+            max_retries: default_max_retries(),
+            request_timeout_secs: default_request_timeout_secs(),
+            stream_idle_timeout_secs: default_stream_idle_timeout_secs(),
+            stream_retries: default_stream_retries(),
+            stream: true,
+            embedding_model: String::new(),
         }
     }
 }
@@ -89,45 +297,388 @@ impl Default for ProviderConfig {
 pub struct ToolsConfig {
     #[serde(default = "default_shell_timeout")]
     pub shell_timeout: u64,
+    /// Seconds any tool call may run before it's abandoned (0 = no limit)
+    #[serde(default = "default_tool_timeout_secs")]
+    pub default_timeout_secs: u64,
+    /// Per-tool overrides of `default_timeout_secs`, keyed by tool name
+    #[serde(default)]
+    pub timeouts: HashMap<String, u64>,
     #[serde(default)]
     pub restrict_to_workspace: bool,
+    /// Refuse destructive tools outside the workspace unless the sender is trusted
+    #[serde(default)]
+    pub destructive_guard: bool,
+    #[serde(default = "default_destructive_tools")]
+    pub destructive_tools: Vec<String>,
+    #[serde(default)]
+    pub trusted_senders: Vec<String>,
+    /// Wrap web_fetch/read_file output that contains injection phrases in an untrusted-content warning
+    #[serde(default)]
+    pub injection_guard: bool,
+    #[serde(default = "default_injection_phrases")]
+    pub injection_phrases: Vec<String>,
+    #[serde(default = "default_injection_delimiter")]
+    pub injection_delimiter: String,
+    /// URL prefixes the git tool may clone from (empty = cloning disabled)
+    #[serde(default)]
+    pub git_clone_allowlist: Vec<String>,
+    /// Environment for shell commands besides PATH; an empty value passes the gateway's own through
+    #[serde(default)]
+    pub shell_env: HashMap<String, String>,
+    /// Regexes; shell commands matching any are refused. Setting this replaces the built-in list
+    #[serde(default = "default_shell_denylist")]
+    pub shell_denylist: Vec<String>,
+    /// When non-empty, only commands starting with one of these words may run
+    #[serde(default)]
+    pub shell_allowlist: Vec<String>,
+    /// Tools that only run after the user says yes in the chat (or terminal)
+    #[serde(default)]
+    pub require_approval: Vec<String>,
+    /// How long a chat approval question waits for an answer before denying
+    #[serde(default = "default_approval_timeout_secs")]
+    pub approval_timeout_secs: u64,
+    /// Longest pause the wait tool takes in one call (0 = no wait tool)
+    #[serde(default = "default_max_wait_secs")]
+    pub max_wait_secs: u64,
+    /// Shell commands running at once across all chats; more wait for a slot (0 = no limit)
+    #[serde(default)]
+    pub global_shell_concurrency: usize,
+    /// web_fetch/brave_search requests in flight at once across all chats (0 = no limit)
+    #[serde(default)]
+    pub global_web_concurrency: usize,
+    /// File extensions write_file/edit_file/apply_patch may write, e.g. ["md", "json"]; dotfiles are refused too (empty = no limit)
+    #[serde(default)]
+    pub writable_extensions: Vec<String>,
+    /// When each tool may be used, keyed by tool name; tools without a rule are always available
+    #[serde(default)]
+    pub availability: HashMap<String, ToolAvailabilityRule>,
+}
+
+/// Conditions a tool call must meet; every condition that is set must hold.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ToolAvailabilityRule {
+    /// Sender ids allowed to use the tool (empty = anyone)
+    #[serde(default)]
+    pub senders: Vec<String>,
+    /// Channels the tool may be used from (empty = any)
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// Local time window in `agent.timezone`, e.g. "09:00-17:00"; may wrap past midnight
+    #[serde(default)]
+    pub hours: String,
+    /// Weekdays, e.g. ["mon", "tue", "wed", "thu", "fri"] (empty = every day)
+    #[serde(default)]
+    pub days: Vec<String>,
 }
 
 fn default_shell_timeout() -> u64 {
     60
 }
+fn default_tool_timeout_secs() -> u64 {
+    120
+}
+fn default_destructive_tools() -> Vec<String> {
+    vec!["shell".to_string(), "write_file".to_string(), "edit_file".to_string(), "structured_edit".to_string(), "apply_patch".to_string()]
+}
+fn default_injection_phrases() -> Vec<String> {
+    [
+        "ignore previous instructions",
+        "ignore all previous instructions",
+        "ignore the above",
+        "disregard previous instructions",
+        "disregard your instructions",
+        "forget your instructions",
+        "you are now",
+        "new instructions:",
+        "system prompt",
+        "do not tell the user",
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect()
+}
+pub fn default_shell_denylist() -> Vec<String> {
+    [
+        r"(?i)\bgit\s+clone\b",              // Prevent cloning repos
+        r"(?i)\bcurl\s+.*\|.*sh\b",          // Prevent piping curl to shell
+        r"(?i)\bwget\s+.*\|.*sh\b",          // Prevent piping wget to shell
+        r"(?i)\bmv\b.*?/(etc|bin|usr)\b",     // Prevent moving files to system dirs
+        r"(?i)\bchmod\b.*?/(etc|bin|usr)\b",  // Prevent changing perms in system dirs
+        r"(?i)\bchown\b.*?/(etc|bin|usr)\b",  // Prevent changing ownership in system dirs
+        r"(?i)\bmount\b",                    // Prevent mounting
+        r"(?i)\bumount\b",                   // Prevent unmounting
+        r"(?i)\bpkill\b",                    // Prevent killing arbitrary processes
+        r"(?i)\bkillall\b",                  // Prevent killing all processes by name
+        r"(?i)\bpasswd\b",                   // Prevent password changes
+        r"(?i)\bshadow\b",                   // Prevent access to shadow file
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect()
+}
+fn default_approval_timeout_secs() -> u64 {
+    120
+}
+fn default_max_wait_secs() -> u64 {
+    120
+}
+fn default_injection_delimiter() -> String {
+    "=== UNTRUSTED CONTENT ===".to_string()
+}
 
 impl Default for ToolsConfig {
     fn default() -> Self {
         Self {
             shell_timeout: 60,
+            default_timeout_secs: default_tool_timeout_secs(),
+            timeouts: HashMap::new(),
             restrict_to_workspace: false,
+            destructive_guard: false,
+            destructive_tools: default_destructive_tools(),
+            trusted_senders: Vec::new(),
+            injection_guard: false,
+            injection_phrases: default_injection_phrases(),
+            injection_delimiter: default_injection_delimiter(),
+            git_clone_allowlist: Vec::new(),
+            shell_env: HashMap::new(),
+            shell_denylist: default_shell_denylist(),
+            shell_allowlist: Vec::new(),
+            require_approval: Vec::new(),
+            approval_timeout_secs: default_approval_timeout_secs(),
+            max_wait_secs: default_max_wait_secs(),
+            global_shell_concurrency: 0,
+            global_web_concurrency: 0,
+            writable_extensions: Vec::new(),
+            availability: HashMap::new(),
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ChannelsConfig {
-    #[serde(default)]
-    pub telegram: TelegramConfig,
+    /// One `[channels.telegram]` table, or several `[[channels.telegram]]` bots
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub telegram: Vec<TelegramConfig>,
     #[serde(default)]
     pub cli: CliConfig,
+    #[serde(default)]
+    pub whatsapp: WhatsAppConfig,
+    #[serde(default)]
+    pub http: HttpChannelConfig,
+    /// Destinations that receive the output of `/broadcast` turns
+    #[serde(default)]
+    pub broadcast: Vec<BroadcastTarget>,
+    /// Per-channel workspace and persona, e.g. `[channels.profiles.telegram]`
+    #[serde(default)]
+    pub profiles: HashMap<String, ChannelProfile>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// A channel served as its own assistant: memory and bootstrap files come
+/// from `workspace` instead of `agent.workspace`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChannelProfile {
+    #[serde(default)]
+    pub workspace: String,
+    /// Extra system-prompt instructions for every conversation on the channel
+    #[serde(default)]
+    pub persona: String,
+}
+
+impl ChannelProfile {
+    /// The profile's workspace with `~` expanded, or `None` to share the global one.
+    pub fn workspace_path(&self) -> Option<PathBuf> {
+        (!self.workspace.trim().is_empty()).then(|| expand_home(self.workspace.trim()))
+    }
+}
+
+impl ChannelsConfig {
+    /// Enabled Telegram bots with a token, keyed by bot id. The first bot keeps
+    /// an empty id unless one is configured, so single-bot setups don't tag
+    /// their messages (and keep their existing session keys).
+    pub fn telegram_bots(&self) -> Vec<(String, TelegramConfig)> {
+        self.telegram
+            .iter()
+            .enumerate()
+            .filter(|(_, bot)| bot.enabled && !bot.token.is_empty())
+            .map(|(i, bot)| {
+                let id = match (bot.id.is_empty(), i) {
+                    (false, _) => bot.id.clone(),
+                    (true, 0) => String::new(),
+                    (true, i) => format!("bot{}", i + 1),
+                };
+                (id, bot.clone())
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(item) => vec![item],
+        OneOrMany::Many(items) => items,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BroadcastTarget {
+    pub channel: String,
+    pub chat_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TelegramConfig {
+    /// Identifies this bot when several run in one gateway; carried in message
+    /// metadata so replies go out through the bot that received the message
+    #[serde(default)]
+    pub id: String,
     #[serde(default)]
     pub enabled: bool,
     #[serde(default)]
     pub token: String,
     #[serde(default)]
     pub allow_from: Vec<String>,
+    /// Telegram parse_mode for replies ("Markdown", "MarkdownV2", "HTML"); empty sends plain text
+    #[serde(default = "default_parse_mode")]
+    pub parse_mode: String,
+    /// Resend as plain text when Telegram rejects the formatting
+    #[serde(default = "default_enabled")]
+    pub plain_text_fallback: bool,
+    /// Extra system-prompt instructions for conversations on this bot
+    #[serde(default)]
+    pub persona: String,
+    /// Show long replies as they are generated by editing one message
+    #[serde(default = "default_enabled")]
+    pub stream_replies: bool,
+}
+
+fn default_parse_mode() -> String {
+    "Markdown".to_string()
+}
+
+impl Default for TelegramConfig {
+    fn default() -> Self {
+        Self {
+            id: String::new(),
+            enabled: false,
+            token: String::new(),
+            allow_from: Vec::new(),
+            parse_mode: default_parse_mode(),
+            plain_text_fallback: true,
+            persona: String::new(),
+            stream_replies: true,
+        }
+    }
+}
+
+/// WhatsApp Cloud API: inbound messages arrive on a webhook served at
+/// `bind` + `webhook_path`, replies go out through the Graph API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WhatsAppConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Graph API access token for the WhatsApp Business account
+    #[serde(default)]
+    pub access_token: String,
+    /// The business phone number that sends replies
+    #[serde(default)]
+    pub phone_number_id: String,
+    /// Shared secret Meta echoes back when subscribing the webhook
+    #[serde(default)]
+    pub verify_token: String,
+    /// The Meta app secret that signs every webhook POST (`X-Hub-Signature-256`)
+    #[serde(default)]
+    pub app_secret: String,
+    /// Phone numbers (as WhatsApp sends them, e.g. "6281234567890") allowed to talk to the bot (empty = anyone)
+    #[serde(default)]
+    pub allow_from: Vec<String>,
+    /// Address the webhook server listens on
+    #[serde(default = "default_whatsapp_bind")]
+    pub bind: String,
+    #[serde(default = "default_whatsapp_webhook_path")]
+    pub webhook_path: String,
+}
+
+fn default_whatsapp_bind() -> String {
+    "127.0.0.1:8080".to_string()
+}
+fn default_whatsapp_webhook_path() -> String {
+    "/whatsapp".to_string()
+}
+
+impl Default for WhatsAppConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            access_token: String::new(),
+            phone_number_id: String::new(),
+            verify_token: String::new(),
+            app_secret: String::new(),
+            allow_from: Vec::new(),
+            bind: default_whatsapp_bind(),
+            webhook_path: default_whatsapp_webhook_path(),
+        }
+    }
+}
+
+/// Plain HTTP inbound channel: `POST <path>` with `{"chat_id", "content"}`
+/// and a bearer token, answered with the agent's reply.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpChannelConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Callers must send `Authorization: Bearer <token>`
+    #[serde(default)]
+    pub token: String,
+    /// Address the server listens on
+    #[serde(default = "default_http_bind")]
+    pub bind: String,
+    #[serde(default = "default_http_path")]
+    pub path: String,
+    /// How long a synchronous request waits for the reply before giving up
+    #[serde(default = "default_http_reply_timeout_secs")]
+    pub reply_timeout_secs: u64,
+}
+
+fn default_http_bind() -> String {
+    "127.0.0.1:8081".to_string()
+}
+fn default_http_path() -> String {
+    "/message".to_string()
+}
+fn default_http_reply_timeout_secs() -> u64 {
+    120
+}
+
+impl Default for HttpChannelConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            token: String::new(),
+            bind: default_http_bind(),
+            path: default_http_path(),
+            reply_timeout_secs: default_http_reply_timeout_secs(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CliConfig {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
+    /// Show a spinner on stderr until the first token of a reply arrives
+    #[serde(default = "default_enabled")]
+    pub spinner: bool,
 }
 
 fn default_enabled() -> bool {
@@ -136,29 +687,310 @@ fn default_enabled() -> bool {
 
 impl Default for CliConfig {
     fn default() -> Self {
-        Self { enabled: true }
+        Self {
+            enabled: true,
+            spinner: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// JSON Lines file recording every tool execution; relative paths are in the workspace (empty = off)
+    #[serde(default)]
+    pub file: String,
+    /// Argument names whose values are logged as "[redacted]"
+    #[serde(default)]
+    pub redact_arguments: Vec<String>,
+    /// Longest tool result kept in an audit line
+    #[serde(default = "default_audit_max_result_chars")]
+    pub max_result_chars: usize,
+}
+
+fn default_audit_max_result_chars() -> usize {
+    2000
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            file: String::new(),
+            redact_arguments: Vec::new(),
+            max_result_chars: default_audit_max_result_chars(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventsConfig {
+    /// URL that receives a JSON POST for turn_start, turn_complete, turn_error and tool_error (empty = off)
+    #[serde(default)]
+    pub webhook_url: String,
+    /// How long one POST may take before it's given up on
+    #[serde(default = "default_events_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_events_timeout_secs() -> u64 {
+    5
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: String::new(),
+            timeout_secs: default_events_timeout_secs(),
+        }
+    }
+}
+
+/// Liveness and counters for monitoring the gateway.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayConfig {
+    /// Port serving GET /healthz and GET /metrics (0 = off)
+    #[serde(default)]
+    pub metrics_port: u16,
+    /// Address the metrics server binds to
+    #[serde(default = "default_metrics_host")]
+    pub metrics_host: String,
+}
+
+fn default_metrics_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            metrics_port: 0,
+            metrics_host: default_metrics_host(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    /// After every turn, ask the model for durable facts and merge them into MEMORY.md
+    #[serde(default)]
+    pub auto_extract: bool,
+    /// Keep extracted facts per sender in `memory/users/`; MEMORY.md stays shared
+    #[serde(default)]
+    pub per_user: bool,
+    /// System prompt for turning consolidated history into facts for MEMORY.md
+    #[serde(default = "default_consolidation_prompt")]
+    pub consolidation_prompt: String,
+    /// Longest memory section in the system prompt; older entries are left out (0 = no limit)
+    #[serde(default)]
+    pub max_context_chars: usize,
+}
+
+fn default_consolidation_prompt() -> String {
+    "You maintain a long-term memory of durable facts about the user. Summarize the conversation \
+below into facts worth keeping across conversations: names, preferences, decisions, ongoing \
+projects and commitments. Skip small talk and anything already in the existing memory. Reply \
+with only a JSON array of short, self-contained strings, or [] if there is nothing new."
+        .to_string()
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            auto_extract: false,
+            per_user: false,
+            consolidation_prompt: default_consolidation_prompt(),
+            max_context_chars: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiConfig {
+    /// Drop decorative emoji from bot-generated text (banner, status, reminders)
+    #[serde(default)]
+    pub plain: bool,
+    /// `emoji = false` is an alias for `plain = true`
+    #[serde(default = "default_enabled")]
+    pub emoji: bool,
+}
+
+impl UiConfig {
+    pub fn is_plain(&self) -> bool {
+        self.plain || !self.emoji
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            plain: false,
+            emoji: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub smtp_host: String,
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub from: String,
+    /// Addresses (or `@domain` wildcards) the agent may send to
+    #[serde(default)]
+    pub allowed_recipients: Vec<String>,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+impl Default for EmailConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            smtp_host: String::new(),
+            smtp_port: 587,
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            allowed_recipients: Vec::new(),
+        }
     }
 }
 
+/// `path` with `~` replaced by the home directory.
+fn expand_home(path: &str) -> PathBuf {
+    PathBuf::from(path.replace("~", &dirs::home_dir().unwrap_or_default().display().to_string()))
+}
+
+/// A `${VAR}` reference to an environment variable
+static ENV_REFERENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+/// Replace every `${VAR}` in `value` with that environment variable. Values
+/// without a reference come back unchanged.
+fn expand_env(field: &str, value: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut last = 0;
+    for reference in ENV_REFERENCE.captures_iter(value) {
+        let whole = reference.get(0).unwrap();
+        let name = &reference[1];
+        let var = std::env::var(name)
+            .map_err(|_| format!("{} refers to ${{{}}}, but that environment variable is not set", field, name))?;
+        expanded.push_str(&value[last..whole.start()]);
+        expanded.push_str(&var);
+        last = whole.end();
+    }
+    expanded.push_str(&value[last..]);
+    Ok(expanded)
+}
+
 impl Config {
     pub fn load(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
         let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let mut config: Config = toml::from_str(&content)?;
+        config.expand_secrets()?;
         Ok(config)
     }
 
+    /// Expand `${VAR}` references in the fields that hold secrets, so they can
+    /// come from the environment instead of the file.
+    fn expand_secrets(&mut self) -> Result<(), String> {
+        self.provider.api_key = expand_env("provider.api_key", &self.provider.api_key)?;
+        self.provider.brave_api_key = expand_env("provider.brave_api_key", &self.provider.brave_api_key)?;
+        self.email.password = expand_env("email.password", &self.email.password)?;
+        for (i, bot) in self.channels.telegram.iter_mut().enumerate() {
+            bot.token = expand_env(&format!("channels.telegram[{}].token", i), &bot.token)?;
+        }
+        let whatsapp = &mut self.channels.whatsapp;
+        whatsapp.access_token = expand_env("channels.whatsapp.access_token", &whatsapp.access_token)?;
+        whatsapp.verify_token = expand_env("channels.whatsapp.verify_token", &whatsapp.verify_token)?;
+        whatsapp.app_secret = expand_env("channels.whatsapp.app_secret", &whatsapp.app_secret)?;
+        self.channels.http.token = expand_env("channels.http.token", &self.channels.http.token)?;
+        Ok(())
+    }
+
+    /// Catch values that parse but would only fail later, at the API or a channel.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=2.0).contains(&self.agent.temperature) {
+            return Err(format!("agent.temperature must be between 0.0 and 2.0, got {}", self.agent.temperature));
+        }
+        if self.agent.max_tokens == 0 {
+            return Err("agent.max_tokens must be greater than 0".to_string());
+        }
+        if self.agent.max_iterations == 0 {
+            return Err("agent.max_iterations must be greater than 0".to_string());
+        }
+        if !["brief", "normal", "detailed"].contains(&self.agent.verbosity.as_str()) {
+            return Err(format!("agent.verbosity must be brief, normal or detailed, got '{}'", self.agent.verbosity));
+        }
+        url::Url::parse(&self.provider.api_base)
+            .map_err(|e| format!("provider.api_base '{}' is not a valid URL: {}", self.provider.api_base, e))?;
+        for (i, bot) in self.channels.telegram.iter().enumerate() {
+            if bot.enabled && bot.token.trim().is_empty() {
+                return Err(format!("channels.telegram[{}] is enabled but has no token", i));
+            }
+        }
+        let whatsapp = &self.channels.whatsapp;
+        if whatsapp.enabled {
+            for (field, value) in [
+                ("access_token", &whatsapp.access_token),
+                ("phone_number_id", &whatsapp.phone_number_id),
+                ("verify_token", &whatsapp.verify_token),
+                ("app_secret", &whatsapp.app_secret),
+            ] {
+                if value.trim().is_empty() {
+                    return Err(format!("channels.whatsapp is enabled but has no {}", field));
+                }
+            }
+            if !whatsapp.webhook_path.starts_with('/') {
+                return Err(format!("channels.whatsapp.webhook_path must start with '/', got '{}'", whatsapp.webhook_path));
+            }
+        }
+        let http = &self.channels.http;
+        if http.enabled {
+            if http.token.trim().is_empty() {
+                return Err("channels.http is enabled but has no token".to_string());
+            }
+            if !http.path.starts_with('/') {
+                return Err(format!("channels.http.path must start with '/', got '{}'", http.path));
+            }
+        }
+        Ok(())
+    }
+
     pub fn workspace_path(&self) -> PathBuf {
-        let path = self.agent.workspace.replace(
-            "~",
-            &dirs::home_dir().unwrap_or_default().display().to_string(),
-        );
-        PathBuf::from(path)
+        expand_home(&self.agent.workspace)
+    }
+
+    /// Where the tool audit log goes, if `audit.file` is set.
+    pub fn audit_path(&self) -> Option<PathBuf> {
+        self.workspace_file(&self.audit.file)
+    }
+
+    pub fn trace_path(&self) -> Option<PathBuf> {
+        self.workspace_file(&self.agent.trace_file)
+    }
+
+    /// A configured file path, relative to the workspace unless absolute.
+    /// Empty means the feature is off.
+    fn workspace_file(&self, file: &str) -> Option<PathBuf> {
+        if file.trim().is_empty() {
+            return None;
+        }
+        let path = expand_home(file);
+        Some(if path.is_relative() { self.workspace_path().join(path) } else { path })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
     use tempfile::TempDir;
 
     #[test]
@@ -169,22 +1001,130 @@ mod tests {
         assert_eq!(config.agent.max_tokens, 8192);
         assert_eq!(config.agent.temperature, 0.7);
         assert_eq!(config.agent.max_iterations, 20);
+        assert_eq!(config.agent.max_tool_calls, 50);
+        assert_eq!(config.agent.keep_tool_rounds, 0);
         assert_eq!(config.agent.memory_window, 50);
         assert_eq!(config.agent.workspace, "~/.santosobot/workspace");
+        assert_eq!(config.agent.empty_retries, 1);
+        assert_eq!(config.agent.followup_after_minutes, 0);
+        assert_eq!(config.agent.max_context_tokens, 0);
+        assert_eq!(config.agent.max_persisted_turns, 0);
+        assert!(!config.agent.show_usage);
+        assert!(!config.agent.show_cost);
+        assert!(!config.agent.use_examples);
+        assert_eq!(config.agent.clear_commands, vec!["/clear", "/reset"]);
+        assert_eq!(config.agent.timezone, "UTC");
+        assert_eq!(config.agent.dedup_window_secs, 0);
+        assert!(config.agent.trace_file.is_empty());
         
         assert_eq!(config.provider.api_base, "https://api.openai.com/v1");
         assert!(config.provider.api_key.is_empty());
         assert!(config.provider.model.is_empty());
         assert!(config.provider.brave_api_key.is_empty());
+        assert_eq!(config.provider.max_retries, 3);
+        assert_eq!(config.provider.request_timeout_secs, 120);
+        assert!(config.provider.stream);
         
         assert_eq!(config.tools.shell_timeout, 60);
+        assert_eq!(config.tools.default_timeout_secs, 120);
+        assert_eq!(config.tools.max_wait_secs, 120);
+        assert!(config.tools.timeouts.is_empty());
         assert!(!config.tools.restrict_to_workspace);
+        assert!(!config.tools.destructive_guard);
+        assert_eq!(config.tools.destructive_tools, vec!["shell", "write_file", "edit_file", "structured_edit", "apply_patch"]);
+        assert!(config.tools.trusted_senders.is_empty());
+        assert!(!config.tools.injection_guard);
+        assert!(config.tools.git_clone_allowlist.is_empty());
+        assert!(config.tools.shell_env.is_empty());
+        assert_eq!(config.tools.shell_denylist.len(), 12);
+        assert!(config.tools.shell_allowlist.is_empty());
+        assert!(config.tools.injection_phrases.contains(&"ignore previous instructions".to_string()));
         
-        assert!(!config.channels.telegram.enabled);
-        assert!(config.channels.telegram.token.is_empty());
-        assert!(config.channels.telegram.allow_from.is_empty());
+        assert!(config.channels.telegram.is_empty());
+        let telegram = super::TelegramConfig::default();
+        assert!(!telegram.enabled);
+        assert!(telegram.token.is_empty());
+        assert!(telegram.allow_from.is_empty());
+        assert_eq!(telegram.parse_mode, "Markdown");
+        assert!(telegram.plain_text_fallback);
+        assert!(telegram.persona.is_empty());
         
         assert!(config.channels.cli.enabled);
+        assert!(config.channels.cli.spinner);
+        assert!(!config.channels.whatsapp.enabled);
+        assert_eq!(config.channels.whatsapp.bind, "127.0.0.1:8080");
+        assert_eq!(config.channels.whatsapp.webhook_path, "/whatsapp");
+        assert_eq!(config.gateway.metrics_port, 0);
+        assert_eq!(config.gateway.metrics_host, "127.0.0.1");
+        assert!(!config.channels.http.enabled);
+        assert_eq!(config.channels.http.bind, "127.0.0.1:8081");
+        assert_eq!(config.channels.http.path, "/message");
+        assert!(config.channels.broadcast.is_empty());
+
+        assert!(!config.memory.auto_extract);
+        assert!(config.events.webhook_url.is_empty());
+        assert_eq!(config.events.timeout_secs, 5);
+        assert!(!config.email.enabled);
+        assert_eq!(config.email.smtp_port, 587);
+        assert!(config.email.allowed_recipients.is_empty());
+
+        assert!(!config.ui.is_plain());
+        let ui: super::UiConfig = toml::from_str("emoji = false").unwrap();
+        assert!(ui.is_plain());
+    }
+
+    #[test]
+    fn test_validate_rejects_values_the_api_would() {
+        assert!(super::Config::default().validate().is_ok());
+
+        let error = |edit: fn(&mut super::Config)| {
+            let mut config = super::Config::default();
+            edit(&mut config);
+            config.validate().unwrap_err()
+        };
+        assert!(error(|c| c.agent.temperature = 5.0).contains("agent.temperature"));
+        assert!(error(|c| c.agent.temperature = -0.1).contains("agent.temperature"));
+        assert!(error(|c| c.agent.max_tokens = 0).contains("agent.max_tokens"));
+        assert!(error(|c| c.agent.max_iterations = 0).contains("agent.max_iterations"));
+        assert!(error(|c| c.agent.verbosity = "chatty".to_string()).contains("agent.verbosity"));
+        assert!(error(|c| c.provider.api_base = String::new()).contains("provider.api_base"));
+        assert!(error(|c| c.provider.api_base = "api.openai.com/v1".to_string()).contains("provider.api_base"));
+        assert!(error(|c| c.channels.telegram = vec![super::TelegramConfig { enabled: true, ..Default::default() }])
+            .contains("channels.telegram[0]"));
+        assert!(error(|c| c.channels.http.enabled = true).contains("channels.http is enabled but has no token"));
+
+        // A disabled bot without a token is fine
+        let mut config = super::Config::default();
+        config.channels.telegram = vec![super::TelegramConfig { token: String::new(), ..Default::default() }];
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_secrets_are_expanded_from_the_environment() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        std::env::set_var("SANTOSOBOT_TEST_API_KEY", "sk-from-env");
+        std::env::set_var("SANTOSOBOT_TEST_BOT_TOKEN", "123:from-env");
+        std::fs::write(&config_path, r#"
+[provider]
+model = "gpt-4o-mini"
+api_key = "${SANTOSOBOT_TEST_API_KEY}"
+brave_api_key = "literal-key"
+
+[channels.telegram]
+enabled = true
+token = "${SANTOSOBOT_TEST_BOT_TOKEN}"
+"#).unwrap();
+
+        let config = super::Config::load(&config_path).unwrap();
+        assert_eq!(config.provider.api_key, "sk-from-env");
+        assert_eq!(config.provider.brave_api_key, "literal-key");
+        assert_eq!(config.channels.telegram[0].token, "123:from-env");
+
+        std::fs::write(&config_path, "[provider]\nmodel = \"m\"\napi_key = \"${SANTOSOBOT_TEST_UNSET_KEY}\"\n").unwrap();
+        let error = super::Config::load(&config_path).unwrap_err().to_string();
+        assert!(error.contains("provider.api_key"));
+        assert!(error.contains("SANTOSOBOT_TEST_UNSET_KEY"));
     }
 
     #[test]
@@ -229,6 +1169,10 @@ allow_from = ["123456789"]
 
 [channels.cli]
 enabled = false
+
+[[channels.broadcast]]
+channel = "telegram"
+chat_id = "-100200300"
 "#;
         
         std::fs::write(&config_path, config_content).unwrap();
@@ -249,10 +1193,36 @@ enabled = false
         assert_eq!(config.tools.shell_timeout, 30);
         assert!(config.tools.restrict_to_workspace);
         
-        assert!(config.channels.telegram.enabled);
-        assert_eq!(config.channels.telegram.token, "test-token");
-        assert_eq!(config.channels.telegram.allow_from, vec!["123456789"]);
+        assert_eq!(config.channels.telegram.len(), 1);
+        assert!(config.channels.telegram[0].enabled);
+        assert_eq!(config.channels.telegram[0].token, "test-token");
+        assert_eq!(config.channels.telegram[0].allow_from, vec!["123456789"]);
         
         assert!(!config.channels.cli.enabled);
+        assert_eq!(config.channels.broadcast[0].chat_id, "-100200300");
+    }
+
+    #[test]
+    fn test_multiple_telegram_bots() {
+        let config: super::Config = toml::from_str(r#"
+[[channels.telegram]]
+enabled = true
+token = "personal-token"
+
+[[channels.telegram]]
+id = "work"
+enabled = true
+token = "work-token"
+persona = "You are a terse work assistant."
+
+[[channels.telegram]]
+enabled = true
+token = ""
+"#).unwrap();
+
+        let bots = config.channels.telegram_bots();
+        let ids: Vec<&str> = bots.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["", "work"]);
+        assert_eq!(bots[1].1.persona, "You are a terse work assistant.");
     }
 }
@@ -1,19 +1,28 @@
 mod agent;
+mod bench;
 mod bus;
 mod channels;
 mod config;
 mod providers;
+mod ui;
 mod utils;
 
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 use config::Config;
 #[allow(unused_imports)]
 use bus::{InboundMessage, OutboundMessage};
-use agent::AgentLoop;
-use channels::telegram::TelegramChannel;
+use agent::{format_thousands, AgentLoop, AgentMetrics, ChatApprover, CliApprover, SessionStore};
+use channels::cli::{Spinner, ThinkingIndicator};
+use channels::dry_run::{self, DryRunLog};
+use channels::telegram::{self, StreamingSender, TelegramChannel};
+use channels::http::HttpChannel;
+use channels::whatsapp::WhatsAppChannel;
+use std::io::IsTerminal;
 
 #[derive(Parser)]
 #[command(name = "santosobot")]
@@ -29,11 +38,45 @@ enum Commands {
     Agent {
         #[arg(short, long)]
         message: Option<String>,
+        /// Don't show the thinking spinner
+        #[arg(short, long)]
+        quiet: bool,
+        /// Extra system instruction for this run, added after the base identity
+        #[arg(long, conflicts_with = "system_file")]
+        system: Option<String>,
+        /// Read the extra system instruction from a file
+        #[arg(long)]
+        system_file: Option<PathBuf>,
+        /// Show the tool calls the agent would make without running any of them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    Gateway {
+        /// Run without contacting channels: no polling, and replies are logged instead of sent
+        #[arg(long)]
+        dry_run: bool,
+        /// Write dry-run output to this file instead of stdout
+        #[arg(long, requires = "dry_run")]
+        dry_run_log: Option<PathBuf>,
+        /// Feed the inbound messages in this JSON Lines file through the gateway
+        #[arg(long, requires = "dry_run")]
+        replay: Option<PathBuf>,
     },
-    Gateway,
     Status,
+    /// Measure latency and throughput of the configured provider and model
+    Bench {
+        /// Prompt sent on every run
+        #[arg(short, long, default_value = "Write one short paragraph about the sea.")]
+        prompt: String,
+        /// Number of requests to send
+        #[arg(short, long, default_value_t = 5)]
+        runs: u32,
+    },
 }
 
+/// How long shutdown waits for the current turn, and then for queued replies
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(15);
+
 fn get_config_path() -> PathBuf {
     let path = dirs::config_dir()
         .unwrap_or_else(|| PathBuf::from("."))
@@ -61,6 +104,7 @@ max_iterations = 20
 memory_window = 50
 
 [provider]
+kind = "openai"
 api_key = ""
 api_base = "https://api.openai.com/v1"
 model = ""
@@ -95,38 +139,47 @@ fn setup_logging() {
 }
 
 fn print_banner() {
-    println!(r#"
-╔═══════════════════════════════════════════════════════════╗
-║   🤖 S A N T O S O B O T                                ║
-║   Ultra-Lightweight Personal AI Assistant               ║
-╠═══════════════════════════════════════════════════════════╣
-║   Version: 0.1.0  |  Rust-based  |  OpenAI-compatible   ║
-╚═══════════════════════════════════════════════════════════╝
-    "#);
+    println!("{}", ui::banner(ui::is_plain()));
 }
 
 fn print_success(message: &str) {
-    println!("✅ {}", message);
+    println!("{}", ui::decorate("✅", message, ui::is_plain()));
 }
 
 fn print_info(message: &str) {
-    println!("ℹ️  {}", message);
+    println!("{}", ui::decorate("ℹ️ ", message, ui::is_plain()));
 }
 
 fn print_warning(message: &str) {
-    println!("⚠️  {}", message);
+    println!("{}", ui::decorate("⚠️ ", message, ui::is_plain()));
+}
+
+fn print_error(message: &str) {
+    eprintln!("{}", ui::decorate("❌", message, ui::is_plain()));
 }
 
-async fn run_agent_mode(message: Option<String>, config: Config) {
+async fn run_agent_mode(message: Option<String>, quiet: bool, system: Option<String>, config: Config) {
     let (_inbound_tx, inbound_rx) = mpsc::channel(10);
     let (outbound_tx, _outbound_rx) = mpsc::channel(10);
-    
-    let agent = AgentLoop::new(&config, inbound_rx, outbound_tx);
+    let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+
+    let agent = AgentLoop::new(&config, inbound_rx, outbound_tx)
+        .with_progress(progress_tx)
+        .with_approver(Arc::new(CliApprover::new()));
+    let agent = match system {
+        Some(instruction) => agent.with_system_instruction(instruction),
+        None => agent,
+    };
+
+    let show_spinner = config.channels.cli.spinner && !quiet && std::io::stderr().is_terminal();
+    let spinner = Spinner::spawn(ThinkingIndicator::new(show_spinner, ui::is_plain()), progress_rx);
     
     if let Some(msg) = message {
-        match agent.process_direct(&msg).await {
+        let result = agent.process_direct(&msg).await;
+        spinner.stop();
+        match result {
             Ok(response) => println!("\n{}", response),
-            Err(e) => eprintln!("❌ Error: {}", e),
+            Err(e) => print_error(&format!("Error: {}", e)),
         }
     } else {
         println!("\nInteractive mode - Type 'exit' or 'quit' to end\n");
@@ -145,61 +198,173 @@ async fn run_agent_mode(message: Option<String>, config: Config) {
                 break;
             }
             
-            match agent.process_direct(input).await {
+            let result = agent.process_direct(input).await;
+            spinner.stop();
+            match result {
                 Ok(response) => println!("\nSantoso: {}", response),
-                Err(e) => eprintln!("\n❌ Error: {}", e),
+                Err(e) => {
+                    eprintln!();
+                    print_error(&format!("Error: {}", e));
+                }
             }
         }
     }
 }
 
-async fn run_gateway_mode(config: Config) {
+/// Options for `gateway --dry-run`.
+struct DryRun {
+    log: DryRunLog,
+    replay: Option<PathBuf>,
+}
+
+async fn run_gateway_mode(config: Config, dry_run: Option<DryRun>) {
     print_banner();
     println!();
 
-    let (inbound_tx, inbound_rx) = mpsc::channel(100);
+    let (inbound_tx, raw_inbound_rx) = mpsc::channel(100);
+    let (agent_tx, inbound_rx) = mpsc::channel(100);
     let (outbound_tx, mut outbound_rx) = mpsc::channel(100);
 
-    let mut agent = AgentLoop::new(&config, inbound_rx, outbound_tx.clone());
-
-    tokio::spawn(async move {
+    // Answers to approval questions are picked off before they reach the agent,
+    // which is still busy with the turn that asked
+    let approver = Arc::new(ChatApprover::new(
+        outbound_tx.clone(),
+        Duration::from_secs(config.tools.approval_timeout_secs),
+    ));
+    Arc::clone(&approver).forward(raw_inbound_rx, agent_tx);
+
+    // Set on Ctrl+C: pollers stop, the agent finishes its turn and saves sessions
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    // Set once the agent is done, so its last replies still go out
+    let (flush_tx, mut flush_rx) = tokio::sync::watch::channel(false);
+
+    let metrics = Arc::new(AgentMetrics::default());
+    let mut agent = AgentLoop::new(&config, inbound_rx, outbound_tx.clone())
+        .with_approver(approver)
+        .with_shutdown(shutdown_rx.clone())
+        .with_metrics(Arc::clone(&metrics));
+
+    let agent_task = tokio::spawn(async move {
         agent.run().await;
     });
 
-    let telegram_enabled = config.channels.telegram.enabled && !config.channels.telegram.token.is_empty();
+    if config.gateway.metrics_port > 0 {
+        let bind = format!("{}:{}", config.gateway.metrics_host, config.gateway.metrics_port);
+        match tokio::net::TcpListener::bind(&bind).await {
+            Ok(listener) => {
+                let mut shutdown = shutdown_rx.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        _ = metrics.serve(listener) => {}
+                        _ = shutdown.wait_for(|stop| *stop) => {}
+                    }
+                });
+                print_success(&format!("Metrics server started (http://{}/healthz, /metrics)", bind));
+            }
+            Err(e) => print_error(&format!("Metrics server not started: can't listen on {}: {}", bind, e)),
+        }
+    }
 
-    if telegram_enabled {
-        let telegram = TelegramChannel::new(
-            config.channels.telegram.token.clone(),
-            inbound_tx.clone(),
-            config.channels.telegram.allow_from.clone(),
-        );
+    // One channel per bot, shared by its polling task and the outbound loop
+    let telegram_bots: Vec<(String, Arc<TelegramChannel>)> = config
+        .channels
+        .telegram_bots()
+        .iter()
+        .map(|(bot_id, bot)| (bot_id.clone(), Arc::new(TelegramChannel::from_config(bot_id, bot, inbound_tx.clone()))))
+        .collect();
+
+    for (bot_id, telegram) in telegram_bots.iter().filter(|_| dry_run.is_none()) {
+        let telegram = Arc::clone(telegram);
+        let mut shutdown = shutdown_rx.clone();
 
         tokio::spawn(async move {
-            telegram.start().await;
+            tokio::select! {
+                _ = telegram.start() => {}
+                _ = shutdown.wait_for(|stop| *stop) => {}
+            }
         });
 
-        print_success("Telegram channel started");
+        if bot_id.is_empty() {
+            print_success("Telegram channel started");
+        } else {
+            print_success(&format!("Telegram channel started ({})", bot_id));
+        }
     }
 
-    let telegram_config = config.channels.telegram;
+    let whatsapp = config
+        .channels
+        .whatsapp
+        .enabled
+        .then(|| Arc::new(WhatsAppChannel::from_config(&config.channels.whatsapp, inbound_tx.clone())));
+
+    if let Some(whatsapp) = whatsapp.as_ref().filter(|_| dry_run.is_none()) {
+        let bind = &config.channels.whatsapp.bind;
+        match tokio::net::TcpListener::bind(bind).await {
+            Ok(listener) => {
+                let whatsapp = Arc::clone(whatsapp);
+                let mut shutdown = shutdown_rx.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        _ = whatsapp.serve(listener) => {}
+                        _ = shutdown.wait_for(|stop| *stop) => {}
+                    }
+                });
+                print_success(&format!("WhatsApp channel started (webhook on {}{})", bind, config.channels.whatsapp.webhook_path));
+            }
+            Err(e) => print_error(&format!("WhatsApp channel not started: can't listen on {}: {}", bind, e)),
+        }
+    }
 
-    tokio::spawn(async move {
-        while let Some(msg) = outbound_rx.recv().await {
-            match msg.channel.as_str() {
-                "telegram" => {
-                    if telegram_config.enabled && !telegram_config.token.is_empty() {
-                        let telegram = TelegramChannel::new(
-                            telegram_config.token.clone(),
-                            inbound_tx.clone(),
-                            telegram_config.allow_from.clone(),
-                        );
+    let http = config
+        .channels
+        .http
+        .enabled
+        .then(|| Arc::new(HttpChannel::from_config(&config.channels.http, inbound_tx.clone())));
+
+    if let Some(http) = http.as_ref().filter(|_| dry_run.is_none()) {
+        let bind = &config.channels.http.bind;
+        match tokio::net::TcpListener::bind(bind).await {
+            Ok(listener) => {
+                let http = Arc::clone(http);
+                let mut shutdown = shutdown_rx.clone();
+                tokio::spawn(async move {
+                    tokio::select! {
+                        _ = http.serve(listener) => {}
+                        _ = shutdown.wait_for(|stop| *stop) => {}
+                    }
+                });
+                print_success(&format!("HTTP channel started (POST {}{})", bind, config.channels.http.path));
+            }
+            Err(e) => print_error(&format!("HTTP channel not started: can't listen on {}: {}", bind, e)),
+        }
+    }
+
+    let dry_run_log = match dry_run {
+        Some(DryRun { log, replay }) => {
+            print_warning("Dry run: channels are not contacted, replies are logged instead");
+            if let Some(path) = replay {
+                replay_script(&path, &telegram_bots, &inbound_tx).await;
+            }
+            Some(log)
+        }
+        None => None,
+    };
 
-                        let _ = telegram.send(msg).await;
+    let outbound_task = tokio::spawn(async move {
+        let mut streams = StreamingSender::new(telegram::STREAM_EDIT_INTERVAL);
+        let dry_run_log = dry_run_log.as_ref();
+        loop {
+            tokio::select! {
+                msg = outbound_rx.recv() => match msg {
+                    Some(msg) => deliver(&telegram_bots, whatsapp.as_deref(), http.as_deref(), &mut streams, dry_run_log, msg).await,
+                    None => break,
+                },
+                _ = flush_rx.changed() => {
+                    while let Ok(msg) = outbound_rx.try_recv() {
+                        deliver(&telegram_bots, whatsapp.as_deref(), http.as_deref(), &mut streams, dry_run_log, msg).await;
                     }
+                    break;
                 }
-                "cli" => println!("\nSantoso: {}", msg.content),
-                _ => tracing::warn!("Unknown channel: {}", msg.channel),
             }
         }
     });
@@ -210,9 +375,124 @@ async fn run_gateway_mode(config: Config) {
     println!();
 
     tokio::signal::ctrl_c().await.ok();
+    print_warning("Shutting down...");
+
+    let _ = shutdown_tx.send(true);
+    if tokio::time::timeout(SHUTDOWN_GRACE, agent_task).await.is_err() {
+        print_warning("The current turn did not finish in time and was abandoned");
+    }
+    let _ = flush_tx.send(true);
+    if tokio::time::timeout(SHUTDOWN_GRACE, outbound_task).await.is_err() {
+        print_warning("Some replies could not be delivered before exit");
+    }
+
     print_warning("Gateway stopped");
 }
 
+/// Queue the messages of a replay script as if they had arrived on their channels.
+async fn replay_script(path: &std::path::Path, telegram_bots: &[(String, Arc<TelegramChannel>)], inbound_tx: &mpsc::Sender<InboundMessage>) {
+    let script = match dry_run::load_script(path) {
+        Ok(script) => script,
+        Err(e) => {
+            print_error(&e);
+            return;
+        }
+    };
+
+    let total = script.len();
+    let mut queued = 0;
+    for msg in script {
+        match dry_run::admit(telegram_bots, msg) {
+            Ok(msg) => {
+                if inbound_tx.send(msg).await.is_err() {
+                    break;
+                }
+                queued += 1;
+            }
+            Err(reason) => print_warning(&format!("Replay: dropped: {}", reason)),
+        }
+    }
+    print_info(&format!("Replay: queued {} of {} scripted messages", queued, total));
+}
+
+/// Send one outbound message through the channel it names, or just log it on a dry run.
+async fn deliver(
+    telegram_bots: &[(String, Arc<TelegramChannel>)],
+    whatsapp: Option<&WhatsAppChannel>,
+    http: Option<&HttpChannel>,
+    streams: &mut StreamingSender,
+    dry_run_log: Option<&DryRunLog>,
+    msg: OutboundMessage,
+) {
+    if let Some(log) = dry_run_log {
+        if let Err(e) = log.record(telegram_bots, &msg) {
+            tracing::warn!("{}", e);
+        }
+        return;
+    }
+
+    match msg.channel.as_str() {
+        "telegram" => {
+            let Some((_, telegram)) = telegram::route(telegram_bots, &msg) else {
+                tracing::warn!("No Telegram bot to deliver to telegram:{}", msg.chat_id);
+                return;
+            };
+            if msg.is_streaming && !telegram.streams_replies() {
+                return;
+            }
+
+            let chat_id = msg.chat_id.clone();
+            if let Err(e) = streams.deliver(telegram, msg).await {
+                tracing::warn!("Failed to deliver to telegram:{}: {}", chat_id, e);
+            }
+        }
+        // WhatsApp messages can't be edited, so only final replies go out
+        "whatsapp" if msg.is_streaming => {}
+        "whatsapp" => {
+            let Some(whatsapp) = whatsapp else {
+                tracing::warn!("WhatsApp channel is not enabled, dropping reply to whatsapp:{}", msg.chat_id);
+                return;
+            };
+            let chat_id = msg.chat_id.clone();
+            if let Err(e) = whatsapp.send(msg).await {
+                tracing::warn!("Failed to deliver to whatsapp:{}: {}", chat_id, e);
+            }
+        }
+        // The caller gets one response, so partials are skipped
+        "http" if msg.is_streaming => {}
+        "http" => match http {
+            Some(http) => http.send(msg),
+            None => tracing::warn!("HTTP channel is not enabled, dropping reply to http:{}", msg.chat_id),
+        },
+        "cli" if msg.is_streaming => {}
+        "cli" => println!("\nSantoso: {}", msg.content),
+        _ => tracing::warn!("Unknown channel: {}", msg.channel),
+    }
+}
+
+async fn run_bench(config: &Config, prompt: &str, runs: u32) {
+    let provider = providers::create_provider(&config.provider);
+    print_info(&format!("Benchmarking {} at {} ({} runs)", config.agent.model, config.provider.api_base, runs));
+
+    let mut timings = Vec::new();
+    for run in 1..=runs {
+        match bench::run_once(provider.as_ref(), prompt, &config.agent.model, config.agent.max_tokens).await {
+            Ok(timing) => {
+                println!("  Run {}: {:.0} ms", run, timing.total.as_secs_f64() * 1000.0);
+                timings.push(timing);
+            }
+            Err(e) => print_error(&format!("Run {} failed: {}", run, e)),
+        }
+    }
+
+    if timings.is_empty() {
+        print_error("No successful runs");
+        return;
+    }
+    println!();
+    println!("{}", bench::report(&timings));
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     setup_logging();
@@ -255,46 +535,110 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
             
-            println!("\n🎉 Setup complete! Please edit the config file and add your API key.");
+            println!();
+            println!("{}", ui::decorate("🎉", "Setup complete! Please edit the config file and add your API key.", ui::is_plain()));
         }
         
-        Commands::Agent { message } => {
+        Commands::Agent { message, quiet, system, system_file, dry_run } => {
             if !config_path.exists() {
-                eprintln!("❌ Config not found. Run 'santosobot onboard' first.");
+                print_error("Config not found. Run 'santosobot onboard' first.");
                 return Ok(());
             }
             
-            let config = Config::load(&config_path)?;
+            let mut config = Config::load(&config_path)?;
+            ui::set_plain(config.ui.is_plain());
+
+            if let Err(e) = config.validate() {
+                print_error(&format!("Invalid config in {:?}: {}", config_path, e));
+                return Ok(());
+            }
             
-            if config.provider.api_key.is_empty() {
-                eprintln!("❌ API key not configured. Edit {:?} and add your API key.", config_path);
+            if config.provider.needs_api_key() && config.provider.api_key.is_empty() {
+                print_error(&format!("API key not configured. Edit {:?} and add your API key.", config_path));
                 return Ok(());
             }
             
             if config.provider.model.is_empty() {
-                eprintln!("❌ Model not configured. Edit {:?} and add your model.", config_path);
+                print_error(&format!("Model not configured. Edit {:?} and add your model.", config_path));
                 return Ok(());
             }
+
+            config.agent.dry_run |= dry_run;
             
-            run_agent_mode(message, config).await;
+            let system = match system_file {
+                Some(path) => match std::fs::read_to_string(&path) {
+                    Ok(text) => Some(text),
+                    Err(e) => {
+                        print_error(&format!("Failed to read {}: {}", path.display(), e));
+                        return Ok(());
+                    }
+                },
+                None => system,
+            };
+
+            run_agent_mode(message, quiet, system, config).await;
         }
         
-        Commands::Gateway => {
+        Commands::Gateway { dry_run, dry_run_log, replay } => {
             if !config_path.exists() {
-                eprintln!("❌ Config not found. Run 'santosobot onboard' first.");
+                print_error("Config not found. Run 'santosobot onboard' first.");
                 return Ok(());
             }
             
             let config = Config::load(&config_path)?;
+            ui::set_plain(config.ui.is_plain());
+
+            if let Err(e) = config.validate() {
+                print_error(&format!("Invalid config in {:?}: {}", config_path, e));
+                return Ok(());
+            }
             
-            if config.provider.api_key.is_empty() {
-                eprintln!("❌ API key not configured. Edit {:?} and add your API key.", config_path);
+            if config.provider.needs_api_key() && config.provider.api_key.is_empty() {
+                print_error(&format!("API key not configured. Edit {:?} and add your API key.", config_path));
                 return Ok(());
             }
             
-            run_gateway_mode(config).await;
+            let dry_run = if dry_run {
+                let log = match dry_run_log {
+                    Some(path) => match DryRunLog::to_file(&path) {
+                        Ok(log) => log,
+                        Err(e) => {
+                            print_error(&e);
+                            return Ok(());
+                        }
+                    },
+                    None => DryRunLog::stdout(),
+                };
+                Some(DryRun { log, replay })
+            } else {
+                None
+            };
+
+            run_gateway_mode(config, dry_run).await;
         }
         
+        Commands::Bench { prompt, runs } => {
+            if !config_path.exists() {
+                print_error("Config not found. Run 'santosobot onboard' first.");
+                return Ok(());
+            }
+
+            let config = Config::load(&config_path)?;
+            ui::set_plain(config.ui.is_plain());
+
+            if let Err(e) = config.validate() {
+                print_error(&format!("Invalid config in {:?}: {}", config_path, e));
+                return Ok(());
+            }
+
+            if config.provider.needs_api_key() && config.provider.api_key.is_empty() {
+                print_error(&format!("API key not configured. Edit {:?} and add your API key.", config_path));
+                return Ok(());
+            }
+
+            run_bench(&config, &prompt, runs).await;
+        }
+
         Commands::Status => {
             if !config_path.exists() {
                 print_warning("Not configured. Run 'santosobot onboard' first.");
@@ -302,14 +646,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             
             let config = Config::load(&config_path)?;
+            ui::set_plain(config.ui.is_plain());
+
+            if let Err(e) = config.validate() {
+                print_error(&format!("Invalid config in {:?}: {}", config_path, e));
+                return Ok(());
+            }
             
-            println!("\n🤖 Santosobot Status");
+            println!();
+            println!("{}", ui::decorate("🤖", "Santosobot Status", ui::is_plain()));
             println!("═══════════════════════════════════════");
             println!("  Config:     {:?}", config_path);
             println!("  Model:      {}", config.agent.model);
             println!("  Provider:   {}", config.provider.api_base);
-            println!("  Telegram:    {}", if config.channels.telegram.enabled { "✅ enabled" } else { "❌ disabled" });
-            println!("  CLI:        {}", if config.channels.cli.enabled { "✅ enabled" } else { "❌ disabled" });
+            println!("  Telegram:    {}", ui::enabled_badge(config.channels.telegram.iter().any(|bot| bot.enabled), ui::is_plain()));
+            println!("  WhatsApp:   {}", ui::enabled_badge(config.channels.whatsapp.enabled, ui::is_plain()));
+            println!("  HTTP:       {}", ui::enabled_badge(config.channels.http.enabled, ui::is_plain()));
+            println!("  CLI:        {}", ui::enabled_badge(config.channels.cli.enabled, ui::is_plain()));
+            let (chats, usage) = SessionStore::new(&config.workspace_path()).total_usage();
+            println!(
+                "  Tokens:     {} total ({} prompt, {} completion) across {} chat(s)",
+                format_thousands(usage.total_tokens as u64),
+                format_thousands(usage.prompt_tokens as u64),
+                format_thousands(usage.completion_tokens as u64),
+                chats
+            );
             println!("═══════════════════════════════════════\n");
         }
     }
@@ -0,0 +1,419 @@
+use async_trait::async_trait;
+use futures::stream::TryStreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::retry;
+use super::sse::{self, SseDecoder};
+use super::{error_response, http_client, request_error, ChatStream, LLMProvider};
+use super::types::*;
+use crate::config::ProviderConfig;
+
+pub const ANTHROPIC_API_BASE: &str = "https://api.anthropic.com/v1";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Anthropic requires max_tokens on every request
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+
+pub struct AnthropicProvider {
+    client: Client,
+    config: ProviderConfig,
+    api_base: String,
+}
+
+impl AnthropicProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        let client = http_client(&config);
+
+        // The config default points at OpenAI; swap it for Anthropic's endpoint
+        let api_base = if config.api_base.is_empty() || config.api_base == ProviderConfig::default().api_base {
+            ANTHROPIC_API_BASE.to_string()
+        } else {
+            config.api_base.trim_end_matches('/').to_string()
+        };
+
+        Self { client, config, api_base }
+    }
+
+    fn build_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        model: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Value {
+        let (system, messages) = to_anthropic_messages(messages);
+
+        let mut request = json!({
+            "model": model.unwrap_or_else(|| self.config.model.clone()),
+            "max_tokens": max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+            "messages": messages,
+        });
+
+        if !system.is_empty() {
+            request["system"] = json!(system);
+        }
+        if let Some(temperature) = temperature {
+            request["temperature"] = json!(temperature);
+        }
+        if let Some(tools) = tools.filter(|t| !t.is_empty()) {
+            request["tools"] = tools
+                .into_iter()
+                .map(|tool| json!({
+                    "name": tool.function.name,
+                    "description": tool.function.description,
+                    "input_schema": tool.function.parameters,
+                }))
+                .collect();
+        }
+        if let Some(choice) = tool_choice {
+            request["tool_choice"] = match choice {
+                ToolChoice::None => json!({"type": "none"}),
+                ToolChoice::Auto => json!({"type": "auto"}),
+                ToolChoice::Required => json!({"type": "any"}),
+                ToolChoice::Function(name) => json!({"type": "tool", "name": name}),
+            };
+        }
+        request
+    }
+
+    async fn post(&self, body: &Value) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+        let url = format!("{}/messages", self.api_base);
+
+        // Streams are bounded by the client's idle read timeout instead of a total deadline
+        let total_timeout = (body["stream"] != json!(true))
+            .then(|| std::time::Duration::from_secs(self.config.request_timeout_secs));
+
+        let response = retry::send_with_retry(self.config.max_retries, || {
+            let request = self.client.post(&url);
+            let request = match total_timeout {
+                Some(timeout) => request.timeout(timeout),
+                None => request,
+            };
+            request
+                .header("x-api-key", &self.config.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .header("Content-Type", "application/json")
+                .json(body)
+        })
+        .await
+        .map_err(|e| request_error(e, self.config.request_timeout_secs))?;
+
+        if !response.status().is_success() {
+            return Err(error_response(response, "Anthropic request").await.into());
+        }
+
+        Ok(response)
+    }
+}
+
+/// Split out the system prompt and convert the rest into Anthropic content blocks.
+/// Tool results travel as `tool_result` blocks in a user turn, and consecutive
+/// turns from the same role are merged since the API expects them to alternate.
+fn to_anthropic_messages(messages: Vec<ChatMessage>) -> (String, Vec<Value>) {
+    let mut system = Vec::new();
+    let mut converted: Vec<(String, Vec<Value>)> = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match message.role.as_str() {
+            "system" => {
+                system.push(message.content);
+                continue;
+            }
+            "tool" => (
+                "user",
+                vec![json!({
+                    "type": "tool_result",
+                    "tool_use_id": message.tool_call_id.unwrap_or_default(),
+                    "content": message.content,
+                })],
+            ),
+            "assistant" => {
+                let mut blocks = Vec::new();
+                if !message.content.trim().is_empty() {
+                    blocks.push(json!({"type": "text", "text": message.content}));
+                }
+                for call in message.tool_calls.unwrap_or_default() {
+                    let input: Value = serde_json::from_str(&call.function.arguments).unwrap_or_else(|_| json!({}));
+                    blocks.push(json!({
+                        "type": "tool_use",
+                        "id": call.id,
+                        "name": call.function.name,
+                        "input": input,
+                    }));
+                }
+                ("assistant", blocks)
+            }
+            _ => ("user", vec![json!({"type": "text", "text": message.content})]),
+        };
+
+        if blocks.is_empty() {
+            continue;
+        }
+
+        match converted.last_mut() {
+            Some((last_role, last_blocks)) if last_role == role => last_blocks.extend(blocks),
+            _ => converted.push((role.to_string(), blocks)),
+        }
+    }
+
+    let messages = converted
+        .into_iter()
+        .map(|(role, content)| json!({"role": role, "content": content}))
+        .collect();
+
+    (system.join("\n\n"), messages)
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    #[serde(default)]
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    stop_reason: Option<String>,
+    #[serde(default)]
+    usage: Option<AnthropicUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentBlock {
+    Text { text: String },
+    ToolUse { id: String, name: String, input: Value },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnthropicUsage {
+    #[serde(default)]
+    input_tokens: u32,
+    #[serde(default)]
+    output_tokens: u32,
+}
+
+impl From<MessagesResponse> for LLMResponse {
+    fn from(resp: MessagesResponse) -> Self {
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+
+        for block in resp.content {
+            match block {
+                ContentBlock::Text { text: t } => text.push_str(&t),
+                ContentBlock::ToolUse { id, name, input } => tool_calls.push(ToolCallRequest {
+                    id,
+                    name,
+                    arguments: serde_json::from_value(input).unwrap_or_default(),
+                }),
+                ContentBlock::Other => {}
+            }
+        }
+
+        let usage = resp.usage.map(|u| Usage {
+            prompt_tokens: u.input_tokens,
+            completion_tokens: u.output_tokens,
+            total_tokens: u.input_tokens + u.output_tokens,
+        });
+
+        Self {
+            content: Some(text),
+            tool_calls,
+            finish_reason: resp.stop_reason.unwrap_or_default(),
+            usage: usage.unwrap_or_default(),
+        }
+    }
+}
+
+/// Anthropic stream events. Text and tool input arrive as `content_block_delta`;
+/// a tool's id and name come with its `content_block_start`.
+fn parse_anthropic_data(data: &str) -> Option<Vec<StreamEvent>> {
+    let Ok(event) = serde_json::from_str::<Value>(data) else {
+        tracing::debug!("Skipping unparseable stream line: {}", data);
+        return Some(Vec::new());
+    };
+
+    let index = event["index"].as_u64().unwrap_or(0) as usize;
+
+    let events = match event["type"].as_str() {
+        Some("message_stop") => return None,
+        // Input tokens arrive with message_start, output tokens with message_delta
+        Some("message_start") | Some("message_delta") => {
+            let (input, output) = if event["type"] == "message_start" {
+                (event["message"]["usage"]["input_tokens"].as_u64().unwrap_or(0) as u32, 0)
+            } else {
+                (0, event["usage"]["output_tokens"].as_u64().unwrap_or(0) as u32)
+            };
+            if input + output == 0 {
+                Vec::new()
+            } else {
+                vec![StreamEvent::Usage(Usage {
+                    prompt_tokens: input,
+                    completion_tokens: output,
+                    total_tokens: input + output,
+                })]
+            }
+        }
+        Some("content_block_start") if event["content_block"]["type"] == "tool_use" => {
+            vec![StreamEvent::ToolCall(ToolCallDelta {
+                index,
+                id: event["content_block"]["id"].as_str().map(String::from),
+                function: Some(FunctionCallDelta {
+                    name: event["content_block"]["name"].as_str().map(String::from),
+                    arguments: None,
+                }),
+            })]
+        }
+        Some("content_block_delta") => match event["delta"]["type"].as_str() {
+            Some("text_delta") => event["delta"]["text"]
+                .as_str()
+                .filter(|t| !t.is_empty())
+                .map(|t| vec![StreamEvent::Content(t.to_string())])
+                .unwrap_or_default(),
+            Some("input_json_delta") => vec![StreamEvent::ToolCall(ToolCallDelta {
+                index,
+                id: None,
+                function: Some(FunctionCallDelta {
+                    name: None,
+                    arguments: event["delta"]["partial_json"].as_str().map(String::from),
+                }),
+            })],
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+
+    Some(events)
+}
+
+#[async_trait]
+impl LLMProvider for AnthropicProvider {
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        model: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let body = self.build_request(messages, tools, tool_choice, model, temperature, max_tokens);
+
+        info!(model = %body["model"], "Sending Anthropic request");
+        tracing::debug!("Request payload: {:#}", body);
+
+        let timeout_secs = self.config.request_timeout_secs;
+        let text = self.post(&body).await?
+            .text()
+            .await
+            .map_err(|e| request_error(e, timeout_secs))?;
+        let response: MessagesResponse = parse_response(&text)?;
+        tracing::debug!("Response from LLM: {:#?}", response);
+        Ok(response.into())
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        model: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Result<ChatStream, Box<dyn std::error::Error + Send + Sync>> {
+        let mut body = self.build_request(messages, tools, tool_choice, model, temperature, max_tokens);
+        body["stream"] = json!(true);
+
+        info!(model = %body["model"], "Sending streaming Anthropic request");
+        tracing::debug!("Request payload: {:#}", body);
+
+        let timeout_secs = self.config.request_timeout_secs;
+        let response = self.post(&body).await?;
+        let body = response.bytes_stream().map_err(move |e| request_error(e, timeout_secs));
+        Ok(sse::event_stream(body, SseDecoder::new(parse_anthropic_data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_are_translated() {
+        let call = ToolCall::function("toolu_1", "read_file", &json!({"path": "a.txt"}));
+        let messages = vec![
+            ChatMessage::system("Be brief."),
+            ChatMessage::user("Read a.txt"),
+            ChatMessage::assistant_with_tool_calls("", vec![call]),
+            ChatMessage::tool("hello", "toolu_1"),
+            ChatMessage::user("Tool executed. Continue."),
+        ];
+
+        let (system, converted) = to_anthropic_messages(messages);
+
+        assert_eq!(system, "Be brief.");
+        assert_eq!(converted.len(), 3);
+        assert_eq!(converted[1]["role"], "assistant");
+        assert_eq!(converted[1]["content"][0]["type"], "tool_use");
+        assert_eq!(converted[1]["content"][0]["input"]["path"], "a.txt");
+        // The tool result and the follow-up prompt share one user turn
+        assert_eq!(converted[2]["role"], "user");
+        assert_eq!(converted[2]["content"][0]["type"], "tool_result");
+        assert_eq!(converted[2]["content"][0]["tool_use_id"], "toolu_1");
+        assert_eq!(converted[2]["content"][1]["text"], "Tool executed. Continue.");
+    }
+
+    #[test]
+    fn test_response_with_tool_use() {
+        let response: MessagesResponse = serde_json::from_value(json!({
+            "content": [
+                {"type": "text", "text": "Let me check."},
+                {"type": "tool_use", "id": "toolu_1", "name": "list_dir", "input": {"path": "."}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 12, "output_tokens": 7}
+        }))
+        .unwrap();
+
+        let response = LLMResponse::from(response);
+        assert_eq!(response.content.as_deref(), Some("Let me check."));
+        assert_eq!(response.tool_calls[0].name, "list_dir");
+        assert_eq!(response.tool_calls[0].arguments["path"], ".");
+        assert_eq!(response.usage.total_tokens, 19);
+    }
+
+    #[test]
+    fn test_stream_events_are_decoded() {
+        let mut decoder = SseDecoder::new(parse_anthropic_data);
+        let body = concat!(
+            "data: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hi\"}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"tool_use\",\"id\":\"toolu_9\",\"name\":\"read_file\",\"input\":{}}}\n\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"{\\\"path\\\": \"}}\n\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"input_json_delta\",\"partial_json\":\"\\\"a.txt\\\"}\"}}\n\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"tool_use\"},\"usage\":{\"output_tokens\":15}}\n\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+
+        let mut text = String::new();
+        let mut calls = ToolCallAccumulator::default();
+        let mut usage = Usage::default();
+        for event in decoder.push(body) {
+            match event {
+                StreamEvent::Content(t) => text.push_str(&t),
+                StreamEvent::ToolCall(delta) => calls.push(&delta),
+                StreamEvent::Usage(reported) => usage.add(&reported),
+            }
+        }
+
+        let calls = calls.finish();
+        assert_eq!(text, "Hi");
+        assert_eq!(calls[0].id, "toolu_9");
+        assert_eq!(calls[0].arguments["path"], "a.txt");
+        assert_eq!(usage, Usage { prompt_tokens: 25, completion_tokens: 15, total_tokens: 40 });
+    }
+}
@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Why a provider request failed, sorted into the cases the user can act on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderError {
+    /// The API key was missing, wrong or lacks access
+    Unauthorized,
+    RateLimited { retry_after: Option<Duration> },
+    ModelNotFound,
+    BadRequest(String),
+    Server(String),
+    /// The request never got an answer: connection refused, timeout, reset
+    Network(String),
+}
+
+/// `{"error": {"message": ..., "type": ..., "code": ...}}`, as sent by OpenAI
+/// and compatible servers. Anthropic uses the same shape without `code`.
+#[derive(Debug, Default, Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ErrorDetail {
+    #[serde(default)]
+    message: String,
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    /// A string in OpenAI's bodies, but some compatible servers send numbers
+    #[serde(default)]
+    code: Option<serde_json::Value>,
+}
+
+impl ProviderError {
+    /// Classify a non-success response by its status and, where the body is
+    /// the usual JSON error object, its error code.
+    pub fn from_response(status: u16, retry_after: Option<Duration>, body: &str) -> Self {
+        let detail = serde_json::from_str::<ErrorBody>(body).unwrap_or_default().error;
+        let code = detail.code.as_ref().and_then(|c| c.as_str()).unwrap_or_default();
+        let kind = detail.kind.as_deref().unwrap_or_default();
+        let message = if detail.message.is_empty() {
+            body.trim().to_string()
+        } else {
+            detail.message
+        };
+
+        match (status, code, kind) {
+            (401 | 403, _, _) | (_, "invalid_api_key", _) | (_, _, "authentication_error") => ProviderError::Unauthorized,
+            (429, _, _) => ProviderError::RateLimited { retry_after },
+            (_, "model_not_found", _) | (404, _, _) => ProviderError::ModelNotFound,
+            (500.., _, _) => ProviderError::Server(message),
+            _ => ProviderError::BadRequest(message),
+        }
+    }
+}
+
+impl std::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProviderError::Unauthorized => f.write_str("the LLM provider rejected the API key; check provider.api_key"),
+            ProviderError::RateLimited { retry_after: Some(wait) } => {
+                write!(f, "the LLM provider is rate limiting requests; try again in {}s", wait.as_secs().max(1))
+            }
+            ProviderError::RateLimited { retry_after: None } => {
+                f.write_str("the LLM provider is rate limiting requests; try again shortly")
+            }
+            ProviderError::ModelNotFound => f.write_str("the LLM provider doesn't know this model; check provider.model"),
+            ProviderError::BadRequest(message) => write!(f, "the LLM provider refused the request: {}", message),
+            ProviderError::Server(message) => write!(f, "the LLM provider had an internal error: {}", message),
+            ProviderError::Network(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_error_bodies_map_to_variants() {
+        let invalid_key = r#"{"error":{"message":"Incorrect API key provided: sk-abc***","type":"invalid_request_error","param":null,"code":"invalid_api_key"}}"#;
+        assert_eq!(ProviderError::from_response(401, None, invalid_key), ProviderError::Unauthorized);
+
+        let no_model = r#"{"error":{"message":"The model `gpt-9` does not exist or you do not have access to it.","type":"invalid_request_error","param":null,"code":"model_not_found"}}"#;
+        assert_eq!(ProviderError::from_response(404, None, no_model), ProviderError::ModelNotFound);
+
+        let rate_limited = r#"{"error":{"message":"Rate limit reached for gpt-4o","type":"requests","param":null,"code":"rate_limit_exceeded"}}"#;
+        assert_eq!(
+            ProviderError::from_response(429, Some(Duration::from_secs(20)), rate_limited),
+            ProviderError::RateLimited { retry_after: Some(Duration::from_secs(20)) }
+        );
+
+        let too_long = r#"{"error":{"message":"This model's maximum context length is 8192 tokens.","type":"invalid_request_error","param":"messages","code":"context_length_exceeded"}}"#;
+        assert_eq!(
+            ProviderError::from_response(400, None, too_long),
+            ProviderError::BadRequest("This model's maximum context length is 8192 tokens.".to_string())
+        );
+
+        let server = r#"{"error":{"message":"The server had an error while processing your request.","type":"server_error","param":null,"code":null}}"#;
+        assert_eq!(
+            ProviderError::from_response(500, None, server),
+            ProviderError::Server("The server had an error while processing your request.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unparseable_bodies_fall_back_to_status_and_raw_text() {
+        assert_eq!(
+            ProviderError::from_response(502, None, "<html>Bad Gateway</html>\n"),
+            ProviderError::Server("<html>Bad Gateway</html>".to_string())
+        );
+
+        // Anthropic's shape: no code, but a typed error
+        let anthropic = r#"{"type":"error","error":{"type":"authentication_error","message":"invalid x-api-key"}}"#;
+        assert_eq!(ProviderError::from_response(400, None, anthropic), ProviderError::Unauthorized);
+    }
+}
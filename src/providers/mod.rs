@@ -1,11 +1,91 @@
+mod anthropic;
+mod error;
+mod ollama;
+pub(crate) mod retry;
+mod sse;
 mod types;
 
+pub use anthropic::AnthropicProvider;
+pub use error::ProviderError;
+pub use ollama::OllamaProvider;
 pub use types::*;
 
+use sse::SseDecoder;
+
 use crate::config::ProviderConfig;
+use async_trait::async_trait;
 use reqwest::Client;
 use tracing::{info, error};
-use futures::stream::{StreamExt, BoxStream};
+use futures::stream::{BoxStream, TryStreamExt};
+use std::time::Duration;
+
+pub type ChatStream = BoxStream<'static, Result<StreamEvent, Box<dyn std::error::Error + Send + Sync>>>;
+
+/// Chat completion backend used by the agent loop.
+#[async_trait]
+pub trait LLMProvider: Send + Sync {
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        model: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>>;
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
+        model: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Result<ChatStream, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Build the provider selected by `provider.kind`.
+pub fn create_provider(config: &ProviderConfig) -> Box<dyn LLMProvider> {
+    match config.kind.as_str() {
+        "anthropic" => Box::new(AnthropicProvider::new(config.clone())),
+        "ollama" => Box::new(OllamaProvider::new(config.clone())),
+        "openai" | "" => Box::new(OpenAIProvider::new(config.clone())),
+        other => {
+            tracing::warn!("Unknown provider kind '{}', using the OpenAI-compatible provider", other);
+            Box::new(OpenAIProvider::new(config.clone()))
+        }
+    }
+}
+
+/// HTTP client for LLM calls. The read timeout resets on every chunk, so it
+/// catches a stalled stream without capping how long a healthy one may run.
+fn http_client(config: &ProviderConfig) -> Client {
+    let timeout = Duration::from_secs(config.request_timeout_secs);
+    Client::builder()
+        .connect_timeout(timeout)
+        .read_timeout(timeout)
+        .build()
+        .expect("Failed to create HTTP client")
+}
+
+/// Turn a reqwest error into the message shown to the user, naming timeouts plainly.
+fn request_error(e: reqwest::Error, timeout_secs: u64) -> Box<dyn std::error::Error + Send + Sync> {
+    if e.is_timeout() {
+        Box::new(ProviderError::Network(format!("request timed out after {}s", timeout_secs)))
+    } else {
+        Box::new(ProviderError::Network(e.to_string()))
+    }
+}
+
+/// Read a failed response into a `ProviderError`, logging the raw body.
+async fn error_response(response: reqwest::Response, what: &str) -> ProviderError {
+    let status = response.status();
+    let retry_after = retry::retry_after(response.headers());
+    let body = response.text().await.unwrap_or_default();
+    error!(status = %status, body = %body, "{} failed", what);
+    ProviderError::from_response(status.as_u16(), retry_after, &body)
+}
 
 pub struct OpenAIProvider {
     client: Client,
@@ -14,18 +94,62 @@ pub struct OpenAIProvider {
 
 impl OpenAIProvider {
     pub fn new(config: ProviderConfig) -> Self {
-        let client = Client::builder()
-            .build()
-            .expect("Failed to create HTTP client");
-
+        let client = http_client(&config);
         Self { client, config }
     }
 
-    #[allow(dead_code)]
-    pub async fn chat(
+    /// Embed `texts` with `provider.embedding_model`, one vector per text in order.
+    pub async fn embeddings(&self, texts: Vec<String>) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error + Send + Sync>> {
+        #[derive(serde::Deserialize)]
+        struct Embedding {
+            index: usize,
+            embedding: Vec<f32>,
+        }
+        #[derive(serde::Deserialize)]
+        struct EmbeddingsResponse {
+            data: Vec<Embedding>,
+        }
+
+        let count = texts.len();
+        let request = serde_json::json!({
+            "model": self.config.embedding_model,
+            "input": texts,
+        });
+        let url = format!("{}/embeddings", self.config.api_base.trim_end_matches('/'));
+
+        let timeout_secs = self.config.request_timeout_secs;
+        let response = retry::send_with_retry(self.config.max_retries, || {
+            self.client
+                .post(&url)
+                .timeout(Duration::from_secs(timeout_secs))
+                .header("Authorization", format!("Bearer {}", self.config.api_key))
+                .header("Content-Type", "application/json")
+                .json(&request)
+        })
+        .await
+        .map_err(|e| request_error(e, timeout_secs))?;
+
+        if !response.status().is_success() {
+            return Err(error_response(response, "Embeddings request").await.into());
+        }
+
+        let body = response.text().await.map_err(|e| request_error(e, timeout_secs))?;
+        let mut parsed: EmbeddingsResponse = serde_json::from_str(&body)?;
+        if parsed.data.len() != count {
+            return Err(format!("Embeddings API returned {} vectors for {} inputs", parsed.data.len(), count).into());
+        }
+        parsed.data.sort_by_key(|e| e.index);
+        Ok(parsed.data.into_iter().map(|e| e.embedding).collect())
+    }
+}
+
+#[async_trait]
+impl LLMProvider for OpenAIProvider {
+    async fn chat(
         &self,
         messages: Vec<ChatMessage>,
         tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
         model: Option<String>,
         temperature: Option<f32>,
         max_tokens: Option<u32>,
@@ -36,6 +160,7 @@ impl OpenAIProvider {
             model: model.clone(),
             messages,
             tools,
+            tool_choice,
             temperature,
             max_tokens,
         };
@@ -45,41 +170,44 @@ impl OpenAIProvider {
 
         let url = format!("{}/chat/completions", self.config.api_base.trim_end_matches('/'));
 
-        let response = self.client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .send()
-            .await?;
+        let timeout_secs = self.config.request_timeout_secs;
+        let response = retry::send_with_retry(self.config.max_retries, || {
+            self.client
+                .post(&url)
+                .timeout(Duration::from_secs(timeout_secs))
+                .header("Authorization", format!("Bearer {}", self.config.api_key))
+                .header("Content-Type", "application/json")
+                .json(&request)
+        })
+        .await
+        .map_err(|e| request_error(e, timeout_secs))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            error!(status = %status, body = %body, "LLM request failed");
-            return Err(format!("LLM API error: {} - {}", status, body).into());
+            return Err(error_response(response, "LLM request").await.into());
         }
 
-        let chat_resp: ChatResponse = response.json().await?;
+        let body = response.text().await.map_err(|e| request_error(e, timeout_secs))?;
+        let chat_resp: ChatResponse = parse_response(&body)?;
         tracing::debug!("Response from LLM: {:#?}", chat_resp);
         Ok(chat_resp.into())
     }
 
-    #[allow(dead_code)]
-    pub async fn chat_stream(
+    async fn chat_stream(
         &self,
         messages: Vec<ChatMessage>,
         tools: Option<Vec<ToolDefinition>>,
+        tool_choice: Option<ToolChoice>,
         model: Option<String>,
         temperature: Option<f32>,
         max_tokens: Option<u32>,
-    ) -> Result<BoxStream<'static, Result<String, Box<dyn std::error::Error + Send + Sync>>>, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<ChatStream, Box<dyn std::error::Error + Send + Sync>> {
         let model = model.unwrap_or_else(|| self.config.model.clone());
 
         let request = ChatRequest {
             model: model.clone(),
             messages,
             tools,
+            tool_choice,
             temperature,
             max_tokens,
         };
@@ -89,57 +217,117 @@ impl OpenAIProvider {
 
         let url = format!("{}/chat/completions", self.config.api_base.trim_end_matches('/'));
 
-        let response = self.client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.config.api_key))
-            .header("Content-Type", "application/json")
-            .json(&serde_json::json!({
-                "model": request.model,
-                "messages": request.messages,
-                "tools": request.tools,
-                "temperature": request.temperature,
-                "max_tokens": request.max_tokens,
-                "stream": true,
-            }))
-            .send()
-            .await?;
+        let mut body = serde_json::to_value(&request)?;
+        body["stream"] = serde_json::Value::Bool(true);
+        // Ask for a final chunk carrying token usage
+        body["stream_options"] = serde_json::json!({"include_usage": true});
+
+        let timeout_secs = self.config.request_timeout_secs;
+        let response = retry::send_with_retry(self.config.max_retries, || {
+            self.client
+                .post(&url)
+                .header("Authorization", format!("Bearer {}", self.config.api_key))
+                .header("Content-Type", "application/json")
+                .json(&body)
+        })
+        .await
+        .map_err(|e| request_error(e, timeout_secs))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            error!(status = %status, body = %body, "LLM stream request failed");
-            return Err(format!("LLM API error: {} - {}", status, body).into());
+            return Err(error_response(response, "LLM stream request").await.into());
         }
 
-        let stream = response.bytes_stream()
-            .filter_map(|chunk_result| async move {
-                let bytes = match chunk_result {
-                    Ok(b) => b,
-                    Err(e) => return Some(Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>)),
-                };
-
-                let text = String::from_utf8_lossy(&bytes);
-                // Parse SSE data lines
-                for line in text.lines() {
-                    if let Some(data) = line.strip_prefix("data: ") {
-                        if data == "[DONE]" {
-                            return None;
-                        }
-                        if let Ok(stream_resp) = serde_json::from_str::<StreamResponse>(data) {
-                            if let Some(choice) = stream_resp.choices.first() {
-                                if let Some(ref content) = choice.delta.content {
-                                    if !content.is_empty() {
-                                        return Some(Ok(content.clone()));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                None
-            })
-            .boxed();
+        let body = response.bytes_stream().map_err(move |e| request_error(e, timeout_secs));
+        let stream = sse::event_stream(body, SseDecoder::default());
 
         Ok(stream)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_chat_retries_until_success() {
+        let ok = r#"{"id":"1","choices":[{"message":{"role":"assistant","content":"Hello"},"finish_reason":"stop"}],"usage":{"prompt_tokens":3,"completion_tokens":1,"total_tokens":4}}"#;
+        let base = retry::tests::mock_server(vec![
+            ("503 Service Unavailable", "Retry-After: 0\r\n", "{}"),
+            ("503 Service Unavailable", "Retry-After: 0\r\n", "{}"),
+            ("200 OK", "", ok),
+        ])
+        .await;
+
+        let provider = OpenAIProvider::new(ProviderConfig {
+            api_base: base,
+            model: "test-model".to_string(),
+            ..Default::default()
+        });
+        let response = provider
+            .chat(vec![ChatMessage::user("Hi")], None, None, None, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.content.as_deref(), Some("Hello"));
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_come_back_in_input_order() {
+        let body = r#"{"data":[{"index":1,"embedding":[0.0,1.0]},{"index":0,"embedding":[1.0,0.0]}]}"#;
+        let base = retry::tests::mock_server(vec![("200 OK", "", body)]).await;
+
+        let provider = OpenAIProvider::new(ProviderConfig {
+            api_base: base,
+            embedding_model: "text-embedding-3-small".to_string(),
+            ..Default::default()
+        });
+        let vectors = provider.embeddings(vec!["first".to_string(), "second".to_string()]).await.unwrap();
+
+        assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+
+    #[tokio::test]
+    async fn test_request_times_out_against_silent_server() {
+        // Accepts connections but never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut open = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                open.push(socket);
+            }
+        });
+
+        let provider = OpenAIProvider::new(ProviderConfig {
+            api_base: format!("http://{}", addr),
+            model: "test-model".to_string(),
+            request_timeout_secs: 1,
+            max_retries: 0,
+            ..Default::default()
+        });
+        let err = provider
+            .chat(vec![ChatMessage::user("Hi")], None, None, None, None, None)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "request timed out after 1s");
+    }
+
+    #[tokio::test]
+    async fn test_chat_stream_returns_structured_error() {
+        let body = r#"{"error":{"message":"Incorrect API key provided","type":"invalid_request_error","param":null,"code":"invalid_api_key"}}"#;
+        let base = retry::tests::mock_server(vec![("401 Unauthorized", "", body)]).await;
+
+        let provider = OpenAIProvider::new(ProviderConfig {
+            api_base: base,
+            model: "test-model".to_string(),
+            ..Default::default()
+        });
+        let err = match provider.chat_stream(vec![ChatMessage::user("Hi")], None, None, None, None, None).await {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e,
+        };
+
+        assert_eq!(err.downcast_ref::<ProviderError>(), Some(&ProviderError::Unauthorized));
+    }
+}
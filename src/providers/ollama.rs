@@ -0,0 +1,319 @@
+use async_trait::async_trait;
+use futures::stream::TryStreamExt;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::retry;
+use super::sse::{self, SseDecoder};
+use super::{error_response, http_client, request_error, ChatStream, LLMProvider};
+use super::types::*;
+use crate::config::ProviderConfig;
+
+pub const OLLAMA_API_BASE: &str = "http://localhost:11434";
+
+/// Ollama's native `/api/chat`. No auth by default; an `api_key`, if set, is
+/// sent as a bearer token for instances behind a proxy.
+pub struct OllamaProvider {
+    client: Client,
+    config: ProviderConfig,
+    api_base: String,
+}
+
+impl OllamaProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        let client = http_client(&config);
+
+        // The config default points at OpenAI; swap it for the local daemon
+        let api_base = if config.api_base.is_empty() || config.api_base == ProviderConfig::default().api_base {
+            OLLAMA_API_BASE.to_string()
+        } else {
+            config.api_base.trim_end_matches('/').to_string()
+        };
+
+        Self { client, config, api_base }
+    }
+
+    fn build_request(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        model: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+        stream: bool,
+    ) -> Value {
+        let mut request = json!({
+            "model": model.unwrap_or_else(|| self.config.model.clone()),
+            "messages": to_ollama_messages(messages),
+            "stream": stream,
+        });
+
+        let mut options = serde_json::Map::new();
+        if let Some(temperature) = temperature {
+            options.insert("temperature".to_string(), json!(temperature));
+        }
+        if let Some(max_tokens) = max_tokens {
+            options.insert("num_predict".to_string(), json!(max_tokens));
+        }
+        if !options.is_empty() {
+            request["options"] = Value::Object(options);
+        }
+        // Same shape as OpenAI's; Ollama has no tool_choice
+        if let Some(tools) = tools.filter(|t| !t.is_empty()) {
+            request["tools"] = json!(tools);
+        }
+        request
+    }
+
+    async fn post(&self, body: &Value) -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+        let url = format!("{}/api/chat", self.api_base);
+
+        // Streams are bounded by the client's idle read timeout instead of a total deadline
+        let total_timeout = (body["stream"] != json!(true))
+            .then(|| std::time::Duration::from_secs(self.config.request_timeout_secs));
+
+        let response = retry::send_with_retry(self.config.max_retries, || {
+            let request = self.client.post(&url).json(body);
+            let request = match total_timeout {
+                Some(timeout) => request.timeout(timeout),
+                None => request,
+            };
+            if self.config.api_key.is_empty() {
+                request
+            } else {
+                request.header("Authorization", format!("Bearer {}", self.config.api_key))
+            }
+        })
+        .await
+        .map_err(|e| request_error(e, self.config.request_timeout_secs))?;
+
+        if !response.status().is_success() {
+            return Err(error_response(response, "Ollama request").await.into());
+        }
+
+        Ok(response)
+    }
+}
+
+/// Ollama takes OpenAI-style roles, but tool call arguments are JSON objects
+/// rather than strings and tool results carry no call id.
+fn to_ollama_messages(messages: Vec<ChatMessage>) -> Vec<Value> {
+    messages
+        .into_iter()
+        .map(|message| {
+            let mut converted = json!({"role": message.role, "content": message.content});
+            if let Some(calls) = message.tool_calls.filter(|c| !c.is_empty()) {
+                converted["tool_calls"] = calls
+                    .into_iter()
+                    .map(|call| json!({
+                        "function": {
+                            "name": call.function.name,
+                            "arguments": serde_json::from_str::<Value>(&call.function.arguments).unwrap_or_else(|_| json!({})),
+                        }
+                    }))
+                    .collect();
+            }
+            converted
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaChunk {
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    #[serde(default)]
+    done_reason: Option<String>,
+    #[serde(default)]
+    prompt_eval_count: u32,
+    #[serde(default)]
+    eval_count: u32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct OllamaMessage {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    tool_calls: Vec<OllamaToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaToolCall {
+    function: OllamaFunction,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+impl OllamaChunk {
+    fn usage(&self) -> Usage {
+        Usage {
+            prompt_tokens: self.prompt_eval_count,
+            completion_tokens: self.eval_count,
+            total_tokens: self.prompt_eval_count + self.eval_count,
+        }
+    }
+}
+
+impl From<OllamaChunk> for LLMResponse {
+    fn from(chunk: OllamaChunk) -> Self {
+        let usage = chunk.usage();
+        let message = chunk.message.unwrap_or_default();
+        let tool_calls: Vec<ToolCallRequest> = message
+            .tool_calls
+            .into_iter()
+            .enumerate()
+            .map(|(index, call)| ToolCallRequest {
+                id: format!("call_{}", index),
+                name: call.function.name,
+                arguments: serde_json::from_value(call.function.arguments).unwrap_or_default(),
+            })
+            .collect();
+        let finish_reason = match chunk.done_reason {
+            _ if !tool_calls.is_empty() => "tool_calls".to_string(),
+            Some(reason) => reason,
+            None => "stop".to_string(),
+        };
+
+        Self {
+            content: Some(message.content),
+            tool_calls,
+            finish_reason,
+            usage,
+        }
+    }
+}
+
+/// One line of Ollama's newline-delimited JSON stream. Text arrives as
+/// `message.content`; a turn's tool calls come whole in a single line, and
+/// the final line has `done: true` with the token counts.
+fn parse_ollama_line(line: &str) -> Option<Vec<StreamEvent>> {
+    let Ok(chunk) = serde_json::from_str::<OllamaChunk>(line) else {
+        tracing::debug!("Skipping unparseable stream line: {}", line);
+        return Some(Vec::new());
+    };
+
+    let mut events = Vec::new();
+    let usage = chunk.usage();
+    if let Some(message) = chunk.message {
+        if !message.content.is_empty() {
+            events.push(StreamEvent::Content(message.content));
+        }
+        for (index, call) in message.tool_calls.into_iter().enumerate() {
+            events.push(StreamEvent::ToolCall(ToolCallDelta {
+                index,
+                id: None,
+                function: Some(FunctionCallDelta {
+                    name: Some(call.function.name),
+                    arguments: Some(call.function.arguments.to_string()),
+                }),
+            }));
+        }
+    }
+    if chunk.done && usage.total_tokens > 0 {
+        events.push(StreamEvent::Usage(usage));
+    }
+    Some(events)
+}
+
+#[async_trait]
+impl LLMProvider for OllamaProvider {
+    async fn chat(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        _tool_choice: Option<ToolChoice>,
+        model: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Result<LLMResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let body = self.build_request(messages, tools, model, temperature, max_tokens, false);
+
+        info!(model = %body["model"], "Sending Ollama request");
+        tracing::debug!("Request payload: {:#}", body);
+
+        let timeout_secs = self.config.request_timeout_secs;
+        let text = self.post(&body).await?
+            .text()
+            .await
+            .map_err(|e| request_error(e, timeout_secs))?;
+        let response: OllamaChunk = parse_response(&text)?;
+        tracing::debug!("Response from LLM: {:#?}", response);
+        Ok(response.into())
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: Vec<ChatMessage>,
+        tools: Option<Vec<ToolDefinition>>,
+        _tool_choice: Option<ToolChoice>,
+        model: Option<String>,
+        temperature: Option<f32>,
+        max_tokens: Option<u32>,
+    ) -> Result<ChatStream, Box<dyn std::error::Error + Send + Sync>> {
+        let body = self.build_request(messages, tools, model, temperature, max_tokens, true);
+
+        info!(model = %body["model"], "Sending streaming Ollama request");
+        tracing::debug!("Request payload: {:#}", body);
+
+        let timeout_secs = self.config.request_timeout_secs;
+        let response = self.post(&body).await?;
+        let body = response.bytes_stream().map_err(move |e| request_error(e, timeout_secs));
+        Ok(sse::event_stream(body, SseDecoder::ndjson(parse_ollama_line)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_lines_are_decoded() {
+        let mut decoder = SseDecoder::ndjson(parse_ollama_line);
+        let body = concat!(
+            "{\"model\":\"llama3.2\",\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n",
+            "{\"model\":\"llama3.2\",\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":false}\n",
+            "{\"model\":\"llama3.2\",\"message\":{\"role\":\"assistant\",\"content\":\"\",\"tool_calls\":[{\"function\":{\"name\":\"read_file\",\"arguments\":{\"path\":\"a.txt\"}}}]},\"done\":false}\n",
+            "{\"model\":\"llama3.2\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"stop\",\"prompt_eval_count\":26,\"eval_count\":12}\n",
+        );
+
+        let mut chunks = Vec::new();
+        let mut calls = ToolCallAccumulator::default();
+        let mut usage = Usage::default();
+        for event in decoder.push(body) {
+            match event {
+                StreamEvent::Content(t) => chunks.push(t),
+                StreamEvent::ToolCall(delta) => calls.push(&delta),
+                StreamEvent::Usage(reported) => usage.add(&reported),
+            }
+        }
+
+        let calls = calls.finish();
+        assert_eq!(chunks, vec!["Hel", "lo"]);
+        assert_eq!(calls[0].id, "call_0");
+        assert_eq!(calls[0].arguments["path"], "a.txt");
+        assert_eq!(usage, Usage { prompt_tokens: 26, completion_tokens: 12, total_tokens: 38 });
+    }
+
+    #[test]
+    fn test_tool_call_arguments_are_sent_as_objects() {
+        let call = ToolCall::function("call_0", "list_dir", &json!({"path": "."}));
+        let converted = to_ollama_messages(vec![
+            ChatMessage::user("What's here?"),
+            ChatMessage::assistant_with_tool_calls("", vec![call]),
+            ChatMessage::tool("a.txt", "call_0"),
+        ]);
+
+        assert_eq!(converted[1]["tool_calls"][0]["function"]["arguments"], json!({"path": "."}));
+        assert_eq!(converted[2], json!({"role": "tool", "content": "a.txt"}));
+    }
+}
@@ -0,0 +1,127 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// Upper bound on a server-provided Retry-After, so one header can't stall a turn
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Rate limits and server-side hiccups are worth another try; other errors
+/// (bad request, auth) will fail the same way again.
+pub fn is_retryable(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504)
+}
+
+/// `Retry-After` in whole seconds. The HTTP-date form is rare for APIs and
+/// falls back to normal backoff.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds: u64 = headers.get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_RETRY_AFTER))
+}
+
+fn backoff(attempt: u32) -> Duration {
+    BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_DELAY)
+}
+
+/// Send a request, retrying connection errors and retryable statuses up to
+/// `max_retries` times with exponential backoff (or the server's Retry-After).
+/// Any other response, or the last one once retries run out, is returned for
+/// the caller to handle.
+pub async fn send_with_retry<F>(max_retries: u32, build: F) -> Result<Response, reqwest::Error>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let mut attempt = 0;
+
+    loop {
+        let delay = match build().send().await {
+            Ok(response) if attempt < max_retries && is_retryable(response.status()) => {
+                let delay = retry_after(response.headers()).unwrap_or_else(|| backoff(attempt));
+                tracing::warn!(
+                    "LLM request returned {}, retrying in {:?} ({}/{})",
+                    response.status(),
+                    delay,
+                    attempt + 1,
+                    max_retries
+                );
+                delay
+            }
+            Err(e) if attempt < max_retries && (e.is_connect() || e.is_timeout()) => {
+                let delay = backoff(attempt);
+                tracing::warn!("LLM request failed: {}, retrying in {:?} ({}/{})", e, delay, attempt + 1, max_retries);
+                delay
+            }
+            result => return result,
+        };
+
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Minimal HTTP server answering each connection with the next canned
+    /// `(status line, extra headers, body)`. Returns its base URL.
+    pub async fn mock_server(responses: Vec<(&'static str, &'static str, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            for (status, headers, body) in responses {
+                let Ok((mut socket, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut buf = vec![0u8; 64 * 1024];
+                let _ = socket.read(&mut buf).await;
+
+                let reply = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+                    status,
+                    body.len(),
+                    headers,
+                    body
+                );
+                let _ = socket.write_all(reply.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        for code in [429, 500, 502, 503, 504] {
+            assert!(is_retryable(StatusCode::from_u16(code).unwrap()));
+        }
+        for code in [400, 401, 403, 404] {
+            assert!(!is_retryable(StatusCode::from_u16(code).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        assert_eq!(backoff(0), Duration::from_millis(500));
+        assert_eq!(backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff(20), MAX_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_non_retryable_status_fails_fast() {
+        let base = mock_server(vec![
+            ("401 Unauthorized", "", "{}"),
+            ("200 OK", "", "{}"),
+        ])
+        .await;
+        let client = reqwest::Client::new();
+
+        let response = send_with_retry(3, || client.get(&base)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,264 @@
+use futures::stream::{self, Stream, StreamExt};
+
+use super::types::{StreamEvent, StreamResponse};
+#[cfg(test)]
+use super::types::Usage;
+use super::ChatStream;
+
+/// Parses the payload of one `data:` line. Returns `None` once the stream is finished.
+pub type DataParser = fn(&str) -> Option<Vec<StreamEvent>>;
+
+/// Turns raw SSE text from a streaming chat completion into `StreamEvent`s.
+/// Keeps a partial trailing line between chunks, since network reads don't
+/// line up with event boundaries.
+pub struct SseDecoder {
+    buffer: String,
+    done: bool,
+    parse_data: DataParser,
+    /// "data:" for SSE; empty for newline-delimited JSON, where every line is data
+    data_prefix: &'static str,
+}
+
+impl Default for SseDecoder {
+    fn default() -> Self {
+        Self::new(parse_openai_data)
+    }
+}
+
+impl SseDecoder {
+    pub fn new(parse_data: DataParser) -> Self {
+        Self {
+            buffer: String::new(),
+            done: false,
+            parse_data,
+            data_prefix: "data:",
+        }
+    }
+
+    /// Decode newline-delimited JSON (one event per line) instead of SSE.
+    pub fn ndjson(parse_data: DataParser) -> Self {
+        Self {
+            data_prefix: "",
+            ..Self::new(parse_data)
+        }
+    }
+
+    pub fn push(&mut self, text: &str) -> Vec<StreamEvent> {
+        self.buffer.push_str(text);
+
+        let mut events = Vec::new();
+        while let Some(newline) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=newline).collect();
+            self.parse_line(line.trim_end_matches(['\r', '\n']), &mut events);
+        }
+        events
+    }
+
+    /// Parse whatever is left once the body ends without a trailing newline.
+    pub fn finish(&mut self) -> Vec<StreamEvent> {
+        let line = std::mem::take(&mut self.buffer);
+        let mut events = Vec::new();
+        self.parse_line(line.trim_end_matches('\r'), &mut events);
+        events
+    }
+
+    fn parse_line(&mut self, line: &str, events: &mut Vec<StreamEvent>) {
+        if self.done {
+            return;
+        }
+        let Some(data) = line.strip_prefix(self.data_prefix).map(str::trim_start) else {
+            return;
+        };
+        if data.is_empty() {
+            return;
+        }
+
+        match (self.parse_data)(data) {
+            Some(parsed) => events.extend(parsed),
+            None => self.done = true,
+        }
+    }
+}
+
+/// Decodes UTF-8 across chunk boundaries, holding back the bytes of a
+/// character that was cut in half until the rest arrives.
+#[derive(Debug, Default)]
+pub struct Utf8Buffer {
+    pending: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+
+        let mut text = String::new();
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    self.pending.clear();
+                    return text;
+                }
+                Err(e) => {
+                    let valid_up_to = e.valid_up_to();
+                    // Safe: from_utf8 just confirmed this prefix is valid
+                    text.push_str(std::str::from_utf8(&self.pending[..valid_up_to]).unwrap());
+
+                    match e.error_len() {
+                        // Genuinely invalid bytes: replace them and keep going
+                        Some(len) => {
+                            tracing::debug!("Replacing {} invalid UTF-8 byte(s) in stream", len);
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            self.pending.drain(..valid_up_to + len);
+                        }
+                        // Incomplete sequence at the end: wait for the next chunk
+                        None => {
+                            self.pending.drain(..valid_up_to);
+                            return text;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flush at end of stream; a dangling partial character becomes U+FFFD.
+    pub fn finish(&mut self) -> String {
+        let rest = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        rest
+    }
+}
+
+/// Decode an HTTP body stream into chat events.
+pub fn event_stream<S, B, E>(body: S, mut decoder: SseDecoder) -> ChatStream
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]> + Send + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+{
+    let mut utf8 = Utf8Buffer::default();
+
+    body.map(Some)
+        .chain(stream::once(async { None }))
+        .flat_map(move |chunk| {
+            let events: Vec<Result<StreamEvent, Box<dyn std::error::Error + Send + Sync>>> = match chunk {
+                Some(Ok(bytes)) => decoder.push(&utf8.push(bytes.as_ref())).into_iter().map(Ok).collect(),
+                Some(Err(e)) => vec![Err(e.into())],
+                // End of body: flush a final line that had no trailing newline
+                None => {
+                    let mut events = decoder.push(&utf8.finish());
+                    events.extend(decoder.finish());
+                    events.into_iter().map(Ok).collect()
+                }
+            };
+            stream::iter(events)
+        })
+        .boxed()
+}
+
+/// OpenAI `/chat/completions` chunks, terminated by `data: [DONE]`.
+pub fn parse_openai_data(data: &str) -> Option<Vec<StreamEvent>> {
+    if data == "[DONE]" {
+        return None;
+    }
+
+    let Ok(response) = serde_json::from_str::<StreamResponse>(data) else {
+        tracing::debug!("Skipping unparseable stream line: {}", data);
+        return Some(Vec::new());
+    };
+
+    let mut events = Vec::new();
+    if let Some(choice) = response.choices.into_iter().next() {
+        if let Some(content) = choice.delta.content.filter(|c| !c.is_empty()) {
+            events.push(StreamEvent::Content(content));
+        }
+        for delta in choice.delta.tool_calls.into_iter().flatten() {
+            events.push(StreamEvent::ToolCall(delta));
+        }
+    }
+    if let Some(usage) = response.usage {
+        events.push(StreamEvent::Usage(usage));
+    }
+    Some(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_split_across_chunks() {
+        let mut decoder = SseDecoder::default();
+
+        let mut events = decoder.push("data: {\"id\":\"1\",\"choices\":[{\"delta\":{\"content\":\"Hel");
+        assert!(events.is_empty());
+
+        events.extend(decoder.push("lo\"},\"finish_reason\":null}]}\n\ndata: [DONE]\n"));
+        assert_eq!(events, vec![StreamEvent::Content("Hello".to_string())]);
+
+        assert!(decoder.push("data: {\"id\":\"1\",\"choices\":[{\"delta\":{\"content\":\"late\"}}]}\n").is_empty());
+    }
+
+    #[test]
+    fn test_utf8_buffer_rejoins_split_character() {
+        let mut buffer = Utf8Buffer::default();
+        let bytes = "héllo 👋".as_bytes();
+        // Cut inside the 4-byte emoji
+        let (head, tail) = bytes.split_at(bytes.len() - 2);
+
+        let mut text = buffer.push(head);
+        assert_eq!(text, "héllo ");
+        text.push_str(&buffer.push(tail));
+        assert_eq!(text, "héllo 👋");
+        assert_eq!(buffer.finish(), "");
+
+        assert_eq!(buffer.push(b"a\xffb"), "a\u{FFFD}b");
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_preserves_multibyte_content() {
+        let body = "data: {\"id\":\"1\",\"choices\":[{\"delta\":{\"content\":\"日本\"},\"finish_reason\":null}]}\n\n";
+        let bytes = body.as_bytes();
+        // Split in the middle of 日 (3 bytes)
+        let cut = body.find('日').unwrap() + 1;
+        let chunks: Vec<Result<Vec<u8>, std::io::Error>> = vec![Ok(bytes[..cut].to_vec()), Ok(bytes[cut..].to_vec())];
+
+        let events: Vec<StreamEvent> = event_stream(stream::iter(chunks), SseDecoder::default())
+            .map(|e| e.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(events, vec![StreamEvent::Content("日本".to_string())]);
+    }
+
+    #[test]
+    fn test_usage_chunk_is_emitted() {
+        let mut decoder = SseDecoder::default();
+        let events = decoder.push(
+            "data: {\"id\":\"1\",\"choices\":[],\"usage\":{\"prompt_tokens\":1200,\"completion_tokens\":34,\"total_tokens\":1234}}\n\ndata: [DONE]\n",
+        );
+
+        assert_eq!(events, vec![StreamEvent::Usage(Usage {
+            prompt_tokens: 1200,
+            completion_tokens: 34,
+            total_tokens: 1234,
+        })]);
+    }
+
+    #[test]
+    fn test_tool_call_deltas_are_emitted() {
+        let mut decoder = SseDecoder::default();
+        let events = decoder.push(
+            "data: {\"id\":\"1\",\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_1\",\"function\":{\"name\":\"read_file\"}}]},\"finish_reason\":null}]}\n",
+        );
+
+        match &events[..] {
+            [StreamEvent::ToolCall(delta)] => {
+                assert_eq!(delta.index, 0);
+                assert_eq!(delta.id.as_deref(), Some("call_1"));
+            }
+            other => panic!("unexpected events: {:?}", other),
+        }
+    }
+}
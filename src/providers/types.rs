@@ -1,6 +1,27 @@
-use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
 
+/// Treat an explicit `null` like a missing field. Proxies disagree on which
+/// fields they send as null.
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// Decode a provider response body. On failure the raw body is logged at
+/// debug and the error names what didn't match (e.g. a missing field), instead
+/// of reqwest's opaque "error decoding response body".
+pub fn parse_response<T: DeserializeOwned>(body: &str) -> Result<T, String> {
+    serde_json::from_str(body).map_err(|e| {
+        tracing::debug!("Unparseable provider response: {}", body);
+        format!("unexpected response shape from provider: {}", e)
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
@@ -9,6 +30,8 @@ pub struct ChatMessage {
     pub name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
 }
 
 impl ChatMessage {
@@ -18,6 +41,7 @@ impl ChatMessage {
             content: content.into(),
             name: None,
             tool_call_id: None,
+            tool_calls: None,
         }
     }
 
@@ -27,6 +51,7 @@ impl ChatMessage {
             content: content.into(),
             name: None,
             tool_call_id: None,
+            tool_calls: None,
         }
     }
 
@@ -36,6 +61,7 @@ impl ChatMessage {
             content: content.into(),
             name: None,
             tool_call_id: None,
+            tool_calls: None,
         }
     }
 
@@ -45,24 +71,61 @@ impl ChatMessage {
             content: content.into(),
             name: None,
             tool_call_id: Some(tool_call_id.into()),
+            tool_calls: None,
+        }
+    }
+
+    /// Assistant turn that requested tools; must precede the matching tool results.
+    pub fn assistant_with_tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: content.into(),
+            name: None,
+            tool_call_id: None,
+            tool_calls: Some(tool_calls),
         }
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ToolDefinition {
     #[serde(rename = "type")]
     pub tool_type: String,
     pub function: FunctionDefinition,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FunctionDefinition {
     pub name: String,
     pub description: String,
     pub parameters: serde_json::Value,
 }
 
+/// OpenAI `tool_choice`: forbid, allow, force, or pin tool use for a request.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub enum ToolChoice {
+    None,
+    Auto,
+    Required,
+    Function(String),
+}
+
+impl Serialize for ToolChoice {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ToolChoice::None => serializer.serialize_str("none"),
+            ToolChoice::Auto => serializer.serialize_str("auto"),
+            ToolChoice::Required => serializer.serialize_str("required"),
+            ToolChoice::Function(name) => serde_json::json!({
+                "type": "function",
+                "function": { "name": name }
+            })
+            .serialize(serializer),
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct ChatRequest {
     pub model: String,
@@ -70,6 +133,8 @@ pub struct ChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<ToolDefinition>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
@@ -78,8 +143,10 @@ pub struct ChatRequest {
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 pub struct ChatResponse {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub id: String,
     pub choices: Vec<Choice>,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub usage: Usage,
 }
 
@@ -87,69 +154,83 @@ pub struct ChatResponse {
 #[allow(dead_code)]
 pub struct Choice {
     pub message: ResponseMessage,
-    #[serde(rename = "finish_reason")]
+    #[serde(default, deserialize_with = "null_as_default")]
     pub finish_reason: String,
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(untagged)]
 #[allow(dead_code)]
-pub enum ResponseMessage {
-    Simple {
-        role: String,
-        content: Option<String>,
-    },
-    WithTools {
-        role: String,
-        content: Option<String>,
-        #[serde(rename = "tool_calls")]
-        tool_calls: Option<Vec<ToolCall>>,
-    },
+pub struct ResponseMessage {
+    #[serde(default, deserialize_with = "null_as_default")]
+    role: String,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<ToolCall>>,
 }
 
 impl ResponseMessage {
     pub fn content(&self) -> Option<&str> {
-        match self {
-            ResponseMessage::Simple { content, .. } => content.as_deref(),
-            ResponseMessage::WithTools { content, .. } => content.as_deref(),
-        }
+        self.content.as_deref()
     }
 
     pub fn tool_calls(&self) -> Option<&Vec<ToolCall>> {
-        match self {
-            ResponseMessage::Simple { .. } => None,
-            ResponseMessage::WithTools { tool_calls, .. } => tool_calls.as_ref(),
-        }
+        self.tool_calls.as_ref()
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct ToolCall {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub id: String,
-    #[serde(rename = "type")]
+    #[serde(rename = "type", default = "default_tool_type")]
     pub tool_type: String,
     pub function: FunctionCall,
 }
 
-#[derive(Debug, Deserialize)]
+fn default_tool_type() -> String {
+    "function".to_string()
+}
+
+impl ToolCall {
+    pub fn function(id: impl Into<String>, name: impl Into<String>, arguments: &serde_json::Value) -> Self {
+        Self {
+            id: id.into(),
+            tool_type: "function".to_string(),
+            function: FunctionCall {
+                name: name.into(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub struct FunctionCall {
     pub name: String,
     pub arguments: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct Usage {
-    #[serde(rename = "prompt_tokens")]
+    #[serde(default, deserialize_with = "null_as_default")]
     pub prompt_tokens: u32,
-    #[serde(rename = "completion_tokens")]
+    #[serde(default, deserialize_with = "null_as_default")]
     pub completion_tokens: u32,
-    #[serde(rename = "total_tokens")]
+    #[serde(default, deserialize_with = "null_as_default")]
     pub total_tokens: u32,
 }
 
+impl Usage {
+    pub fn add(&mut self, other: &Usage) {
+        self.prompt_tokens = self.prompt_tokens.saturating_add(other.prompt_tokens);
+        self.completion_tokens = self.completion_tokens.saturating_add(other.completion_tokens);
+        self.total_tokens = self.total_tokens.saturating_add(other.total_tokens);
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCallRequest {
     pub id: String,
@@ -169,28 +250,102 @@ pub struct LLMResponse {
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 pub struct StreamChoice {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub delta: StreamDelta,
     pub finish_reason: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 #[allow(dead_code)]
 pub struct StreamDelta {
     #[serde(default)]
     pub role: Option<String>,
     #[serde(default)]
     pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// One fragment of a streamed tool call. The id and name arrive with the first
+/// fragment for an index; the arguments JSON is spread across the rest.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct ToolCallDelta {
+    pub index: usize,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct FunctionCallDelta {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+/// Items yielded by a streaming chat completion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    Content(String),
+    ToolCall(ToolCallDelta),
+    /// Token counts, reported once the provider knows them (may come in parts)
+    Usage(Usage),
+}
+
+/// Reassembles streamed tool-call fragments, keyed by their index.
+#[derive(Debug, Default)]
+pub struct ToolCallAccumulator {
+    calls: std::collections::BTreeMap<usize, (String, String, String)>,
+}
+
+impl ToolCallAccumulator {
+    pub fn push(&mut self, delta: &ToolCallDelta) {
+        let (id, name, arguments) = self.calls.entry(delta.index).or_default();
+        if let Some(delta_id) = &delta.id {
+            id.push_str(delta_id);
+        }
+        if let Some(function) = &delta.function {
+            if let Some(delta_name) = &function.name {
+                name.push_str(delta_name);
+            }
+            if let Some(fragment) = &function.arguments {
+                arguments.push_str(fragment);
+            }
+        }
+    }
+
+    /// The completed calls in index order. Arguments that don't parse as a JSON
+    /// object become empty, matching the non-streaming path.
+    pub fn finish(self) -> Vec<ToolCallRequest> {
+        self.calls
+            .into_iter()
+            .filter(|(_, (_, name, _))| !name.is_empty())
+            .map(|(index, (id, name, arguments))| ToolCallRequest {
+                id: if id.is_empty() { format!("call_{}", index) } else { id },
+                name,
+                arguments: serde_json::from_str(&arguments).unwrap_or_default(),
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
 pub struct StreamResponse {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub id: String,
+    #[serde(default, deserialize_with = "null_as_default")]
     pub choices: Vec<StreamChoice>,
+    /// Only on the final chunk, when `stream_options.include_usage` is set
+    #[serde(default)]
+    pub usage: Option<Usage>,
 }
 
 impl LLMResponse {
-    pub fn _has_tool_calls(&self) -> bool {
+    #[allow(dead_code)]
+    pub fn has_tool_calls(&self) -> bool {
         !self.tool_calls.is_empty()
     }
 }
@@ -251,11 +406,16 @@ impl From<ChatResponse> for LLMResponse {
 
         let finish_reason = choice.map(|c| c.finish_reason.clone()).unwrap_or_default();
 
+        let mut usage = resp.usage;
+        if usage.total_tokens == 0 {
+            usage.total_tokens = usage.prompt_tokens.saturating_add(usage.completion_tokens);
+        }
+
         Self {
             content,
             tool_calls,
             finish_reason,
-            usage: resp.usage,
+            usage,
         }
     }
 }
@@ -284,6 +444,22 @@ mod tests {
         assert_eq!(tool_msg.tool_call_id, Some("call_123".to_string()));
     }
 
+    #[test]
+    fn test_assistant_message_with_tool_calls_serialization() {
+        let call = ToolCall::function("call_1", "read_file", &serde_json::json!({"path": "a.txt"}));
+        let msg = ChatMessage::assistant_with_tool_calls("", vec![call]);
+
+        let value = serde_json::to_value(&msg).unwrap();
+        assert_eq!(value["role"], "assistant");
+        assert_eq!(value["tool_calls"][0]["id"], "call_1");
+        assert_eq!(value["tool_calls"][0]["type"], "function");
+        assert_eq!(value["tool_calls"][0]["function"]["name"], "read_file");
+        assert_eq!(value["tool_calls"][0]["function"]["arguments"], r#"{"path":"a.txt"}"#);
+
+        let plain = serde_json::to_value(ChatMessage::assistant("Hi")).unwrap();
+        assert!(plain.get("tool_calls").is_none());
+    }
+
     #[test]
     fn test_llm_response_has_tool_calls() {
         let mut response = LLMResponse {
@@ -308,6 +484,57 @@ mod tests {
         assert!(response.has_tool_calls());
     }
 
+    #[test]
+    fn test_stream_delta_tool_call_fragments_are_reassembled() {
+        let chunks = [
+            r#"{"id":"c","choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_a","function":{"name":"read_file","arguments":""}}]},"finish_reason":null}]}"#,
+            r#"{"id":"c","choices":[{"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"list_dir","arguments":"{\"path\":"}}]},"finish_reason":null}]}"#,
+            r#"{"id":"c","choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"path\": \"a."}}]},"finish_reason":null}]}"#,
+            r#"{"id":"c","choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"txt\"}"}}]},"finish_reason":null}]}"#,
+            r#"{"id":"c","choices":[{"delta":{"tool_calls":[{"index":1,"function":{"arguments":"\".\"}"}}]},"finish_reason":"tool_calls"}]}"#,
+        ];
+
+        let mut accumulator = ToolCallAccumulator::default();
+        for chunk in chunks {
+            let response: StreamResponse = serde_json::from_str(chunk).unwrap();
+            for delta in response.choices[0].delta.tool_calls.iter().flatten() {
+                accumulator.push(delta);
+            }
+        }
+
+        let calls = accumulator.finish();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_a");
+        assert_eq!(calls[0].name, "read_file");
+        assert_eq!(calls[0].arguments["path"], "a.txt");
+        assert_eq!(calls[1].name, "list_dir");
+        assert_eq!(calls[1].arguments["path"], ".");
+    }
+
+    #[test]
+    fn test_chat_request_tool_choice_serialization() {
+        let mut request = ChatRequest {
+            model: "gpt-4o-mini".to_string(),
+            messages: vec![ChatMessage::user("Hi")],
+            tools: None,
+            tool_choice: Some(ToolChoice::None),
+            temperature: None,
+            max_tokens: None,
+        };
+
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["tool_choice"], "none");
+
+        request.tool_choice = Some(ToolChoice::Function("read_file".to_string()));
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(value["tool_choice"]["type"], "function");
+        assert_eq!(value["tool_choice"]["function"]["name"], "read_file");
+
+        request.tool_choice = None;
+        let value = serde_json::to_value(&request).unwrap();
+        assert!(value.get("tool_choice").is_none());
+    }
+
     #[test]
     fn test_usage_struct() {
         let usage = Usage {
@@ -320,4 +547,43 @@ mod tests {
         assert_eq!(usage.completion_tokens, 50);
         assert_eq!(usage.total_tokens, 150);
     }
+
+    #[test]
+    fn test_tolerates_proxy_response_variants() {
+        // No id, no usage, null finish_reason
+        let resp: ChatResponse = parse_response(
+            r#"{"choices":[{"message":{"role":"assistant","content":"Hi"},"finish_reason":null}]}"#,
+        ).unwrap();
+        let llm: LLMResponse = resp.into();
+        assert_eq!(llm.content.as_deref(), Some("Hi"));
+        assert_eq!(llm.usage, Usage::default());
+
+        // usage: null, no role, tool call without a type
+        let resp: ChatResponse = parse_response(
+            r#"{"id":null,"usage":null,"choices":[{"message":{"content":null,"tool_calls":[{"id":"call_1","function":{"name":"list_dir","arguments":"{\"path\":\".\"}"}}]},"finish_reason":"tool_calls"}]}"#,
+        ).unwrap();
+        let llm: LLMResponse = resp.into();
+        assert_eq!(llm.tool_calls.len(), 1);
+        assert_eq!(llm.tool_calls[0].name, "list_dir");
+
+        // Usage without total_tokens
+        let resp: ChatResponse = parse_response(
+            r#"{"choices":[{"message":{"role":"assistant","content":"ok"}}],"usage":{"prompt_tokens":5,"completion_tokens":2}}"#,
+        ).unwrap();
+        let llm: LLMResponse = resp.into();
+        assert_eq!(llm.usage.total_tokens, 7);
+
+        // Stream chunks with null delta or no choices at all
+        let chunk: StreamResponse = parse_response(r#"{"choices":[{"delta":null,"finish_reason":"stop"}]}"#).unwrap();
+        assert!(chunk.choices[0].delta.content.is_none());
+        let chunk: StreamResponse = parse_response(r#"{"usage":{"prompt_tokens":1,"completion_tokens":1,"total_tokens":2}}"#).unwrap();
+        assert!(chunk.choices.is_empty());
+    }
+
+    #[test]
+    fn test_unexpected_shape_names_the_missing_field() {
+        let err = parse_response::<ChatResponse>(r#"{"error":"upstream unavailable"}"#).unwrap_err();
+        assert!(err.starts_with("unexpected response shape from provider"));
+        assert!(err.contains("missing field `choices`"));
+    }
 }
@@ -0,0 +1,85 @@
+//! Bot-originated decoration (banner, status badges, reminder prefix, ...).
+//! Model and user content never passes through here, so plain mode only
+//! strips what we add ourselves.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Switch the process-wide output style; set once from `ui` config at startup.
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// Prefix a status line with `emoji`, unless plain mode is on.
+pub fn decorate(emoji: &str, text: &str, plain: bool) -> String {
+    if plain {
+        text.to_string()
+    } else {
+        format!("{} {}", emoji, text)
+    }
+}
+
+pub fn enabled_badge(enabled: bool, plain: bool) -> String {
+    if enabled {
+        decorate("✅", "enabled", plain)
+    } else {
+        decorate("❌", "disabled", plain)
+    }
+}
+
+pub fn reminder_text(message: &str, plain: bool) -> String {
+    if plain {
+        format!("REMINDER: {}", message)
+    } else {
+        format!("⏰ **REMINDER**: {}", message)
+    }
+}
+
+/// Shown while a streamed reply is still being generated.
+pub fn streaming_placeholder(plain: bool) -> String {
+    decorate("⏳", "Generating response...", plain)
+}
+
+const BANNER: &str = r#"
+╔═══════════════════════════════════════════════════════════╗
+║   🤖 S A N T O S O B O T                                ║
+║   Ultra-Lightweight Personal AI Assistant               ║
+╠═══════════════════════════════════════════════════════════╣
+║   Version: 0.1.0  |  Rust-based  |  OpenAI-compatible   ║
+╚═══════════════════════════════════════════════════════════╝
+    "#;
+
+pub fn banner(plain: bool) -> String {
+    if plain {
+        // The robot is two columns wide; pad so the box stays aligned
+        BANNER.replace("🤖 ", "   ")
+    } else {
+        BANNER.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has_emoji(s: &str) -> bool {
+        s.chars().any(|c| matches!(c as u32, 0x2300..=0x23FF | 0x2600..=0x27BF | 0x1F000..=0x1FAFF))
+    }
+
+    #[test]
+    fn test_plain_mode_strips_emoji() {
+        assert!(!has_emoji(&streaming_placeholder(true)));
+        assert!(!has_emoji(&reminder_text("Stand-up", true)));
+        assert!(!has_emoji(&enabled_badge(false, true)));
+        assert!(!has_emoji(&banner(true)));
+        assert_eq!(reminder_text("Stand-up", true), "REMINDER: Stand-up");
+
+        assert!(has_emoji(&streaming_placeholder(false)));
+        assert!(has_emoji(&reminder_text("Stand-up", false)));
+    }
+}